@@ -0,0 +1,301 @@
+//! Parsing of the `#[dds(...)]` attributes.
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Attribute, DeriveInput, Field, Ident, LitInt, LitStr, Type, Variant};
+
+/// Attributes of the type the derive is applied to.
+#[derive(Default)]
+pub(crate) struct ContainerAttrs {
+    /// `#[dds(bit_bound = N)]`
+    pub(crate) bit_bound: Option<LitInt>,
+    /// `#[dds(final)]`, `#[dds(appendable)]` or `#[dds(mutable)]`
+    pub(crate) extensibility: Option<Ident>,
+    /// `#[dds(autoid = "sequential")]` or `#[dds(autoid = "hash")]`
+    pub(crate) autoid: Option<LitStr>,
+    /// `#[dds(bitmask(a, b, c = N))]`, the flags and their explicit
+    /// positions
+    pub(crate) bitmask: Option<Vec<(Ident, Option<LitInt>)>>,
+    /// `#[dds(extends = Base)]`, the base of a struct
+    pub(crate) extends: Option<Type>,
+    /// `#[dds(type_name = "Module::Name")]`, the scoped IDL name
+    pub(crate) type_name: Option<LitStr>,
+    /// `#[dds(topic = "name")]`, the topic name of a struct
+    pub(crate) topic: Option<LitStr>,
+    /// `#[dds(pod)]`, the struct is plain old data
+    pub(crate) pod: Option<Ident>,
+}
+
+impl ContainerAttrs {
+    pub(crate) fn parse(input: &DeriveInput) -> syn::Result<ContainerAttrs> {
+        let mut attrs = ContainerAttrs::default();
+        for attr in dds_attrs(&input.attrs) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("bit_bound") {
+                    attrs.bit_bound = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if ["final", "appendable", "mutable"]
+                    .iter()
+                    .any(|kind| meta.path.is_ident(kind))
+                {
+                    if attrs.extensibility.is_some() {
+                        return Err(meta.error("duplicate extensibility attribute"));
+                    }
+                    attrs.extensibility = meta.path.get_ident().cloned();
+                    Ok(())
+                } else if meta.path.is_ident("autoid") {
+                    let autoid: LitStr = meta.value()?.parse()?;
+                    if autoid.value() != "sequential" && autoid.value() != "hash" {
+                        return Err(syn::Error::new_spanned(
+                            autoid,
+                            "autoid must be \"sequential\" or \"hash\"",
+                        ));
+                    }
+                    attrs.autoid = Some(autoid);
+                    Ok(())
+                } else if meta.path.is_ident("bitmask") {
+                    let mut flags = Vec::new();
+                    meta.parse_nested_meta(|flag| {
+                        let Some(name) = flag.path.get_ident() else {
+                            return Err(flag.error("expected a flag name"));
+                        };
+                        let position = if flag.input.peek(syn::Token![=]) {
+                            let position: LitInt = flag.value()?.parse()?;
+                            position.base10_parse::<u32>()?;
+                            Some(position)
+                        } else {
+                            None
+                        };
+                        flags.push((name.clone(), position));
+                        Ok(())
+                    })?;
+                    attrs.bitmask = Some(flags);
+                    Ok(())
+                } else if meta.path.is_ident("extends") {
+                    attrs.extends = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("type_name") {
+                    let name: LitStr = meta.value()?.parse()?;
+                    if !is_scoped_name(&name.value()) {
+                        return Err(syn::Error::new_spanned(
+                            name,
+                            "type_name must be IDL identifiers separated by ::",
+                        ));
+                    }
+                    attrs.type_name = Some(name);
+                    Ok(())
+                } else if meta.path.is_ident("topic") {
+                    let name: LitStr = meta.value()?.parse()?;
+                    if !is_topic_name(&name.value()) {
+                        return Err(syn::Error::new_spanned(
+                            name,
+                            "topic must consist of letters, digits, _ and / and not start \
+                             with a digit",
+                        ));
+                    }
+                    attrs.topic = Some(name);
+                    Ok(())
+                } else if meta.path.is_ident("pod") {
+                    attrs.pod = meta.path.get_ident().cloned();
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported dds attribute"))
+                }
+            })?;
+        }
+        Ok(attrs)
+    }
+
+    /// Returns the `TypeExtensibility` of the type, [None] if it is not
+    /// given.
+    pub(crate) fn extensibility(&self) -> Option<TokenStream> {
+        self.extensibility.as_ref().map(|kind| {
+            let kind = match kind.to_string().as_str() {
+                "final" => quote!(Final),
+                "appendable" => quote!(Appendable),
+                _ => quote!(Mutable),
+            };
+            quote!(::cyclonedds_rs::descriptor::TypeExtensibility::#kind)
+        })
+    }
+
+    /// Returns the `TypeAutoid` of the type, [None] if it is not given.
+    pub(crate) fn autoid(&self) -> Option<TokenStream> {
+        self.autoid
+            .as_ref()
+            .map(|autoid| match autoid.value().as_str() {
+                "hash" => quote!(::cyclonedds_rs::descriptor::TypeAutoid::Hash),
+                _ => quote!(::cyclonedds_rs::descriptor::TypeAutoid::Sequential),
+            })
+    }
+
+    /// Fails if `bitmask` is given on a type that is not a newtype struct.
+    pub(crate) fn reject_bitmask(&self, input: &DeriveInput) -> syn::Result<()> {
+        if self.bitmask.is_some() {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "bitmask is only supported on newtype structs",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the scoped IDL name of `Self`, the module path of the type
+    /// if `type_name` is not given.
+    pub(crate) fn type_name(&self) -> TokenStream {
+        match &self.type_name {
+            Some(name) => quote!(#name),
+            None => quote!(::cyclonedds_rs::descriptor::type_name_of::<Self>()),
+        }
+    }
+
+    /// Fails if `extends`, `topic` or `pod` is given on a type that is not
+    /// a struct.
+    pub(crate) fn reject_extends(&self, what: &str) -> syn::Result<()> {
+        if let Some(extends) = &self.extends {
+            return Err(syn::Error::new_spanned(
+                extends,
+                format!("extends is not supported on {}", what),
+            ));
+        }
+        if let Some(topic) = &self.topic {
+            return Err(syn::Error::new_spanned(
+                topic,
+                format!("topic is not supported on {}", what),
+            ));
+        }
+        if let Some(pod) = &self.pod {
+            return Err(syn::Error::new_spanned(
+                pod,
+                format!("pod is not supported on {}", what),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Fails if any of the attributes that only apply to structs and unions
+    /// is given.
+    pub(crate) fn reject_extensibility(&self, what: &str) -> syn::Result<()> {
+        if let Some(kind) = &self.extensibility {
+            return Err(syn::Error::new_spanned(
+                kind,
+                format!("{} is not supported on {}", kind, what),
+            ));
+        }
+        if let Some(autoid) = &self.autoid {
+            return Err(syn::Error::new_spanned(
+                autoid,
+                format!("autoid is not supported on {}", what),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Attributes of a struct member.
+#[derive(Default)]
+pub(crate) struct FieldAttrs {
+    /// `#[dds(key)]`
+    pub(crate) key: bool,
+    /// `#[dds(id = N)]`
+    pub(crate) id: Option<LitInt>,
+    /// `#[dds(hashid)]` or `#[dds(hashid = "name")]`
+    pub(crate) hashid: Option<Option<LitStr>>,
+}
+
+impl FieldAttrs {
+    pub(crate) fn parse(field: &Field) -> syn::Result<FieldAttrs> {
+        let mut attrs = FieldAttrs::default();
+        for attr in dds_attrs(&field.attrs) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("key") {
+                    attrs.key = true;
+                    Ok(())
+                } else if meta.path.is_ident("id") {
+                    let id: LitInt = meta.value()?.parse()?;
+                    id.base10_parse::<u32>()?;
+                    attrs.id = Some(id);
+                    Ok(())
+                } else if meta.path.is_ident("hashid") {
+                    attrs.hashid = Some(if meta.input.peek(syn::Token![=]) {
+                        Some(meta.value()?.parse()?)
+                    } else {
+                        None
+                    });
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported dds attribute"))
+                }
+            })?;
+        }
+        if attrs.id.is_some() && attrs.hashid.is_some() {
+            return Err(syn::Error::new_spanned(
+                field,
+                "a member can not have both an id and a hashid",
+            ));
+        }
+        Ok(attrs)
+    }
+}
+
+/// Attributes of an enum variant.
+#[derive(Default)]
+pub(crate) struct VariantAttrs {
+    /// `#[dds(default)]`
+    pub(crate) default: bool,
+}
+
+impl VariantAttrs {
+    pub(crate) fn parse(variant: &Variant) -> syn::Result<VariantAttrs> {
+        let mut attrs = VariantAttrs::default();
+        for attr in dds_attrs(&variant.attrs) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("default") {
+                    attrs.default = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported dds attribute"))
+                }
+            })?;
+        }
+        Ok(attrs)
+    }
+}
+
+/// Returns whether `name` is IDL identifiers separated by `::`, the same
+/// check as `descriptor::is_valid_type_name`.
+fn is_scoped_name(name: &str) -> bool {
+    name.split("::").all(|identifier| {
+        identifier.starts_with(|c: char| c.is_ascii_alphabetic())
+            && identifier
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// Returns whether `name` is a legal topic name, the same check as
+/// `topic::is_valid_topic_name`.
+fn is_topic_name(name: &str) -> bool {
+    !name.starts_with(|c: char| c.is_ascii_digit())
+        && !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '/')
+}
+
+fn dds_attrs(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs.iter().filter(|attr| attr.path().is_ident("dds"))
+}
+
+/// Returns the hints of the `#[repr(..)]` attributes, e.g. `C` and `u32`.
+pub(crate) fn repr(attrs: &[Attribute]) -> syn::Result<Vec<Ident>> {
+    let mut repr = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            if let Some(ident) = meta.path.get_ident() {
+                repr.push(ident.clone());
+            }
+            Ok(())
+        })?;
+    }
+    Ok(repr)
+}
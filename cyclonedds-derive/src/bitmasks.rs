@@ -0,0 +1,214 @@
+//! Derive for newtype structs of an unsigned integer, which map to an IDL
+//! `bitmask`.
+use proc_macro2::{Literal, TokenStream};
+use quote::quote;
+use syn::{ext::IdentExt, DataStruct, DeriveInput, Fields, Type};
+
+use crate::attrs::{repr, ContainerAttrs};
+
+pub(crate) fn derive(
+    input: &DeriveInput,
+    data: &DataStruct,
+    attrs: &ContainerAttrs,
+) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    attrs.reject_extensibility("bitmasks")?;
+    attrs.reject_extends("bitmasks")?;
+
+    if !repr(&input.attrs)?.iter().any(|repr| repr == "transparent") {
+        return Err(syn::Error::new_spanned(
+            ident,
+            "bitmasks must be #[repr(transparent)] to be used as a DDS bitmask",
+        ));
+    }
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "generic bitmasks are not supported",
+        ));
+    }
+
+    // The serializer reads the value as an unsigned integer of the size of
+    // the bit bound
+    let holder = match &data.fields {
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => &fields.unnamed[0].ty,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "bitmasks must be a newtype struct of u8, u16, u32 or u64",
+            ))
+        }
+    };
+    let width = match holder {
+        Type::Path(path) if path.path.is_ident("u8") => 8,
+        Type::Path(path) if path.path.is_ident("u16") => 16,
+        Type::Path(path) if path.path.is_ident("u32") => 32,
+        Type::Path(path) if path.path.is_ident("u64") => 64,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                holder,
+                "bitmasks must be a newtype struct of u8, u16, u32 or u64",
+            ))
+        }
+    };
+
+    let bit_bound = match &attrs.bit_bound {
+        Some(lit) => {
+            let bit_bound = lit.base10_parse::<u32>()?;
+            if bit_bound > width || bit_bound <= width / 2 {
+                return Err(syn::Error::new_spanned(
+                    lit,
+                    format!(
+                        "bit_bound of a bitmask of u{} must be in {}..={}",
+                        width,
+                        width / 2 + 1,
+                        width
+                    ),
+                ));
+            }
+            bit_bound
+        }
+        None => width,
+    };
+
+    let mut flags = Vec::new();
+    let mut next = 0u32;
+    for (name, position) in attrs.bitmask.as_deref().unwrap_or_default() {
+        let position = match position {
+            Some(lit) => lit.base10_parse::<u32>()?,
+            None => next,
+        };
+        if position >= bit_bound {
+            return Err(syn::Error::new_spanned(
+                name,
+                format!(
+                    "position {} does not fit in bit_bound {}",
+                    position, bit_bound
+                ),
+            ));
+        }
+        if let Some((other, _)) = flags.iter().find(|(_, other)| *other == position) {
+            return Err(syn::Error::new_spanned(
+                name,
+                format!("position {} is already taken by {}", position, other),
+            ));
+        }
+        flags.push((name, position));
+        next = position + 1;
+    }
+
+    let all = Literal::u64_unsuffixed(
+        flags
+            .iter()
+            .fold(0u64, |bits, (_, position)| bits | 1 << position),
+    );
+    let consts = flags.iter().map(|(name, position)| {
+        let position = Literal::u32_unsuffixed(*position);
+        quote! {
+            pub const #name: Self = Self(1 << #position);
+        }
+    });
+    let desc_flags = flags.iter().map(|(name, position)| {
+        let name = name.unraw().to_string();
+        quote! {
+            .flag(#name, #position)
+        }
+    });
+
+    let type_name = attrs.type_name();
+    Ok(quote! {
+        impl #ident {
+            #(#consts)*
+
+            /// The value without any flag set.
+            pub const fn empty() -> Self {
+                Self(0)
+            }
+
+            /// The value with all flags set.
+            pub const fn all() -> Self {
+                Self(#all)
+            }
+
+            /// Returns the bits of the value.
+            pub const fn bits(&self) -> #holder {
+                self.0
+            }
+
+            /// Returns the value of `bits`, [None] if a bit is set that is
+            /// not a flag.
+            pub const fn from_bits(bits: #holder) -> ::core::option::Option<Self> {
+                if bits & !#all == 0 {
+                    ::core::option::Option::Some(Self(bits))
+                } else {
+                    ::core::option::Option::None
+                }
+            }
+
+            /// Returns the value of `bits` without the bits that are not
+            /// a flag.
+            pub const fn from_bits_truncate(bits: #holder) -> Self {
+                Self(bits & #all)
+            }
+
+            /// Returns whether all flags of `other` are set.
+            pub const fn contains(&self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            /// Sets the flags of `other`.
+            pub fn insert(&mut self, other: Self) {
+                self.0 |= other.0;
+            }
+
+            /// Clears the flags of `other`.
+            pub fn remove(&mut self, other: Self) {
+                self.0 &= !other.0;
+            }
+        }
+
+        impl ::core::ops::BitOr for #ident {
+            type Output = Self;
+
+            fn bitor(self, other: Self) -> Self {
+                Self(self.0 | other.0)
+            }
+        }
+
+        impl ::core::ops::BitAnd for #ident {
+            type Output = Self;
+
+            fn bitand(self, other: Self) -> Self {
+                Self(self.0 & other.0)
+            }
+        }
+
+        impl ::core::convert::TryFrom<#holder> for #ident {
+            type Error = ::cyclonedds_rs::descriptor::InvalidBitsError;
+
+            fn try_from(bits: #holder) -> ::core::result::Result<Self, Self::Error> {
+                Self::from_bits(bits).ok_or(::cyclonedds_rs::descriptor::InvalidBitsError {
+                    bits: u64::from(bits & !#all),
+                })
+            }
+        }
+
+        impl ::core::convert::From<#ident> for #holder {
+            fn from(value: #ident) -> #holder {
+                value.0
+            }
+        }
+
+        impl ::cyclonedds_rs::descriptor::DdsType for #ident {
+            fn type_desc() -> ::cyclonedds_rs::descriptor::TypeDesc {
+                ::cyclonedds_rs::descriptor::TypeDesc::Bitmask(
+                    ::cyclonedds_rs::descriptor::BitmaskDesc::new(
+                        #type_name,
+                        #bit_bound,
+                    )
+                    #(#desc_flags)*
+                )
+            }
+        }
+    })
+}
@@ -0,0 +1,117 @@
+//! Derive for fieldless enums, which map to an IDL `enum`.
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{ext::IdentExt, DataEnum, DeriveInput, Expr, ExprLit, Lit};
+
+use crate::attrs::{repr, ContainerAttrs, VariantAttrs};
+
+pub(crate) fn derive(input: &DeriveInput, data: &DataEnum) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    let attrs = ContainerAttrs::parse(input)?;
+    attrs.reject_extensibility("enums")?;
+    attrs.reject_bitmask(input)?;
+    attrs.reject_extends("enums")?;
+
+    if !repr(&input.attrs)?
+        .iter()
+        .any(|repr| repr == "u32" || repr == "i32")
+    {
+        return Err(syn::Error::new_spanned(
+            ident,
+            "enums must be #[repr(u32)] or #[repr(i32)] to be used as a DDS enum",
+        ));
+    }
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "generic enums are not supported",
+        ));
+    }
+
+    let bit_bound = match &attrs.bit_bound {
+        Some(lit) => {
+            let bit_bound = lit.base10_parse::<u32>()?;
+            if !(1..=32).contains(&bit_bound) {
+                return Err(syn::Error::new_spanned(lit, "bit_bound must be in 1..=32"));
+            }
+            bit_bound
+        }
+        None => 32,
+    };
+
+    let mut values = Vec::with_capacity(data.variants.len());
+    let mut next = 0u64;
+    for variant in &data.variants {
+        if VariantAttrs::parse(variant)?.default {
+            return Err(syn::Error::new_spanned(
+                variant,
+                "default is only supported on union cases",
+            ));
+        }
+
+        let value = match &variant.discriminant {
+            Some((_, expr)) => discriminant(expr)?,
+            None => next,
+        };
+        if bit_bound < 32 && value >= 1 << bit_bound {
+            return Err(syn::Error::new_spanned(
+                variant,
+                format!("value {} does not fit in bit_bound {}", value, bit_bound),
+            ));
+        }
+        values.push((&variant.ident, value as u32));
+        next = value + 1;
+    }
+
+    // The serializer only checks that a value does not exceed the largest
+    // enumerator, a gap in the values would let it write an invalid
+    // discriminant into the sample.
+    let mut sorted = values.iter().map(|(_, value)| *value).collect::<Vec<_>>();
+    sorted.sort_unstable();
+    if sorted
+        .iter()
+        .enumerate()
+        .any(|(i, value)| i as u32 != *value)
+    {
+        return Err(syn::Error::new_spanned(
+            ident,
+            "enumerator values must be contiguous and start at 0",
+        ));
+    }
+
+    let enumerators = values.iter().map(|(name, value)| {
+        let name = name.unraw().to_string();
+        quote! {
+            .enumerator(#name, #value)
+        }
+    });
+
+    let type_name = attrs.type_name();
+    Ok(quote! {
+        impl ::cyclonedds_rs::descriptor::DdsType for #ident {
+            fn type_desc() -> ::cyclonedds_rs::descriptor::TypeDesc {
+                ::cyclonedds_rs::descriptor::TypeDesc::Enum(
+                    ::cyclonedds_rs::descriptor::EnumDesc::new(
+                        #type_name,
+                        #bit_bound,
+                    )
+                    #(#enumerators)*
+                )
+            }
+        }
+    })
+}
+
+/// Parses an explicit discriminant, which must be a non-negative integer
+/// literal so its value is known to the derive.
+pub(crate) fn discriminant(expr: &Expr) -> syn::Result<u64> {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(lit), ..
+        }) => lit.base10_parse::<u64>(),
+        _ => Err(syn::Error::new_spanned(
+            expr,
+            "discriminants must be non-negative integer literals",
+        )),
+    }
+}
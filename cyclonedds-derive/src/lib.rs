@@ -1 +1,106 @@
+//! Derive macros for `cyclonedds-rs`.
+//!
+//! `#[derive(TopicType)]` implements `cyclonedds_rs::descriptor::DdsType` and
+//! `cyclonedds_rs::topic::TopicType` for a struct, so it can be used as the
+//! type of a topic or as a member of another topic type.
+//!
+//! Fieldless enums map to an IDL `enum`, they implement only `DdsType` as
+//! an enum can be a member of a topic type but not a topic type itself. The
+//! enum must be `#[repr(u32)]` or `#[repr(i32)]` and its discriminants, if
+//! given, must be non-negative integer literals. `#[dds(bit_bound = N)]`
+//! sets the `@bit_bound` of the enum.
+//!
+//! Enums with data map to an IDL `union`, they also implement only
+//! `DdsType`. Every variant must have exactly one field and the enum must
+//! have an integer representation of at most 32 bits, e.g. `#[repr(u32)]`
+//! or `#[repr(C, u8)]`, which is the type of the discriminator. The
+//! discriminant of a variant is its case label and `#[dds(default)]` marks
+//! the default case. A received discriminator that matches no label selects
+//! the default case, without one the sample fails to deserialize.
+//!
+//! A `#[repr(transparent)]` newtype struct of `u8`, `u16`, `u32` or `u64`
+//! with `#[dds(bitmask(a, b, c = N))]` maps to an IDL `bitmask` with the
+//! given flags, which are at the position of the previous flag plus one
+//! unless given. It implements only `DdsType` and gets an associated
+//! constant for every flag, `bits`, `from_bits`, `contains` and the like,
+//! and `TryFrom` the integer, which fails for bits that are not a flag. The
+//! bit bound is the width of the integer unless given with
+//! `#[dds(bit_bound = N)]`. IDL bitsets are not supported, the serializer
+//! of Cyclone DDS has no instruction for them.
+//!
+//! ```ignore
+//! #[derive(TopicType, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+//! #[dds(bitmask(READ, WRITE, EXECUTE = 4))]
+//! #[serde(try_from = "u8", into = "u8")]
+//! #[repr(transparent)]
+//! struct Permissions(u8);
+//! ```
+//!
+//! Struct members of type `Option<T>` are `@optional` and members of type
+//! `Box<T>` are `@external`.
+//!
+//! Structs can be annotated with `#[dds(...)]`:
+//! * `#[dds(final)]`, `#[dds(appendable)]` or `#[dds(mutable)]` - the
+//!   extensibility of the type, final by default. Unions can be final or
+//!   appendable. Types that are not final are serialized with XCDR2 only.
+//! * `#[dds(autoid = "hash")]` - members without an explicit id get the hash
+//!   of their name as id instead of the id of the previous member plus one
+//! * `#[dds(extends = Base)]` - the struct inherits from `Base` (IDL
+//!   `struct Derived : Base`). The first field must hold the base, its
+//!   members are serialized before the members of the struct. Both types
+//!   must have the same extensibility. Implements `XType`.
+//! * `#[dds(topic = "name")]` - the topic name, the name of the struct
+//!   without module path by default
+//! * `#[dds(pod)]` - the struct is plain old data, which must be
+//!   `#[repr(C)]` and `Copy` with members that are `Pod`: primitives,
+//!   arrays of them and other `pod` structs. Implements `Pod`, `Loanable`
+//!   and `PodTopic`.
+//!
+//! All types can be annotated with `#[dds(type_name = "Module::Name")]`,
+//! the scoped IDL name peers know the type by. By default it is the module
+//! path of the type without the crate name, so `HelloWorldData::Msg` in any
+//! crate is `struct Msg` in `module HelloWorldData`. Both names are checked
+//! to be legal IDL and topic names.
+//!
+//! ```ignore
+//! #[derive(TopicType, Clone, Debug, PartialEq)]
+//! #[dds(type_name = "HelloWorldData::Msg", topic = "hello")]
+//! struct Greeting {
+//!     #[dds(key)]
+//!     user_id: i32,
+//!     message: String,
+//! }
+//! ```
+//!
+//! Members can be annotated with `#[dds(...)]`:
+//! * `#[dds(key)]` - the member is part of the key of the topic
+//! * `#[dds(id = N)]` - the member id (`@id`)
+//! * `#[dds(hashid)]` or `#[dds(hashid = "name")]` - the member id is the
+//!   hash of the name of the member or of the given name (`@hashid`)
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, Data, DeriveInput};
 
+mod attrs;
+mod bitmasks;
+mod enums;
+mod structs;
+mod unions;
+
+#[proc_macro_derive(TopicType, attributes(dds))]
+pub fn derive_topic_type(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let result = match &input.data {
+        Data::Struct(data) => structs::derive(&input, data),
+        Data::Enum(data) if data.variants.iter().all(|v| v.fields.is_empty()) => {
+            enums::derive(&input, data)
+        }
+        Data::Enum(data) => unions::derive(&input, data),
+        Data::Union(_) => Err(syn::Error::new_spanned(
+            &input.ident,
+            "TopicType can not be derived for unions",
+        )),
+    };
+
+    result.unwrap_or_else(|err| err.to_compile_error()).into()
+}
@@ -0,0 +1,189 @@
+//! Derive for structs, which map to an IDL `struct`.
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{ext::IdentExt, parse_quote, DataStruct, DeriveInput, Fields};
+
+use crate::{
+    attrs::{repr, ContainerAttrs, FieldAttrs},
+    bitmasks,
+};
+
+pub(crate) fn derive(input: &DeriveInput, data: &DataStruct) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    let attrs = ContainerAttrs::parse(input)?;
+    if attrs.bitmask.is_some() {
+        return bitmasks::derive(input, data, &attrs);
+    }
+    if let Some(bit_bound) = &attrs.bit_bound {
+        return Err(syn::Error::new_spanned(
+            bit_bound,
+            "bit_bound is only supported on enums",
+        ));
+    }
+
+    let fields = match &data.fields {
+        Fields::Named(fields) => &fields.named,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "TopicType can only be derived for structs with named fields",
+            ))
+        }
+    };
+
+    let mut generics = input.generics.clone();
+    for param in input.generics.type_params() {
+        let param = &param.ident;
+        generics
+            .make_where_clause()
+            .predicates
+            .push(parse_quote!(#param: ::cyclonedds_rs::descriptor::DdsType));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // Plain old data is checked by bounds on the types of all fields, the
+    // one holding the base included, which the impls of the type share
+    let mut pod = None;
+    let mut descriptor = quote!(from_type);
+    let mut type_where_clause = quote!(#where_clause);
+    if let Some(pod_attr) = &attrs.pod {
+        if !repr(&input.attrs)?.iter().any(|hint| hint == "C") {
+            return Err(syn::Error::new_spanned(
+                pod_attr,
+                "pod structs must be #[repr(C)]",
+            ));
+        }
+        let mut pod_generics = generics.clone();
+        for field in fields {
+            let ty = &field.ty;
+            pod_generics
+                .make_where_clause()
+                .predicates
+                .push(parse_quote!(#ty: ::cyclonedds_rs::descriptor::Pod));
+        }
+        let (impl_generics, ty_generics, where_clause) = pod_generics.split_for_impl();
+        pod = Some(quote! {
+            unsafe impl #impl_generics ::cyclonedds_rs::descriptor::Pod for #ident #ty_generics #where_clause {}
+
+            unsafe impl #impl_generics ::cyclonedds_rs::topic::Loanable for #ident #ty_generics #where_clause {}
+
+            unsafe impl #impl_generics ::cyclonedds_rs::topic::PodTopic for #ident #ty_generics #where_clause {}
+        });
+        descriptor = quote!(from_pod_type);
+        type_where_clause = quote!(#where_clause);
+    }
+
+    let extensibility = attrs
+        .extensibility()
+        .map(|extensibility| quote!(.extensibility(#extensibility)));
+    let autoid = attrs.autoid().map(|autoid| quote!(.autoid(#autoid)));
+
+    // The base is held by the first field, which is not a member of its own
+    let mut fields = fields.iter();
+    let mut base = None;
+    let mut xtype = None;
+    if let Some(extends) = &attrs.extends {
+        let field = match fields.next() {
+            Some(field) if field.ty == *extends => field,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    extends,
+                    "the first field of a struct with extends must hold the base",
+                ))
+            }
+        };
+        let field_attrs = FieldAttrs::parse(field)?;
+        if field_attrs.key || field_attrs.id.is_some() || field_attrs.hashid.is_some() {
+            return Err(syn::Error::new_spanned(
+                field,
+                "the field holding the base can not have dds attributes",
+            ));
+        }
+        let name = field.ident.as_ref().unwrap();
+        base = Some(quote! {
+            .base(
+                ::core::mem::offset_of!(Self, #name),
+                <#extends as ::cyclonedds_rs::descriptor::DdsType>::type_desc(),
+            )
+        });
+        xtype = Some(quote! {
+            impl #impl_generics ::cyclonedds_rs::topic::XType for #ident #ty_generics #where_clause {
+                type Extends = #extends;
+
+                fn extends(&self) -> &#extends {
+                    &self.#name
+                }
+            }
+        });
+    }
+
+    let mut members = Vec::with_capacity(fields.len());
+    for field in fields {
+        let attrs = FieldAttrs::parse(field)?;
+        let name = field.ident.as_ref().unwrap();
+        // Raw identifiers such as `r#type` keep their IDL name
+        let idl_name = name.unraw().to_string();
+        let ty = &field.ty;
+        let key = attrs.key.then(|| quote!(.key()));
+        let id = match (&attrs.id, &attrs.hashid) {
+            (Some(id), _) => Some(quote!(.id(#id))),
+            (None, Some(Some(hash_name))) => Some(quote!(.hashid(#hash_name))),
+            (None, Some(None)) => Some(quote!(.hashid(#idl_name))),
+            (None, None) => None,
+        };
+        members.push(quote! {
+            .member(::cyclonedds_rs::descriptor::MemberDesc::new(
+                #idl_name,
+                ::core::mem::offset_of!(Self, #name),
+                <#ty as ::cyclonedds_rs::descriptor::DdsType>::type_desc(),
+            )#key #id)
+        });
+    }
+
+    let topic = attrs.topic.as_ref().map(|topic| {
+        quote! {
+            fn name() -> &'static str {
+                #topic
+            }
+        }
+    });
+    let type_name = attrs.type_name.as_ref().map(|type_name| {
+        quote! {
+            fn type_name() -> &'static str {
+                #type_name
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::cyclonedds_rs::descriptor::DdsType for #ident #ty_generics #type_where_clause {
+            fn type_desc() -> ::cyclonedds_rs::descriptor::TypeDesc {
+                ::cyclonedds_rs::descriptor::TypeDesc::Struct(
+                    ::cyclonedds_rs::descriptor::StructDesc::new(
+                        <Self as ::cyclonedds_rs::topic::TopicType>::type_name(),
+                        ::core::mem::size_of::<Self>(),
+                        ::core::mem::align_of::<Self>(),
+                    )
+                    #extensibility
+                    #autoid
+                    #base
+                    #(#members)*
+                )
+            }
+        }
+
+        impl #impl_generics ::cyclonedds_rs::topic::TopicType for #ident #ty_generics #type_where_clause {
+            #topic
+
+            #type_name
+
+            fn topic_descriptor() -> ::cyclonedds_rs::descriptor::TopicDescriptor {
+                ::cyclonedds_rs::descriptor::TopicDescriptor::#descriptor::<Self>()
+            }
+        }
+
+        #xtype
+
+        #pod
+    })
+}
@@ -0,0 +1,121 @@
+//! Derive for enums with data, which map to an IDL `union`.
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{ext::IdentExt, DataEnum, DeriveInput, Fields};
+
+use crate::{
+    attrs::{repr, ContainerAttrs, VariantAttrs},
+    enums::discriminant,
+};
+
+pub(crate) fn derive(input: &DeriveInput, data: &DataEnum) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    let attrs = ContainerAttrs::parse(input)?;
+    attrs.reject_bitmask(input)?;
+    attrs.reject_extends("unions")?;
+    if let Some(bit_bound) = &attrs.bit_bound {
+        return Err(syn::Error::new_spanned(
+            bit_bound,
+            "bit_bound is only supported on enums",
+        ));
+    }
+    if let Some(autoid) = &attrs.autoid {
+        return Err(syn::Error::new_spanned(
+            autoid,
+            "autoid is not supported on unions",
+        ));
+    }
+    if let Some(kind) = attrs
+        .extensibility
+        .as_ref()
+        .filter(|kind| *kind == "mutable")
+    {
+        return Err(syn::Error::new_spanned(kind, "unions can not be mutable"));
+    }
+    let extensibility = attrs
+        .extensibility()
+        .map(|extensibility| quote!(.extensibility(#extensibility)));
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "generic unions are not supported",
+        ));
+    }
+
+    // The integer of the representation is the type of the discriminator.
+    // Samples are never read into the memory of the enum, the serde path
+    // rejects a discriminator that matches no case, so the layout of the
+    // variants does not matter.
+    let repr = repr(&input.attrs)?;
+    let kind = match repr.iter().find_map(|repr| {
+        let kind = match repr.to_string().as_str() {
+            "u8" => quote!(UInt8),
+            "i8" => quote!(Int8),
+            "u16" => quote!(UInt16),
+            "i16" => quote!(Int16),
+            "u32" => quote!(UInt32),
+            "i32" => quote!(Int32),
+            _ => return None,
+        };
+        Some(kind)
+    }) {
+        Some(kind) => kind,
+        None => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "unions must have an integer representation of at most 32 bits, \
+                 e.g. #[repr(u32)] or #[repr(C, u32)]",
+            ))
+        }
+    };
+
+    let mut cases = Vec::with_capacity(data.variants.len());
+    let mut next = 0u64;
+    for variant in &data.variants {
+        let variant_attrs = VariantAttrs::parse(variant)?;
+        // The case member is named after the variant
+        let name = variant.ident.unraw().to_string();
+        let ty = match &variant.fields {
+            Fields::Named(fields) if fields.named.len() == 1 => &fields.named[0].ty,
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => &fields.unnamed[0].ty,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "union cases must have exactly one field",
+                ))
+            }
+        };
+
+        let label = match &variant.discriminant {
+            Some((_, expr)) => discriminant(expr)?,
+            None => next,
+        };
+        next = label + 1;
+        let label = label as u32;
+
+        let default = variant_attrs.default.then(|| quote!(.default()));
+        cases.push(quote! {
+            .case(::cyclonedds_rs::descriptor::CaseDesc::new(
+                #name,
+                #label,
+                <#ty as ::cyclonedds_rs::descriptor::DdsType>::type_desc(),
+            )#default)
+        });
+    }
+
+    let type_name = attrs.type_name();
+    Ok(quote! {
+        impl ::cyclonedds_rs::descriptor::DdsType for #ident {
+            fn type_desc() -> ::cyclonedds_rs::descriptor::TypeDesc {
+                ::cyclonedds_rs::descriptor::TypeDesc::Union(
+                    ::cyclonedds_rs::descriptor::UnionDesc::new(
+                        #type_name,
+                        ::cyclonedds_rs::descriptor::PrimitiveKind::#kind,
+                    )
+                    #extensibility
+                    #(#cases)*
+                )
+            }
+        }
+    })
+}
@@ -0,0 +1,139 @@
+//! The IDL definitions the generator understands, decoupled from the libidl
+//! tree so the Rust emitter does not depend on the parser.
+
+/// A module or the root of an IDL file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Module {
+    /// Name of the module, empty for the root
+    pub name: String,
+    pub definitions: Vec<Definition>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Definition {
+    Module(Module),
+    Struct(Struct),
+    Enum(Enum),
+    Bitmask(Bitmask),
+    Union(Union),
+    Typedef(Typedef),
+    Const(Const),
+}
+
+/// Extensibility of a struct or union.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Extensibility {
+    #[default]
+    Final,
+    Appendable,
+    Mutable,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Struct {
+    pub name: String,
+    /// The struct this struct inherits from
+    pub base: Option<ScopedName>,
+    pub extensibility: Extensibility,
+    /// `@autoid(HASH)`
+    pub hash_autoid: bool,
+    pub members: Vec<Member>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub name: String,
+    pub ty: Type,
+    pub key: bool,
+    pub optional: bool,
+    pub external: bool,
+    /// Member id given with `@id` or `@hashid`
+    pub id: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Enum {
+    pub name: String,
+    /// `@bit_bound`, if not the default of 32
+    pub bit_bound: Option<u16>,
+    pub enumerators: Vec<(String, u32)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bitmask {
+    pub name: String,
+    /// `@bit_bound`, if not the default of 32
+    pub bit_bound: Option<u16>,
+    /// The flags and their positions
+    pub flags: Vec<(String, u16)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Union {
+    pub name: String,
+    pub extensibility: Extensibility,
+    pub discriminator: Type,
+    pub cases: Vec<Case>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Case {
+    pub name: String,
+    /// Case label, `None` for the `default` case
+    pub label: Option<i64>,
+    pub ty: Type,
+    pub external: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Typedef {
+    pub name: String,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Const {
+    pub name: String,
+    pub ty: Type,
+    pub value: Literal,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+    Char(u8),
+    String(String),
+    /// An enumerator of an enum constant
+    Enumerator(ScopedName, String),
+}
+
+/// An IDL type specification.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Bool,
+    Char,
+    Octet,
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Float32,
+    Float64,
+    /// `string` or `string<N>`
+    String(Option<u32>),
+    /// `sequence<T>` or `sequence<T, N>`
+    Sequence(Box<Type>, Option<u32>),
+    /// An array declarator, the dimensions are outermost first
+    Array(Box<Type>, Vec<u32>),
+    /// A reference to a struct, union, enum, bitmask or typedef
+    Named(ScopedName),
+}
+
+/// Fully scoped name of a definition, e.g. `["HelloWorldData", "Msg"]`.
+pub type ScopedName = Vec<String>;
@@ -0,0 +1,290 @@
+//! Generates Rust topic types from IDL files.
+//!
+//! The IDL is parsed with the parser of Cyclone DDS (libidl) and every
+//! struct, enum and union is emitted as a Rust type with
+//! `#[derive(TopicType)]`:
+//!
+//! | IDL                             | Rust                                |
+//! |---------------------------------|-------------------------------------|
+//! | `module M`                      | `pub mod M`                         |
+//! | `struct`                        | `struct`                            |
+//! | `struct D : B`                  | `#[dds(extends = B)] struct`        |
+//! | `enum`                          | `#[repr(u32)] enum`                 |
+//! | `bitmask`                       | `#[repr(transparent)] struct`       |
+//! | `union switch (long)`           | `#[repr(C, i32)] enum` with data    |
+//! | `typedef`                       | `pub type`                          |
+//! | `const`                         | `pub const`                         |
+//! | `char`, `octet`, `uint8`        | `u8`                                |
+//! | `short`, `long`, `long long`    | `i16`, `i32`, `i64`                 |
+//! | `string`, `string<N>`           | `String`, `BoundedString<N>`        |
+//! | `sequence<T>`, `sequence<T, N>` | `Vec<T>`, `BoundedVec<T, N>`        |
+//! | `T a[N][M]`                     | `a: [[T; M]; N]`                    |
+//! | `@optional T`                   | `Option<T>`                         |
+//! | `@external T`                   | `Box<T>`                            |
+//!
+//! `@key`, `#pragma keylist`, `@final`, `@appendable`, `@mutable`,
+//! `@autoid`, `@id`, `@hashid` and `@bit_bound` carry over to the
+//! corresponding `#[dds(...)]` attributes.
+//!
+//! The generated code uses the IDL module of a type as Rust module and sets
+//! the type name to the scoped IDL name with `#[dds(type_name = "...")]`,
+//! e.g. `HelloWorldData::Msg`, wherever it is included.
+//!
+//! From a `build.rs`:
+//!
+//! ```no_run
+//! cyclonedds_idl::Generator::new()
+//!     .file("idl/HelloWorldData.idl")
+//!     .build("HelloWorldData.rs")
+//!     .unwrap();
+//! ```
+//!
+//! and at the root of the crate:
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/HelloWorldData.rs"));
+//! ```
+//!
+//! The `cyclonedds-idl` binary does the same from the command line.
+use std::{
+    collections::HashSet,
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+pub mod ast;
+mod parse;
+mod rust;
+
+/// Errors of the code generator.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to read {path}: {source}")]
+    Read { path: PathBuf, source: io::Error },
+    #[error("failed to write {path}: {source}")]
+    Write { path: PathBuf, source: io::Error },
+    #[error("{file}: include file {name} not found")]
+    IncludeNotFound { file: PathBuf, name: String },
+    #[error("{0}")]
+    Parse(String),
+    #[error("{0} is not supported")]
+    Unsupported(String),
+    #[error("OUT_DIR is not set, build() is meant to be called from a build script")]
+    NoOutDir,
+}
+
+/// Generates Rust code for a set of IDL files.
+#[derive(Debug, Clone, Default)]
+pub struct Generator {
+    files: Vec<PathBuf>,
+    include_dirs: Vec<PathBuf>,
+    options: rust::Options,
+}
+
+impl Generator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an IDL file to generate code for.
+    pub fn file(mut self, path: impl AsRef<Path>) -> Self {
+        self.files.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Adds a directory to search for `#include <...>` files, after the
+    /// directory of the including file.
+    pub fn include_dir(mut self, path: impl AsRef<Path>) -> Self {
+        self.include_dirs.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Also derive `serde::Serialize` and `serde::Deserialize` for the
+    /// generated types, which requires `serde` with the `derive` feature.
+    pub fn serde(mut self, serde: bool) -> Self {
+        self.options.serde = serde;
+        self
+    }
+
+    /// Generates the code for the files, returns it and the paths of all IDL
+    /// files read, including the included ones.
+    fn run(&self) -> Result<(String, Vec<PathBuf>), Error> {
+        let mut idl = String::new();
+        let mut seen = HashSet::new();
+        let mut read = Vec::new();
+        for file in &self.files {
+            self.preprocess(file, &mut seen, &mut read, &mut idl)?;
+        }
+        let root = parse::parse(&idl)?;
+        let mut code = String::from("// Generated by cyclonedds-idl, do not edit.\n\n");
+        code.push_str(&rust::emit(&root, &self.options)?);
+        Ok((code, read))
+    }
+
+    /// Returns the generated code.
+    pub fn generate(&self) -> Result<String, Error> {
+        self.run().map(|(code, _)| code)
+    }
+
+    /// Writes the generated code to a file.
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let code = self.generate()?;
+        fs::write(path, code).map_err(|source| Error::Write {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Writes the generated code to `name` in `OUT_DIR` and tells cargo to
+    /// rerun the build script when one of the IDL files changes. To be
+    /// called from a build script.
+    pub fn build(&self, name: impl AsRef<Path>) -> Result<(), Error> {
+        let out_dir = env::var_os("OUT_DIR").ok_or(Error::NoOutDir)?;
+        let path = Path::new(&out_dir).join(name);
+        let (code, read) = self.run()?;
+        for file in read {
+            println!("cargo:rerun-if-changed={}", file.display());
+        }
+        fs::write(&path, code).map_err(|source| Error::Write { path, source })
+    }
+
+    /// Inlines the `#include` directives of a file, which libidl leaves to
+    /// a preprocessor. Every file is included once.
+    fn preprocess(
+        &self,
+        file: &Path,
+        seen: &mut HashSet<PathBuf>,
+        read: &mut Vec<PathBuf>,
+        out: &mut String,
+    ) -> Result<(), Error> {
+        let canonical = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+        if !seen.insert(canonical) {
+            return Ok(());
+        }
+        let idl = fs::read_to_string(file).map_err(|source| Error::Read {
+            path: file.to_path_buf(),
+            source,
+        })?;
+        read.push(file.to_path_buf());
+
+        out.push_str(&format!("#line 1 \"{}\"\n", file.display()));
+        for (i, line) in idl.lines().enumerate() {
+            let Some((name, quoted)) = include_name(line) else {
+                out.push_str(line);
+                out.push('\n');
+                continue;
+            };
+            let dir = file.parent().unwrap_or(Path::new("."));
+            let found = quoted
+                .then(|| dir.join(name))
+                .into_iter()
+                .chain(self.include_dirs.iter().map(|dir| dir.join(name)))
+                .chain((!quoted).then(|| dir.join(name)))
+                .find(|path| path.is_file())
+                .ok_or_else(|| Error::IncludeNotFound {
+                    file: file.to_path_buf(),
+                    name: name.to_string(),
+                })?;
+            self.preprocess(&found, seen, read, out)?;
+            out.push_str(&format!("#line {} \"{}\"\n", i + 2, file.display()));
+        }
+        Ok(())
+    }
+}
+
+/// Parses IDL that needs no preprocessing into its definitions.
+pub fn parse(idl: &str) -> Result<ast::Module, Error> {
+    parse::parse(idl)
+}
+
+/// Returns the file name of an `#include` directive and whether it is
+/// quoted rather than in angle brackets.
+fn include_name(line: &str) -> Option<(&str, bool)> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start();
+    let rest = rest.strip_prefix("include")?.trim();
+    if let Some(name) = rest.strip_prefix('"') {
+        Some((name.strip_suffix('"')?, true))
+    } else {
+        Some((rest.strip_prefix('<')?.strip_suffix('>')?, false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cyclonedds_rs::{
+        bounded::{BoundedString, BoundedVec},
+        idl::to_idl,
+        topic::TopicType,
+    };
+
+    use super::{ast::*, include_name, parse};
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[dds(appendable)]
+    struct Msg {
+        #[dds(key)]
+        id: u32,
+        #[dds(id = 5)]
+        name: BoundedString<8>,
+        values: Option<BoundedVec<f64, 4>>,
+    }
+
+    #[test]
+    fn test_include_name() {
+        assert_eq!(include_name("#include \"a.idl\""), Some(("a.idl", true)));
+        assert_eq!(
+            include_name("  #  include <dir/b.idl> "),
+            Some(("dir/b.idl", false))
+        );
+        assert_eq!(include_name("#pragma keylist Msg userID"), None);
+        assert_eq!(include_name("struct Msg {"), None);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        // The IDL exported for a Rust type parses to the same definition
+        let root = parse(&to_idl::<Msg>()).unwrap();
+        let msg = Struct {
+            name: "Msg".into(),
+            base: None,
+            extensibility: Extensibility::Appendable,
+            hash_autoid: false,
+            members: vec![
+                Member {
+                    name: "id".into(),
+                    ty: Type::UInt32,
+                    key: true,
+                    optional: false,
+                    external: false,
+                    id: None,
+                },
+                Member {
+                    name: "name".into(),
+                    ty: Type::String(Some(8)),
+                    key: false,
+                    optional: false,
+                    external: false,
+                    id: Some(5),
+                },
+                Member {
+                    name: "values".into(),
+                    ty: Type::Sequence(Box::new(Type::Float64), Some(4)),
+                    key: false,
+                    optional: true,
+                    external: false,
+                    id: None,
+                },
+            ],
+        };
+        assert_eq!(
+            root.definitions,
+            vec![Definition::Module(Module {
+                name: "tests".into(),
+                definitions: vec![Definition::Struct(msg)],
+            })]
+        );
+    }
+}
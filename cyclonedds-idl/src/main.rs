@@ -0,0 +1,65 @@
+//! Command line front-end of the generator:
+//!
+//! ```text
+//! cyclonedds-idl [-I dir]... [--serde] [-o out.rs] file.idl...
+//! ```
+//!
+//! The code is written to standard output unless `-o` is given.
+use std::{env, process::ExitCode};
+
+use cyclonedds_idl::Generator;
+
+const USAGE: &str = "usage: cyclonedds-idl [-I dir]... [--serde] [-o out.rs] file.idl...";
+
+fn main() -> ExitCode {
+    let mut generator = Generator::new();
+    let mut output = None;
+    let mut files = 0;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            "-I" | "-o" => {
+                let Some(value) = args.next() else {
+                    eprintln!("{} requires an argument\n{}", arg, USAGE);
+                    return ExitCode::FAILURE;
+                };
+                if arg == "-I" {
+                    generator = generator.include_dir(value);
+                } else {
+                    output = Some(value);
+                }
+            }
+            "--serde" => generator = generator.serde(true),
+            _ if arg.starts_with("-I") => generator = generator.include_dir(&arg[2..]),
+            _ if arg.starts_with('-') => {
+                eprintln!("unknown option {}\n{}", arg, USAGE);
+                return ExitCode::FAILURE;
+            }
+            _ => {
+                generator = generator.file(arg);
+                files += 1;
+            }
+        }
+    }
+    if files == 0 {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    }
+
+    let result = match output {
+        Some(path) => generator.write(path),
+        None => generator.generate().map(|code| print!("{}", code)),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("cyclonedds-idl: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,370 @@
+//! Front-end on top of the IDL parser of Cyclone DDS (libidl), converts the
+//! parse tree into the definitions of [crate::ast].
+use std::{
+    ffi::{c_void, CStr, CString},
+    ptr,
+};
+
+use cyclonedds_sys::idl::*;
+
+use crate::{
+    ast::{
+        Bitmask, Case, Const, Definition, Enum, Extensibility, Literal, Member, Module, ScopedName,
+        Struct, Type, Typedef, Union,
+    },
+    Error,
+};
+
+/// Parses preprocessed IDL into the definitions of the root module.
+pub(crate) fn parse(idl: &str) -> Result<Module, Error> {
+    let idl = CString::new(idl).map_err(|_| Error::Parse("IDL contains a nul byte".into()))?;
+    let flags = IDL_FLAG_EXTENDED_DATA_TYPES | IDL_FLAG_ANONYMOUS_TYPES | IDL_FLAG_ANNOTATIONS;
+
+    let mut pstate: *mut idl_pstate_t = ptr::null_mut();
+    unsafe {
+        let ret = idl_create_pstate(flags, ptr::null(), &mut pstate);
+        if ret < 0 {
+            return Err(Error::Parse(format!(
+                "failed to create the parser state ({})",
+                ret
+            )));
+        }
+        // libidl reports the details of parse errors on stderr
+        let ret = idl_parse_string(pstate, idl.as_ptr());
+        let result = if ret < 0 {
+            Err(Error::Parse(format!("failed to parse the IDL ({})", ret)))
+        } else {
+            let mut root = Module::default();
+            definitions((*pstate).root as *const c_void, &mut root).map(|_| root)
+        };
+        idl_delete_pstate(pstate);
+        result
+    }
+}
+
+/// Iterates over a list of sibling nodes.
+unsafe fn siblings<T>(first: *const T) -> impl Iterator<Item = *const T> {
+    let mut node = first as *const c_void;
+    std::iter::from_fn(move || {
+        if node.is_null() {
+            return None;
+        }
+        let current = node as *const T;
+        node = idl_next(node) as *const c_void;
+        Some(current)
+    })
+}
+
+unsafe fn identifier(node: *const c_void) -> String {
+    CStr::from_ptr(idl_identifier(node))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Fully scoped name of a struct, union, enum or typedef declarator.
+unsafe fn scoped_name(node: *const c_void) -> ScopedName {
+    let mut name = vec![identifier(node)];
+    let mut parent = idl_parent(node) as *const c_void;
+    while !parent.is_null() {
+        if idl_is_module(parent) {
+            name.push(identifier(parent));
+        }
+        parent = idl_parent(parent) as *const c_void;
+    }
+    name.reverse();
+    name
+}
+
+unsafe fn definitions(first: *const c_void, module: &mut Module) -> Result<(), Error> {
+    for node in siblings(first) {
+        if idl_is_module(node) {
+            let node = node as *const idl_module_t;
+            let name = identifier(node as *const c_void);
+            // A module can be reopened, its definitions are merged
+            let index =
+                match module.definitions.iter().position(
+                    |def| matches!(def, Definition::Module(module) if module.name == name),
+                ) {
+                    Some(index) => index,
+                    None => {
+                        module.definitions.push(Definition::Module(Module {
+                            name,
+                            definitions: Vec::new(),
+                        }));
+                        module.definitions.len() - 1
+                    }
+                };
+            let Definition::Module(nested) = &mut module.definitions[index] else {
+                unreachable!()
+            };
+            definitions((*node).definitions as *const c_void, nested)?;
+        } else if idl_is_struct(node) {
+            let def = structure(node as *const idl_struct_t)?;
+            module.definitions.push(Definition::Struct(def));
+        } else if idl_is_union(node) {
+            let def = union(node as *const idl_union_t)?;
+            module.definitions.push(Definition::Union(def));
+        } else if idl_is_enum(node) {
+            let def = enumeration(node as *const idl_enum_t);
+            module.definitions.push(Definition::Enum(def));
+        } else if idl_is_bitmask(node) {
+            let def = bitmask(node as *const idl_bitmask_t);
+            module.definitions.push(Definition::Bitmask(def));
+        } else if idl_is_typedef(node) {
+            let node = node as *const idl_typedef_t;
+            let ty = type_spec((*node).type_spec)?;
+            for declarator in siblings((*node).declarators) {
+                module.definitions.push(Definition::Typedef(Typedef {
+                    name: identifier(declarator as *const c_void),
+                    ty: declarator_type(declarator, ty.clone()),
+                }));
+            }
+        } else if idl_is_const(node) {
+            let def = constant(node as *const idl_const_t)?;
+            module.definitions.push(Definition::Const(def));
+        }
+        // Forward declarations and annotations need no code
+    }
+    Ok(())
+}
+
+fn extensibility(value: idl_extensibility_t) -> Extensibility {
+    match value {
+        idl_extensibility_t::IDL_APPENDABLE => Extensibility::Appendable,
+        idl_extensibility_t::IDL_MUTABLE => Extensibility::Mutable,
+        _ => Extensibility::Final,
+    }
+}
+
+unsafe fn structure(node: *const idl_struct_t) -> Result<Struct, Error> {
+    let name = identifier(node as *const c_void);
+    let base = if (*node).inherit_spec.is_null() {
+        None
+    } else {
+        Some(scoped_name((*(*node).inherit_spec).base))
+    };
+
+    let mut members = Vec::new();
+    for member in siblings((*node).members) {
+        let ty = type_spec((*member).type_spec)?;
+        for declarator in siblings((*member).declarators) {
+            let id = &(*declarator).id;
+            members.push(Member {
+                name: identifier(declarator as *const c_void),
+                ty: declarator_type(declarator, ty.clone()),
+                key: (*member).key.value == idl_boolean_t::IDL_TRUE,
+                optional: idl_is_optional(member as *const c_void),
+                external: idl_is_external(member as *const c_void),
+                id: (id.annotation != idl_id_kind_t::IDL_AUTOID).then_some(id.value),
+            });
+        }
+    }
+
+    // `#pragma keylist` names the key members of the struct
+    if !(*node).keylist.is_null() {
+        for key in siblings((*(*node).keylist).keys) {
+            let field_name = (*key).field_name;
+            if (*field_name).length != 1 {
+                return Err(Error::Unsupported(format!(
+                    "keys of nested members in the keylist of struct {}",
+                    name
+                )));
+            }
+            let key = CStr::from_ptr((*(*(*field_name).names)).identifier)
+                .to_string_lossy()
+                .into_owned();
+            match members.iter_mut().find(|member| member.name == key) {
+                Some(member) => member.key = true,
+                None => {
+                    return Err(Error::Parse(format!(
+                        "struct {} has no member {} named in its keylist",
+                        name, key
+                    )))
+                }
+            }
+        }
+    }
+
+    Ok(Struct {
+        name,
+        base,
+        extensibility: extensibility((*node).extensibility.value),
+        hash_autoid: (*node).autoid.value == idl_autoid_t::IDL_HASH,
+        members,
+    })
+}
+
+unsafe fn union(node: *const idl_union_t) -> Result<Union, Error> {
+    let name = identifier(node as *const c_void);
+    let discriminator = type_spec(unalias((*(*node).switch_type_spec).type_spec)?)?;
+
+    let mut cases = Vec::new();
+    for case in siblings((*node).cases) {
+        let mut labels = siblings((*case).labels)
+            .filter(|label| !(*(*label)).const_expr.is_null())
+            .map(|label| literal_int((*label).const_expr).map(|value| value as i64))
+            .collect::<Result<Vec<_>, _>>()?;
+        let declarator = (*case).declarator;
+        let case_name = identifier(declarator as *const c_void);
+        if labels.len() > 1 {
+            return Err(Error::Unsupported(format!(
+                "more than one label for case {} of union {}",
+                case_name, name
+            )));
+        }
+        let ty = type_spec((*case).type_spec)?;
+        cases.push(Case {
+            name: case_name,
+            label: if idl_is_default_case(case as *const c_void) {
+                None
+            } else {
+                labels.pop()
+            },
+            ty: declarator_type(declarator, ty),
+            external: idl_is_external(case as *const c_void),
+        });
+    }
+
+    Ok(Union {
+        name,
+        extensibility: extensibility((*node).extensibility.value),
+        discriminator,
+        cases,
+    })
+}
+
+unsafe fn enumeration(node: *const idl_enum_t) -> Enum {
+    let bit_bound = (*node).bit_bound.value;
+    Enum {
+        name: identifier(node as *const c_void),
+        bit_bound: (bit_bound != 32).then_some(bit_bound),
+        enumerators: siblings((*node).enumerators)
+            .map(|enumerator| {
+                (
+                    identifier(enumerator as *const c_void),
+                    (*enumerator).value.value,
+                )
+            })
+            .collect(),
+    }
+}
+
+unsafe fn bitmask(node: *const idl_bitmask_t) -> Bitmask {
+    let bit_bound = (*node).bit_bound.value;
+    Bitmask {
+        name: identifier(node as *const c_void),
+        bit_bound: (bit_bound != 32).then_some(bit_bound),
+        flags: siblings((*node).bit_values)
+            .map(|flag| (identifier(flag as *const c_void), (*flag).position.value))
+            .collect(),
+    }
+}
+
+unsafe fn constant(node: *const idl_const_t) -> Result<Const, Error> {
+    let name = identifier(node as *const c_void);
+    let ty = type_spec((*node).type_spec)?;
+    let expr = (*node).const_expr as *const c_void;
+
+    let value = if idl_is_enumerator(expr) {
+        Literal::Enumerator(
+            scoped_name(idl_parent(expr) as *const c_void),
+            identifier(expr),
+        )
+    } else {
+        let literal = expr as *const idl_literal_t;
+        match idl_type(expr) {
+            idl_type_t::IDL_BOOL => Literal::Bool((*literal).value.bln),
+            idl_type_t::IDL_CHAR => Literal::Char((*literal).value.chr as u8),
+            idl_type_t::IDL_FLOAT => Literal::Float((*literal).value.flt as f64),
+            idl_type_t::IDL_DOUBLE => Literal::Float((*literal).value.dbl),
+            idl_type_t::IDL_STRING => Literal::String(
+                CStr::from_ptr((*literal).value.str_)
+                    .to_string_lossy()
+                    .into_owned(),
+            ),
+            _ => Literal::Int(literal_int(expr)?),
+        }
+    };
+    Ok(Const { name, ty, value })
+}
+
+/// Value of an integer literal, a boolean or an enumerator as used for
+/// union labels.
+unsafe fn literal_int(expr: *const c_void) -> Result<i128, Error> {
+    if idl_is_enumerator(expr) {
+        return Ok((*(expr as *const idl_enumerator_t)).value.value as i128);
+    }
+    let value = &(*(expr as *const idl_literal_t)).value;
+    Ok(match idl_type(expr) {
+        idl_type_t::IDL_BOOL => value.bln as i128,
+        idl_type_t::IDL_CHAR => value.chr as u8 as i128,
+        idl_type_t::IDL_INT8 => value.int8 as i128,
+        idl_type_t::IDL_OCTET | idl_type_t::IDL_UINT8 => value.uint8 as i128,
+        idl_type_t::IDL_SHORT | idl_type_t::IDL_INT16 => value.int16 as i128,
+        idl_type_t::IDL_USHORT | idl_type_t::IDL_UINT16 => value.uint16 as i128,
+        idl_type_t::IDL_LONG | idl_type_t::IDL_INT32 => value.int32 as i128,
+        idl_type_t::IDL_ULONG | idl_type_t::IDL_UINT32 => value.uint32 as i128,
+        idl_type_t::IDL_LLONG | idl_type_t::IDL_INT64 => value.int64 as i128,
+        idl_type_t::IDL_ULLONG | idl_type_t::IDL_UINT64 => value.uint64 as i128,
+        _ => return Err(Error::Unsupported("non-integer constant expression".into())),
+    })
+}
+
+/// Resolves typedefs down to the aliased type.
+unsafe fn unalias(mut spec: *const c_void) -> Result<*const c_void, Error> {
+    while idl_is_declarator(spec) && idl_is_typedef(idl_parent(spec) as *const c_void) {
+        if !(*(spec as *const idl_declarator_t)).const_expr.is_null() {
+            return Err(Error::Unsupported(format!(
+                "array typedef {} as union discriminator",
+                identifier(spec)
+            )));
+        }
+        spec = (*(idl_parent(spec) as *const idl_typedef_t)).type_spec as *const c_void;
+    }
+    Ok(spec)
+}
+
+unsafe fn type_spec(spec: *const c_void) -> Result<Type, Error> {
+    let bound = || match idl_bound(spec) {
+        0 => None,
+        bound => Some(bound),
+    };
+    Ok(match idl_type(spec) {
+        idl_type_t::IDL_BOOL => Type::Bool,
+        idl_type_t::IDL_CHAR => Type::Char,
+        idl_type_t::IDL_OCTET => Type::Octet,
+        idl_type_t::IDL_INT8 => Type::Int8,
+        idl_type_t::IDL_UINT8 => Type::UInt8,
+        idl_type_t::IDL_SHORT | idl_type_t::IDL_INT16 => Type::Int16,
+        idl_type_t::IDL_USHORT | idl_type_t::IDL_UINT16 => Type::UInt16,
+        idl_type_t::IDL_LONG | idl_type_t::IDL_INT32 => Type::Int32,
+        idl_type_t::IDL_ULONG | idl_type_t::IDL_UINT32 => Type::UInt32,
+        idl_type_t::IDL_LLONG | idl_type_t::IDL_INT64 => Type::Int64,
+        idl_type_t::IDL_ULLONG | idl_type_t::IDL_UINT64 => Type::UInt64,
+        idl_type_t::IDL_FLOAT => Type::Float32,
+        idl_type_t::IDL_DOUBLE => Type::Float64,
+        idl_type_t::IDL_STRING => Type::String(bound()),
+        idl_type_t::IDL_SEQUENCE => {
+            let element = (*(spec as *const idl_sequence_t)).type_spec as *const c_void;
+            Type::Sequence(Box::new(type_spec(element)?), bound())
+        }
+        idl_type_t::IDL_STRUCT
+        | idl_type_t::IDL_UNION
+        | idl_type_t::IDL_ENUM
+        | idl_type_t::IDL_BITMASK
+        | idl_type_t::IDL_TYPEDEF => Type::Named(scoped_name(spec)),
+        other => return Err(Error::Unsupported(format!("IDL type {:?}", other))),
+    })
+}
+
+/// Applies the array dimensions of a declarator to its type.
+unsafe fn declarator_type(declarator: *const idl_declarator_t, ty: Type) -> Type {
+    let dims = siblings((*declarator).const_expr as *const idl_literal_t)
+        .map(|literal| (*literal).value.uint32)
+        .collect::<Vec<_>>();
+    if dims.is_empty() {
+        ty
+    } else {
+        Type::Array(Box::new(ty), dims)
+    }
+}
@@ -0,0 +1,710 @@
+//! Emits the Rust source for the IDL definitions.
+//!
+//! IDL modules become Rust modules and every type gets its scoped IDL name
+//! as `#[dds(type_name = "...")]`, so `TopicType::type_name()` matches peers
+//! wherever the generated file is included.
+use std::fmt::Write;
+
+use crate::{
+    ast::{
+        Bitmask, Case, Const, Definition, Enum, Extensibility, Literal, Member, Module, ScopedName,
+        Struct, Type, Typedef, Union,
+    },
+    Error,
+};
+
+/// Options of the emitted code.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Options {
+    /// Derive `serde::Serialize` and `serde::Deserialize` as well
+    pub(crate) serde: bool,
+}
+
+/// Identifiers that are keywords in Rust but not in IDL, the generated code
+/// uses raw identifiers for them.
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "continue", "crate", "do", "dyn", "else", "extern",
+    "fn", "for", "if", "impl", "in", "let", "loop", "macro", "match", "mod", "move", "mut", "priv",
+    "pub", "ref", "return", "self", "Self", "static", "super", "trait", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// Keywords that can not be raw identifiers.
+const RESERVED: &[&str] = &["crate", "self", "Self", "super"];
+
+fn ident(name: &str) -> String {
+    if RESERVED.contains(&name) {
+        format!("{}_", name)
+    } else if KEYWORDS.contains(&name) {
+        format!("r#{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+pub(crate) fn emit(root: &Module, options: &Options) -> Result<String, Error> {
+    let mut emitter = Emitter {
+        out: String::new(),
+        scope: Vec::new(),
+        options,
+        root,
+    };
+    emitter.definitions(&root.definitions)?;
+    Ok(emitter.out)
+}
+
+struct Emitter<'a> {
+    out: String,
+    /// Names of the enclosing modules
+    scope: Vec<&'a str>,
+    options: &'a Options,
+    root: &'a Module,
+}
+
+impl<'a> Emitter<'a> {
+    fn line(&mut self, line: &str) {
+        if !line.is_empty() {
+            for _ in 0..self.scope.len() {
+                self.out.push_str("    ");
+            }
+        }
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    fn definitions(&mut self, definitions: &'a [Definition]) -> Result<(), Error> {
+        for (i, definition) in definitions.iter().enumerate() {
+            if i > 0 {
+                self.line("");
+            }
+            match definition {
+                Definition::Module(module) => self.module(module)?,
+                Definition::Struct(def) => self.structure(def)?,
+                Definition::Enum(def) => self.enumeration(def)?,
+                Definition::Bitmask(def) => self.bitmask(def)?,
+                Definition::Union(def) => self.union(def)?,
+                Definition::Typedef(def) => self.typedef(def)?,
+                Definition::Const(def) => self.constant(def)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn module(&mut self, module: &'a Module) -> Result<(), Error> {
+        self.line("#[allow(non_snake_case, non_camel_case_types, non_upper_case_globals)]");
+        self.line(&format!("pub mod {} {{", ident(&module.name)));
+        self.scope.push(&module.name);
+        self.definitions(&module.definitions)?;
+        self.scope.pop();
+        self.line("}");
+        Ok(())
+    }
+
+    /// Lints the IDL naming conventions trip over, modules already allow
+    /// them for their contents.
+    fn allow(&mut self) {
+        if self.scope.is_empty() {
+            self.line("#[allow(non_snake_case, non_camel_case_types)]");
+        }
+    }
+
+    fn derive(&mut self, extra: &str) {
+        let serde = if self.options.serde {
+            ", ::serde::Serialize, ::serde::Deserialize"
+        } else {
+            ""
+        };
+        self.line(&format!(
+            "#[derive(::cyclonedds_rs::topic::TopicType, Clone, {}Debug, PartialEq{})]",
+            extra, serde
+        ));
+    }
+
+    fn structure(&mut self, def: &Struct) -> Result<(), Error> {
+        self.allow();
+        self.derive("");
+        let mut attrs = vec![self.type_name(&def.name)];
+        match def.extensibility {
+            Extensibility::Final => {}
+            Extensibility::Appendable => attrs.push("appendable".to_string()),
+            Extensibility::Mutable => attrs.push("mutable".to_string()),
+        }
+        if def.hash_autoid {
+            attrs.push("autoid = \"hash\"".to_string());
+        }
+        // The base is held by a first field named `base`
+        let base = def.base.as_ref().map(|base| self.path(base));
+        if let Some(base) = &base {
+            if def.members.iter().any(|member| member.name == "base") {
+                return Err(Error::Unsupported(format!(
+                    "member base of struct {}, which inherits from another struct",
+                    def.name
+                )));
+            }
+            attrs.push(format!("extends = {}", base));
+        }
+        self.line(&format!("#[dds({})]", attrs.join(", ")));
+        self.line(&format!("pub struct {} {{", ident(&def.name)));
+        if let Some(base) = &base {
+            self.line(&format!("    pub base: {},", base));
+        }
+        for member in &def.members {
+            self.member(member)?;
+        }
+        self.line("}");
+        Ok(())
+    }
+
+    fn member(&mut self, member: &Member) -> Result<(), Error> {
+        let mut attrs = Vec::new();
+        if member.key {
+            attrs.push("key".to_string());
+        }
+        if let Some(id) = member.id {
+            attrs.push(format!("id = {}", id));
+        }
+        if !attrs.is_empty() {
+            self.line(&format!("    #[dds({})]", attrs.join(", ")));
+        }
+        let mut ty = self.rust_type(&member.ty)?;
+        if member.external {
+            ty = format!("Box<{}>", ty);
+        }
+        if member.optional {
+            ty = format!("Option<{}>", ty);
+        }
+        self.line(&format!("    pub {}: {},", ident(&member.name), ty));
+        Ok(())
+    }
+
+    fn enumeration(&mut self, def: &Enum) -> Result<(), Error> {
+        // The derive requires the values to be contiguous from 0
+        if def
+            .enumerators
+            .iter()
+            .enumerate()
+            .any(|(i, (_, value))| i as u32 != *value)
+        {
+            return Err(Error::Unsupported(format!(
+                "enum {} with enumerator values that do not count up from 0",
+                def.name
+            )));
+        }
+        self.allow();
+        self.derive("Copy, ");
+        self.line("#[repr(u32)]");
+        match def.bit_bound {
+            Some(bit_bound) => self.line(&format!(
+                "#[dds({}, bit_bound = {})]",
+                self.type_name(&def.name),
+                bit_bound
+            )),
+            None => self.line(&format!("#[dds({})]", self.type_name(&def.name))),
+        }
+        self.line(&format!("pub enum {} {{", ident(&def.name)));
+        for (name, value) in &def.enumerators {
+            self.line(&format!("    {} = {},", ident(name), value));
+        }
+        self.line("}");
+        Ok(())
+    }
+
+    fn bitmask(&mut self, def: &Bitmask) -> Result<(), Error> {
+        let bit_bound = def.bit_bound.unwrap_or(32);
+        let width = match bit_bound {
+            1..=8 => 8,
+            9..=16 => 16,
+            17..=32 => 32,
+            33..=64 => 64,
+            _ => {
+                return Err(Error::Unsupported(format!(
+                    "bit_bound {} of bitmask {}",
+                    bit_bound, def.name
+                )))
+            }
+        };
+
+        // Positions are only given where they differ from the previous
+        // position plus one
+        let mut flags = Vec::with_capacity(def.flags.len());
+        let mut next = 0;
+        for (name, position) in &def.flags {
+            if *position == next {
+                flags.push(ident(name));
+            } else {
+                flags.push(format!("{} = {}", ident(name), position));
+            }
+            next = position + 1;
+        }
+        let mut attrs = format!(
+            "{}, bitmask({})",
+            self.type_name(&def.name),
+            flags.join(", ")
+        );
+        if bit_bound != width {
+            write!(attrs, ", bit_bound = {}", bit_bound).unwrap();
+        }
+
+        self.allow();
+        self.derive("Copy, ");
+        self.line(&format!("#[dds({})]", attrs));
+        if self.options.serde {
+            // Values with bits that are not a flag are rejected
+            self.line(&format!(
+                "#[serde(try_from = \"u{0}\", into = \"u{0}\")]",
+                width
+            ));
+        }
+        self.line("#[repr(transparent)]");
+        self.line(&format!("pub struct {}(u{});", ident(&def.name), width));
+        Ok(())
+    }
+
+    fn union(&mut self, def: &Union) -> Result<(), Error> {
+        let repr = match &def.discriminator {
+            Type::Int8 => "i8",
+            Type::Bool | Type::Char | Type::Octet | Type::UInt8 => "u8",
+            Type::Int16 => "i16",
+            Type::UInt16 => "u16",
+            Type::Int32 => "i32",
+            Type::UInt32 => "u32",
+            Type::Named(name) if matches!(self.lookup(name), Some(Definition::Enum(_))) => "u32",
+            _ => {
+                return Err(Error::Unsupported(format!(
+                    "discriminator of union {}, it must be an integer of at most 32 bits, \
+                     a boolean, a char or an enum",
+                    def.name
+                )))
+            }
+        };
+
+        // Every variant needs a distinct discriminant, the default case gets
+        // the first value after the labels.
+        let default = def
+            .cases
+            .iter()
+            .filter_map(|case| case.label)
+            .max()
+            .map_or(0, |max| max + 1);
+
+        self.allow();
+        self.derive("");
+        self.line(&format!("#[repr(C, {})]", repr));
+        if def.extensibility == Extensibility::Appendable {
+            self.line(&format!(
+                "#[dds({}, appendable)]",
+                self.type_name(&def.name)
+            ));
+        } else {
+            self.line(&format!("#[dds({})]", self.type_name(&def.name)));
+        }
+        self.line(&format!("pub enum {} {{", ident(&def.name)));
+        for Case {
+            name,
+            label,
+            ty,
+            external,
+        } in &def.cases
+        {
+            if label.is_none() {
+                self.line("    #[dds(default)]");
+            }
+            let mut ty = self.rust_type(ty)?;
+            if *external {
+                ty = format!("Box<{}>", ty);
+            }
+            self.line(&format!(
+                "    {}({}) = {},",
+                ident(name),
+                ty,
+                label.unwrap_or(default)
+            ));
+        }
+        self.line("}");
+        Ok(())
+    }
+
+    fn typedef(&mut self, def: &Typedef) -> Result<(), Error> {
+        let ty = self.rust_type(&def.ty)?;
+        self.allow();
+        self.line(&format!("pub type {} = {};", ident(&def.name), ty));
+        Ok(())
+    }
+
+    fn constant(&mut self, def: &Const) -> Result<(), Error> {
+        let (ty, value) = match (&def.ty, &def.value) {
+            (Type::String(_), Literal::String(value)) => {
+                ("&str".to_string(), format!("{:?}", value))
+            }
+            (Type::Char, Literal::Char(value)) => (
+                "u8".to_string(),
+                format!("b'{}'", (*value as char).escape_default()),
+            ),
+            (_, Literal::Bool(value)) => ("bool".to_string(), value.to_string()),
+            (ty, Literal::Int(value)) => (self.rust_type(ty)?, value.to_string()),
+            (ty, Literal::Float(value)) => (self.rust_type(ty)?, format!("{:?}", value)),
+            (ty, Literal::Enumerator(name, enumerator)) => {
+                let ty = self.rust_type(ty)?;
+                (ty, format!("{}::{}", self.path(name), ident(enumerator)))
+            }
+            _ => {
+                return Err(Error::Unsupported(format!(
+                    "value of the constant {}",
+                    def.name
+                )))
+            }
+        };
+        if self.scope.is_empty() {
+            self.line("#[allow(non_upper_case_globals)]");
+        }
+        self.line(&format!(
+            "pub const {}: {} = {};",
+            ident(&def.name),
+            ty,
+            value
+        ));
+        Ok(())
+    }
+
+    fn rust_type(&self, ty: &Type) -> Result<String, Error> {
+        Ok(match ty {
+            Type::Bool => "bool".to_string(),
+            Type::Char | Type::Octet | Type::UInt8 => "u8".to_string(),
+            Type::Int8 => "i8".to_string(),
+            Type::Int16 => "i16".to_string(),
+            Type::UInt16 => "u16".to_string(),
+            Type::Int32 => "i32".to_string(),
+            Type::UInt32 => "u32".to_string(),
+            Type::Int64 => "i64".to_string(),
+            Type::UInt64 => "u64".to_string(),
+            Type::Float32 => "f32".to_string(),
+            Type::Float64 => "f64".to_string(),
+            Type::String(None) => "String".to_string(),
+            Type::String(Some(bound)) => {
+                format!("::cyclonedds_rs::bounded::BoundedString<{}>", bound)
+            }
+            Type::Sequence(element, None) => format!("Vec<{}>", self.rust_type(element)?),
+            Type::Sequence(element, Some(bound)) => format!(
+                "::cyclonedds_rs::bounded::BoundedVec<{}, {}>",
+                self.rust_type(element)?,
+                bound
+            ),
+            Type::Array(element, dims) => {
+                let mut ty = self.rust_type(element)?;
+                for dim in dims.iter().rev() {
+                    ty = format!("[{}; {}]", ty, dim);
+                }
+                ty
+            }
+            Type::Named(name) => self.path(name),
+        })
+    }
+
+    /// Path of a definition relative to the current module.
+    /// Returns the `type_name` attribute with the scoped IDL name of the
+    /// definition `name` in the current module, so the type name does not
+    /// depend on where the generated code is included.
+    fn type_name(&self, name: &str) -> String {
+        let mut scoped = String::new();
+        for module in &self.scope {
+            write!(scoped, "{}::", module).unwrap();
+        }
+        format!("type_name = \"{}{}\"", scoped, name)
+    }
+
+    fn path(&self, name: &ScopedName) -> String {
+        let mut path = String::new();
+        for _ in 0..self.scope.len() {
+            path.push_str("super::");
+        }
+        for (i, part) in name.iter().enumerate() {
+            if i > 0 {
+                path.push_str("::");
+            }
+            write!(path, "{}", ident(part)).unwrap();
+        }
+        path
+    }
+
+    fn lookup(&self, name: &ScopedName) -> Option<&'a Definition> {
+        let (last, modules) = name.split_last()?;
+        let mut module = self.root;
+        for part in modules {
+            module = module.definitions.iter().find_map(|def| match def {
+                Definition::Module(module) if module.name == *part => Some(module),
+                _ => None,
+            })?;
+        }
+        module.definitions.iter().find(|def| match def {
+            Definition::Module(_) => false,
+            Definition::Struct(def) => def.name == *last,
+            Definition::Enum(def) => def.name == *last,
+            Definition::Bitmask(def) => def.name == *last,
+            Definition::Union(def) => def.name == *last,
+            Definition::Typedef(def) => def.name == *last,
+            Definition::Const(def) => def.name == *last,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{emit, Options};
+    use crate::ast::*;
+
+    fn member(name: &str, ty: Type) -> Member {
+        Member {
+            name: name.to_string(),
+            ty,
+            key: false,
+            optional: false,
+            external: false,
+            id: None,
+        }
+    }
+
+    fn module(name: &str, definitions: Vec<Definition>) -> Definition {
+        Definition::Module(Module {
+            name: name.to_string(),
+            definitions,
+        })
+    }
+
+    #[test]
+    fn test_struct() {
+        let root = Module {
+            name: String::new(),
+            definitions: vec![module(
+                "HelloWorldData",
+                vec![
+                    Definition::Const(Const {
+                        name: "MAX_NAME".into(),
+                        ty: Type::UInt32,
+                        value: Literal::Int(16),
+                    }),
+                    Definition::Struct(Struct {
+                        name: "Msg".into(),
+                        base: None,
+                        extensibility: Extensibility::Appendable,
+                        hash_autoid: false,
+                        members: vec![
+                            Member {
+                                key: true,
+                                ..member("userID", Type::Int32)
+                            },
+                            member("message", Type::String(None)),
+                            member("name", Type::String(Some(16))),
+                            Member {
+                                optional: true,
+                                id: Some(10),
+                                ..member(
+                                    "samples",
+                                    Type::Sequence(Box::new(Type::Float64), Some(4)),
+                                )
+                            },
+                            member("type", Type::Array(Box::new(Type::Octet), vec![2, 3])),
+                        ],
+                    }),
+                ],
+            )],
+        };
+
+        let expected = r#"#[allow(non_snake_case, non_camel_case_types, non_upper_case_globals)]
+pub mod HelloWorldData {
+    pub const MAX_NAME: u32 = 16;
+
+    #[derive(::cyclonedds_rs::topic::TopicType, Clone, Debug, PartialEq)]
+    #[dds(type_name = "HelloWorldData::Msg", appendable)]
+    pub struct Msg {
+        #[dds(key)]
+        pub userID: i32,
+        pub message: String,
+        pub name: ::cyclonedds_rs::bounded::BoundedString<16>,
+        #[dds(id = 10)]
+        pub samples: Option<::cyclonedds_rs::bounded::BoundedVec<f64, 4>>,
+        pub r#type: [[u8; 3]; 2],
+    }
+}
+"#;
+        assert_eq!(emit(&root, &Options::default()).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_enum_and_union() {
+        let color = vec!["Shapes".to_string(), "Color".to_string()];
+        let root = Module {
+            name: String::new(),
+            definitions: vec![
+                module(
+                    "Shapes",
+                    vec![Definition::Enum(Enum {
+                        name: "Color".into(),
+                        bit_bound: Some(8),
+                        enumerators: vec![("RED".into(), 0), ("GREEN".into(), 1)],
+                    })],
+                ),
+                Definition::Union(Union {
+                    name: "Paint".into(),
+                    extensibility: Extensibility::Final,
+                    discriminator: Type::Named(color.clone()),
+                    cases: vec![
+                        Case {
+                            name: "red".into(),
+                            label: Some(0),
+                            ty: Type::Float32,
+                            external: false,
+                        },
+                        Case {
+                            name: "other".into(),
+                            label: None,
+                            ty: Type::Named(color.clone()),
+                            external: true,
+                        },
+                    ],
+                }),
+                Definition::Typedef(Typedef {
+                    name: "Palette".into(),
+                    ty: Type::Sequence(Box::new(Type::Named(color.clone())), None),
+                }),
+                Definition::Const(Const {
+                    name: "DEFAULT_COLOR".into(),
+                    ty: Type::Named(color.clone()),
+                    value: Literal::Enumerator(color, "GREEN".into()),
+                }),
+            ],
+        };
+
+        let expected = r#"#[allow(non_snake_case, non_camel_case_types, non_upper_case_globals)]
+pub mod Shapes {
+    #[derive(::cyclonedds_rs::topic::TopicType, Clone, Copy, Debug, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
+    #[repr(u32)]
+    #[dds(type_name = "Shapes::Color", bit_bound = 8)]
+    pub enum Color {
+        RED = 0,
+        GREEN = 1,
+    }
+}
+
+#[allow(non_snake_case, non_camel_case_types)]
+#[derive(::cyclonedds_rs::topic::TopicType, Clone, Debug, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
+#[repr(C, u32)]
+#[dds(type_name = "Paint")]
+pub enum Paint {
+    red(f32) = 0,
+    #[dds(default)]
+    other(Box<Shapes::Color>) = 1,
+}
+
+#[allow(non_snake_case, non_camel_case_types)]
+pub type Palette = Vec<Shapes::Color>;
+
+#[allow(non_upper_case_globals)]
+pub const DEFAULT_COLOR: Shapes::Color = Shapes::Color::GREEN;
+"#;
+        let options = Options { serde: true };
+        assert_eq!(emit(&root, &options).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_bitmask() {
+        let root = Module {
+            name: String::new(),
+            definitions: vec![module(
+                "Files",
+                vec![Definition::Bitmask(Bitmask {
+                    name: "Permissions".into(),
+                    bit_bound: Some(5),
+                    flags: vec![
+                        ("READ".into(), 0),
+                        ("WRITE".into(), 1),
+                        ("EXECUTE".into(), 4),
+                    ],
+                })],
+            )],
+        };
+
+        let expected = r#"#[allow(non_snake_case, non_camel_case_types, non_upper_case_globals)]
+pub mod Files {
+    #[derive(::cyclonedds_rs::topic::TopicType, Clone, Copy, Debug, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
+    #[dds(type_name = "Files::Permissions", bitmask(READ, WRITE, EXECUTE = 4), bit_bound = 5)]
+    #[serde(try_from = "u8", into = "u8")]
+    #[repr(transparent)]
+    pub struct Permissions(u8);
+}
+"#;
+        let options = Options { serde: true };
+        assert_eq!(emit(&root, &options).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_inheritance() {
+        let shape = Struct {
+            name: "Shape".into(),
+            base: None,
+            extensibility: Extensibility::Appendable,
+            hash_autoid: false,
+            members: vec![Member {
+                key: true,
+                ..member("id", Type::UInt32)
+            }],
+        };
+        let circle = Struct {
+            name: "Circle".into(),
+            base: Some(vec!["Shapes".to_string(), "Shape".to_string()]),
+            members: vec![member("radius", Type::Float32)],
+            ..shape.clone()
+        };
+        let root = Module {
+            name: String::new(),
+            definitions: vec![module(
+                "Shapes",
+                vec![
+                    Definition::Struct(shape),
+                    Definition::Struct(circle.clone()),
+                ],
+            )],
+        };
+
+        let expected = r#"#[allow(non_snake_case, non_camel_case_types, non_upper_case_globals)]
+pub mod Shapes {
+    #[derive(::cyclonedds_rs::topic::TopicType, Clone, Debug, PartialEq)]
+    #[dds(type_name = "Shapes::Shape", appendable)]
+    pub struct Shape {
+        #[dds(key)]
+        pub id: u32,
+    }
+
+    #[derive(::cyclonedds_rs::topic::TopicType, Clone, Debug, PartialEq)]
+    #[dds(type_name = "Shapes::Circle", appendable, extends = super::Shapes::Shape)]
+    pub struct Circle {
+        pub base: super::Shapes::Shape,
+        pub radius: f32,
+    }
+}
+"#;
+        assert_eq!(emit(&root, &Options::default()).unwrap(), expected);
+
+        // The field holding the base is named base
+        let root = Module {
+            name: String::new(),
+            definitions: vec![Definition::Struct(Struct {
+                members: vec![member("base", Type::Float32)],
+                ..circle
+            })],
+        };
+        assert!(emit(&root, &Options::default()).is_err());
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let root = Module {
+            name: String::new(),
+            definitions: vec![Definition::Enum(Enum {
+                name: "Sparse".into(),
+                bit_bound: None,
+                enumerators: vec![("A".into(), 0), ("B".into(), 5)],
+            })],
+        };
+        assert!(emit(&root, &Options::default()).is_err());
+    }
+}
@@ -0,0 +1,80 @@
+//! Compares writing plain old data through its fixed size descriptor and
+//! through loans with writing it through the serde based sertype.
+//!
+//! Needs Cyclone DDS at run time: `cargo bench --bench pod`.
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use cyclonedds_rs::{
+    domain::DomainParticipant,
+    sertype::to_cdr,
+    topic::{Topic, TopicType},
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(TopicType, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[dds(pod)]
+#[repr(C)]
+struct Pose {
+    #[dds(key)]
+    id: u32,
+    position: [f64; 3],
+    orientation: [f64; 4],
+    covariance: [[f64; 6]; 6],
+}
+
+/// Runs `f` for about a second after warming up and prints the time per
+/// call.
+fn bench(name: &str, mut f: impl FnMut()) {
+    for _ in 0..1_000 {
+        f();
+    }
+    let start = Instant::now();
+    let mut iterations = 0u64;
+    while start.elapsed() < Duration::from_secs(1) {
+        for _ in 0..1_000 {
+            f();
+        }
+        iterations += 1_000;
+    }
+    let nanos = start.elapsed().as_nanos() as f64 / iterations as f64;
+    println!("{name:<20} {nanos:>10.1} ns/iter");
+}
+
+fn main() {
+    let pose = Pose {
+        id: 7,
+        position: [1.0, 2.0, 3.0],
+        orientation: [0.0, 0.0, 0.0, 1.0],
+        covariance: [[0.01; 6]; 6],
+    };
+
+    bench("serialize/serde", || {
+        black_box(to_cdr(black_box(&pose)));
+    });
+    bench("serialize/pod", || {
+        black_box(*black_box(&pose));
+    });
+
+    let mut participant = DomainParticipant::new(0).unwrap();
+    let mut publisher = participant.publisher().unwrap();
+
+    let topic = Topic::<Pose>::with_sertype_named(&participant, "PoseSerde").unwrap();
+    let mut writer = publisher.create_datawriter(&topic).unwrap();
+    bench("write/serde", || {
+        writer.write(black_box(&pose)).unwrap();
+    });
+
+    let topic = Topic::<Pose>::new(&participant).unwrap();
+    let mut writer = publisher.create_datawriter(&topic).unwrap();
+    bench("write/pod", || {
+        writer.write(black_box(&pose)).unwrap();
+    });
+    bench("write/pod loan", || {
+        let mut sample = writer.loan().unwrap();
+        *sample = *black_box(&pose);
+        sample.commit().unwrap();
+    });
+}
@@ -2,24 +2,29 @@ use cyclonedds_rs::domain::DomainParticipant;
 
 fn main() {
     let mut participant: DomainParticipant = DomainParticipant::new(0).unwrap();
+    // Msg holds a String, so it is not plain old data and goes through the
+    // serde sertype
+    let topic = participant.serde_topic::<HelloWorldData::Msg>().unwrap();
     let mut publisher = participant.publisher().unwrap();
     println!("=== [Publisher]  Waiting for a reader to be discovered ...\n");
-    // let mut writer = publisher.create_datawriter().unwrap();
-    let mut msg = HelloWorldData::Msg {
+    let mut writer = publisher.create_datawriter(&topic).unwrap();
+    let msg = HelloWorldData::Msg {
         userID: 1,
         message: "Hello World".to_string(),
     };
 
     println!("=== [Publisher]  Writing : ");
 
-    // writer.write(&msg).unwrap();
+    writer.write(&msg).unwrap();
 
+    drop(writer);
+    drop(topic);
     drop(participant)
 }
 
 pub mod HelloWorldData {
     use cyclonedds_rs::topic::TopicType;
-    use serde::Serialize;
+    use serde::{Deserialize, Serialize};
 
     /// ```idl
     /// module HelloWorldData {
@@ -30,11 +35,11 @@ pub mod HelloWorldData {
     ///   };
     /// };
     /// ```
-    #[derive(Serialize, Debug, Clone, PartialEq)]
+    #[derive(TopicType, Serialize, Deserialize, Debug, Clone, PartialEq)]
+    #[dds(topic = "HelloWorldData_Msg")]
     pub struct Msg {
-        pub userID: i64,
+        #[dds(key)]
+        pub userID: i32,
         pub message: String,
     }
-
-    // impl TopicType for Msg {}
 }
@@ -0,0 +1,19 @@
+//! Writes the IDL of the HelloWorld type, to the file given as argument or
+//! to standard output:
+//!
+//! ```text
+//! cargo run --example export_idl -- HelloWorldData.idl
+//! ```
+cyclonedds_rs::export_idl!(HelloWorldData::Msg);
+
+#[allow(non_snake_case)]
+pub mod HelloWorldData {
+    use cyclonedds_rs::topic::TopicType;
+
+    #[derive(TopicType, Debug, Clone, PartialEq)]
+    pub struct Msg {
+        #[dds(key)]
+        pub userID: i32,
+        pub message: String,
+    }
+}
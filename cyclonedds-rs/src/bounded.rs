@@ -0,0 +1,458 @@
+//! Collections with an upper bound on their length, the Rust counterparts of
+//! the IDL `sequence<T, N>`, `string<N>` and `map<K, V, N>` types.
+//!
+//! The bound is checked whenever the length of a collection changes, so a
+//! value that is written never violates the bound of its IDL type.
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    marker::PhantomData,
+    ops::Deref,
+};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use crate::descriptor::{map_desc, DdsType, SequenceDesc, TypeDesc};
+
+/// The length of a value exceeds the bound of a bounded collection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("length {len} exceeds the bound {bound}")]
+pub struct BoundError {
+    /// Length of the rejected value
+    pub len: usize,
+    /// Bound of the collection
+    pub bound: usize,
+}
+
+fn check_bound(len: usize, bound: usize) -> Result<(), BoundError> {
+    if len > bound {
+        Err(BoundError { len, bound })
+    } else {
+        Ok(())
+    }
+}
+
+/// A vector of at most `N` elements, an IDL `sequence<T, N>`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BoundedVec<T, const N: usize> {
+    elements: Vec<T>,
+}
+
+impl<T, const N: usize> BoundedVec<T, N> {
+    /// The maximum number of elements
+    pub const BOUND: usize = N;
+
+    /// Creates an empty vector.
+    pub fn new() -> Self {
+        BoundedVec {
+            elements: Vec::new(),
+        }
+    }
+
+    /// Appends an element, fails if the vector already holds `N` elements.
+    pub fn push(&mut self, element: T) -> Result<(), BoundError> {
+        check_bound(self.elements.len() + 1, N)?;
+        self.elements.push(element);
+        Ok(())
+    }
+
+    /// Removes the last element and returns it.
+    pub fn pop(&mut self) -> Option<T> {
+        self.elements.pop()
+    }
+
+    /// Shortens the vector to `len` elements.
+    pub fn truncate(&mut self, len: usize) {
+        self.elements.truncate(len);
+    }
+
+    /// Removes all elements.
+    pub fn clear(&mut self) {
+        self.elements.clear();
+    }
+
+    /// Returns a mutable slice of the elements.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.elements
+    }
+
+    /// Returns the elements as a [Vec].
+    pub fn into_inner(self) -> Vec<T> {
+        self.elements
+    }
+}
+
+impl<T, const N: usize> Default for BoundedVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Deref for BoundedVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.elements
+    }
+}
+
+impl<T, const N: usize> TryFrom<Vec<T>> for BoundedVec<T, N> {
+    type Error = BoundError;
+
+    fn try_from(elements: Vec<T>) -> Result<Self, BoundError> {
+        check_bound(elements.len(), N)?;
+        Ok(BoundedVec { elements })
+    }
+}
+
+impl<T, const N: usize> From<BoundedVec<T, N>> for Vec<T> {
+    fn from(value: BoundedVec<T, N>) -> Self {
+        value.elements
+    }
+}
+
+impl<T, const N: usize> IntoIterator for BoundedVec<T, N> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.elements.into_iter()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a BoundedVec<T, N> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.elements.iter()
+    }
+}
+
+impl<T: DdsType, const N: usize> DdsType for BoundedVec<T, N> {
+    fn type_desc() -> TypeDesc {
+        TypeDesc::Sequence(Box::new(SequenceDesc::new(T::type_desc(), Some(N))))
+    }
+}
+
+impl<T: Serialize, const N: usize> Serialize for BoundedVec<T, N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.elements.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>, const N: usize> Deserialize<'de> for BoundedVec<T, N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor<T, const N: usize>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>, const N: usize> de::Visitor<'de> for Visitor<T, N> {
+            type Value = BoundedVec<T, N>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "a sequence of at most {} elements", N)
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut value = BoundedVec::new();
+                while let Some(element) = seq.next_element()? {
+                    value.push(element).map_err(de::Error::custom)?;
+                }
+                Ok(value)
+            }
+        }
+
+        deserializer.deserialize_seq(Visitor(PhantomData))
+    }
+}
+
+/// A string of at most `N` bytes, an IDL `string<N>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BoundedString<const N: usize> {
+    string: String,
+}
+
+impl<const N: usize> BoundedString<N> {
+    /// The maximum length in bytes
+    pub const BOUND: usize = N;
+
+    /// Creates an empty string.
+    pub fn new() -> Self {
+        BoundedString {
+            string: String::new(),
+        }
+    }
+
+    /// Appends a string slice, fails if the result would exceed `N` bytes.
+    pub fn push_str(&mut self, string: &str) -> Result<(), BoundError> {
+        check_bound(self.string.len() + string.len(), N)?;
+        self.string.push_str(string);
+        Ok(())
+    }
+
+    /// Appends a character, fails if the result would exceed `N` bytes.
+    pub fn push(&mut self, c: char) -> Result<(), BoundError> {
+        check_bound(self.string.len() + c.len_utf8(), N)?;
+        self.string.push(c);
+        Ok(())
+    }
+
+    /// Removes all characters.
+    pub fn clear(&mut self) {
+        self.string.clear();
+    }
+
+    /// Returns the string slice.
+    pub fn as_str(&self) -> &str {
+        &self.string
+    }
+
+    /// Returns the string as a [String].
+    pub fn into_inner(self) -> String {
+        self.string
+    }
+}
+
+impl<const N: usize> Deref for BoundedString<N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.string
+    }
+}
+
+impl<const N: usize> Display for BoundedString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.string.fmt(f)
+    }
+}
+
+impl<const N: usize> TryFrom<String> for BoundedString<N> {
+    type Error = BoundError;
+
+    fn try_from(string: String) -> Result<Self, BoundError> {
+        check_bound(string.len(), N)?;
+        Ok(BoundedString { string })
+    }
+}
+
+impl<const N: usize> TryFrom<&str> for BoundedString<N> {
+    type Error = BoundError;
+
+    fn try_from(string: &str) -> Result<Self, BoundError> {
+        check_bound(string.len(), N)?;
+        Ok(BoundedString {
+            string: string.to_string(),
+        })
+    }
+}
+
+impl<const N: usize> From<BoundedString<N>> for String {
+    fn from(value: BoundedString<N>) -> Self {
+        value.string
+    }
+}
+
+impl<const N: usize> DdsType for BoundedString<N> {
+    fn type_desc() -> TypeDesc {
+        TypeDesc::BoundedString(N as u32)
+    }
+}
+
+impl<const N: usize> Serialize for BoundedString<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.string)
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for BoundedString<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        BoundedString::try_from(string).map_err(de::Error::custom)
+    }
+}
+
+/// A map of at most `N` entries, an IDL `map<K, V, N>`. The entries are
+/// ordered by key like those of a [BTreeMap].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BoundedMap<K, V, const N: usize> {
+    entries: BTreeMap<K, V>,
+}
+
+impl<K, V, const N: usize> BoundedMap<K, V, N> {
+    /// The maximum number of entries
+    pub const BOUND: usize = N;
+
+    /// Creates an empty map.
+    pub fn new() -> Self {
+        BoundedMap {
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Removes all entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Returns the entries as a [BTreeMap].
+    pub fn into_inner(self) -> BTreeMap<K, V> {
+        self.entries
+    }
+}
+
+impl<K: Ord, V, const N: usize> BoundedMap<K, V, N> {
+    /// Inserts an entry and returns the previous value of the key, fails if
+    /// the key is new and the map already holds `N` entries.
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>, BoundError> {
+        if !self.entries.contains_key(&key) {
+            check_bound(self.entries.len() + 1, N)?;
+        }
+        Ok(self.entries.insert(key, value))
+    }
+
+    /// Removes the entry of a key and returns its value.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.entries.remove(key)
+    }
+
+    /// Returns a mutable reference to the value of a key.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.entries.get_mut(key)
+    }
+}
+
+impl<K, V, const N: usize> Default for BoundedMap<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize> Deref for BoundedMap<K, V, N> {
+    type Target = BTreeMap<K, V>;
+
+    fn deref(&self) -> &BTreeMap<K, V> {
+        &self.entries
+    }
+}
+
+impl<K, V, const N: usize> TryFrom<BTreeMap<K, V>> for BoundedMap<K, V, N> {
+    type Error = BoundError;
+
+    fn try_from(entries: BTreeMap<K, V>) -> Result<Self, BoundError> {
+        check_bound(entries.len(), N)?;
+        Ok(BoundedMap { entries })
+    }
+}
+
+impl<K, V, const N: usize> From<BoundedMap<K, V, N>> for BTreeMap<K, V> {
+    fn from(value: BoundedMap<K, V, N>) -> Self {
+        value.entries
+    }
+}
+
+impl<K, V, const N: usize> IntoIterator for BoundedMap<K, V, N> {
+    type Item = (K, V);
+    type IntoIter = std::collections::btree_map::IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a, K, V, const N: usize> IntoIterator for &'a BoundedMap<K, V, N> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = std::collections::btree_map::Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+impl<K: DdsType, V: DdsType, const N: usize> DdsType for BoundedMap<K, V, N> {
+    fn type_desc() -> TypeDesc {
+        map_desc::<K, V>(Some(N))
+    }
+}
+
+impl<K: Serialize, V: Serialize, const N: usize> Serialize for BoundedMap<K, V, N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.entries.serialize(serializer)
+    }
+}
+
+impl<'de, K, V, const N: usize> Deserialize<'de> for BoundedMap<K, V, N>
+where
+    K: Deserialize<'de> + Ord,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor<K, V, const N: usize>(PhantomData<(K, V)>);
+
+        impl<'de, K, V, const N: usize> de::Visitor<'de> for Visitor<K, V, N>
+        where
+            K: Deserialize<'de> + Ord,
+            V: Deserialize<'de>,
+        {
+            type Value = BoundedMap<K, V, N>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "a map of at most {} entries", N)
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut value = BoundedMap::new();
+                while let Some((key, element)) = map.next_entry()? {
+                    value.insert(key, element).map_err(de::Error::custom)?;
+                }
+                Ok(value)
+            }
+        }
+
+        deserializer.deserialize_map(Visitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{BoundError, BoundedMap, BoundedString, BoundedVec};
+
+    #[test]
+    fn test_bounded_vec() {
+        let mut values = BoundedVec::<i32, 2>::new();
+        values.push(1).unwrap();
+        values.push(2).unwrap();
+        assert_eq!(values.push(3), Err(BoundError { len: 3, bound: 2 }));
+        assert_eq!(&values[..], &[1, 2]);
+
+        assert!(BoundedVec::<i32, 2>::try_from(vec![1, 2, 3]).is_err());
+        assert_eq!(Vec::from(values), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_bounded_string() {
+        let mut name = BoundedString::<5>::try_from("abc").unwrap();
+        name.push_str("de").unwrap();
+        assert!(name.push('f').is_err());
+        assert_eq!(name.as_str(), "abcde");
+
+        // The bound is in bytes
+        assert!(BoundedString::<3>::try_from("äö").is_err());
+    }
+
+    #[test]
+    fn test_bounded_map() {
+        let mut values = BoundedMap::<u32, i32, 2>::new();
+        assert_eq!(values.insert(2, 20), Ok(None));
+        assert_eq!(values.insert(1, 10), Ok(None));
+        assert_eq!(values.insert(3, 30), Err(BoundError { len: 3, bound: 2 }));
+        // Replacing the value of a key does not grow the map
+        assert_eq!(values.insert(2, 21), Ok(Some(20)));
+        assert_eq!(values.iter().collect::<Vec<_>>(), [(&1, &10), (&2, &21)]);
+
+        let entries = BTreeMap::from([(1, 1), (2, 2), (3, 3)]);
+        assert!(BoundedMap::<u32, i32, 2>::try_from(entries).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+//! Topics of opaque bytes, for payloads that are serialized already, e.g.
+//! with protobuf or flatbuffers, and only need DDS as transport.
+//!
+//! [BytesTopic] and [KeyedBytesTopic] are the IDL types
+//!
+//! ```idl
+//! module bytes {
+//!     struct BytesTopic {
+//!         sequence<octet> data;
+//!     };
+//!     struct KeyedBytesTopic {
+//!         @key string key;
+//!         sequence<octet> data;
+//!     };
+//! };
+//! ```
+//!
+//! where the key of a [KeyedBytesTopic] makes every key an instance of its
+//! own. Both are [SerdeTopic]s that many topics share, so topics of them
+//! are created with [Topic::with_sertype_named]. The payload is copied as
+//! is into the serialized sample, and [DataWriter::write_bytes] writes one
+//! without creating a sample first.
+//!
+//! ```ignore
+//! let topic = Topic::<BytesTopic>::with_sertype_named(&participant, "camera/frames")?;
+//! let mut writer = publisher.create_datawriter(&topic)?;
+//! writer.write_bytes(&frame.encode_to_vec())?;
+//! ```
+//!
+//! [Topic::with_sertype_named]: crate::topic::Topic::with_sertype_named
+//! [DataWriter::write_bytes]: crate::publisher::DataWriter::write_bytes
+
+use std::fmt;
+
+use serde::{de, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    sertype::key_cdr,
+    topic::SerdeTopic,
+    xcdr::{XcdrError, CDR_LE, HEADER_SIZE},
+};
+
+/// An opaque payload, an IDL `sequence<octet>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BytesTopic {
+    pub data: Vec<u8>,
+}
+
+impl BytesTopic {
+    pub fn new(data: Vec<u8>) -> Self {
+        BytesTopic { data }
+    }
+
+    /// Serializes a sample of `data` with its encapsulation header, the
+    /// same as [crate::sertype::to_cdr] does.
+    pub(crate) fn cdr(data: &[u8]) -> Vec<u8> {
+        cdr(None, data)
+    }
+}
+
+impl From<Vec<u8>> for BytesTopic {
+    fn from(data: Vec<u8>) -> Self {
+        BytesTopic { data }
+    }
+}
+
+impl SerdeTopic for BytesTopic {}
+
+impl Serialize for BytesTopic {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("BytesTopic", 1)?;
+        state.serialize_field("data", &Octets(&self.data))?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for BytesTopic {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = BytesTopic;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("struct BytesTopic")
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let OctetBuf(data) = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                Ok(BytesTopic { data })
+            }
+        }
+
+        deserializer.deserialize_struct("BytesTopic", &["data"], Visitor)
+    }
+}
+
+/// An opaque payload of the instance `key`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct KeyedBytesTopic {
+    pub key: String,
+    pub data: Vec<u8>,
+}
+
+impl KeyedBytesTopic {
+    pub fn new(key: impl Into<String>, data: Vec<u8>) -> Self {
+        KeyedBytesTopic {
+            key: key.into(),
+            data,
+        }
+    }
+
+    /// Serializes a sample of `key` and `data` with its encapsulation
+    /// header, the same as [crate::sertype::to_cdr] does.
+    pub(crate) fn cdr(key: &str, data: &[u8]) -> Vec<u8> {
+        cdr(Some(key), data)
+    }
+}
+
+impl SerdeTopic for KeyedBytesTopic {
+    fn has_key() -> bool {
+        true
+    }
+
+    fn key(&self) -> Result<Vec<u8>, XcdrError> {
+        Ok(key_cdr(&self.key))
+    }
+}
+
+impl Serialize for KeyedBytesTopic {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("KeyedBytesTopic", 2)?;
+        state.serialize_field("key", &self.key)?;
+        state.serialize_field("data", &Octets(&self.data))?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyedBytesTopic {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = KeyedBytesTopic;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("struct KeyedBytesTopic")
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let key = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let OctetBuf(data) = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                Ok(KeyedBytesTopic { key, data })
+            }
+        }
+
+        deserializer.deserialize_struct("KeyedBytesTopic", &["key", "data"], Visitor)
+    }
+}
+
+/// Serializes a slice of bytes in one piece rather than byte by byte.
+struct Octets<'a>(&'a [u8]);
+
+impl Serialize for Octets<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+/// Deserializes bytes in one piece rather than byte by byte.
+struct OctetBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for OctetBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = OctetBuf;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a sequence of octets")
+            }
+
+            fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Self::Value, E> {
+                Ok(OctetBuf(value.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, value: Vec<u8>) -> Result<Self::Value, E> {
+                Ok(OctetBuf(value))
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut data = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(byte) = seq.next_element()? {
+                    data.push(byte);
+                }
+                Ok(OctetBuf(data))
+            }
+        }
+
+        deserializer.deserialize_byte_buf(Visitor)
+    }
+}
+
+/// Writes the little endian XCDR1 of a sample straight into a buffer of
+/// its final size.
+fn cdr(key: Option<&str>, data: &[u8]) -> Vec<u8> {
+    // A string is its length including the terminating nul, the bytes and
+    // the nul, after which the length of the sequence is aligned to 4.
+    let key_size = key.map_or(0, |key| (4 + key.len() + 1).next_multiple_of(4));
+    let size = HEADER_SIZE + key_size + 4 + data.len();
+    let padding = size.next_multiple_of(HEADER_SIZE) - size;
+
+    let mut buf = Vec::with_capacity(size + padding);
+    buf.extend_from_slice(&CDR_LE.to_be_bytes());
+    buf.extend_from_slice(&[0, padding as u8]);
+    if let Some(key) = key {
+        buf.extend_from_slice(&(key.len() as u32 + 1).to_le_bytes());
+        buf.extend_from_slice(key.as_bytes());
+        buf.resize(HEADER_SIZE + key_size, 0);
+    }
+    buf.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    buf.extend_from_slice(data);
+    buf.resize(size + padding, 0);
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BytesTopic, KeyedBytesTopic};
+    use crate::{
+        sertype::{from_cdr, key_cdr, to_cdr},
+        topic::SerdeTopic,
+    };
+
+    #[test]
+    fn test_bytes_cdr() {
+        for len in 0..9 {
+            let data = (0..len).collect::<Vec<u8>>();
+            let sample = BytesTopic::new(data.clone());
+            let cdr = BytesTopic::cdr(&data);
+            assert_eq!(cdr, to_cdr(&sample).unwrap());
+            assert_eq!(cdr.capacity(), cdr.len());
+            assert_eq!(from_cdr::<BytesTopic>(&cdr), Some(sample));
+            assert!(!BytesTopic::has_key());
+        }
+    }
+
+    #[test]
+    fn test_keyed_bytes_cdr() {
+        for key in ["", "a", "abc", "abcd"] {
+            for len in 0..5 {
+                let data = (0..len).collect::<Vec<u8>>();
+                let sample = KeyedBytesTopic::new(key, data.clone());
+                let cdr = KeyedBytesTopic::cdr(key, &data);
+                assert_eq!(cdr, to_cdr(&sample).unwrap());
+                assert_eq!(cdr.capacity(), cdr.len());
+                assert_eq!(from_cdr::<KeyedBytesTopic>(&cdr), Some(sample.clone()));
+                assert_eq!(sample.key(), Ok(key_cdr(key)));
+            }
+        }
+    }
+}
@@ -0,0 +1,3099 @@
+//! Description of Rust types in terms of the IDL type system.
+//!
+//! Cyclone DDS needs a [TopicDescriptor] for every topic type: the size and
+//! alignment of the sample, its key fields and a small program of `DDS_OP_*`
+//! instructions that tells the serializer where each member lives and how it
+//! is encoded. Instead of writing that program by hand, a type implements
+//! [DdsType] to describe itself as a [TypeDesc] tree and
+//! [TopicDescriptor::from_type] compiles the tree into the descriptor.
+//!
+//! Cyclone DDS takes the descriptor as a C struct of pointers.
+//! [OwnedTopicDescriptor] holds that struct with the storage it points to,
+//! and [registered_descriptor] keeps one per topic type alive for the rest
+//! of the process, which is what topics are created with.
+//!
+//! The program describes the sample as the C type `idlc` generates for the
+//! IDL type: strings are a `char *`, sequences and maps a `dds_sequence_t`,
+//! optional and external members pointers and unions a struct of the
+//! discriminator and a C union. Only plain old data ([Pod]) is laid out the
+//! same in Rust, so only its samples are handed to Cyclone DDS as they are.
+//! Topics of all other types serialize with serde ([crate::sertype]), for
+//! which the descriptor only describes the serialized data.
+//!
+//! [DdsType] is implemented here for the primitive types, [String], [Vec]
+//! (an IDL `sequence`), arrays and [HashMap] and [BTreeMap] (an IDL `map`),
+//! [crate::bounded] provides the bounded `sequence<T, N>`, `string<N>` and
+//! `map<K, V, N>`. The entries of a [HashMap] are written in iteration
+//! order, those of a [BTreeMap] in key order, so use the latter where equal
+//! samples must serialize the same. A struct member of type [Option] is
+//! `@optional` and one of type [Box] is `@external`, a pointer in the C
+//! sample, and the serializer encodes optional members with XCDR2 only.
+//!
+//! [DdsType] can be generated for structs, fieldless enums, enums with
+//! data (IDL unions) and newtypes of an unsigned integer marked as
+//! `#[dds(bitmask(...))]` (IDL bitmasks) with `#[derive(TopicType)]`:
+//!
+//! ```ignore
+//! use cyclonedds_rs::topic::TopicType;
+//!
+//! #[derive(TopicType, Clone, Debug, PartialEq)]
+//! struct Msg {
+//!     user_id: i32,
+//!     message: String,
+//! }
+//! ```
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::c_char,
+    mem::{align_of, size_of},
+};
+
+use cyclonedds_sys::{
+    dds_sequence_t, dds_stream_opcode, dds_stream_typecode, DDS_DATA_REPRESENTATION_FLAG_XCDR2,
+    DDS_OP_FLAG_DEF, DDS_OP_FLAG_EXT, DDS_OP_FLAG_FP, DDS_OP_FLAG_KEY, DDS_OP_FLAG_MU,
+    DDS_OP_FLAG_OPT, DDS_OP_FLAG_SGN, DDS_OP_FLAG_SZ_SHIFT, DDS_TOPIC_CONTAINS_UNION,
+    DDS_TOPIC_FIXED_KEY, DDS_TOPIC_FIXED_KEY_XCDR2, DDS_TOPIC_FIXED_KEY_XCDR2_KEYHASH,
+    DDS_TOPIC_FIXED_SIZE, DDS_TOPIC_RESTRICT_DATA_REPRESENTATION, DDS_TOPIC_XTYPES_METADATA,
+};
+use thiserror::Error;
+
+pub use crate::dynamic::{TypeAutoid, TypeExtensibility};
+pub use crate::internal::{
+    registered_descriptor, KeyDescriptor, OwnedTopicDescriptor, TopicDescriptor, TypeMetaSer,
+};
+use crate::{qos::DataRepresentation, typeinfo::TypeInfo};
+
+/// Trait for types that can be described in the IDL type system.
+///
+/// All members of a topic type must implement [DdsType].
+pub trait DdsType {
+    /// Returns the IDL description of the type.
+    fn type_desc() -> TypeDesc;
+}
+
+/// Marker for [DdsType]s that are plain old data: of fixed size, without
+/// pointers and laid out the same in Rust and C, so Cyclone DDS can copy
+/// them as they are. Implemented for the primitive types, arrays of plain
+/// old data and structs that derive [TopicType](crate::topic::TopicType)
+/// with `#[dds(pod)]`, see [PodTopic](crate::topic::PodTopic).
+///
+/// # Safety
+///
+/// The type must be `#[repr(C)]` or a primitive, all its members must be
+/// [Pod] and all bytes zero must be a valid value.
+pub unsafe trait Pod: DdsType + Copy + 'static {}
+
+/// IDL primitive types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PrimitiveKind {
+    Boolean,
+    Octet,
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Float32,
+    Float64,
+}
+
+impl PrimitiveKind {
+    /// Size of the primitive in bytes.
+    pub fn size(&self) -> u32 {
+        match self {
+            PrimitiveKind::Boolean
+            | PrimitiveKind::Octet
+            | PrimitiveKind::Int8
+            | PrimitiveKind::UInt8 => 1,
+            PrimitiveKind::Int16 | PrimitiveKind::UInt16 => 2,
+            PrimitiveKind::Int32 | PrimitiveKind::UInt32 | PrimitiveKind::Float32 => 4,
+            PrimitiveKind::Int64 | PrimitiveKind::UInt64 | PrimitiveKind::Float64 => 8,
+        }
+    }
+
+    /// Name of the primitive in IDL.
+    pub fn idl_name(&self) -> &'static str {
+        match self {
+            PrimitiveKind::Boolean => "boolean",
+            PrimitiveKind::Octet => "octet",
+            PrimitiveKind::Int8 => "int8",
+            PrimitiveKind::UInt8 => "uint8",
+            PrimitiveKind::Int16 => "short",
+            PrimitiveKind::UInt16 => "unsigned short",
+            PrimitiveKind::Int32 => "long",
+            PrimitiveKind::UInt32 => "unsigned long",
+            PrimitiveKind::Int64 => "long long",
+            PrimitiveKind::UInt64 => "unsigned long long",
+            PrimitiveKind::Float32 => "float",
+            PrimitiveKind::Float64 => "double",
+        }
+    }
+
+    /// The serializer type code and the sign/floating point flags.
+    fn typecode(&self) -> (dds_stream_typecode, u32) {
+        match self {
+            PrimitiveKind::Boolean => (dds_stream_typecode::DDS_OP_VAL_BLN, 0),
+            PrimitiveKind::Octet | PrimitiveKind::UInt8 => (dds_stream_typecode::DDS_OP_VAL_1BY, 0),
+            PrimitiveKind::Int8 => (dds_stream_typecode::DDS_OP_VAL_1BY, DDS_OP_FLAG_SGN),
+            PrimitiveKind::UInt16 => (dds_stream_typecode::DDS_OP_VAL_2BY, 0),
+            PrimitiveKind::Int16 => (dds_stream_typecode::DDS_OP_VAL_2BY, DDS_OP_FLAG_SGN),
+            PrimitiveKind::UInt32 => (dds_stream_typecode::DDS_OP_VAL_4BY, 0),
+            PrimitiveKind::Int32 => (dds_stream_typecode::DDS_OP_VAL_4BY, DDS_OP_FLAG_SGN),
+            PrimitiveKind::UInt64 => (dds_stream_typecode::DDS_OP_VAL_8BY, 0),
+            PrimitiveKind::Int64 => (dds_stream_typecode::DDS_OP_VAL_8BY, DDS_OP_FLAG_SGN),
+            PrimitiveKind::Float32 => (dds_stream_typecode::DDS_OP_VAL_4BY, DDS_OP_FLAG_FP),
+            PrimitiveKind::Float64 => (dds_stream_typecode::DDS_OP_VAL_8BY, DDS_OP_FLAG_FP),
+        }
+    }
+}
+
+/// Description of a type in the IDL type system.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeDesc {
+    /// A primitive type
+    Primitive(PrimitiveKind),
+    /// An unbounded `string`
+    String,
+    /// A `string<N>`, the bound excludes the terminating nul
+    BoundedString(u32),
+    /// A `sequence<T>` or `sequence<T, N>`
+    Sequence(Box<SequenceDesc>),
+    /// An array `T[N][M]...`
+    Array(Box<ArrayDesc>),
+    /// A `map<K, V>` or `map<K, V, N>`
+    Map(Box<MapDesc>),
+    /// A `struct`
+    Struct(StructDesc),
+    /// An `enum`
+    Enum(EnumDesc),
+    /// A `bitmask`
+    Bitmask(BitmaskDesc),
+    /// A discriminated `union`
+    Union(UnionDesc),
+    /// An `@optional` member, which may be absent. Only members of a struct
+    /// can be optional.
+    Optional(Box<TypeDesc>),
+    /// An `@external` member, which is a pointer in the C sample. Only
+    /// members of a struct and union cases can be external.
+    External(Box<TypeDesc>),
+}
+
+impl TypeDesc {
+    /// Returns the name of a constructed type, [None] for primitives,
+    /// strings and collections.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            TypeDesc::Struct(desc) => Some(&desc.name),
+            TypeDesc::Enum(desc) => Some(&desc.name),
+            TypeDesc::Bitmask(desc) => Some(&desc.name),
+            TypeDesc::Union(desc) => Some(&desc.name),
+            TypeDesc::Primitive(_)
+            | TypeDesc::String
+            | TypeDesc::BoundedString(_)
+            | TypeDesc::Sequence(_)
+            | TypeDesc::Array(_)
+            | TypeDesc::Map(_)
+            | TypeDesc::Optional(_)
+            | TypeDesc::External(_) => None,
+        }
+    }
+
+    /// Returns the [StructDesc] if the type is a struct.
+    pub fn as_struct(&self) -> Option<&StructDesc> {
+        match self {
+            TypeDesc::Struct(desc) => Some(desc),
+            _ => None,
+        }
+    }
+
+    /// Returns whether samples of the type all have the same size: the
+    /// type holds no strings, collections, optional, external or union
+    /// members.
+    pub fn is_fixed_size(&self) -> bool {
+        match self {
+            TypeDesc::Primitive(_) | TypeDesc::Enum(_) | TypeDesc::Bitmask(_) => true,
+            TypeDesc::Array(desc) => desc.element.is_fixed_size(),
+            TypeDesc::Struct(desc) => desc
+                .all_members()
+                .iter()
+                .all(|(_, member)| member.type_desc.is_fixed_size()),
+            TypeDesc::String
+            | TypeDesc::BoundedString(_)
+            | TypeDesc::Sequence(_)
+            | TypeDesc::Map(_)
+            | TypeDesc::Union(_)
+            | TypeDesc::Optional(_)
+            | TypeDesc::External(_) => false,
+        }
+    }
+}
+
+/// Description of an IDL `struct`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructDesc {
+    /// Fully scoped IDL name of the struct, e.g. `HelloWorldData::Msg`
+    pub name: String,
+    /// Size of the Rust type
+    pub size: u32,
+    /// Alignment of the Rust type
+    pub align: u32,
+    /// `@final`, `@appendable` or `@mutable`
+    pub extensibility: TypeExtensibility,
+    /// How the ids of members without an explicit id are assigned
+    pub autoid: TypeAutoid,
+    /// The struct this struct inherits from, if any
+    pub base: Option<Box<BaseDesc>>,
+    /// Members in declaration order, without the members of the base
+    pub members: Vec<MemberDesc>,
+}
+
+/// The base of a struct that inherits from another struct, e.g.
+/// `struct Derived : Base`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaseDesc {
+    /// `offset_of` the field holding the base in the Rust type
+    pub offset: u32,
+    /// The base struct, always a [TypeDesc::Struct]
+    pub type_desc: TypeDesc,
+}
+
+impl BaseDesc {
+    /// Returns the description of the base struct.
+    pub fn desc(&self) -> &StructDesc {
+        match &self.type_desc {
+            TypeDesc::Struct(desc) => desc,
+            _ => unreachable!("the base of a struct is a struct"),
+        }
+    }
+}
+
+impl StructDesc {
+    /// Creates a struct description without members.
+    /// * `name` - Fully scoped IDL name of the struct
+    /// * `size` - `size_of` the Rust type
+    /// * `align` - `align_of` the Rust type
+    pub fn new(name: &str, size: usize, align: usize) -> StructDesc {
+        StructDesc {
+            name: name.to_string(),
+            size: size as u32,
+            align: align as u32,
+            extensibility: TypeExtensibility::Final,
+            autoid: TypeAutoid::Sequential,
+            base: None,
+            members: Vec::new(),
+        }
+    }
+
+    /// Sets the extensibility of the struct, [TypeExtensibility::Final] by
+    /// default.
+    pub fn extensibility(mut self, extensibility: TypeExtensibility) -> StructDesc {
+        self.extensibility = extensibility;
+        self
+    }
+
+    /// Sets how member ids are assigned (`@autoid`),
+    /// [TypeAutoid::Sequential] by default.
+    pub fn autoid(mut self, autoid: TypeAutoid) -> StructDesc {
+        self.autoid = autoid;
+        self
+    }
+
+    /// Appends a member to the struct.
+    pub fn member(mut self, member: MemberDesc) -> StructDesc {
+        self.members.push(member);
+        self
+    }
+
+    /// Makes the struct inherit from `base`, which is held by the Rust type
+    /// at `offset`. The members of the base come before the members of the
+    /// struct in the serialized data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `base` is not a struct.
+    pub fn base(mut self, offset: usize, base: TypeDesc) -> StructDesc {
+        if base.as_struct().is_none() {
+            panic!("struct {} can not inherit from {:?}", self.name, base);
+        }
+        self.base = Some(Box::new(BaseDesc {
+            offset: offset as u32,
+            type_desc: base,
+        }));
+        self
+    }
+
+    /// Returns the members of the base, if any, followed by the members of
+    /// the struct, each with its offset in the Rust type.
+    pub fn all_members(&self) -> Vec<(u32, &MemberDesc)> {
+        let mut members = match &self.base {
+            Some(base) => base
+                .desc()
+                .all_members()
+                .into_iter()
+                .map(|(offset, member)| (base.offset + offset, member))
+                .collect(),
+            None => Vec::with_capacity(self.members.len()),
+        };
+        members.extend(self.members.iter().map(|member| (member.offset, member)));
+        members
+    }
+
+    /// Returns the member ids in the order of [StructDesc::all_members].
+    ///
+    /// A member without an explicit id gets the id of the previous member
+    /// plus one, or 0 for the first member, with [TypeAutoid::Sequential]
+    /// and the hash of its name with [TypeAutoid::Hash]. The numbering of
+    /// a derived struct continues after the last member of its base.
+    ///
+    /// # Panics
+    ///
+    /// Panics if two members have the same id.
+    pub fn member_ids(&self) -> Vec<u32> {
+        let mut ids = match &self.base {
+            Some(base) => base.desc().member_ids(),
+            None => Vec::with_capacity(self.members.len()),
+        };
+        let inherited = ids.len();
+        for member in &self.members {
+            let id = match (member.id, self.autoid) {
+                (Some(id), _) => id,
+                (None, TypeAutoid::Hash) => hash_member_id(&member.name),
+                (None, TypeAutoid::Sequential) => ids.last().map_or(0, |id| id + 1),
+            };
+            if let Some(index) = ids.iter().position(|other| *other == id) {
+                let other = if index < inherited {
+                    self.all_members()[index].1
+                } else {
+                    &self.members[index - inherited]
+                };
+                panic!(
+                    "members {} and {} of struct {} have the same id {}",
+                    other.name, member.name, self.name, id
+                );
+            }
+            ids.push(id);
+        }
+        ids
+    }
+}
+
+/// Member ids are limited to 28 bits, the upper bits of the member header of
+/// a mutable type are flags.
+pub(crate) const MEMBER_ID_MASK: u32 = 0x0fff_ffff;
+
+/// Returns the member id derived from a name (`@hashid`): the first four
+/// bytes of the MD5 hash of the name as a little endian integer, limited to
+/// 28 bits.
+pub fn hash_member_id(name: &str) -> u32 {
+    let hash = md5::compute(name.as_bytes());
+    u32::from_le_bytes([hash[0], hash[1], hash[2], hash[3]]) & MEMBER_ID_MASK
+}
+
+/// Description of an IDL `enum`.
+///
+/// The serializer reads and writes enums as a `u32` in the sample, so the
+/// Rust type must be `#[repr(u32)]` or `#[repr(i32)]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumDesc {
+    /// Fully scoped IDL name of the enum
+    pub name: String,
+    /// Number of bits needed to hold the values (`@bit_bound`), 32 by
+    /// default
+    pub bit_bound: u32,
+    /// Enumerators in declaration order
+    pub enumerators: Vec<EnumeratorDesc>,
+}
+
+impl EnumDesc {
+    /// Creates an enum description without enumerators.
+    /// * `name` - Fully scoped IDL name of the enum
+    /// * `bit_bound` - Number of bits used to serialize the values, in the
+    ///   range `1..=32`
+    pub fn new(name: &str, bit_bound: u32) -> EnumDesc {
+        assert!(
+            (1..=32).contains(&bit_bound),
+            "bit_bound of enum {} must be in 1..=32",
+            name
+        );
+        EnumDesc {
+            name: name.to_string(),
+            bit_bound,
+            enumerators: Vec::new(),
+        }
+    }
+
+    /// Appends an enumerator to the enum.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value does not fit in the bit bound.
+    pub fn enumerator(mut self, name: &str, value: u32) -> EnumDesc {
+        assert!(
+            self.bit_bound == 32 || value < 1 << self.bit_bound,
+            "value {} of enumerator {}::{} does not fit in {} bits",
+            value,
+            self.name,
+            name,
+            self.bit_bound
+        );
+        self.enumerators.push(EnumeratorDesc {
+            name: name.to_string(),
+            value,
+        });
+        self
+    }
+
+    /// The highest value of the enumerators, the serializer rejects larger
+    /// values.
+    pub fn max_value(&self) -> u32 {
+        self.enumerators
+            .iter()
+            .map(|enumerator| enumerator.value)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Size of the serialized value in bytes.
+    pub fn serialized_size(&self) -> u32 {
+        match self.bit_bound {
+            1..=8 => 1,
+            9..=16 => 2,
+            _ => 4,
+        }
+    }
+}
+
+/// Description of an enumerator of an IDL `enum`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumeratorDesc {
+    /// Name of the enumerator
+    pub name: String,
+    /// Value of the enumerator
+    pub value: u32,
+}
+
+/// Description of an IDL `bitmask`.
+///
+/// The serializer reads and writes a bitmask as an unsigned integer of its
+/// serialized size in the sample, so the Rust type must be a `u8`, `u16`,
+/// `u32` or `u64` for a bit bound of up to 8, 16, 32 or 64 bits. Values
+/// with bits set that are not a flag are rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitmaskDesc {
+    /// Fully scoped IDL name of the bitmask
+    pub name: String,
+    /// Number of bits of the bitmask (`@bit_bound`), 32 by default
+    pub bit_bound: u32,
+    /// Flags in declaration order
+    pub flags: Vec<BitflagDesc>,
+}
+
+impl BitmaskDesc {
+    /// Creates a bitmask description without flags.
+    /// * `name` - Fully scoped IDL name of the bitmask
+    /// * `bit_bound` - Number of bits of the bitmask, in the range `1..=64`
+    pub fn new(name: &str, bit_bound: u32) -> BitmaskDesc {
+        assert!(
+            (1..=64).contains(&bit_bound),
+            "bit_bound of bitmask {} must be in 1..=64",
+            name
+        );
+        BitmaskDesc {
+            name: name.to_string(),
+            bit_bound,
+            flags: Vec::new(),
+        }
+    }
+
+    /// Appends a flag to the bitmask.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside the bit bound or already taken by
+    /// another flag.
+    pub fn flag(mut self, name: &str, position: u32) -> BitmaskDesc {
+        assert!(
+            position < self.bit_bound,
+            "position {} of flag {}::{} does not fit in {} bits",
+            position,
+            self.name,
+            name,
+            self.bit_bound
+        );
+        if let Some(other) = self.flags.iter().find(|flag| flag.position == position) {
+            panic!(
+                "flags {} and {} of bitmask {} have the same position {}",
+                other.name, name, self.name, position
+            );
+        }
+        self.flags.push(BitflagDesc {
+            name: name.to_string(),
+            position,
+        });
+        self
+    }
+
+    /// The bits of all flags, the serializer rejects values with other bits
+    /// set.
+    pub fn bits(&self) -> u64 {
+        self.flags
+            .iter()
+            .fold(0, |bits, flag| bits | 1 << flag.position)
+    }
+
+    /// Size of the serialized value in bytes, which is also the size of the
+    /// value in the sample.
+    pub fn serialized_size(&self) -> u32 {
+        match self.bit_bound {
+            1..=8 => 1,
+            9..=16 => 2,
+            17..=32 => 4,
+            _ => 8,
+        }
+    }
+
+    /// The valid bits split into the high and low word, as the serializer
+    /// expects them after a bitmask instruction.
+    fn bits_words(&self) -> [u32; 2] {
+        let bits = self.bits();
+        [(bits >> 32) as u32, bits as u32]
+    }
+}
+
+/// A value of a bitmask has bits set that are not a flag of the bitmask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("bits {bits:#x} are not flags of the bitmask")]
+pub struct InvalidBitsError {
+    /// The bits that are not a flag
+    pub bits: u64,
+}
+
+/// Description of a flag of an IDL `bitmask`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitflagDesc {
+    /// Name of the flag
+    pub name: String,
+    /// Position of the bit of the flag
+    pub position: u32,
+}
+
+/// Description of an IDL `union`.
+///
+/// The serializer reads and writes a union as the C struct of the
+/// discriminator and a C union of the case values. A Rust enum with data
+/// is laid out differently, so topics of types with unions are serialized
+/// with serde, see [crate::sertype]. The discriminant of a variant is the
+/// label of the case.
+///
+/// A received discriminator that matches none of the labels selects the
+/// default case, without a default case the sample is rejected with
+/// [crate::xcdr::XcdrError::Discriminator].
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnionDesc {
+    /// Fully scoped IDL name of the union
+    pub name: String,
+    /// Type of the discriminator, an integer of at most 4 bytes
+    pub discriminator: PrimitiveKind,
+    /// `@final` or `@appendable`
+    pub extensibility: TypeExtensibility,
+    /// Cases in declaration order
+    pub cases: Vec<CaseDesc>,
+}
+
+impl UnionDesc {
+    /// Creates a union description without cases.
+    /// * `name` - Fully scoped IDL name of the union
+    /// * `discriminator` - Type of the discriminator, which must match the
+    ///   representation of the Rust enum
+    pub fn new(name: &str, discriminator: PrimitiveKind) -> UnionDesc {
+        assert!(
+            !matches!(
+                discriminator,
+                PrimitiveKind::Boolean
+                    | PrimitiveKind::Int64
+                    | PrimitiveKind::UInt64
+                    | PrimitiveKind::Float32
+                    | PrimitiveKind::Float64
+            ),
+            "discriminator of union {} must be an integer of at most 4 bytes",
+            name
+        );
+        UnionDesc {
+            name: name.to_string(),
+            discriminator,
+            extensibility: TypeExtensibility::Final,
+            cases: Vec::new(),
+        }
+    }
+
+    /// Sets the extensibility of the union, [TypeExtensibility::Final] by
+    /// default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `extensibility` is [TypeExtensibility::Mutable], the
+    /// serializer does not support mutable unions.
+    pub fn extensibility(mut self, extensibility: TypeExtensibility) -> UnionDesc {
+        assert!(
+            extensibility != TypeExtensibility::Mutable,
+            "union {} can not be mutable",
+            self.name
+        );
+        self.extensibility = extensibility;
+        self
+    }
+
+    /// Appends a case to the union.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the union already has a case with the same label or a
+    /// second default case.
+    pub fn case(mut self, case: CaseDesc) -> UnionDesc {
+        for other in &self.cases {
+            assert!(
+                other.label != case.label,
+                "cases {} and {} of union {} have the same label",
+                other.name,
+                case.name,
+                self.name
+            );
+            assert!(
+                !(other.default && case.default),
+                "union {} has more than one default case",
+                self.name
+            );
+        }
+        self.cases.push(case);
+        self
+    }
+
+    /// The default case, if any.
+    pub fn default_case(&self) -> Option<&CaseDesc> {
+        self.cases.iter().find(|case| case.default)
+    }
+}
+
+/// Description of a case of an IDL `union`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseDesc {
+    /// Name of the case member
+    pub name: String,
+    /// Value of the discriminator that selects the case
+    pub label: u32,
+    /// Type of the case member
+    pub type_desc: TypeDesc,
+    /// The case is the `default` case
+    pub default: bool,
+}
+
+impl CaseDesc {
+    /// Creates a case description.
+    /// * `name` - Name of the case member
+    /// * `label` - Discriminator value of the case
+    /// * `type_desc` - Description of the case member type
+    pub fn new(name: &str, label: u32, type_desc: TypeDesc) -> CaseDesc {
+        CaseDesc {
+            name: name.to_string(),
+            label,
+            type_desc,
+            default: false,
+        }
+    }
+
+    /// Marks the case as the `default` case of the union.
+    pub fn default(mut self) -> CaseDesc {
+        self.default = true;
+        self
+    }
+}
+
+/// Description of an IDL `sequence`.
+///
+/// The serializer reads and writes a sequence as a C `dds_sequence_t`,
+/// which a [Vec] is not, so topics of types with sequences are serialized
+/// with serde, see [crate::sertype].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequenceDesc {
+    /// Type of the elements
+    pub element: TypeDesc,
+    /// Maximum number of elements of a bounded sequence
+    pub bound: Option<u32>,
+}
+
+impl SequenceDesc {
+    /// Creates a sequence description.
+    /// * `element` - Description of the element type
+    /// * `bound` - Maximum length of a bounded sequence
+    pub fn new(element: TypeDesc, bound: Option<usize>) -> SequenceDesc {
+        SequenceDesc {
+            element,
+            bound: bound.map(|bound| bound as u32),
+        }
+    }
+}
+
+/// Description of an IDL `map`.
+///
+/// The serializer has no instruction for maps, the program describes a map
+/// as a sequence of key-value structs, which XCDR encodes the same as the
+/// map. Neither a [HashMap] nor a [BTreeMap] is such a sequence, so topics
+/// of types with maps are serialized with serde, see [crate::sertype].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapDesc {
+    /// Type of the keys
+    pub key: TypeDesc,
+    /// Type of the values
+    pub value: TypeDesc,
+    /// Maximum number of entries of a bounded map
+    pub bound: Option<u32>,
+}
+
+impl MapDesc {
+    /// Creates a map description.
+    /// * `key` - Description of the key type
+    /// * `value` - Description of the value type
+    /// * `bound` - Maximum number of entries of a bounded map
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not an integer or a string, or if the value is
+    /// an array, which IDL can only express through a typedef.
+    pub fn new(key: TypeDesc, value: TypeDesc, bound: Option<usize>) -> MapDesc {
+        assert!(
+            matches!(
+                key,
+                TypeDesc::Primitive(
+                    PrimitiveKind::Int8
+                        | PrimitiveKind::UInt8
+                        | PrimitiveKind::Int16
+                        | PrimitiveKind::UInt16
+                        | PrimitiveKind::Int32
+                        | PrimitiveKind::UInt32
+                        | PrimitiveKind::Int64
+                        | PrimitiveKind::UInt64
+                ) | TypeDesc::String
+                    | TypeDesc::BoundedString(_)
+            ),
+            "map keys must be integers or strings, not {:?}",
+            key
+        );
+        assert!(
+            !matches!(
+                value,
+                TypeDesc::Array(_) | TypeDesc::Optional(_) | TypeDesc::External(_)
+            ),
+            "map values can not be arrays, optional or external"
+        );
+        MapDesc {
+            key,
+            value,
+            bound: bound.map(|bound| bound as u32),
+        }
+    }
+
+    /// Returns the sequence of entries the serializer sees, the entries are
+    /// a final struct with the members `key` and `value`. No Rust type holds
+    /// the entries, the struct is laid out as the C struct.
+    pub fn entries(&self) -> SequenceDesc {
+        let name = format!(
+            "map<{}, {}>",
+            crate::idl::type_spec(&self.key),
+            crate::idl::type_spec(&self.value)
+        );
+        let mut entry = StructDesc::new(&name, 0, 1)
+            .member(MemberDesc::new("key", 0, self.key.clone()))
+            .member(MemberDesc::new("value", 0, self.value.clone()));
+        let (layout, offsets) = c_struct_layout(&entry);
+        entry.size = layout.size;
+        entry.align = layout.align;
+        for (member, offset) in entry.members.iter_mut().zip(offsets) {
+            member.offset = offset;
+        }
+        SequenceDesc {
+            element: TypeDesc::Struct(entry),
+            bound: self.bound,
+        }
+    }
+}
+
+/// Description of an IDL array.
+///
+/// Arrays of arrays are a single multi-dimensional array, as in IDL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrayDesc {
+    /// Type of the elements
+    pub element: TypeDesc,
+    /// Size of an element of the Rust type
+    pub element_size: u32,
+    /// Dimensions, outermost first
+    pub dims: Vec<u32>,
+}
+
+impl ArrayDesc {
+    /// Creates an array description.
+    /// * `element` - Description of the element type
+    /// * `element_size` - `size_of` the Rust element type
+    /// * `len` - Number of elements
+    pub fn new(element: TypeDesc, element_size: usize, len: usize) -> ArrayDesc {
+        match element {
+            TypeDesc::Array(inner) => {
+                let mut dims = vec![len as u32];
+                dims.extend(&inner.dims);
+                ArrayDesc {
+                    element: inner.element,
+                    element_size: inner.element_size,
+                    dims,
+                }
+            }
+            element => ArrayDesc {
+                element,
+                element_size: element_size as u32,
+                dims: vec![len as u32],
+            },
+        }
+    }
+
+    /// Total number of elements.
+    pub fn len(&self) -> u32 {
+        self.dims.iter().product()
+    }
+
+    /// Returns `true` if the array has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Description of a member of an IDL `struct`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemberDesc {
+    /// Name of the member
+    pub name: String,
+    /// Offset of the member from the start of the Rust struct
+    pub offset: u32,
+    /// Type of the member
+    pub type_desc: TypeDesc,
+    /// The member is part of the key (`@key`)
+    pub key: bool,
+    /// Explicit member id (`@id` or `@hashid`)
+    pub id: Option<u32>,
+}
+
+impl MemberDesc {
+    /// Creates a member description.
+    /// * `name` - Name of the member
+    /// * `offset` - `offset_of` the member in the Rust struct
+    /// * `type_desc` - Description of the member type
+    pub fn new(name: &str, offset: usize, type_desc: TypeDesc) -> MemberDesc {
+        MemberDesc {
+            name: name.to_string(),
+            offset: offset as u32,
+            type_desc,
+            key: false,
+            id: None,
+        }
+    }
+
+    /// Marks the member as part of the key.
+    pub fn key(mut self) -> MemberDesc {
+        self.key = true;
+        self
+    }
+
+    /// Sets the member id (`@id`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the id does not fit in 28 bits.
+    pub fn id(mut self, id: u32) -> MemberDesc {
+        assert!(
+            id <= MEMBER_ID_MASK,
+            "id {} of member {} does not fit in 28 bits",
+            id,
+            self.name
+        );
+        self.id = Some(id);
+        self
+    }
+
+    /// Sets the member id to the hash of `name` (`@hashid`), which is
+    /// usually the name of the member.
+    pub fn hashid(mut self, name: &str) -> MemberDesc {
+        self.id = Some(hash_member_id(name));
+        self
+    }
+}
+
+/// Returns the default IDL name for `T`: the module path of the type
+/// excluding the crate name, the same as [crate::topic::TopicType::type_name].
+///
+/// The modules become IDL modules, e.g. `HelloWorldData::Msg` in any crate
+/// is `struct Msg` in `module HelloWorldData`. The name is not a legal IDL
+/// name for generic types, which need `#[dds(type_name = "...")]`.
+pub fn type_name_of<T: ?Sized>() -> &'static str {
+    let full = std::any::type_name::<T>();
+    if let Some((_, rest)) = full.split_once("::") {
+        rest
+    } else {
+        full
+    }
+}
+
+/// Returns whether `name` is a legal scoped IDL name: identifiers of
+/// letters, digits and `_` that start with a letter, separated by `::`.
+pub fn is_valid_type_name(name: &str) -> bool {
+    name.split("::").all(|identifier| {
+        identifier.starts_with(|c: char| c.is_ascii_alphabetic())
+            && identifier
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+macro_rules! impl_primitive {
+    ($($ty:ty => $kind:ident),* $(,)?) => {
+        $(
+            impl DdsType for $ty {
+                fn type_desc() -> TypeDesc {
+                    TypeDesc::Primitive(PrimitiveKind::$kind)
+                }
+            }
+        )*
+    };
+}
+
+impl_primitive! {
+    bool => Boolean,
+    i8 => Int8,
+    u8 => UInt8,
+    i16 => Int16,
+    u16 => UInt16,
+    i32 => Int32,
+    u32 => UInt32,
+    i64 => Int64,
+    u64 => UInt64,
+    f32 => Float32,
+    f64 => Float64,
+}
+
+macro_rules! impl_pod {
+    ($($ty:ty),* $(,)?) => {
+        $(unsafe impl Pod for $ty {})*
+    };
+}
+
+impl_pod!(bool, i8, u8, i16, u16, i32, u32, i64, u64, f32, f64);
+
+impl DdsType for String {
+    fn type_desc() -> TypeDesc {
+        TypeDesc::String
+    }
+}
+
+impl<T: DdsType> DdsType for Vec<T> {
+    fn type_desc() -> TypeDesc {
+        TypeDesc::Sequence(Box::new(SequenceDesc::new(T::type_desc(), None)))
+    }
+}
+
+impl<T: DdsType, const N: usize> DdsType for [T; N] {
+    fn type_desc() -> TypeDesc {
+        TypeDesc::Array(Box::new(ArrayDesc::new(T::type_desc(), size_of::<T>(), N)))
+    }
+}
+
+impl<K: DdsType, V: DdsType, S> DdsType for HashMap<K, V, S> {
+    fn type_desc() -> TypeDesc {
+        map_desc::<K, V>(None)
+    }
+}
+
+/// Iterates in the order of the keys, so equal maps serialize to the same
+/// bytes.
+impl<K: DdsType, V: DdsType> DdsType for BTreeMap<K, V> {
+    fn type_desc() -> TypeDesc {
+        map_desc::<K, V>(None)
+    }
+}
+
+/// Returns the description of a map of `K` to `V`.
+pub(crate) fn map_desc<K: DdsType, V: DdsType>(bound: Option<usize>) -> TypeDesc {
+    TypeDesc::Map(Box::new(MapDesc::new(
+        K::type_desc(),
+        V::type_desc(),
+        bound,
+    )))
+}
+
+impl<T: DdsType> DdsType for Option<T> {
+    fn type_desc() -> TypeDesc {
+        TypeDesc::Optional(Box::new(T::type_desc()))
+    }
+}
+
+impl<T: DdsType> DdsType for Box<T> {
+    fn type_desc() -> TypeDesc {
+        TypeDesc::External(Box::new(T::type_desc()))
+    }
+}
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// Size and alignment of a value in the C sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Layout {
+    size: u32,
+    align: u32,
+}
+
+impl Layout {
+    fn of<T>() -> Layout {
+        Layout {
+            size: size_of::<T>() as u32,
+            align: align_of::<T>() as u32,
+        }
+    }
+}
+
+/// Returns the layout of a value of type `type_desc` in the C sample, the
+/// type `idlc` generates for the IDL type: strings, optional and external
+/// members are pointers, sequences and maps a `dds_sequence_t` and enums
+/// an `int`.
+fn c_layout(type_desc: &TypeDesc) -> Layout {
+    match type_desc {
+        TypeDesc::Primitive(kind) => Layout {
+            size: kind.size(),
+            align: kind.size(),
+        },
+        TypeDesc::Enum(_) => Layout::of::<u32>(),
+        TypeDesc::Bitmask(desc) => Layout {
+            size: desc.serialized_size(),
+            align: desc.serialized_size(),
+        },
+        TypeDesc::String | TypeDesc::Optional(_) | TypeDesc::External(_) => {
+            Layout::of::<*mut c_char>()
+        }
+        TypeDesc::BoundedString(bound) => Layout {
+            size: bound + 1,
+            align: 1,
+        },
+        TypeDesc::Sequence(_) | TypeDesc::Map(_) => Layout::of::<dds_sequence_t>(),
+        TypeDesc::Array(desc) => {
+            let element = c_layout(&desc.element);
+            Layout {
+                size: element.size * desc.len(),
+                align: element.align,
+            }
+        }
+        TypeDesc::Struct(desc) => c_struct_layout(desc).0,
+        TypeDesc::Union(desc) => c_union_layout(desc).0,
+    }
+}
+
+/// Returns the layout of a struct in the C sample and the offsets of its
+/// members in the order of [StructDesc::all_members]. The base is the first
+/// member of the C struct.
+fn c_struct_layout(desc: &StructDesc) -> (Layout, Vec<u32>) {
+    let (mut layout, mut offsets) = match &desc.base {
+        Some(base) => c_struct_layout(base.desc()),
+        None => (Layout { size: 0, align: 1 }, Vec::new()),
+    };
+    for member in &desc.members {
+        let member = c_layout(&member.type_desc);
+        let offset = layout.size.next_multiple_of(member.align);
+        offsets.push(offset);
+        layout.size = offset + member.size;
+        layout.align = layout.align.max(member.align);
+    }
+    layout.size = layout.size.next_multiple_of(layout.align);
+    (layout, offsets)
+}
+
+/// Returns the layout of a union in the C sample, a struct of the
+/// discriminator and a C union of the values, and the offset of the values.
+fn c_union_layout(desc: &UnionDesc) -> (Layout, u32) {
+    let discriminator = desc.discriminator.size();
+    let mut values = Layout { size: 0, align: 1 };
+    for case in &desc.cases {
+        let case = c_layout(&case.type_desc);
+        values.size = values.size.max(case.size);
+        values.align = values.align.max(case.align);
+    }
+    let offset = discriminator.next_multiple_of(values.align);
+    let align = discriminator.max(values.align);
+    let layout = Layout {
+        size: (offset + values.size).next_multiple_of(align),
+        align,
+    };
+    (layout, offset)
+}
+
+/// Returns whether the Rust type is laid out the same as the C sample, which
+/// holds for plain old data only.
+fn is_c_layout(type_desc: &TypeDesc) -> bool {
+    match type_desc {
+        TypeDesc::Primitive(_) => true,
+        TypeDesc::Array(desc) => {
+            desc.element_size == c_layout(&desc.element).size && is_c_layout(&desc.element)
+        }
+        TypeDesc::Struct(desc) => {
+            let (layout, offsets) = c_struct_layout(desc);
+            desc.size == layout.size
+                && desc.align == layout.align
+                && desc.all_members().into_iter().zip(offsets).all(
+                    |((offset, member), c_offset)| {
+                        offset == c_offset && is_c_layout(&member.type_desc)
+                    },
+                )
+        }
+        _ => false,
+    }
+}
+
+fn op(opcode: dds_stream_opcode) -> u32 {
+    opcode as u32
+}
+
+fn op_type(typecode: dds_stream_typecode) -> u32 {
+    (typecode as u32) << 16
+}
+
+fn op_subtype(typecode: dds_stream_typecode) -> u32 {
+    (typecode as u32) << 8
+}
+
+/// Encodes the size of an enum or bitmask in bytes in the flags of an
+/// instruction.
+fn op_size(size: u32) -> u32 {
+    size.trailing_zeros() << DDS_OP_FLAG_SZ_SHIFT
+}
+
+/// Encodes a jump as the (signed) offset from the instruction at `from` to
+/// the instruction at `to`.
+fn rel_jump(from: usize, to: usize) -> u32 {
+    (to as isize - from as isize) as i16 as u16 as u32
+}
+
+/// Splits the type of a struct member into the type of the value and the
+/// `DDS_OP_FLAG_OPT` and `DDS_OP_FLAG_EXT` flags of the member.
+///
+/// Optional members are a pointer in the C sample, except for strings which
+/// are a pointer already. A Rust [Option] or [Box] is not such a pointer,
+/// neither is plain old data, so samples with them are never handed to
+/// Cyclone DDS as they are.
+fn member_flags(type_desc: &TypeDesc) -> (&TypeDesc, u32) {
+    match type_desc {
+        TypeDesc::Optional(inner) => match inner.as_ref() {
+            TypeDesc::External(inner) => (inner, DDS_OP_FLAG_OPT | DDS_OP_FLAG_EXT),
+            TypeDesc::String => (inner, DDS_OP_FLAG_OPT),
+            inner => (inner, DDS_OP_FLAG_OPT | DDS_OP_FLAG_EXT),
+        },
+        TypeDesc::External(inner) => (inner, DDS_OP_FLAG_EXT),
+        type_desc => (type_desc, 0),
+    }
+}
+
+/// A reference to the program of a nested type that is patched once the
+/// program has been emitted: the offset from the instruction at `insn` to
+/// the program is or'ed into the word at `word`.
+struct Patch {
+    insn: usize,
+    word: usize,
+}
+
+/// Compiles a [TypeDesc] tree into a serializer program.
+///
+/// The program of the top-level struct comes first, the programs of nested
+/// types are appended after it and referenced through relative jumps. Each
+/// nested type is emitted only once.
+#[derive(Default)]
+struct OpsCompiler {
+    ops: Vec<u32>,
+    /// Number of instructions in `ops`
+    nops: u32,
+    /// Nested types that still need to be emitted, with the instruction
+    /// referencing them.
+    pending: Vec<(Patch, TypeDesc)>,
+    /// Start of the program of every emitted type, by type name.
+    emitted: HashMap<String, usize>,
+    /// Index of the `ADR` instruction of every member, relative to the start
+    /// of the program of the struct, by type name.
+    members: HashMap<String, Vec<usize>>,
+    /// Set if any of the types is a union
+    contains_union: bool,
+    /// Set if the type can only be serialized with XCDR2: it has optional
+    /// members or types that are not final
+    xcdr2: bool,
+}
+
+impl OpsCompiler {
+    fn compile(&mut self, desc: &StructDesc) {
+        self.emitted.insert(desc.name.clone(), 0);
+        self.emit_struct(desc);
+
+        while !self.pending.is_empty() {
+            let (patch, nested) = self.pending.remove(0);
+            let start = match nested.name() {
+                Some(name) => match self.emitted.get(name) {
+                    Some(start) => *start,
+                    None => {
+                        let start = self.ops.len();
+                        self.emitted.insert(name.to_string(), start);
+                        match &nested {
+                            TypeDesc::Struct(desc) => self.emit_struct(desc),
+                            TypeDesc::Union(desc) => self.emit_union(desc),
+                            // A bitmask in a union case has a program with
+                            // only the bitmask at offset 0
+                            TypeDesc::Bitmask(_) => {
+                                self.emit_adr(&nested, 0, 0);
+                                self.insn(&[op(dds_stream_opcode::DDS_OP_RTS)]);
+                            }
+                            _ => unreachable!(
+                                "only structs, unions and bitmasks are referenced by name"
+                            ),
+                        }
+                        start
+                    }
+                },
+                // Collections in union cases have a program with only the
+                // collection at offset 0
+                None => {
+                    let start = self.ops.len();
+                    self.emit_adr(&nested, 0, 0);
+                    self.insn(&[op(dds_stream_opcode::DDS_OP_RTS)]);
+                    start
+                }
+            };
+            self.ops[patch.word] |= rel_jump(patch.insn, start);
+        }
+    }
+
+    fn insn(&mut self, words: &[u32]) {
+        self.ops.extend_from_slice(words);
+        self.nops += 1;
+    }
+
+    /// Adds a reference from the word at `word` of the instruction at `insn`
+    /// to the program of a nested type.
+    fn refer(&mut self, insn: usize, word: usize, type_desc: &TypeDesc) {
+        self.pending.push((Patch { insn, word }, type_desc.clone()));
+    }
+
+    /// Emits the program of a struct.
+    ///
+    /// An appendable struct starts with a `DLC`, which makes the serializer
+    /// write a DHEADER. A mutable struct starts with a `PLC` and a `PLM` with
+    /// the id of every member, which jumps to a program with only the
+    /// `ADR` of the member.
+    ///
+    /// The members of a base are emitted as if they were members of the
+    /// derived struct, which gives the same serialized data as a reference
+    /// to the program of the base.
+    fn emit_struct(&mut self, desc: &StructDesc) {
+        if let Some(base) = &desc.base {
+            if base.desc().extensibility != desc.extensibility {
+                panic!(
+                    "struct {} and its base {} must have the same extensibility",
+                    desc.name,
+                    base.desc().name
+                );
+            }
+        }
+        let start = self.ops.len();
+        let (_, offsets) = c_struct_layout(desc);
+        let all_members = offsets
+            .into_iter()
+            .zip(desc.all_members().into_iter().map(|(_, member)| member))
+            .collect::<Vec<_>>();
+        let mut members = Vec::with_capacity(all_members.len());
+        match desc.extensibility {
+            TypeExtensibility::Final | TypeExtensibility::Appendable => {
+                if desc.extensibility == TypeExtensibility::Appendable {
+                    self.xcdr2 = true;
+                    self.insn(&[op(dds_stream_opcode::DDS_OP_DLC)]);
+                }
+                for (offset, member) in all_members {
+                    members.push(self.ops.len() - start);
+                    self.emit_member(member, offset);
+                }
+                self.insn(&[op(dds_stream_opcode::DDS_OP_RTS)]);
+            }
+            TypeExtensibility::Mutable => {
+                self.xcdr2 = true;
+                self.insn(&[op(dds_stream_opcode::DDS_OP_PLC)]);
+                let plm = self.ops.len();
+                for id in desc.member_ids() {
+                    self.insn(&[op(dds_stream_opcode::DDS_OP_PLM), id]);
+                }
+                self.insn(&[op(dds_stream_opcode::DDS_OP_RTS)]);
+                for (index, (offset, member)) in all_members.into_iter().enumerate() {
+                    let insn = plm + 2 * index;
+                    self.ops[insn] |= rel_jump(insn, self.ops.len());
+                    members.push(self.ops.len() - start);
+                    self.emit_member(member, offset);
+                    self.insn(&[op(dds_stream_opcode::DDS_OP_RTS)]);
+                }
+            }
+        }
+        self.members.insert(desc.name.clone(), members);
+    }
+
+    fn emit_member(&mut self, member: &MemberDesc, offset: u32) {
+        let (type_desc, mut flags) = member_flags(&member.type_desc);
+        if flags & DDS_OP_FLAG_OPT != 0 {
+            self.xcdr2 = true;
+        }
+        if member.key {
+            flags |= DDS_OP_FLAG_KEY | DDS_OP_FLAG_MU;
+        }
+        self.emit_adr(type_desc, offset, flags);
+    }
+
+    /// Emits the `ADR` instruction of a value of type `type_desc` at `offset`.
+    fn emit_adr(&mut self, type_desc: &TypeDesc, offset: u32, flags: u32) {
+        let adr = op(dds_stream_opcode::DDS_OP_ADR) | flags;
+        match type_desc {
+            TypeDesc::Primitive(kind) => {
+                let (typecode, flags) = kind.typecode();
+                self.insn(&[adr | op_type(typecode) | flags, offset]);
+            }
+            TypeDesc::String => {
+                self.insn(&[adr | op_type(dds_stream_typecode::DDS_OP_VAL_STR), offset]);
+            }
+            TypeDesc::BoundedString(bound) => {
+                // The bound includes the terminating nul
+                self.insn(&[
+                    adr | op_type(dds_stream_typecode::DDS_OP_VAL_BST),
+                    offset,
+                    bound + 1,
+                ]);
+            }
+            TypeDesc::Struct(_) | TypeDesc::Union(_) => {
+                // The jump is patched once the nested program has been emitted
+                let index = self.ops.len();
+                self.refer(index, index + 2, type_desc);
+                self.insn(&[
+                    adr | op_type(dds_stream_typecode::DDS_OP_VAL_EXT),
+                    offset,
+                    3 << 16,
+                ]);
+            }
+            TypeDesc::Enum(desc) => {
+                self.insn(&[
+                    adr | op_type(dds_stream_typecode::DDS_OP_VAL_ENU)
+                        | op_size(desc.serialized_size()),
+                    offset,
+                    desc.max_value(),
+                ]);
+            }
+            TypeDesc::Bitmask(desc) => {
+                let [high, low] = desc.bits_words();
+                self.insn(&[
+                    adr | op_type(dds_stream_typecode::DDS_OP_VAL_BMK)
+                        | op_size(desc.serialized_size()),
+                    offset,
+                    high,
+                    low,
+                ]);
+            }
+            TypeDesc::Sequence(desc) => match desc.bound {
+                Some(bound) => self.emit_collection(
+                    adr | op_type(dds_stream_typecode::DDS_OP_VAL_BSQ),
+                    offset,
+                    Some(bound),
+                    &desc.element,
+                    false,
+                ),
+                None => self.emit_collection(
+                    adr | op_type(dds_stream_typecode::DDS_OP_VAL_SEQ),
+                    offset,
+                    None,
+                    &desc.element,
+                    false,
+                ),
+            },
+            TypeDesc::Array(desc) => self.emit_collection(
+                adr | op_type(dds_stream_typecode::DDS_OP_VAL_ARR),
+                offset,
+                Some(desc.len()),
+                &desc.element,
+                true,
+            ),
+            TypeDesc::Map(desc) => {
+                self.emit_adr(&TypeDesc::Sequence(Box::new(desc.entries())), offset, flags)
+            }
+            TypeDesc::Optional(_) | TypeDesc::External(_) => {
+                panic!("only struct members can be optional or external")
+            }
+        }
+    }
+
+    /// Emits a sequence, bounded sequence or array. `count` is the bound of a
+    /// bounded sequence or the length of an array.
+    ///
+    /// Elements of a primitive type, string or enum are described by the
+    /// instruction itself. For structs and unions the instruction refers to
+    /// the program of the type, for nested collections the program of the
+    /// element follows the instruction.
+    fn emit_collection(
+        &mut self,
+        adr: u32,
+        offset: u32,
+        count: Option<u32>,
+        element: &TypeDesc,
+        array: bool,
+    ) {
+        let mut words = vec![0, offset];
+        words.extend(count);
+
+        let subtype = match element {
+            TypeDesc::Primitive(kind) => {
+                let (typecode, flags) = kind.typecode();
+                op_subtype(typecode) | flags
+            }
+            TypeDesc::String => op_subtype(dds_stream_typecode::DDS_OP_VAL_STR),
+            TypeDesc::BoundedString(bound) => {
+                if array {
+                    words.extend([0, bound + 1]);
+                } else {
+                    words.push(bound + 1);
+                }
+                op_subtype(dds_stream_typecode::DDS_OP_VAL_BST)
+            }
+            TypeDesc::Enum(desc) => {
+                words.push(desc.max_value());
+                op_subtype(dds_stream_typecode::DDS_OP_VAL_ENU) | op_size(desc.serialized_size())
+            }
+            TypeDesc::Bitmask(desc) => {
+                words.extend(desc.bits_words());
+                op_subtype(dds_stream_typecode::DDS_OP_VAL_BMK) | op_size(desc.serialized_size())
+            }
+            TypeDesc::Struct(_)
+            | TypeDesc::Union(_)
+            | TypeDesc::Sequence(_)
+            | TypeDesc::Array(_)
+            | TypeDesc::Map(_) => {
+                let subtype = match element {
+                    TypeDesc::Struct(_) => dds_stream_typecode::DDS_OP_VAL_STU,
+                    TypeDesc::Union(_) => dds_stream_typecode::DDS_OP_VAL_UNI,
+                    TypeDesc::Sequence(desc) if desc.bound.is_some() => {
+                        dds_stream_typecode::DDS_OP_VAL_BSQ
+                    }
+                    TypeDesc::Map(desc) if desc.bound.is_some() => {
+                        dds_stream_typecode::DDS_OP_VAL_BSQ
+                    }
+                    TypeDesc::Sequence(_) | TypeDesc::Map(_) => dds_stream_typecode::DDS_OP_VAL_SEQ,
+                    _ => dds_stream_typecode::DDS_OP_VAL_ARR,
+                };
+                return self.emit_complex_collection(
+                    adr | op_subtype(subtype),
+                    words,
+                    array,
+                    element,
+                );
+            }
+            TypeDesc::Optional(_) | TypeDesc::External(_) => {
+                panic!("collection elements can not be optional or external")
+            }
+        };
+        words[0] = adr | subtype;
+        self.insn(&words);
+    }
+
+    /// Emits a collection of structs, unions or collections: the
+    /// instruction ends with the size of an element and a word that holds
+    /// the length of the instruction and the offset to the program of the
+    /// element. Arrays have the two in reverse order.
+    fn emit_complex_collection(
+        &mut self,
+        adr: u32,
+        mut words: Vec<u32>,
+        array: bool,
+        element: &TypeDesc,
+    ) {
+        let element_size = c_layout(element).size;
+        let index = self.ops.len();
+        let length = words.len() as u32 + 2;
+        let jump = if array {
+            words.extend([length << 16, element_size]);
+            index + words.len() - 2
+        } else {
+            words.extend([element_size, length << 16]);
+            index + words.len() - 1
+        };
+        words[0] = adr;
+
+        match element {
+            TypeDesc::Struct(_) | TypeDesc::Union(_) => {
+                self.refer(index, jump, element);
+                self.insn(&words);
+            }
+            _ => {
+                // The program of the element follows the instruction
+                self.insn(&words);
+                self.emit_adr(element, 0, 0);
+                self.insn(&[op(dds_stream_opcode::DDS_OP_RTS)]);
+                let length = (self.ops.len() - index) as u32;
+                self.ops[jump] = (length << 16) | words.len() as u32;
+            }
+        }
+    }
+
+    /// Emits the program of a union: an `ADR` for the discriminator followed
+    /// by a `JEQ4` for every case, with the default case last.
+    fn emit_union(&mut self, desc: &UnionDesc) {
+        self.contains_union = true;
+        if desc.extensibility == TypeExtensibility::Appendable {
+            self.xcdr2 = true;
+            self.insn(&[op(dds_stream_opcode::DDS_OP_DLC)]);
+        }
+
+        let (typecode, flags) = desc.discriminator.typecode();
+        let mut adr = op(dds_stream_opcode::DDS_OP_ADR)
+            | op_type(dds_stream_typecode::DDS_OP_VAL_UNI)
+            | op_subtype(typecode)
+            | flags;
+        if desc.default_case().is_some() {
+            adr |= DDS_OP_FLAG_DEF;
+        }
+        let ncases = desc.cases.len() as u32;
+        // The jump skips the cases, the cases start right after the `ADR`
+        self.insn(&[adr, 0, ncases, ((4 + 4 * ncases) << 16) | 4]);
+
+        let (_, offset) = c_union_layout(desc);
+        let cases = desc.cases.iter().filter(|case| !case.default);
+        for case in cases.chain(desc.default_case()) {
+            self.emit_case(case, offset);
+        }
+        self.insn(&[op(dds_stream_opcode::DDS_OP_RTS)]);
+    }
+
+    /// Emits the `JEQ4` of a case, the value is at `offset` in the union.
+    fn emit_case(&mut self, case: &CaseDesc, offset: u32) {
+        let (type_desc, jeq) = match &case.type_desc {
+            TypeDesc::External(inner) => (
+                inner.as_ref(),
+                op(dds_stream_opcode::DDS_OP_JEQ4) | DDS_OP_FLAG_EXT,
+            ),
+            type_desc => (type_desc, op(dds_stream_opcode::DDS_OP_JEQ4)),
+        };
+        let (insn, extra) = match type_desc {
+            TypeDesc::Primitive(kind) => {
+                let (typecode, flags) = kind.typecode();
+                (jeq | op_type(typecode) | flags, 0)
+            }
+            TypeDesc::String => (jeq | op_type(dds_stream_typecode::DDS_OP_VAL_STR), 0),
+            TypeDesc::BoundedString(bound) => (
+                jeq | op_type(dds_stream_typecode::DDS_OP_VAL_BST),
+                bound + 1,
+            ),
+            TypeDesc::Enum(desc) => (
+                jeq | op_type(dds_stream_typecode::DDS_OP_VAL_ENU)
+                    | op_size(desc.serialized_size()),
+                desc.max_value(),
+            ),
+            TypeDesc::Struct(_)
+            | TypeDesc::Union(_)
+            | TypeDesc::Bitmask(_)
+            | TypeDesc::Sequence(_)
+            | TypeDesc::Array(_)
+            | TypeDesc::Map(_) => {
+                let typecode = match type_desc {
+                    TypeDesc::Bitmask(_) => dds_stream_typecode::DDS_OP_VAL_BMK,
+                    TypeDesc::Sequence(desc) if desc.bound.is_some() => {
+                        dds_stream_typecode::DDS_OP_VAL_BSQ
+                    }
+                    TypeDesc::Map(desc) if desc.bound.is_some() => {
+                        dds_stream_typecode::DDS_OP_VAL_BSQ
+                    }
+                    TypeDesc::Sequence(_) | TypeDesc::Map(_) => dds_stream_typecode::DDS_OP_VAL_SEQ,
+                    TypeDesc::Array(_) => dds_stream_typecode::DDS_OP_VAL_ARR,
+                    _ => dds_stream_typecode::DDS_OP_VAL_EXT,
+                };
+                let index = self.ops.len();
+                self.refer(index, index, type_desc);
+                (jeq | op_type(typecode), 0)
+            }
+            TypeDesc::Optional(_) | TypeDesc::External(_) => {
+                panic!("union case {} can not be optional", case.name)
+            }
+        };
+        self.insn(&[insn, case.label, offset, extra]);
+    }
+}
+
+/// A key field of a topic type, which is never a struct: keys on nested
+/// structs are expanded into their members.
+struct KeyField<'a> {
+    /// Dotted name of the key field, e.g. `position.x`
+    name: String,
+    /// The structs and member indices leading to the key field
+    path: Vec<(&'a StructDesc, usize)>,
+    type_desc: &'a TypeDesc,
+}
+
+/// Collects the key fields of a struct in declaration order.
+///
+/// If `implicit` is set, the struct is used as a key member of its parent
+/// and all of its members are key fields unless some are marked as key.
+fn key_fields<'a>(
+    desc: &'a StructDesc,
+    implicit: bool,
+    prefix: &str,
+    path: &[(&'a StructDesc, usize)],
+    fields: &mut Vec<KeyField<'a>>,
+) {
+    let members = desc.all_members();
+    let explicit = members.iter().any(|(_, member)| member.key);
+    for (index, (_, member)) in members.into_iter().enumerate() {
+        if !member.key && (explicit || !implicit) {
+            continue;
+        }
+
+        let name = format!("{}{}", prefix, member.name);
+        let mut path = path.to_vec();
+        path.push((desc, index));
+        match &member.type_desc {
+            TypeDesc::Struct(nested) => {
+                key_fields(nested, true, &format!("{}.", name), &path, fields);
+            }
+            TypeDesc::Union(_) => {
+                panic!("union member {} can not be part of the key", name)
+            }
+            TypeDesc::Map(_) => {
+                panic!("map member {} can not be part of the key", name)
+            }
+            TypeDesc::Optional(_) | TypeDesc::External(_) => {
+                panic!("key member {} can not be optional or external", name)
+            }
+            type_desc => fields.push(KeyField {
+                name,
+                path,
+                type_desc,
+            }),
+        }
+    }
+}
+
+/// Returns the indices of the key fields in the order of the serialized
+/// key, which is the order of their member ids: the declaration order
+/// unless members have an explicit id.
+fn key_order(fields: &[KeyField]) -> Vec<usize> {
+    let ids = fields
+        .iter()
+        .map(|field| {
+            field
+                .path
+                .iter()
+                .map(|(desc, member)| desc.member_ids()[*member])
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut order = (0..fields.len()).collect::<Vec<_>>();
+    order.sort_by_key(|index| &ids[*index]);
+    order
+}
+
+/// Returns the key fields of a topic type as the members leading to them,
+/// in the order of the serialized key, and whether the key serialized as
+/// XCDR2 always fits in the 16 bytes of a key hash.
+pub(crate) fn key_members(desc: &StructDesc) -> (Vec<Vec<&MemberDesc>>, bool) {
+    let mut fields = Vec::new();
+    key_fields(desc, false, "", &[], &mut fields);
+    let fields = key_order(&fields)
+        .into_iter()
+        .map(|index| &fields[index])
+        .collect::<Vec<_>>();
+    let fixed = fixed_key_flags(&fields) & DDS_TOPIC_FIXED_KEY_XCDR2_KEYHASH != 0;
+    let members = fields
+        .iter()
+        .map(|field| {
+            field
+                .path
+                .iter()
+                .map(|(desc, member)| desc.all_members()[*member].1)
+                .collect()
+        })
+        .collect();
+    (members, fixed)
+}
+
+/// Serialized size and alignment in XCDR1 and XCDR2 of a key field, [None]
+/// if the key field has no fixed size.
+fn key_field_size(type_desc: &TypeDesc) -> Option<(u32, u32, u32)> {
+    match type_desc {
+        TypeDesc::Primitive(kind) => {
+            let size = kind.size();
+            Some((size, size, size.min(4)))
+        }
+        TypeDesc::Enum(desc) => {
+            let size = desc.serialized_size();
+            Some((size, size, size))
+        }
+        TypeDesc::Bitmask(desc) => {
+            let size = desc.serialized_size();
+            Some((size, size, size.min(4)))
+        }
+        TypeDesc::Array(desc) => key_field_size(&desc.element)
+            .map(|(size, align1, align2)| (size * desc.len(), align1, align2)),
+        _ => None,
+    }
+}
+
+/// Computes the `DDS_TOPIC_FIXED_KEY*` flags: a key that always serializes
+/// to at most 16 bytes is used as the key hash as is.
+fn fixed_key_flags(fields: &[&KeyField]) -> u32 {
+    let mut xcdr1: u32 = 0;
+    let mut xcdr2: u32 = 0;
+    for field in fields {
+        match key_field_size(field.type_desc) {
+            Some((size, align1, align2)) => {
+                xcdr1 = xcdr1.next_multiple_of(align1) + size;
+                xcdr2 = xcdr2.next_multiple_of(align2) + size;
+            }
+            None => return 0,
+        }
+    }
+
+    let mut flags = 0;
+    if xcdr1 <= 16 {
+        flags |= DDS_TOPIC_FIXED_KEY;
+    }
+    if xcdr2 <= 16 {
+        flags |= DDS_TOPIC_FIXED_KEY_XCDR2 | DDS_TOPIC_FIXED_KEY_XCDR2_KEYHASH;
+    }
+    flags
+}
+
+impl KeyDescriptor {
+    /// Dotted name of the key field
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Index of the key offset list in the ops of the [TopicDescriptor]
+    pub fn offset(&self) -> u32 {
+        self.m_offset
+    }
+
+    /// Position of the key in the serialized key
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+}
+
+impl TopicDescriptor {
+    /// Generates the [TopicDescriptor] of a type from its [DdsType]
+    /// description, including its XTypes type information
+    /// ([TypeInfo]).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is not described as a struct, only structs can be used
+    /// as topic types.
+    pub fn from_type<T: DdsType>() -> TopicDescriptor {
+        let type_desc = T::type_desc();
+        let desc = type_desc
+            .as_struct()
+            .expect("topic types must be described as a struct");
+
+        let mut compiler = OpsCompiler::default();
+        compiler.compile(desc);
+
+        // The key offset lists follow the type programs, each one lists the
+        // index of the `ADR` of the key in the top-level program followed
+        // by the indices in the programs of the nested structs.
+        let mut fields = Vec::new();
+        key_fields(desc, false, "", &[], &mut fields);
+        let order = key_order(&fields);
+
+        let mut keys = Vec::with_capacity(fields.len());
+        let mut ops = std::mem::take(&mut compiler.ops);
+        for (index, field) in fields.iter().enumerate() {
+            keys.push(KeyDescriptor {
+                name: field.name.clone(),
+                m_offset: ops.len() as u32,
+                index: order.iter().position(|other| *other == index).unwrap() as u32,
+            });
+            ops.push(op(dds_stream_opcode::DDS_OP_KOF) | field.path.len() as u32);
+            for (desc, member) in &field.path {
+                ops.push(compiler.members[&desc.name][*member] as u32);
+            }
+        }
+
+        let mut flagset = if fields.is_empty() {
+            0
+        } else {
+            let fields = order
+                .iter()
+                .map(|index| &fields[*index])
+                .collect::<Vec<_>>();
+            fixed_key_flags(&fields)
+        };
+        if compiler.contains_union {
+            flagset |= DDS_TOPIC_CONTAINS_UNION;
+        }
+        let (type_information, type_mapping) = TypeInfo::new(desc).into_meta();
+        flagset |= DDS_TOPIC_XTYPES_METADATA;
+        let mut restrict_data_representation = 0;
+        if compiler.xcdr2 {
+            flagset |= DDS_TOPIC_RESTRICT_DATA_REPRESENTATION;
+            restrict_data_representation = DDS_DATA_REPRESENTATION_FLAG_XCDR2;
+        }
+
+        let (layout, _) = c_struct_layout(desc);
+        TopicDescriptor {
+            m_size: layout.size,
+            m_align: layout.align,
+            m_flagset: flagset,
+            m_nkeys: keys.len() as u32,
+            m_typename: desc.name.clone(),
+            m_keys: keys,
+            m_nops: compiler.nops,
+            m_ops: ops,
+            m_meta: String::new(),
+            type_information,
+            type_mapping,
+            restrict_data_representation,
+        }
+    }
+
+    /// Generates the [TopicDescriptor] of a plain old data type like
+    /// [TopicDescriptor::from_type], flagged [DDS_TOPIC_FIXED_SIZE]. Cyclone
+    /// DDS then knows the samples have no pointers and copies them as they
+    /// are, which is what loans and PSMX shared memory need.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is not described as a struct of fixed size or is not
+    /// laid out the same as the C sample.
+    pub fn from_pod_type<T: Pod>() -> TopicDescriptor {
+        let type_desc = T::type_desc();
+        assert!(
+            type_desc.is_fixed_size(),
+            "plain old data must be described as a type of fixed size"
+        );
+        assert!(
+            is_c_layout(&type_desc),
+            "plain old data must be laid out the same in Rust and C"
+        );
+        let mut descriptor = Self::from_type::<T>();
+        descriptor.m_flagset |= DDS_TOPIC_FIXED_SIZE;
+        descriptor
+    }
+
+    /// Size of the topic type
+    pub fn size(&self) -> u32 {
+        self.m_size
+    }
+
+    /// Alignment of the topic type
+    pub fn align(&self) -> u32 {
+        self.m_align
+    }
+
+    /// Flags
+    pub fn flagset(&self) -> u32 {
+        self.m_flagset
+    }
+
+    /// Type name
+    pub fn type_name(&self) -> &str {
+        &self.m_typename
+    }
+
+    /// Key descriptors
+    pub fn keys(&self) -> &[KeyDescriptor] {
+        &self.m_keys
+    }
+
+    /// Marshalling meta data
+    pub fn ops(&self) -> &[u32] {
+        &self.m_ops
+    }
+
+    /// The data representations samples of the type can be serialized in,
+    /// XCDR2 only if the descriptor restricts it to XCDR2
+    /// ([DDS_TOPIC_RESTRICT_DATA_REPRESENTATION]).
+    pub fn data_representations(&self) -> Vec<DataRepresentation> {
+        [DataRepresentation::Xcdr1, DataRepresentation::Xcdr2]
+            .into_iter()
+            .filter(|representation| {
+                self.m_flagset & DDS_TOPIC_RESTRICT_DATA_REPRESENTATION == 0
+                    || self.restrict_data_representation & representation.flag() != 0
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        ffi::c_void,
+        fmt::Debug,
+        mem::{offset_of, size_of, MaybeUninit},
+        slice,
+    };
+
+    use cyclonedds_sys::{
+        dds_sequence_t, dds_stream_opcode, dds_stream_typecode::*, DDS_OP_FLAG_FP, DDS_OP_FLAG_KEY,
+        DDS_OP_FLAG_MU, DDS_OP_FLAG_SGN, DDS_OP_FLAG_SZ_SHIFT, DDS_TOPIC_FIXED_KEY,
+        DDS_TOPIC_FIXED_KEY_XCDR2,
+    };
+    use cyclonedds_sys::{
+        DDS_CDR_ENC_VERSION_1, DDS_CDR_ENC_VERSION_2, DDS_OP_FLAG_DEF, DDS_OP_FLAG_EXT,
+        DDS_OP_FLAG_OPT, DDS_TOPIC_CONTAINS_UNION,
+    };
+    use cyclonedds_sys::{
+        DDS_DATA_REPRESENTATION_FLAG_XCDR2, DDS_TOPIC_FIXED_KEY_XCDR2_KEYHASH,
+        DDS_TOPIC_FIXED_SIZE, DDS_TOPIC_RESTRICT_DATA_REPRESENTATION, DDS_TOPIC_XTYPES_METADATA,
+    };
+
+    use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+    use super::{
+        hash_member_id, is_c_layout, is_valid_type_name, op_size, op_subtype, op_type, BitmaskDesc,
+        DdsType, EnumDesc, InvalidBitsError, MemberDesc, OpsCompiler, OwnedTopicDescriptor,
+        StructDesc, TopicDescriptor, TypeDesc,
+    };
+    use crate::{
+        bounded::{BoundedMap, BoundedString, BoundedVec},
+        qos::DataRepresentation,
+        topic::{is_valid_topic_name, TopicType, XType},
+        xcdr::{self, Endianness, XcdrError, XcdrVersion},
+    };
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Position {
+        x: f64,
+        y: f64,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[dds(final)]
+    struct Vehicle {
+        id: i32,
+        name: String,
+        position: Position,
+        moving: bool,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    struct Msg {
+        #[dds(key)]
+        user_id: i32,
+        message: String,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    struct Track {
+        #[dds(key)]
+        sensor: i64,
+        #[dds(key)]
+        position: Position,
+        label: String,
+    }
+
+    #[allow(dead_code)]
+    #[derive(TopicType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+    #[repr(u32)]
+    enum Color {
+        Red,
+        Green = 2,
+        Blue = 1,
+    }
+
+    #[allow(dead_code)]
+    #[derive(TopicType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+    #[repr(i32)]
+    #[dds(bit_bound = 8)]
+    enum Level {
+        Low,
+        High,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    struct Light {
+        #[dds(key)]
+        level: Level,
+        color: Color,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+    #[repr(C, u32)]
+    enum Value {
+        Int(i32) = 1,
+        Real(f64),
+        #[dds(default)]
+        Level(Level) = 7,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[repr(i8)]
+    enum Small {
+        Byte(u8),
+        Word(u32),
+        Point(Position),
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Reading {
+        id: i32,
+        value: Value,
+        small: Small,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Cloud {
+        points: Vec<Position>,
+        intensity: Vec<f32>,
+        matrix: [[f64; 3]; 2],
+        rows: Vec<Vec<i16>>,
+        tags: BoundedVec<BoundedString<8>, 4>,
+        frame: BoundedString<16>,
+        colors: [Color; 2],
+        names: [String; 2],
+    }
+
+    #[allow(dead_code)]
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[repr(C, u32)]
+    enum Blob {
+        Data(Vec<u8>),
+        Name(BoundedString<4>),
+        Shape(Box<Position>),
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    struct Packet {
+        blob: Blob,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Sensor {
+        #[dds(key)]
+        id: u32,
+        range: Option<f32>,
+        label: Option<String>,
+        origin: Option<Position>,
+        calibration: Box<Position>,
+        history: Option<Box<[f64; 4]>>,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    struct OptionalKey {
+        #[dds(key)]
+        id: Option<u32>,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[dds(appendable)]
+    struct Status {
+        #[dds(key)]
+        id: u32,
+        level: u8,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[dds(mutable)]
+    struct Config {
+        #[dds(key, id = 10)]
+        id: u32,
+        name: String,
+        #[dds(hashid)]
+        origin: Position,
+        #[dds(key, id = 2)]
+        zone: u16,
+    }
+
+    const ADR: u32 = dds_stream_opcode::DDS_OP_ADR as u32;
+    const DLC: u32 = dds_stream_opcode::DDS_OP_DLC as u32;
+    const PLC: u32 = dds_stream_opcode::DDS_OP_PLC as u32;
+    const PLM: u32 = dds_stream_opcode::DDS_OP_PLM as u32;
+    const JEQ4: u32 = dds_stream_opcode::DDS_OP_JEQ4 as u32;
+    const RTS: u32 = dds_stream_opcode::DDS_OP_RTS as u32;
+    const KOF: u32 = dds_stream_opcode::DDS_OP_KOF as u32;
+
+    #[test]
+    fn test_primitive_members() {
+        let descriptor = TopicDescriptor::from_type::<Position>();
+
+        assert_eq!(descriptor.size(), 16);
+        assert_eq!(descriptor.align(), 8);
+        assert_eq!(
+            descriptor.ops(),
+            &[
+                ADR | op_type(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                0,
+                ADR | op_type(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                8,
+                RTS,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_struct() {
+        let descriptor = TopicDescriptor::from_type::<Vehicle>();
+
+        assert_eq!(
+            descriptor.ops(),
+            &[
+                ADR | op_type(DDS_OP_VAL_4BY) | DDS_OP_FLAG_SGN,
+                0,
+                ADR | op_type(DDS_OP_VAL_STR),
+                8,
+                ADR | op_type(DDS_OP_VAL_EXT),
+                16,
+                (3 << 16) + 6,
+                ADR | op_type(DDS_OP_VAL_BLN),
+                32,
+                RTS,
+                // Position
+                ADR | op_type(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                0,
+                ADR | op_type(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                8,
+                RTS,
+            ]
+        );
+        assert_eq!(descriptor.m_nops, 8);
+        assert_eq!(descriptor.type_name(), Vehicle::type_name());
+    }
+
+    #[test]
+    fn test_type_desc() {
+        let type_desc = Vehicle::type_desc();
+        let desc = type_desc.as_struct().unwrap();
+
+        assert_eq!(desc.members.len(), 4);
+        assert_eq!(desc.members[1].name, "name");
+        assert_eq!(desc.members[1].type_desc, TypeDesc::String);
+        assert_eq!(
+            desc.members[2].type_desc.as_struct().unwrap().name,
+            Position::type_name()
+        );
+    }
+
+    #[test]
+    fn test_key() {
+        let descriptor = TopicDescriptor::from_type::<Msg>();
+
+        assert_eq!(
+            descriptor.ops(),
+            &[
+                ADR | DDS_OP_FLAG_KEY | DDS_OP_FLAG_MU | op_type(DDS_OP_VAL_4BY) | DDS_OP_FLAG_SGN,
+                0,
+                ADR | op_type(DDS_OP_VAL_STR),
+                8,
+                RTS,
+                // key: user_id
+                KOF | 1,
+                0,
+            ]
+        );
+        assert_eq!(descriptor.m_nops, 3);
+        assert_eq!(descriptor.m_nkeys, 1);
+        assert_eq!(descriptor.keys()[0].name(), "user_id");
+        assert_eq!(descriptor.keys()[0].offset(), 5);
+        assert_eq!(descriptor.keys()[0].index(), 0);
+        assert_ne!(descriptor.flagset() & DDS_TOPIC_FIXED_KEY, 0);
+        assert_ne!(descriptor.flagset() & DDS_TOPIC_FIXED_KEY_XCDR2, 0);
+    }
+
+    #[test]
+    fn test_nested_key() {
+        let descriptor = TopicDescriptor::from_type::<Track>();
+        let ops = descriptor.ops();
+
+        assert_eq!(
+            &ops[..9],
+            &[
+                ADR | DDS_OP_FLAG_KEY | DDS_OP_FLAG_MU | op_type(DDS_OP_VAL_8BY) | DDS_OP_FLAG_SGN,
+                0,
+                ADR | DDS_OP_FLAG_KEY | DDS_OP_FLAG_MU | op_type(DDS_OP_VAL_EXT),
+                8,
+                (3 << 16) + 6,
+                ADR | op_type(DDS_OP_VAL_STR),
+                24,
+                RTS,
+                // Position
+                ADR | op_type(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+            ]
+        );
+
+        let keys = descriptor.keys();
+        assert_eq!(keys.len(), 3);
+        assert_eq!(keys[0].name(), "sensor");
+        assert_eq!(keys[1].name(), "position.x");
+        assert_eq!(keys[2].name(), "position.y");
+        assert_eq!(&ops[keys[0].offset() as usize..][..2], &[KOF | 1, 0]);
+        assert_eq!(&ops[keys[1].offset() as usize..][..3], &[KOF | 2, 2, 0]);
+        assert_eq!(&ops[keys[2].offset() as usize..][..3], &[KOF | 2, 2, 2]);
+        assert_eq!(
+            keys.iter().map(|k| k.index()).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+
+        // 24 bytes of key do not fit in the key hash
+        assert_eq!(descriptor.flagset() & DDS_TOPIC_FIXED_KEY, 0);
+    }
+
+    #[test]
+    fn test_enum() {
+        let type_desc = Color::type_desc();
+        let TypeDesc::Enum(desc) = &type_desc else {
+            panic!("Color is not an enum");
+        };
+        assert_eq!(desc.bit_bound, 32);
+        assert_eq!(desc.max_value(), 2);
+        assert_eq!(
+            desc.enumerators
+                .iter()
+                .map(|e| (e.name.as_str(), e.value))
+                .collect::<Vec<_>>(),
+            [("Red", 0), ("Green", 2), ("Blue", 1)]
+        );
+
+        let descriptor = TopicDescriptor::from_type::<Light>();
+        assert_eq!(
+            descriptor.ops(),
+            &[
+                ADR | DDS_OP_FLAG_KEY | DDS_OP_FLAG_MU | op_type(DDS_OP_VAL_ENU),
+                0,
+                1,
+                ADR | op_type(DDS_OP_VAL_ENU) | (2 << DDS_OP_FLAG_SZ_SHIFT),
+                4,
+                2,
+                RTS,
+                // key: level
+                KOF | 1,
+                0,
+            ]
+        );
+        assert_eq!(descriptor.m_nops, 3);
+        assert_ne!(descriptor.flagset() & DDS_TOPIC_FIXED_KEY, 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_enum_bit_bound() {
+        EnumDesc::new("Level", 2).enumerator("Max", 4);
+    }
+
+    /// Serializes `sample` with [xcdr], reads it with Cyclone DDS into a C
+    /// sample laid out as the descriptor describes and serializes that with
+    /// Cyclone DDS again. Returns the data of Cyclone DDS, which must be the
+    /// same and deserialize to `sample`.
+    fn stream_roundtrip<T>(sample: &T, version: XcdrVersion) -> Vec<u8>
+    where
+        T: TopicType + DdsType + Serialize + DeserializeOwned,
+    {
+        let descriptor = OwnedTopicDescriptor::new(&T::topic_descriptor());
+        let type_desc = T::type_desc();
+        let xcdr_version = match version {
+            XcdrVersion::Xcdr1 => DDS_CDR_ENC_VERSION_1,
+            XcdrVersion::Xcdr2 => DDS_CDR_ENC_VERSION_2,
+        };
+        let ours = xcdr::to_payload(sample, Some(&type_desc), version, Endianness::NATIVE).unwrap();
+
+        let theirs = unsafe {
+            let allocator = &cyclonedds_sys::dds_cdrstream_default_allocator;
+            let mut desc = MaybeUninit::zeroed().assume_init();
+            cyclonedds_sys::dds_cdrstream_desc_from_topic_desc(&mut desc, descriptor.as_c());
+
+            // The strings, sequences and pointers of a zeroed C sample are
+            // empty, Cyclone DDS allocates them while reading
+            let size = descriptor.topic_descriptor().size() as usize;
+            let mut buf = vec![0u64; size.div_ceil(size_of::<u64>())];
+            let c_sample = buf.as_mut_ptr() as *mut c_void;
+            let mut is = MaybeUninit::zeroed().assume_init();
+            cyclonedds_sys::dds_istream_init(
+                &mut is,
+                ours.len() as u32,
+                ours.as_ptr() as *const c_void,
+                xcdr_version,
+            );
+            cyclonedds_sys::dds_stream_read_sample(&mut is, c_sample, allocator, &desc);
+
+            let mut os = MaybeUninit::zeroed().assume_init();
+            cyclonedds_sys::dds_ostream_init(&mut os, allocator, 0, xcdr_version);
+            assert!(cyclonedds_sys::dds_stream_write_sample(
+                &mut os, allocator, c_sample, &desc
+            ));
+            let cdr = slice::from_raw_parts(os.m_buffer, os.m_index as usize).to_vec();
+            cyclonedds_sys::dds_ostream_fini(&mut os, allocator);
+
+            cyclonedds_sys::dds_stream_free_sample(c_sample, allocator, descriptor.as_c().m_ops);
+            cyclonedds_sys::dds_cdrstream_desc_fini(&mut desc, allocator);
+            cdr
+        };
+        assert_eq!(ours, theirs);
+        assert_eq!(
+            xcdr::from_payload::<T>(&theirs, Some(&type_desc), version, Endianness::NATIVE)
+                .as_ref(),
+            Ok(sample)
+        );
+        theirs
+    }
+
+    #[test]
+    fn test_collections() {
+        let descriptor = TopicDescriptor::from_type::<Cloud>();
+
+        assert_eq!(
+            descriptor.ops(),
+            &[
+                ADR | op_type(DDS_OP_VAL_SEQ) | op_subtype(DDS_OP_VAL_STU),
+                0,
+                16,
+                (4 << 16) + 31,
+                ADR | op_type(DDS_OP_VAL_SEQ) | op_subtype(DDS_OP_VAL_4BY) | DDS_OP_FLAG_FP,
+                24,
+                ADR | op_type(DDS_OP_VAL_ARR) | op_subtype(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                48,
+                6,
+                // The program of the element follows the sequence
+                ADR | op_type(DDS_OP_VAL_SEQ) | op_subtype(DDS_OP_VAL_SEQ),
+                96,
+                size_of::<dds_sequence_t>() as u32,
+                (7 << 16) + 4,
+                ADR | op_type(DDS_OP_VAL_SEQ) | op_subtype(DDS_OP_VAL_2BY) | DDS_OP_FLAG_SGN,
+                0,
+                RTS,
+                ADR | op_type(DDS_OP_VAL_BSQ) | op_subtype(DDS_OP_VAL_BST),
+                120,
+                4,
+                9,
+                ADR | op_type(DDS_OP_VAL_BST),
+                144,
+                17,
+                ADR | op_type(DDS_OP_VAL_ARR) | op_subtype(DDS_OP_VAL_ENU) | op_size(4),
+                164,
+                2,
+                2,
+                ADR | op_type(DDS_OP_VAL_ARR) | op_subtype(DDS_OP_VAL_STR),
+                176,
+                2,
+                RTS,
+                // Position
+                ADR | op_type(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                0,
+                ADR | op_type(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                8,
+                RTS,
+            ]
+        );
+        assert_eq!(descriptor.m_nops, 14);
+        // The C sample holds a dds_sequence_t for every sequence and a
+        // pointer for every string
+        assert_eq!(descriptor.size(), 192);
+
+        let type_desc = <[[f64; 3]; 2]>::type_desc();
+        let TypeDesc::Array(desc) = &type_desc else {
+            panic!("not an array");
+        };
+        assert_eq!(desc.dims, [2, 3]);
+        assert_eq!(desc.element_size, 8);
+    }
+
+    #[test]
+    fn test_sequence_roundtrip() {
+        let sample = Cloud {
+            points: vec![Position { x: 1.0, y: 2.0 }, Position { x: -1.0, y: 0.5 }],
+            intensity: vec![0.25, 0.5, 1.0],
+            matrix: [[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]],
+            rows: vec![vec![1, -2], vec![], vec![3]],
+            tags: vec![
+                BoundedString::try_from("front").unwrap(),
+                BoundedString::try_from("").unwrap(),
+            ]
+            .try_into()
+            .unwrap(),
+            frame: BoundedString::try_from("base_link").unwrap(),
+            colors: [Color::Blue, Color::Green],
+            names: ["left".to_string(), "right".to_string()],
+        };
+        for version in [XcdrVersion::Xcdr1, XcdrVersion::Xcdr2] {
+            let cdr = stream_roundtrip(&sample, version);
+            // The length of a sequence comes before its elements
+            assert_eq!(cdr[..4], 2u32.to_ne_bytes());
+        }
+
+        let empty = Cloud {
+            points: Vec::new(),
+            intensity: Vec::new(),
+            matrix: [[0.0; 3]; 2],
+            rows: Vec::new(),
+            tags: BoundedVec::new(),
+            frame: BoundedString::new(),
+            colors: [Color::Red; 2],
+            names: Default::default(),
+        };
+        stream_roundtrip(&empty, XcdrVersion::Xcdr2);
+    }
+
+    #[derive(TopicType, Clone, Copy, Debug, PartialEq)]
+    #[dds(bitmask(READ, WRITE, EXECUTE = 4), bit_bound = 5)]
+    #[repr(transparent)]
+    struct Permissions(u8);
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    struct File {
+        #[dds(key)]
+        mode: Permissions,
+        history: Vec<Permissions>,
+    }
+
+    #[test]
+    fn test_bitmask() {
+        let descriptor = TopicDescriptor::from_type::<File>();
+
+        // The valid bits follow the instruction as high and low word
+        assert_eq!(
+            descriptor.ops(),
+            &[
+                ADR | DDS_OP_FLAG_KEY | DDS_OP_FLAG_MU | op_type(DDS_OP_VAL_BMK) | op_size(1),
+                0,
+                0,
+                0b10011,
+                ADR | op_type(DDS_OP_VAL_SEQ) | op_subtype(DDS_OP_VAL_BMK) | op_size(1),
+                8,
+                0,
+                0b10011,
+                RTS,
+                // key: mode
+                KOF | 1,
+                0,
+            ]
+        );
+        assert_ne!(descriptor.flagset() & DDS_TOPIC_FIXED_KEY, 0);
+
+        let mode = Permissions::READ | Permissions::EXECUTE;
+        assert_eq!(mode.bits(), 0b10001);
+        assert!(mode.contains(Permissions::EXECUTE));
+        assert!(!mode.contains(Permissions::WRITE));
+        assert_eq!(Permissions::all().bits(), 0b10011);
+        assert_eq!(Permissions::try_from(0b11), Ok(Permissions(0b11)));
+        assert_eq!(
+            Permissions::try_from(0b1100),
+            Err(InvalidBitsError { bits: 0b1100 })
+        );
+        assert_eq!(Permissions::from_bits_truncate(0xff), Permissions::all());
+    }
+
+    #[test]
+    #[should_panic(expected = "flags A and B of bitmask Flags have the same position 1")]
+    fn test_bitmask_position() {
+        BitmaskDesc::new("Flags", 8).flag("A", 1).flag("B", 1);
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Settings {
+        values: BTreeMap<i32, String>,
+        limits: BoundedMap<u8, f64, 4>,
+    }
+
+    #[test]
+    fn test_map() {
+        let descriptor = TopicDescriptor::from_type::<Settings>();
+
+        // A map is a sequence of key-value entries
+        assert_eq!(
+            descriptor.ops(),
+            &[
+                ADR | op_type(DDS_OP_VAL_SEQ) | op_subtype(DDS_OP_VAL_STU),
+                0,
+                16,
+                (4 << 16) + 10,
+                ADR | op_type(DDS_OP_VAL_BSQ) | op_subtype(DDS_OP_VAL_STU),
+                24,
+                4,
+                16,
+                (5 << 16) + 11,
+                RTS,
+                // map<long, string>
+                ADR | op_type(DDS_OP_VAL_4BY) | DDS_OP_FLAG_SGN,
+                0,
+                ADR | op_type(DDS_OP_VAL_STR),
+                8,
+                RTS,
+                // map<uint8, double>
+                ADR | op_type(DDS_OP_VAL_1BY),
+                0,
+                ADR | op_type(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                8,
+                RTS,
+            ]
+        );
+
+        // Both maps of the standard library are an unbounded IDL map
+        assert_eq!(
+            HashMap::<i32, String>::type_desc(),
+            BTreeMap::<i32, String>::type_desc()
+        );
+
+        // The entries are laid out as the C struct { int32_t key; char *value; }
+        let TypeDesc::Map(desc) = BTreeMap::<i32, String>::type_desc() else {
+            panic!("not a map");
+        };
+        let TypeDesc::Struct(entry) = desc.entries().element else {
+            panic!("not a struct");
+        };
+        assert_eq!((entry.size, entry.align), (16, 8));
+        assert_eq!(entry.members[1].offset, 8);
+    }
+
+    #[test]
+    fn test_map_roundtrip() {
+        let sample = Settings {
+            values: BTreeMap::from([(-1, "low".to_string()), (3, "high".to_string())]),
+            limits: BTreeMap::from([(1, 0.5), (2, 1.5)]).try_into().unwrap(),
+        };
+        for version in [XcdrVersion::Xcdr1, XcdrVersion::Xcdr2] {
+            let cdr = stream_roundtrip(&sample, version);
+            // A map is written as its number of entries and the entries
+            assert_eq!(
+                cdr[..8],
+                [2u32.to_ne_bytes(), (-1i32).to_ne_bytes()].concat()
+            );
+        }
+
+        let empty = Settings {
+            values: BTreeMap::new(),
+            limits: BoundedMap::new(),
+        };
+        stream_roundtrip(&empty, XcdrVersion::Xcdr1);
+    }
+
+    #[test]
+    #[should_panic(expected = "map member values can not be part of the key")]
+    fn test_map_key() {
+        #[derive(TopicType, Clone, Debug, PartialEq)]
+        struct Keyed {
+            #[dds(key)]
+            values: BTreeMap<i32, i32>,
+        }
+
+        TopicDescriptor::from_type::<Keyed>();
+    }
+
+    #[test]
+    fn test_union_collections() {
+        let descriptor = TopicDescriptor::from_type::<Packet>();
+
+        assert_eq!(
+            descriptor.ops(),
+            &[
+                ADR | op_type(DDS_OP_VAL_EXT),
+                0,
+                (3 << 16) + 4,
+                RTS,
+                // Blob
+                ADR | op_type(DDS_OP_VAL_UNI) | op_subtype(DDS_OP_VAL_4BY),
+                0,
+                3,
+                (16 << 16) + 4,
+                JEQ4 | op_type(DDS_OP_VAL_SEQ) | 13,
+                0,
+                8,
+                0,
+                JEQ4 | op_type(DDS_OP_VAL_BST),
+                1,
+                8,
+                5,
+                JEQ4 | op_type(DDS_OP_VAL_EXT) | DDS_OP_FLAG_EXT | 8,
+                2,
+                8,
+                0,
+                RTS,
+                // Blob::Data
+                ADR | op_type(DDS_OP_VAL_SEQ) | op_subtype(DDS_OP_VAL_1BY),
+                0,
+                RTS,
+                // Position
+                ADR | op_type(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                0,
+                ADR | op_type(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                8,
+                RTS,
+            ]
+        );
+        assert_eq!(descriptor.m_nops, 12);
+    }
+
+    #[test]
+    fn test_optional() {
+        let descriptor = TopicDescriptor::from_type::<Sensor>();
+        let opt = DDS_OP_FLAG_OPT | DDS_OP_FLAG_EXT;
+
+        assert_eq!(
+            descriptor.ops(),
+            &[
+                ADR | DDS_OP_FLAG_KEY | DDS_OP_FLAG_MU | op_type(DDS_OP_VAL_4BY),
+                0,
+                ADR | opt | op_type(DDS_OP_VAL_4BY) | DDS_OP_FLAG_FP,
+                8,
+                // Strings are a pointer already
+                ADR | DDS_OP_FLAG_OPT | op_type(DDS_OP_VAL_STR),
+                16,
+                ADR | opt | op_type(DDS_OP_VAL_EXT),
+                24,
+                (3 << 16) + 10,
+                ADR | DDS_OP_FLAG_EXT | op_type(DDS_OP_VAL_EXT),
+                32,
+                (3 << 16) + 7,
+                ADR | opt | op_type(DDS_OP_VAL_ARR) | op_subtype(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                40,
+                4,
+                RTS,
+                // Position
+                ADR | op_type(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                0,
+                ADR | op_type(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                8,
+                RTS,
+                KOF | 1,
+                0,
+            ]
+        );
+        assert_eq!(descriptor.m_nops, 10);
+        assert_eq!(
+            descriptor.restrict_data_representation,
+            DDS_DATA_REPRESENTATION_FLAG_XCDR2
+        );
+        // Every optional and external member is a pointer in the C sample
+        assert_eq!(descriptor.size(), 48);
+    }
+
+    #[test]
+    fn test_optional_roundtrip() {
+        let sample = Sensor {
+            id: 3,
+            range: Some(2.5),
+            label: None,
+            origin: Some(Position { x: 1.0, y: -1.0 }),
+            calibration: Box::new(Position { x: 0.5, y: 0.25 }),
+            history: Some(Box::new([1.0, 2.0, 3.0, 4.0])),
+        };
+        let cdr = stream_roundtrip(&sample, XcdrVersion::Xcdr2);
+        // A present member starts with true, the absent label is only false
+        assert_eq!((cdr[4], cdr[12], cdr[13]), (1, 0, 1));
+
+        let sample = Sensor {
+            id: 4,
+            range: None,
+            label: Some("lidar".to_string()),
+            origin: None,
+            calibration: Box::new(Position { x: 0.0, y: 0.0 }),
+            history: None,
+        };
+        stream_roundtrip(&sample, XcdrVersion::Xcdr2);
+    }
+
+    #[test]
+    #[should_panic(expected = "key member id can not be optional or external")]
+    fn test_optional_key() {
+        TopicDescriptor::from_type::<OptionalKey>();
+    }
+
+    #[test]
+    fn test_appendable() {
+        let descriptor = TopicDescriptor::from_type::<Status>();
+
+        assert_eq!(
+            descriptor.ops(),
+            &[
+                DLC,
+                ADR | DDS_OP_FLAG_KEY | DDS_OP_FLAG_MU | op_type(DDS_OP_VAL_4BY),
+                0,
+                ADR | op_type(DDS_OP_VAL_1BY),
+                4,
+                RTS,
+                KOF | 1,
+                1,
+            ]
+        );
+        assert_eq!(descriptor.m_nops, 4);
+        assert_eq!(
+            descriptor.flagset(),
+            DDS_TOPIC_FIXED_KEY
+                | DDS_TOPIC_FIXED_KEY_XCDR2
+                | DDS_TOPIC_FIXED_KEY_XCDR2_KEYHASH
+                | DDS_TOPIC_RESTRICT_DATA_REPRESENTATION
+                | DDS_TOPIC_XTYPES_METADATA
+        );
+        assert_eq!(
+            descriptor.restrict_data_representation,
+            DDS_DATA_REPRESENTATION_FLAG_XCDR2
+        );
+        assert_eq!(
+            descriptor.data_representations(),
+            [DataRepresentation::Xcdr2]
+        );
+        assert_eq!(
+            TopicDescriptor::from_type::<Msg>().data_representations(),
+            [DataRepresentation::Xcdr1, DataRepresentation::Xcdr2]
+        );
+    }
+
+    #[test]
+    fn test_mutable() {
+        assert_eq!(hash_member_id("name"), 0x0c9368b0);
+        let origin = hash_member_id("origin");
+        assert_eq!(origin, 0x03b1497c);
+
+        let descriptor = TopicDescriptor::from_type::<Config>();
+        assert_eq!(
+            descriptor.ops(),
+            &[
+                PLC,
+                PLM | 9,
+                10,
+                PLM | 10,
+                11,
+                PLM | 11,
+                origin,
+                PLM | 13,
+                2,
+                RTS,
+                ADR | DDS_OP_FLAG_KEY | DDS_OP_FLAG_MU | op_type(DDS_OP_VAL_4BY),
+                0,
+                RTS,
+                ADR | op_type(DDS_OP_VAL_STR),
+                8,
+                RTS,
+                ADR | op_type(DDS_OP_VAL_EXT),
+                16,
+                (3 << 16) + 7,
+                RTS,
+                ADR | DDS_OP_FLAG_KEY | DDS_OP_FLAG_MU | op_type(DDS_OP_VAL_2BY),
+                32,
+                RTS,
+                // Position
+                ADR | op_type(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                0,
+                ADR | op_type(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                8,
+                RTS,
+                KOF | 1,
+                10,
+                KOF | 1,
+                20,
+            ]
+        );
+        assert_eq!(descriptor.m_nops, 17);
+
+        // The key is serialized in the order of the member ids
+        let keys = descriptor
+            .keys()
+            .iter()
+            .map(|key| (key.name(), key.offset(), key.index()))
+            .collect::<Vec<_>>();
+        assert_eq!(keys, [("id", 28, 1), ("zone", 30, 0)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "members id and zone of struct")]
+    fn test_duplicate_member_id() {
+        StructDesc::new("Dup", 8, 4)
+            .member(MemberDesc::new("id", 0, u32::type_desc()).id(3))
+            .member(MemberDesc::new("zone", 4, u32::type_desc()).id(3))
+            .member_ids();
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[dds(appendable, extends = Status)]
+    struct Alarm {
+        status: Status,
+        message: String,
+    }
+
+    #[test]
+    fn test_inheritance() {
+        let descriptor = TopicDescriptor::from_type::<Alarm>();
+
+        // The members of the base come first, within the DHEADER of the
+        // derived struct
+        assert_eq!(
+            descriptor.ops(),
+            &[
+                DLC,
+                ADR | DDS_OP_FLAG_KEY | DDS_OP_FLAG_MU | op_type(DDS_OP_VAL_4BY),
+                0,
+                ADR | op_type(DDS_OP_VAL_1BY),
+                4,
+                ADR | op_type(DDS_OP_VAL_STR),
+                8,
+                RTS,
+                KOF | 1,
+                1,
+            ]
+        );
+        assert_eq!(descriptor.keys()[0].name(), "id");
+
+        let desc = Alarm::type_desc();
+        let desc = desc.as_struct().unwrap();
+        assert_eq!(desc.members.len(), 1);
+        assert_eq!(desc.base.as_ref().unwrap().desc().name, Status::type_name());
+        assert_eq!(desc.member_ids(), [0, 1, 2]);
+
+        let alarm = Alarm {
+            status: Status { id: 7, level: 2 },
+            message: "overheated".to_string(),
+        };
+        assert_eq!(alarm.extends().id, 7);
+        assert_eq!(Alarm::extends_name(), Status::type_name());
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "struct Derived and its base descriptor::tests::Status must have the same"
+    )]
+    fn test_inheritance_extensibility() {
+        let desc = StructDesc::new("Derived", 12, 4)
+            .base(0, Status::type_desc())
+            .member(MemberDesc::new("count", 8, u32::type_desc()));
+        OpsCompiler::default().compile(&desc);
+    }
+
+    #[test]
+    #[should_panic(expected = "members id and serial of struct Derived have the same id 0")]
+    fn test_inheritance_member_id() {
+        StructDesc::new("Derived", 12, 4)
+            .base(0, Status::type_desc())
+            .member(MemberDesc::new("serial", 8, u32::type_desc()).id(0))
+            .member_ids();
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[dds(type_name = "Sensors::Reading", topic = "sensors/temperature")]
+    struct Temperature {
+        celsius: f32,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    struct Wrapper<T: Clone + std::fmt::Debug + PartialEq> {
+        value: T,
+    }
+
+    #[derive(TopicType, Clone, Copy, Debug, PartialEq)]
+    #[dds(pod)]
+    #[repr(C)]
+    struct Vec3 {
+        x: f32,
+        y: f32,
+        z: f32,
+    }
+
+    #[derive(TopicType, Clone, Copy, Debug, PartialEq)]
+    #[dds(pod)]
+    #[repr(C)]
+    struct Pose {
+        #[dds(key)]
+        id: u32,
+        position: Vec3,
+        orientation: [f64; 4],
+    }
+
+    #[test]
+    fn test_pod() {
+        let descriptor = TopicDescriptor::from_pod_type::<Pose>();
+        assert_ne!(descriptor.flagset() & DDS_TOPIC_FIXED_SIZE, 0);
+        assert_ne!(descriptor.flagset() & DDS_TOPIC_FIXED_KEY, 0);
+        assert_eq!(descriptor.size() as usize, size_of::<Pose>());
+        assert_eq!(
+            descriptor.ops(),
+            &[
+                ADR | DDS_OP_FLAG_KEY | DDS_OP_FLAG_MU | op_type(DDS_OP_VAL_4BY),
+                offset_of!(Pose, id) as u32,
+                ADR | op_type(DDS_OP_VAL_EXT),
+                offset_of!(Pose, position) as u32,
+                (3 << 16) + 7,
+                ADR | op_type(DDS_OP_VAL_ARR) | op_subtype(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                offset_of!(Pose, orientation) as u32,
+                4,
+                RTS,
+                // Vec3
+                ADR | op_type(DDS_OP_VAL_4BY) | DDS_OP_FLAG_FP,
+                offset_of!(Vec3, x) as u32,
+                ADR | op_type(DDS_OP_VAL_4BY) | DDS_OP_FLAG_FP,
+                offset_of!(Vec3, y) as u32,
+                ADR | op_type(DDS_OP_VAL_4BY) | DDS_OP_FLAG_FP,
+                offset_of!(Vec3, z) as u32,
+                RTS,
+                // key: id
+                KOF | 1,
+                0,
+            ]
+        );
+        assert_eq!(Pose::topic_descriptor().flagset(), descriptor.flagset());
+        assert!(crate::ops::validate::<Pose>(&descriptor).is_ok());
+
+        // A packed struct has a member off its C offset
+        let packed = StructDesc::new("Packed", 5, 1)
+            .member(MemberDesc::new("flag", 0, u8::type_desc()))
+            .member(MemberDesc::new("count", 1, u32::type_desc()));
+        assert!(is_c_layout(&Pose::type_desc()));
+        assert!(!is_c_layout(&TypeDesc::Struct(packed)));
+        assert!(!is_c_layout(&Vehicle::type_desc()));
+
+        assert!(Pose::type_desc().is_fixed_size());
+        assert!(!Vehicle::type_desc().is_fixed_size());
+        assert_eq!(
+            TopicDescriptor::from_type::<Position>().flagset() & DDS_TOPIC_FIXED_SIZE,
+            0
+        );
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(Temperature::name(), "sensors/temperature");
+        assert_eq!(Temperature::type_name(), "Sensors::Reading");
+        let descriptor = TopicDescriptor::from_type::<Temperature>();
+        assert_eq!(descriptor.type_name(), "Sensors::Reading");
+
+        // The default names leave out the crate and the generic arguments
+        assert_eq!(Position::type_name(), "descriptor::tests::Position");
+        assert_eq!(Wrapper::<u32>::name(), "Wrapper");
+        assert!(!is_valid_type_name(Wrapper::<u32>::type_name()));
+
+        assert!(is_valid_type_name("HelloWorldData::Msg"));
+        assert!(!is_valid_type_name("HelloWorldData::"));
+        assert!(!is_valid_type_name("_Msg"));
+        assert!(is_valid_topic_name("rt/chatter"));
+        assert!(!is_valid_topic_name("1st"));
+        assert!(!is_valid_topic_name("a.b"));
+    }
+
+    #[test]
+    fn test_union() {
+        let descriptor = TopicDescriptor::from_type::<Reading>();
+        let ops = descriptor.ops();
+
+        assert_eq!(
+            &ops[..20],
+            &[
+                ADR | op_type(DDS_OP_VAL_4BY) | DDS_OP_FLAG_SGN,
+                0,
+                ADR | op_type(DDS_OP_VAL_EXT),
+                8,
+                (3 << 16) + 7,
+                ADR | op_type(DDS_OP_VAL_EXT),
+                24,
+                (3 << 16) + 21,
+                RTS,
+                // Value, the default case comes last
+                ADR | op_type(DDS_OP_VAL_UNI) | op_subtype(DDS_OP_VAL_4BY) | DDS_OP_FLAG_DEF,
+                0,
+                3,
+                (16 << 16) + 4,
+                JEQ4 | op_type(DDS_OP_VAL_4BY) | DDS_OP_FLAG_SGN,
+                1,
+                8,
+                0,
+                JEQ4 | op_type(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                2,
+                8,
+            ]
+        );
+        assert_eq!(
+            &ops[21..],
+            &[
+                JEQ4 | op_type(DDS_OP_VAL_ENU) | op_size(1),
+                7,
+                8,
+                1,
+                RTS,
+                // Small, the values of all cases are at the offset of the C
+                // union
+                ADR | op_type(DDS_OP_VAL_UNI) | op_subtype(DDS_OP_VAL_1BY) | DDS_OP_FLAG_SGN,
+                0,
+                3,
+                (16 << 16) + 4,
+                JEQ4 | op_type(DDS_OP_VAL_1BY),
+                0,
+                8,
+                0,
+                JEQ4 | op_type(DDS_OP_VAL_4BY),
+                1,
+                8,
+                0,
+                JEQ4 | op_type(DDS_OP_VAL_EXT) | 5,
+                2,
+                8,
+                0,
+                RTS,
+                // Position
+                ADR | op_type(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                0,
+                ADR | op_type(DDS_OP_VAL_8BY) | DDS_OP_FLAG_FP,
+                8,
+                RTS,
+            ]
+        );
+        assert_eq!(descriptor.m_nops, 17);
+        assert_ne!(descriptor.flagset() & DDS_TOPIC_CONTAINS_UNION, 0);
+    }
+
+    #[test]
+    fn test_union_roundtrip() {
+        let sample = Reading {
+            id: 7,
+            value: Value::Real(1.5),
+            small: Small::Point(Position { x: 1.0, y: -2.0 }),
+        };
+        for version in [XcdrVersion::Xcdr1, XcdrVersion::Xcdr2] {
+            let cdr = stream_roundtrip(&sample, version);
+
+            let mut expected = Vec::new();
+            expected.extend(7i32.to_ne_bytes());
+            expected.extend(2u32.to_ne_bytes());
+            expected.extend(1.5f64.to_ne_bytes());
+            expected.push(2);
+            // XCDR1 aligns doubles to 8 bytes, XCDR2 to 4 bytes
+            let align = if version == XcdrVersion::Xcdr1 { 8 } else { 4 };
+            expected.resize(expected.len().next_multiple_of(align), 0);
+            expected.extend(1.0f64.to_ne_bytes());
+            expected.extend((-2.0f64).to_ne_bytes());
+            assert_eq!(cdr, expected);
+        }
+
+        let sample = Reading {
+            id: -1,
+            value: Value::Level(Level::High),
+            small: Small::Word(0xdead),
+        };
+        let cdr = stream_roundtrip(&sample, XcdrVersion::Xcdr2);
+        let mut expected = Vec::new();
+        expected.extend((-1i32).to_ne_bytes());
+        expected.extend(7u32.to_ne_bytes());
+        expected.push(1);
+        expected.push(1);
+        expected.resize(12, 0);
+        expected.extend(0xdeadu32.to_ne_bytes());
+        assert_eq!(cdr, expected);
+
+        // An unknown label selects the default case or rejects the sample
+        let type_desc = Reading::type_desc();
+        let from_payload = |cdr: &[u8]| {
+            xcdr::from_payload::<Reading>(
+                cdr,
+                Some(&type_desc),
+                XcdrVersion::Xcdr2,
+                Endianness::NATIVE,
+            )
+        };
+        let mut unknown = cdr.clone();
+        unknown[4..8].copy_from_slice(&9u32.to_ne_bytes());
+        assert_eq!(from_payload(&unknown), Ok(sample));
+        let mut unknown = cdr;
+        unknown[9] = 5;
+        assert_eq!(from_payload(&unknown), Err(XcdrError::Discriminator(5)));
+    }
+}
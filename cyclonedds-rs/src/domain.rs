@@ -12,10 +12,11 @@ use crate::core::FetchableEntity;
 use crate::qos::Qos;
 use crate::{
     core::{Entity, EntityParticipantError, ReturnCodes},
+    descriptor::Pod,
     internal::InstanceHandle,
     publisher::Publisher,
     subscriber::Subscriber,
-    topic::{AnyTopic, Topic, TopicType},
+    topic::{AnyTopic, SerdeTopic, Topic, TopicType},
 };
 
 /// A safe wrapper around a Cyclone DDS Participant.
@@ -124,10 +125,16 @@ impl DomainParticipant {
         Publisher::new(self)
     }
 
-    pub fn topic<T: TopicType>(&mut self) -> Result<Topic<T>, ReturnCodes> {
+    /// Creates a topic of a plain old data type, see [Topic::new].
+    pub fn topic<T: TopicType + Pod>(&mut self) -> Result<Topic<T>, ReturnCodes> {
         Topic::new(self)
     }
 
+    /// Creates a topic of a serde type, see [Topic::with_sertype].
+    pub fn serde_topic<T: SerdeTopic>(&mut self) -> Result<Topic<T>, ReturnCodes> {
+        Topic::with_sertype(self)
+    }
+
     /// Creates a new `AnyTopic` associated with this `DomainParticipant`.
     ///
     /// # Returns
@@ -292,97 +299,19 @@ impl TryFrom<File> for DomainParticipantConfigParams {
 
 #[cfg(test)]
 mod test {
-    use std::{mem::offset_of, time::Duration};
+    use std::time::Duration;
 
-    use serde::Serialize;
+    use serde::{Deserialize, Serialize};
 
-    use crate::{
-        core::Entity,
-        domain::DomainParticipant,
-        internal::{KeyDescriptor, TopicDescriptor, TypeMetaSer},
-        topic::TopicType,
-    };
+    use crate::{core::Entity, domain::DomainParticipant, topic::TopicType};
 
-    #[derive(Default, Clone, Serialize, PartialEq, Debug)]
+    #[derive(TopicType, Default, Clone, Serialize, Deserialize, PartialEq, Debug)]
     struct HelloWorldData {
+        #[dds(key)]
         userID: i32,
         data: String,
     }
 
-    impl TopicType for HelloWorldData {
-        fn name() -> &'static str {
-            std::any::type_name::<Self>().rsplit("::").next().unwrap()
-        }
-
-        fn type_name() -> &'static str {
-            // Get the fully qualified name and remove the crate name.
-            let full = std::any::type_name::<Self>();
-            if let Some((_, rest)) = full.split_once("::") {
-                rest
-            } else {
-                full
-            }
-        }
-
-        fn topic_descriptor() -> crate::internal::TopicDescriptor {
-            crate::internal::TopicDescriptor {
-                m_size: 32,
-                m_align: 0,
-                m_flagset: 1 << 6,
-                m_nkeys: 1,
-                m_typename: String::from("HelloWorldData_Msg"),
-                m_keys: vec![KeyDescriptor {
-                    name: "userID".to_string(),
-                    m_offset: 5,
-                    index: 0,
-                }],
-                m_nops: 3,
-                m_ops: vec![],
-                m_meta: "".to_string(),
-                type_information: vec![
-                    cyclonedds_sys::dds_stream_opcode::DDS_OP_ADR as u32
-                        | cyclonedds_sys::DDS_OP_FLAG_KEY
-                        | cyclonedds_sys::DDS_OP_FLAG_MU
-                        | cyclonedds_sys::dds_stream_typecode_primary::DDS_OP_TYPE_4BY as u32
-                        | cyclonedds_sys::DDS_OP_FLAG_SGN,
-                    offset_of!(HelloWorldData, userID) as u32,
-                    cyclonedds_sys::dds_stream_opcode::DDS_OP_ADR as u32
-                        | cyclonedds_sys::idl::dds_stream_typecode_primary::DDS_OP_TYPE_STR as u32,
-                    // Note: In the C file the second op uses the offset of `message`,
-                    // and then a terminal op DDS_OP_RTS is appended.
-                    offset_of!(HelloWorldData, data) as u32,
-                    cyclonedds_sys::dds_stream_opcode::DDS_OP_RTS as u32,
-                ],
-                type_mapping: TypeMetaSer {
-                    data: vec![
-                        0x4c, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0xf1, 0x51, 0x01, 0x00,
-                        0x6e, 0x42, 0x14, 0x9e, 0xb1, 0x41, 0xa0, 0xd7, 0x25, 0x94, 0xc6, 0x50,
-                        0xd7, 0x3c, 0x00, 0x34, 0x00, 0x00, 0x00, 0xf1, 0x51, 0x01, 0x00, 0x01,
-                        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x24, 0x00, 0x00, 0x00, 0x02,
-                        0x00, 0x00, 0x00, 0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x31,
-                        0x00, 0x04, 0x58, 0x5c, 0x95, 0x70, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x01,
-                        0x00, 0x00, 0x00, 0x01, 0x00, 0x70, 0x00, 0x78, 0xe7, 0x31, 0x02, 0x7a,
-                        0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0xf2, 0xb1, 0xa3, 0xb1, 0xfb,
-                        0x8b, 0x1a, 0x60, 0x51, 0x60, 0x14, 0x29, 0x7c, 0x3b, 0x8a, 0x00, 0x62,
-                        0x00, 0x00, 0x00, 0xf2, 0x51, 0x01, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00,
-                        0x00, 0x00, 0x00, 0x14, 0x00, 0x00, 0x00, 0x48, 0x65, 0x6c, 0x6c, 0x6f,
-                        0x57, 0x6f, 0x72, 0x6c, 0x64, 0x44, 0x61, 0x74, 0x61, 0x3a, 0x3a, 0x4d,
-                        0x73, 0x67, 0x00, 0x3a, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x15,
-                        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x31, 0x00, 0x04, 0x00, 0x07,
-                        0x00, 0x00, 0x00, 0x75, 0x73, 0x65, 0x72, 0x49, 0x44, 0x00, 0x00, 0x00,
-                        0x00, 0x00, 0x00, 0x16, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01,
-                        0x00, 0x70, 0x00, 0x08, 0x00, 0x00, 0x00, 0x6d, 0x65, 0x73, 0x73, 0x61,
-                        0x67, 0x65, 0x00, 0x00, 0x00, 0x00, 0x00, 0x22, 0x00, 0x00, 0x00, 0x01,
-                        0x00, 0x00, 0x00, 0xf2, 0xb1, 0xa3, 0xb1, 0xfb, 0x8b, 0x1a, 0x60, 0x51,
-                        0x60, 0x14, 0x29, 0x7c, 0x3b, 0x8a, 0xf1, 0x6e, 0x42, 0x14, 0x9e, 0xb1,
-                        0x41, 0xa0, 0xd7, 0x25, 0x94, 0xc6, 0x50, 0xd7, 0x3c,
-                    ],
-                },
-                restrict_data_representation: 0,
-            }
-        }
-    }
-
     #[test]
     fn test_participant() {
         let participant = super::DomainParticipant::new(0).expect("Unable to create participant");
@@ -411,12 +340,9 @@ mod test {
         println!("Creating Participant");
         let mut participant = DomainParticipant::default();
 
-        
-
-
         println!("Created Participant");
         let topic = participant
-            .topic::<HelloWorldData>()
+            .serde_topic::<HelloWorldData>()
             .expect("Unable to create topic");
         drop(topic);
         drop(participant);
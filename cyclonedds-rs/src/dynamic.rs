@@ -40,13 +40,17 @@ pub enum TypeSpecKind {
     KindPrimitive,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum TypeExtensibility {
+    #[default]
     Final,
     Appendable,
     Mutable,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum TypeAutoid {
+    #[default]
     Sequential,
     Hash,
 }
@@ -107,8 +111,20 @@ impl DynamicType {
         }
     }
 
+    /// Set the bit bound (`@bit_bound`) of an enumeration or bitmask type,
+    /// the number of bits used to represent its values.
     pub fn set_bit_bound(&mut self, bit_bound: u16) -> Result<(), ReturnCodes> {
-        todo!("not implemented")
+        let return_code;
+        unsafe {
+            return_code =
+                cyclonedds_sys::dds_dynamic_type_set_bit_bound(&mut self.dynamic_type, bit_bound);
+        };
+
+        if return_code != 0 {
+            Err(ReturnCodes::from(return_code))
+        } else {
+            Ok(())
+        }
     }
 
     pub fn set_nested(&mut self, nested: bool) -> Result<(), ReturnCodes> {
@@ -126,28 +142,73 @@ impl DynamicType {
         todo!("not implemented")
     }
 
+    /// Add a flag to a bitmask type at the given bit position.
     pub fn add_bitmask_field(&mut self, name: &str, position: u16) -> Result<(), ReturnCodes> {
-        todo!("not implemented")
+        let name = CString::new(name).map_err(|_| ReturnCodes::BadParameter)?;
+        let return_code;
+        unsafe {
+            return_code = cyclonedds_sys::dds_dynamic_type_add_bitmask_field(
+                &mut self.dynamic_type,
+                name.as_ptr(),
+                position,
+            );
+        };
+
+        if return_code != 0 {
+            Err(ReturnCodes::from(return_code))
+        } else {
+            Ok(())
+        }
     }
 
     pub fn member_set_key(&mut self, member_id: u32, is_key: bool) -> Result<(), ReturnCodes> {
         todo!("not implemented")
     }
 
+    /// Set the optional flag (`@optional`) of a member of a structure type.
+    /// An optional member may be absent from a sample.
     pub fn member_set_optional(
         &mut self,
         member_id: u32,
         is_optional: bool,
     ) -> Result<(), ReturnCodes> {
-        todo!("not implemented")
+        let return_code;
+        unsafe {
+            return_code = cyclonedds_sys::dds_dynamic_member_set_optional(
+                &mut self.dynamic_type,
+                member_id,
+                is_optional,
+            );
+        };
+
+        if return_code != 0 {
+            Err(ReturnCodes::from(return_code))
+        } else {
+            Ok(())
+        }
     }
 
+    /// Set the external flag (`@external`) of a member of a structure or
+    /// union type. An external member is stored behind a pointer.
     pub fn member_set_external(
         &mut self,
         member_id: u32,
         is_external: bool,
     ) -> Result<(), ReturnCodes> {
-        todo!("not implemented")
+        let return_code;
+        unsafe {
+            return_code = cyclonedds_sys::dds_dynamic_member_set_external(
+                &mut self.dynamic_type,
+                member_id,
+                is_external,
+            );
+        };
+
+        if return_code != 0 {
+            Err(ReturnCodes::from(return_code))
+        } else {
+            Ok(())
+        }
     }
 
     pub fn member_set_hashid(
@@ -174,7 +235,7 @@ struct DynamicTypeDescriptor {
     name: String,
     base_type: Option<cyclonedds_sys::dds_dynamic_type_spec>,
     discriminator_type: Option<cyclonedds_sys::dds_dynamic_type_spec>,
-    num_bounds: u32, 
+    num_bounds: u32,
 }
 
 struct DynamicMemberDescriptor;
@@ -0,0 +1,554 @@
+//! Export of Rust topic types as IDL.
+//!
+//! [IdlFile] writes the IDL definitions of a set of types and of all the
+//! types they depend on, so that the Rust definition of a topic type can be
+//! the single source of truth for peers written in other languages:
+//!
+//! ```ignore
+//! use cyclonedds_rs::idl::to_idl;
+//!
+//! std::fs::write("HelloWorldData.idl", to_idl::<HelloWorldData::Msg>())?;
+//! ```
+//!
+//! Every definition is placed in the modules of its scoped name, e.g.
+//! `HelloWorldData::Msg` becomes `struct Msg` in `module HelloWorldData`.
+//! Structs and unions carry their extensibility, keys, member ids,
+//! `@optional` and `@external` members and bounds. A binary that writes the
+//! IDL of a set of types can be declared with [crate::export_idl].
+use std::fmt::{self, Display, Write};
+
+use crate::descriptor::{
+    BitmaskDesc, CaseDesc, DdsType, EnumDesc, MemberDesc, StructDesc, TypeAutoid, TypeDesc,
+    TypeExtensibility, UnionDesc,
+};
+
+/// The IDL definitions of a set of types.
+#[derive(Debug, Clone, Default)]
+pub struct IdlFile {
+    types: Vec<TypeDesc>,
+}
+
+impl IdlFile {
+    /// Creates an empty file.
+    pub fn new() -> IdlFile {
+        IdlFile::default()
+    }
+
+    /// Adds a type and the types it depends on.
+    pub fn add<T: DdsType>(self) -> IdlFile {
+        self.add_desc(T::type_desc())
+    }
+
+    /// Adds a described type and the types it depends on.
+    pub fn add_desc(mut self, desc: TypeDesc) -> IdlFile {
+        self.types.push(desc);
+        self
+    }
+
+    /// Returns the constructed types in an order where every type comes
+    /// after the types it depends on, each type once.
+    fn definitions(&self) -> Vec<&TypeDesc> {
+        let mut definitions = Vec::new();
+        for desc in &self.types {
+            collect(desc, &mut definitions);
+        }
+        definitions
+    }
+}
+
+fn collect<'a>(desc: &'a TypeDesc, definitions: &mut Vec<&'a TypeDesc>) {
+    match desc {
+        TypeDesc::Struct(desc) => {
+            if let Some(base) = &desc.base {
+                collect(&base.type_desc, definitions);
+            }
+            for member in &desc.members {
+                collect(&member.type_desc, definitions);
+            }
+        }
+        TypeDesc::Union(desc) => {
+            for case in &desc.cases {
+                collect(&case.type_desc, definitions);
+            }
+        }
+        TypeDesc::Sequence(seq) => collect(&seq.element, definitions),
+        TypeDesc::Array(array) => collect(&array.element, definitions),
+        TypeDesc::Map(map) => {
+            collect(&map.key, definitions);
+            collect(&map.value, definitions);
+        }
+        TypeDesc::Optional(inner) | TypeDesc::External(inner) => collect(inner, definitions),
+        TypeDesc::Primitive(_)
+        | TypeDesc::String
+        | TypeDesc::BoundedString(_)
+        | TypeDesc::Enum(_)
+        | TypeDesc::Bitmask(_) => {}
+    }
+    if let Some(name) = desc.name() {
+        if !definitions.iter().any(|other| other.name() == Some(name)) {
+            definitions.push(desc);
+        }
+    }
+}
+
+impl Display for IdlFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut w = IdlWriter {
+            out: String::new(),
+            modules: Vec::new(),
+        };
+        for (i, desc) in self.definitions().into_iter().enumerate() {
+            let name = desc.name().unwrap();
+            let (modules, local) = match name.rsplit_once("::") {
+                Some((modules, local)) => (modules.split("::").collect(), local),
+                None => (Vec::new(), name),
+            };
+            w.enter(&modules, i > 0);
+            match desc {
+                TypeDesc::Struct(desc) => w.structure(desc, local),
+                TypeDesc::Enum(desc) => w.enumeration(desc, local),
+                TypeDesc::Bitmask(desc) => w.bitmask(desc, local),
+                TypeDesc::Union(desc) => w.union(desc, local),
+                _ => unreachable!(),
+            }
+        }
+        w.enter(&[], false);
+        f.write_str(&w.out)
+    }
+}
+
+/// Returns the IDL definitions of `T` and of the types it depends on.
+pub fn to_idl<T: DdsType>() -> String {
+    IdlFile::new().add::<T>().to_string()
+}
+
+struct IdlWriter<'a> {
+    out: String,
+    /// The open modules
+    modules: Vec<&'a str>,
+}
+
+impl<'a> IdlWriter<'a> {
+    fn line(&mut self, line: &str) {
+        if !line.is_empty() {
+            for _ in 0..self.modules.len() {
+                self.out.push_str("  ");
+            }
+        }
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    /// Closes and opens modules until `modules` are open, separating the
+    /// next definition from the previous one by an empty line.
+    fn enter(&mut self, modules: &[&'a str], separate: bool) {
+        let common = self
+            .modules
+            .iter()
+            .zip(modules)
+            .take_while(|(open, module)| open == module)
+            .count();
+        while self.modules.len() > common {
+            self.modules.pop();
+            self.line("};");
+        }
+        if separate {
+            self.line("");
+        }
+        for module in &modules[common..] {
+            self.line(&format!("module {} {{", module));
+            self.modules.push(module);
+        }
+    }
+
+    fn structure(&mut self, desc: &StructDesc, name: &str) {
+        let mut annotations = vec![extensibility(desc.extensibility)];
+        if desc.autoid == TypeAutoid::Hash {
+            annotations.push("@autoid(HASH)");
+        }
+        self.line(&annotations.join(" "));
+        match &desc.base {
+            Some(base) => self.line(&format!(
+                "struct {} : {} {{",
+                name,
+                type_spec(&base.type_desc)
+            )),
+            None => self.line(&format!("struct {} {{", name)),
+        }
+        for member in &desc.members {
+            self.member(member);
+        }
+        self.line("};");
+    }
+
+    fn member(&mut self, member: &MemberDesc) {
+        let mut annotations = Vec::new();
+        if member.key {
+            annotations.push("@key".to_string());
+        }
+        if let Some(id) = member.id {
+            annotations.push(format!("@id({})", id));
+        }
+        let mut desc = &member.type_desc;
+        if let TypeDesc::Optional(inner) = desc {
+            annotations.push("@optional".to_string());
+            desc = inner;
+        }
+        if let TypeDesc::External(inner) = desc {
+            annotations.push("@external".to_string());
+            desc = inner;
+        }
+        annotations.push(declaration(desc, &member.name));
+        self.line(&format!("  {};", annotations.join(" ")));
+    }
+
+    fn enumeration(&mut self, desc: &EnumDesc, name: &str) {
+        if desc.bit_bound != 32 {
+            self.line(&format!("@bit_bound({})", desc.bit_bound));
+        }
+        self.line(&format!("enum {} {{", name));
+        // Values are only annotated where they differ from the previous
+        // value plus one
+        let mut next = 0;
+        for (i, enumerator) in desc.enumerators.iter().enumerate() {
+            let separator = if i + 1 < desc.enumerators.len() {
+                ","
+            } else {
+                ""
+            };
+            let value = if enumerator.value != next {
+                format!("@value({}) ", enumerator.value)
+            } else {
+                String::new()
+            };
+            next = enumerator.value + 1;
+            self.line(&format!("  {}{}{}", value, enumerator.name, separator));
+        }
+        self.line("};");
+    }
+
+    fn bitmask(&mut self, desc: &BitmaskDesc, name: &str) {
+        if desc.bit_bound != 32 {
+            self.line(&format!("@bit_bound({})", desc.bit_bound));
+        }
+        self.line(&format!("bitmask {} {{", name));
+        // Positions are only annotated where they differ from the previous
+        // position plus one
+        let mut next = 0;
+        for (i, flag) in desc.flags.iter().enumerate() {
+            let separator = if i + 1 < desc.flags.len() { "," } else { "" };
+            let position = if flag.position != next {
+                format!("@position({}) ", flag.position)
+            } else {
+                String::new()
+            };
+            next = flag.position + 1;
+            self.line(&format!("  {}{}{}", position, flag.name, separator));
+        }
+        self.line("};");
+    }
+
+    fn union(&mut self, desc: &UnionDesc, name: &str) {
+        self.line(extensibility(desc.extensibility));
+        self.line(&format!(
+            "union {} switch ({}) {{",
+            name,
+            desc.discriminator.idl_name()
+        ));
+        for case in &desc.cases {
+            self.case(case);
+        }
+        self.line("};");
+    }
+
+    fn case(&mut self, case: &CaseDesc) {
+        if case.default {
+            self.line("  default:");
+        } else {
+            self.line(&format!("  case {}:", case.label));
+        }
+        let declaration = match &case.type_desc {
+            TypeDesc::External(inner) => format!("@external {}", declaration(inner, &case.name)),
+            desc => declaration(desc, &case.name),
+        };
+        self.line(&format!("    {};", declaration));
+    }
+}
+
+fn extensibility(extensibility: TypeExtensibility) -> &'static str {
+    match extensibility {
+        TypeExtensibility::Final => "@final",
+        TypeExtensibility::Appendable => "@appendable",
+        TypeExtensibility::Mutable => "@mutable",
+    }
+}
+
+/// Declaration of a member or case, arrays are part of the declarator.
+fn declaration(desc: &TypeDesc, name: &str) -> String {
+    match desc {
+        TypeDesc::Array(array) => {
+            let mut declaration = format!("{} {}", type_spec(&array.element), name);
+            for dim in &array.dims {
+                write!(declaration, "[{}]", dim).unwrap();
+            }
+            declaration
+        }
+        desc => format!("{} {}", type_spec(desc), name),
+    }
+}
+
+pub(crate) fn type_spec(desc: &TypeDesc) -> String {
+    match desc {
+        TypeDesc::Primitive(kind) => kind.idl_name().to_string(),
+        TypeDesc::String => "string".to_string(),
+        TypeDesc::BoundedString(bound) => format!("string<{}>", bound),
+        TypeDesc::Sequence(seq) => match seq.bound {
+            Some(bound) => format!("sequence<{}, {}>", type_spec(&seq.element), bound),
+            None => format!("sequence<{}>", type_spec(&seq.element)),
+        },
+        TypeDesc::Map(map) => match map.bound {
+            Some(bound) => format!(
+                "map<{}, {}, {}>",
+                type_spec(&map.key),
+                type_spec(&map.value),
+                bound
+            ),
+            None => format!("map<{}, {}>", type_spec(&map.key), type_spec(&map.value)),
+        },
+        TypeDesc::Struct(_) | TypeDesc::Enum(_) | TypeDesc::Bitmask(_) | TypeDesc::Union(_) => {
+            desc.name().unwrap().to_string()
+        }
+        // IDL has no anonymous array types, the derive does not produce
+        // sequences of arrays or optional collection elements.
+        TypeDesc::Array(_) | TypeDesc::Optional(_) | TypeDesc::External(_) => {
+            panic!(
+                "{:?} can not be expressed as an IDL type specification",
+                desc
+            )
+        }
+    }
+}
+
+/// Declares a `main` that writes the IDL of the given types to the file
+/// named by the first argument, or to standard output without arguments.
+///
+/// ```ignore
+/// cyclonedds_rs::export_idl!(HelloWorldData::Msg, Shapes::Shape);
+/// ```
+#[macro_export]
+macro_rules! export_idl {
+    ($($ty:ty),+ $(,)?) => {
+        fn main() {
+            let idl = $crate::idl::IdlFile::new()$(.add::<$ty>())+.to_string();
+            match ::std::env::args_os().nth(1) {
+                Some(path) => {
+                    if let Err(err) = ::std::fs::write(&path, idl) {
+                        eprintln!("failed to write {}: {}", path.to_string_lossy(), err);
+                        ::std::process::exit(1);
+                    }
+                }
+                None => print!("{}", idl),
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{to_idl, IdlFile};
+    use crate::bounded::{BoundedMap, BoundedString, BoundedVec};
+    use crate::topic::TopicType;
+
+    mod shapes {
+        use crate::topic::TopicType;
+
+        #[derive(TopicType, Clone, Copy, Debug, PartialEq)]
+        #[repr(u32)]
+        #[allow(dead_code)]
+        #[dds(bit_bound = 8)]
+        pub enum Color {
+            Red,
+            Green,
+        }
+
+        #[derive(TopicType, Clone, Debug, PartialEq)]
+        pub struct Point {
+            pub x: i32,
+            pub y: i32,
+        }
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[repr(C, u8)]
+    #[allow(dead_code)]
+    enum Fill {
+        Solid(shapes::Color) = 1,
+        #[dds(default)]
+        Pattern(Box<shapes::Point>) = 2,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[dds(appendable)]
+    struct Shape {
+        #[dds(key)]
+        id: u32,
+        name: BoundedString<16>,
+        points: BoundedVec<shapes::Point, 8>,
+        #[dds(id = 10)]
+        fill: Option<Fill>,
+        corners: [[f32; 2]; 4],
+    }
+
+    #[test]
+    fn test_to_idl() {
+        let expected = "\
+module idl {
+  module tests {
+    module shapes {
+      @final
+      struct Point {
+        long x;
+        long y;
+      };
+    };
+  };
+};
+";
+        assert_eq!(to_idl::<shapes::Point>(), expected);
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let expected = "\
+module idl {
+  module tests {
+    module shapes {
+      @final
+      struct Point {
+        long x;
+        long y;
+      };
+
+      @bit_bound(8)
+      enum Color {
+        Red,
+        Green
+      };
+    };
+
+    @final
+    union Fill switch (uint8) {
+      case 1:
+        idl::tests::shapes::Color Solid;
+      default:
+        @external idl::tests::shapes::Point Pattern;
+    };
+
+    @appendable
+    struct Shape {
+      @key unsigned long id;
+      string<16> name;
+      sequence<idl::tests::shapes::Point, 8> points;
+      @id(10) @optional idl::tests::Fill fill;
+      float corners[4][2];
+    };
+  };
+};
+";
+        assert_eq!(IdlFile::new().add::<Shape>().to_string(), expected);
+        // Types are written once
+        let idl = IdlFile::new()
+            .add::<shapes::Point>()
+            .add::<Shape>()
+            .to_string();
+        assert_eq!(idl.matches("struct Point").count(), 1);
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    struct Inventory {
+        counts: BTreeMap<String, u32>,
+        places: BoundedMap<i16, shapes::Point, 4>,
+    }
+
+    #[test]
+    fn test_map() {
+        let expected = "\
+module idl {
+  module tests {
+    module shapes {
+      @final
+      struct Point {
+        long x;
+        long y;
+      };
+    };
+
+    @final
+    struct Inventory {
+      map<string, unsigned long> counts;
+      map<short, idl::tests::shapes::Point, 4> places;
+    };
+  };
+};
+";
+        assert_eq!(to_idl::<Inventory>(), expected);
+    }
+
+    #[derive(TopicType, Clone, Copy, Debug, PartialEq)]
+    #[dds(bitmask(READ, WRITE, EXECUTE = 4))]
+    #[repr(transparent)]
+    struct Permissions(u16);
+
+    #[test]
+    fn test_bitmask() {
+        let expected = "\
+module idl {
+  module tests {
+    @bit_bound(16)
+    bitmask Permissions {
+      READ,
+      WRITE,
+      @position(4) EXECUTE
+    };
+  };
+};
+";
+        assert_eq!(to_idl::<Permissions>(), expected);
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[dds(appendable)]
+    struct Vehicle {
+        #[dds(key)]
+        id: u32,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[dds(appendable, extends = Vehicle)]
+    struct Car {
+        vehicle: Vehicle,
+        doors: u8,
+    }
+
+    #[test]
+    fn test_inheritance() {
+        let expected = "\
+module idl {
+  module tests {
+    @appendable
+    struct Vehicle {
+      @key unsigned long id;
+    };
+
+    @appendable
+    struct Car : idl::tests::Vehicle {
+      uint8 doors;
+    };
+  };
+};
+";
+        assert_eq!(to_idl::<Car>(), expected);
+    }
+}
@@ -1,14 +1,20 @@
 use core::slice;
 use std::{
+    any::TypeId,
+    collections::HashMap,
     ffi::{CStr, CString},
     io::Read,
     mem::{self, offset_of},
     process::{Command, Stdio},
     ptr,
+    sync::{Mutex, OnceLock},
 };
 
 use crate::topic::{MetaSer, TopicType};
 
+/// Handle of an instance, equal for the samples of an instance within a
+/// process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct InstanceHandle {
     pub(super) handle: cyclonedds_sys::dds_instance_handle_t,
 }
@@ -87,7 +93,7 @@ pub struct TopicDescriptor {
     pub(crate) m_meta: String,
     /// XCDR2 serialized TypeInformation, only present if flag
     /// [cyclonedds_sys::DDS_TOPIC_XTYPES_METADATA] is set
-    pub(crate) type_information: Vec<u32>,
+    pub(crate) type_information: TypeMetaSer,
     /// XCDR2 serialized TypeMapping: maps type-id to type object and minimal
     /// to complete type id, only present if flag
     /// [cyclonedds_sys::DDS_TOPIC_XTYPES_METADATA] is set
@@ -111,65 +117,174 @@ impl From<cyclonedds_sys::dds_topic_descriptor_t> for TopicDescriptor {
                     .unwrap()
                     .to_string()
             },
-            m_keys: unsafe {
-                slice::from_raw_parts(topic_descriptor.m_keys, topic_descriptor.m_nkeys as usize)
-                    .to_vec()
+            m_keys: if topic_descriptor.m_keys.is_null() {
+                Vec::new()
+            } else {
+                unsafe {
+                    slice::from_raw_parts(
+                        topic_descriptor.m_keys,
+                        topic_descriptor.m_nkeys as usize,
+                    )
                     .iter()
                     .map(|k| KeyDescriptor::from(*k))
                     .collect()
+                }
             },
             m_nops: topic_descriptor.m_nops,
             m_ops: unsafe {
                 slice::from_raw_parts(topic_descriptor.m_ops, topic_descriptor.m_nops as usize)
                     .to_vec()
             },
-            m_meta: unsafe {
-                CStr::from_ptr(topic_descriptor.m_typename)
-                    .to_str()
-                    .unwrap()
-                    .to_string()
+            m_meta: if topic_descriptor.m_meta.is_null() {
+                String::new()
+            } else {
+                unsafe {
+                    CStr::from_ptr(topic_descriptor.m_meta)
+                        .to_str()
+                        .unwrap()
+                        .to_string()
+                }
             },
-            type_information: todo!(
-                "topic_descriptor.type_information has not been implemented yet"
-            ),
+            type_information: topic_descriptor.type_information.into(),
             type_mapping: topic_descriptor.type_mapping.into(),
             restrict_data_representation: topic_descriptor.restrict_data_representation,
         }
     }
 }
 
-impl From<TopicDescriptor> for cyclonedds_sys::dds_topic_descriptor_t {
-    fn from(topic_descriptor: TopicDescriptor) -> Self {
-        cyclonedds_sys::dds_topic_descriptor_t {
+/// A `dds_topic_descriptor_t` together with the storage its pointers refer
+/// to: the type name, the key descriptors and their names, the ops and the
+/// type meta data. The C descriptor is valid for as long as this value
+/// lives, moving it does not move the heap storage.
+pub struct OwnedTopicDescriptor {
+    descriptor: cyclonedds_sys::dds_topic_descriptor_t,
+    source: TopicDescriptor,
+    _typename: CString,
+    _meta: CString,
+    _key_names: Vec<CString>,
+    _keys: Vec<cyclonedds_sys::dds_key_descriptor_t>,
+    _ops: Vec<u32>,
+    _type_information: Vec<u8>,
+    _type_mapping: Vec<u8>,
+}
+
+// The raw pointers only refer to the storage owned by the value, which is
+// never modified.
+unsafe impl Send for OwnedTopicDescriptor {}
+unsafe impl Sync for OwnedTopicDescriptor {}
+
+impl OwnedTopicDescriptor {
+    /// Copies `topic_descriptor` into storage owned by the C descriptor.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the type name, the meta data or a key name contains a nul
+    /// byte.
+    pub fn new(topic_descriptor: &TopicDescriptor) -> OwnedTopicDescriptor {
+        let typename = CString::new(topic_descriptor.m_typename.as_str())
+            .expect("type names can not contain nul bytes");
+        let meta = CString::new(topic_descriptor.m_meta.as_str())
+            .expect("the meta data can not contain nul bytes");
+        let key_names = topic_descriptor
+            .m_keys
+            .iter()
+            .map(|key| {
+                CString::new(key.name.as_str()).expect("key names can not contain nul bytes")
+            })
+            .collect::<Vec<_>>();
+        let keys = topic_descriptor
+            .m_keys
+            .iter()
+            .zip(&key_names)
+            .map(|(key, name)| cyclonedds_sys::dds_key_descriptor_t {
+                m_name: name.as_ptr(),
+                m_offset: key.m_offset,
+                m_idx: key.index,
+            })
+            .collect::<Vec<_>>();
+        let ops = topic_descriptor.m_ops.clone();
+        let type_information = topic_descriptor.type_information.data.clone();
+        let type_mapping = topic_descriptor.type_mapping.data.clone();
+
+        let descriptor = cyclonedds_sys::dds_topic_descriptor_t {
             m_size: topic_descriptor.m_size,
             m_align: topic_descriptor.m_align,
             m_flagset: topic_descriptor.m_flagset,
             m_nkeys: topic_descriptor.m_nkeys,
-            m_typename: CString::new(topic_descriptor.m_typename)
-                .expect("Unable to convert typename to c string")
-                .as_c_str()
-                .as_ptr(),
-            m_keys: topic_descriptor
-                .m_keys
-                .iter()
-                .map(|k| k.clone().into())
-                .collect::<Vec<cyclonedds_sys::dds_key_descriptor_t>>()
-                .as_slice()
-                .as_ptr(),
-            m_nops: topic_descriptor.m_nops,
-            m_ops: topic_descriptor.m_ops.as_slice().as_ptr(),
-            m_meta: CString::new(topic_descriptor.m_meta)
-                .expect("Unable to convert typename to c string")
-                .as_c_str()
-                .as_ptr(),
-            type_information: cyclonedds_sys::dds_type_meta_ser {
-                data: topic_descriptor.type_information.as_ptr() as *const u8,
-                sz: topic_descriptor.type_information.len() as u32,
+            m_typename: typename.as_ptr(),
+            m_keys: if keys.is_empty() {
+                ptr::null()
+            } else {
+                keys.as_ptr()
             },
-            type_mapping: topic_descriptor.type_mapping.into(),
+            m_nops: topic_descriptor.m_nops,
+            m_ops: ops.as_ptr(),
+            m_meta: meta.as_ptr(),
+            type_information: meta_ser(&type_information),
+            type_mapping: meta_ser(&type_mapping),
             restrict_data_representation: topic_descriptor.restrict_data_representation,
+        };
+        OwnedTopicDescriptor {
+            descriptor,
+            source: topic_descriptor.clone(),
+            _typename: typename,
+            _meta: meta,
+            _key_names: key_names,
+            _keys: keys,
+            _ops: ops,
+            _type_information: type_information,
+            _type_mapping: type_mapping,
         }
     }
+
+    /// Returns the C descriptor, which borrows from `self`.
+    pub fn as_c(&self) -> &cyclonedds_sys::dds_topic_descriptor_t {
+        &self.descriptor
+    }
+
+    /// Returns the descriptor the C descriptor was copied from.
+    pub fn topic_descriptor(&self) -> &TopicDescriptor {
+        &self.source
+    }
+}
+
+impl std::fmt::Debug for OwnedTopicDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OwnedTopicDescriptor")
+            .field("typename", &self._typename)
+            .field("nkeys", &self.descriptor.m_nkeys)
+            .field("nops", &self.descriptor.m_nops)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns the type meta data pointing into `data`, absent if it is empty.
+fn meta_ser(data: &[u8]) -> cyclonedds_sys::dds_type_meta_ser {
+    cyclonedds_sys::dds_type_meta_ser {
+        data: if data.is_empty() {
+            ptr::null()
+        } else {
+            data.as_ptr()
+        },
+        sz: data.len() as u32,
+    }
+}
+
+/// The descriptors of the topic types, leaked so that they outlive every
+/// topic created from them.
+static DESCRIPTORS: OnceLock<Mutex<HashMap<TypeId, &'static OwnedTopicDescriptor>>> =
+    OnceLock::new();
+
+/// Returns the descriptor of `T`, which is built on first use and lives for
+/// the rest of the process, so Cyclone DDS may keep pointers into it.
+pub fn registered_descriptor<T: TopicType + 'static>() -> &'static OwnedTopicDescriptor {
+    let mut descriptors = DESCRIPTORS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    descriptors
+        .entry(TypeId::of::<T>())
+        .or_insert_with(|| Box::leak(Box::new(OwnedTopicDescriptor::new(&T::topic_descriptor()))))
 }
 
 /// Used to describe a named key field in a type with the offset from the start
@@ -196,31 +311,17 @@ impl From<cyclonedds_sys::dds_key_descriptor_t> for KeyDescriptor {
     }
 }
 
-impl From<KeyDescriptor> for cyclonedds_sys::dds_key_descriptor_t {
-    fn from(key_descriptor: KeyDescriptor) -> Self {
-        cyclonedds_sys::dds_key_descriptor_t {
-            m_name: CString::new(key_descriptor.name).unwrap().as_ptr(),
-            m_offset: key_descriptor.m_offset,
-            m_idx: key_descriptor.index,
-        }
-    }
-}
 #[derive(Debug, Clone)]
 pub struct TypeMetaSer {
     pub(crate) data: Vec<u8>,
 }
 
-impl From<TypeMetaSer> for cyclonedds_sys::dds_type_meta_ser {
-    fn from(value: TypeMetaSer) -> Self {
-        cyclonedds_sys::dds_type_meta_ser {
-            data: value.data.as_ptr(),
-            sz: value.data.len() as u32,
-        }
-    }
-}
-
 impl From<cyclonedds_sys::dds_type_meta_ser> for TypeMetaSer {
     fn from(value: cyclonedds_sys::dds_type_meta_ser) -> Self {
+        // The type meta data is absent unless DDS_TOPIC_XTYPES_METADATA is set
+        if value.data.is_null() {
+            return Self { data: Vec::new() };
+        }
         Self {
             data: unsafe { slice::from_raw_parts(value.data, value.sz as usize).to_vec() },
         }
@@ -295,10 +396,93 @@ fn capture_cyclonedds_logs() -> String {
 
 #[cfg(test)]
 mod tests {
+    use std::{ffi::CStr, ptr, slice, thread};
+
     use tracing::trace;
     use tracing_subscriber::FmtSubscriber;
 
-    use crate::domain::DomainParticipant;
+    use super::{registered_descriptor, OwnedTopicDescriptor, TopicDescriptor};
+    use crate::{domain::DomainParticipant, topic::TopicType};
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[dds(type_name = "Internal::Msg")]
+    struct Msg {
+        #[dds(key)]
+        id: u32,
+        #[dds(key)]
+        name: String,
+        text: String,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    struct Empty {
+        value: u8,
+    }
+
+    // The descriptor tests do not call into Cyclone DDS, so they also run
+    // under Miri, which reports reads of freed storage.
+
+    #[test]
+    fn test_owned_descriptor() {
+        let expected = Msg::topic_descriptor();
+        // The source descriptor is dropped right away and the owned one is
+        // moved, the C descriptor must still point to live storage
+        let owned = Box::new(OwnedTopicDescriptor::new(&Msg::topic_descriptor()));
+        let owned = *owned;
+
+        let c = owned.as_c();
+        unsafe {
+            assert_eq!(CStr::from_ptr(c.m_typename).to_str(), Ok("Internal::Msg"));
+            assert_eq!(CStr::from_ptr(c.m_meta).to_str(), Ok(""));
+            let keys = slice::from_raw_parts(c.m_keys, c.m_nkeys as usize);
+            let names = keys
+                .iter()
+                .map(|key| CStr::from_ptr(key.m_name).to_str().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(names, ["id", "name"]);
+            assert_eq!(
+                slice::from_raw_parts(c.m_ops, expected.ops().len()),
+                expected.ops()
+            );
+            assert_eq!(
+                slice::from_raw_parts(c.type_information.data, c.type_information.sz as usize),
+                expected.type_information.data
+            );
+            assert_eq!(
+                slice::from_raw_parts(c.type_mapping.data, c.type_mapping.sz as usize),
+                expected.type_mapping.data
+            );
+        }
+
+        let copy = TopicDescriptor::from(*c);
+        assert_eq!(copy.type_name(), expected.type_name());
+        assert_eq!(copy.keys().len(), 2);
+        assert_eq!(copy.keys()[1].name(), "name");
+        assert_eq!(copy.keys()[1].offset(), expected.keys()[1].offset());
+    }
+
+    #[test]
+    fn test_owned_descriptor_without_keys() {
+        let owned = OwnedTopicDescriptor::new(&Empty::topic_descriptor());
+        assert_eq!(owned.as_c().m_nkeys, 0);
+        assert!(owned.as_c().m_keys.is_null());
+        assert!(TopicDescriptor::from(*owned.as_c()).keys().is_empty());
+    }
+
+    #[test]
+    fn test_registered_descriptor() {
+        let msg = registered_descriptor::<Msg>();
+        assert!(ptr::eq(msg, registered_descriptor::<Msg>()));
+        assert!(!ptr::eq(msg, registered_descriptor::<Empty>()));
+
+        // Every thread gets the same descriptor
+        let other = thread::spawn(|| registered_descriptor::<Msg>() as *const _ as usize)
+            .join()
+            .unwrap();
+        assert_eq!(other, msg as *const _ as usize);
+        let typename = unsafe { CStr::from_ptr(msg.as_c().m_typename) };
+        assert_eq!(typename.to_str(), Ok("Internal::Msg"));
+    }
 
     // use crate::internal::enable_cyclonedds_tracing;
 
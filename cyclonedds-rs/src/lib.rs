@@ -3,17 +3,29 @@ use std::ffi::{c_char, c_int};
 use cyclonedds_sys::*;
 use publisher::PublicationMatchedStatus;
 
+// Lets the derive macros refer to `::cyclonedds_rs` from inside this crate.
+extern crate self as cyclonedds_rs;
+
 pub(crate) mod internal;
 
+pub mod bounded;
+pub mod bytes;
 pub mod core;
+pub mod descriptor;
 pub mod domain;
 pub mod dynamic;
+pub mod idl;
+pub mod ops;
 pub mod psmx;
 pub mod publisher;
 pub mod qos;
+pub mod serdata;
+pub mod sertype;
 pub mod statistics;
 pub mod subscriber;
 pub mod topic;
+pub mod typeinfo;
+pub mod xcdr;
 pub mod xtypes;
 pub mod util;
 pub mod prelude {
@@ -0,0 +1,1157 @@
+//! Decoding and validation of the `DDS_OP_*` program of a [TopicDescriptor].
+//!
+//! The serializer of Cyclone DDS trusts the ops of a descriptor blindly, a
+//! wrong offset or jump in a hand-written or generated program shows up as
+//! garbage on the wire or a crash inside the serializer.
+//! [TopicDescriptor::disassemble] decodes the program into a [Disassembly],
+//! whose `Display` prints one instruction per line:
+//!
+//! ```text
+//! @0:
+//!   0: ADR key mu offset=24 int32
+//!   2: ADR offset=0 string
+//!   4: RTS
+//! keys:
+//!   id (0): KOF@5 [0] -> 0
+//! ```
+//!
+//! [validate] also checks the program against the plain old data type the
+//! descriptor is for: its size and alignment, the offsets of the members
+//! and the key descriptors. [crate::topic::Topic::new] validates the
+//! descriptor before the topic is created. The programs of other types
+//! describe a C sample that is not laid out as the Rust type, so they can
+//! only be disassembled.
+
+use std::{
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
+    fmt,
+    mem::{align_of, size_of},
+};
+
+use cyclonedds_sys::{
+    dds_stream_opcode, dds_stream_typecode, DDS_OP_FLAGS_MASK, DDS_OP_FLAG_BASE, DDS_OP_FLAG_DEF,
+    DDS_OP_FLAG_EXT, DDS_OP_FLAG_FP, DDS_OP_FLAG_KEY, DDS_OP_FLAG_MU, DDS_OP_FLAG_OPT,
+    DDS_OP_FLAG_SGN, DDS_OP_FLAG_SZ_MASK, DDS_OP_FLAG_SZ_SHIFT, DDS_OP_JMP_MASK, DDS_OP_MASK,
+    DDS_OP_SUBTYPE_MASK, DDS_OP_TYPE_MASK,
+};
+use thiserror::Error;
+
+use crate::{descriptor::Pod, internal::TopicDescriptor, topic::TopicType};
+
+/// A program that can not be decoded.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DisasmError {
+    #[error("instruction {index} reads past the end of the ops")]
+    Truncated { index: usize },
+    #[error("instruction {index} has unknown opcode {word:#010x}")]
+    UnknownOpcode { index: usize, word: u32 },
+    #[error("instruction {index} has unknown type {word:#010x}")]
+    UnknownType { index: usize, word: u32 },
+    #[error("instruction {index} can not appear here: {word:#010x}")]
+    Unexpected { index: usize, word: u32 },
+    #[error("instruction {index} jumps out of the ops")]
+    BadJump { index: usize },
+    #[error("key {name} does not point at a valid KOF instruction: {reason}")]
+    BadKey { name: String, reason: String },
+}
+
+/// The type of a member, a collection element or a union case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Bool,
+    Int {
+        size: u32,
+        signed: bool,
+    },
+    Float {
+        size: u32,
+    },
+    /// A string, bounded to `bound - 1` characters if given.
+    String {
+        bound: Option<u32>,
+    },
+    Enum {
+        size: u32,
+        max: u32,
+    },
+    Bitmask {
+        size: u32,
+        bits: u64,
+    },
+    /// A struct, union or collection described by the program at `start`.
+    Program {
+        kind: &'static str,
+        start: usize,
+    },
+    Sequence {
+        bound: Option<u32>,
+        element: Box<Value>,
+        element_size: Option<u32>,
+    },
+    Array {
+        len: u32,
+        element: Box<Value>,
+        element_size: Option<u32>,
+    },
+    Union {
+        discriminator: Box<Value>,
+        default: bool,
+        cases: Vec<Case>,
+    },
+}
+
+impl Value {
+    /// The size of the value in a sample, if it is a primitive.
+    fn primitive_size(&self) -> Option<u32> {
+        match self {
+            Value::Bool => Some(1),
+            Value::Int { size, .. }
+            | Value::Float { size }
+            | Value::Enum { size, .. }
+            | Value::Bitmask { size, .. } => Some(*size),
+            _ => None,
+        }
+    }
+}
+
+/// A case of a union, a `JEQ4` instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Case {
+    pub index: usize,
+    pub label: u32,
+    pub offset: u32,
+    pub value: Value,
+    pub external: bool,
+}
+
+/// A decoded instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    /// A member at `offset`, `flags` are the `DDS_OP_FLAG_*` of the
+    /// instruction.
+    Adr {
+        value: Value,
+        offset: u32,
+        flags: u32,
+    },
+    /// Calls the program at `start`.
+    Jsr {
+        start: usize,
+    },
+    /// Starts an appendable type.
+    Dlc,
+    /// Starts the member list of a mutable type.
+    Plc,
+    /// A member of a mutable type described by the program at `start`, or
+    /// the members of the base type if `base` is set.
+    Plm {
+        id: u32,
+        base: bool,
+        start: usize,
+    },
+    Rts,
+}
+
+/// An instruction and its position in the ops.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Insn {
+    pub index: usize,
+    pub op: Op,
+}
+
+/// A key field and the `KOF` instruction of its key descriptor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Key {
+    pub name: String,
+    /// The position of the field in the key.
+    pub index: u32,
+    /// The position of the `KOF` instruction.
+    pub kof: usize,
+    /// The member indices of the `KOF` instruction.
+    pub path: Vec<u32>,
+    /// The position of the `ADR` instruction of the field.
+    pub adr: usize,
+}
+
+/// The decoded program of a [TopicDescriptor], the type at `@0` and every
+/// program it refers to, by position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disassembly {
+    pub programs: BTreeMap<usize, Vec<Insn>>,
+    pub keys: Vec<Key>,
+}
+
+impl Disassembly {
+    /// The number of instructions, which is what `m_nops` counts.
+    pub fn nops(&self) -> usize {
+        self.programs
+            .values()
+            .flatten()
+            .map(|insn| match &insn.op {
+                Op::Adr {
+                    value: Value::Union { cases, .. },
+                    ..
+                } => 1 + cases.len(),
+                _ => 1,
+            })
+            .sum()
+    }
+
+    /// The member instructions of the struct program at `start` in order,
+    /// following the `PLM` instructions of a mutable struct.
+    fn members(&self, start: usize) -> Vec<&Insn> {
+        let mut members = Vec::new();
+        self.collect_members(start, &mut BTreeSet::new(), &mut members);
+        members
+    }
+
+    fn collect_members<'a>(
+        &'a self,
+        start: usize,
+        seen: &mut BTreeSet<usize>,
+        members: &mut Vec<&'a Insn>,
+    ) {
+        if !seen.insert(start) {
+            return;
+        }
+        for insn in self.programs.get(&start).into_iter().flatten() {
+            match &insn.op {
+                Op::Adr { .. } => members.push(insn),
+                Op::Plm { start, .. } | Op::Jsr { start } => {
+                    self.collect_members(*start, seen, members)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl TopicDescriptor {
+    /// Decodes the ops of the descriptor.
+    pub fn disassemble(&self) -> Result<Disassembly, DisasmError> {
+        let mut decoder = Decoder {
+            ops: &self.m_ops,
+            pending: vec![0],
+        };
+        let mut programs = BTreeMap::new();
+        while let Some(start) = decoder.pending.pop() {
+            if let Entry::Vacant(entry) = programs.entry(start) {
+                entry.insert(decoder.program(start)?);
+            }
+        }
+        let keys = self
+            .m_keys
+            .iter()
+            .map(|key| decoder.key(&programs, &key.name, key.index, key.m_offset as usize))
+            .collect::<Result<_, _>>()?;
+        Ok(Disassembly { programs, keys })
+    }
+}
+
+struct Decoder<'a> {
+    ops: &'a [u32],
+    /// The programs referred to that still have to be decoded.
+    pending: Vec<usize>,
+}
+
+impl Decoder<'_> {
+    fn word(&self, index: usize) -> Result<u32, DisasmError> {
+        self.ops
+            .get(index)
+            .copied()
+            .ok_or(DisasmError::Truncated { index })
+    }
+
+    /// Returns the target of a jump encoded in the low 16 bits of `word`.
+    fn jump(&self, index: usize, word: u32) -> Result<usize, DisasmError> {
+        let target = index as isize + (word & DDS_OP_JMP_MASK) as u16 as i16 as isize;
+        if target < 0 || target as usize >= self.ops.len() {
+            return Err(DisasmError::BadJump { index });
+        }
+        Ok(target as usize)
+    }
+
+    /// Like [Decoder::jump], also queues the target for decoding.
+    fn call(&mut self, index: usize, word: u32) -> Result<usize, DisasmError> {
+        let target = self.jump(index, word)?;
+        self.pending.push(target);
+        Ok(target)
+    }
+
+    fn program(&mut self, start: usize) -> Result<Vec<Insn>, DisasmError> {
+        let mut insns = Vec::new();
+        let mut index = start;
+        loop {
+            let word = self.word(index)?;
+            let (op, len) = match opcode(word) {
+                Some(dds_stream_opcode::DDS_OP_RTS) => (Op::Rts, 1),
+                Some(dds_stream_opcode::DDS_OP_DLC) => (Op::Dlc, 1),
+                Some(dds_stream_opcode::DDS_OP_PLC) => (Op::Plc, 1),
+                Some(dds_stream_opcode::DDS_OP_JSR) => (
+                    Op::Jsr {
+                        start: self.call(index, word)?,
+                    },
+                    1,
+                ),
+                Some(dds_stream_opcode::DDS_OP_PLM) => (
+                    Op::Plm {
+                        id: self.word(index + 1)?,
+                        base: (word >> 16) & DDS_OP_FLAG_BASE != 0,
+                        start: self.call(index, word)?,
+                    },
+                    2,
+                ),
+                Some(dds_stream_opcode::DDS_OP_ADR) => self.adr(index, word)?,
+                Some(_) => return Err(DisasmError::Unexpected { index, word }),
+                None => return Err(DisasmError::UnknownOpcode { index, word }),
+            };
+            let done = op == Op::Rts;
+            insns.push(Insn { index, op });
+            if done {
+                return Ok(insns);
+            }
+            index += len;
+        }
+    }
+
+    /// Decodes an `ADR` instruction into the op and its length in words.
+    fn adr(&mut self, index: usize, word: u32) -> Result<(Op, usize), DisasmError> {
+        let offset = self.word(index + 1)?;
+        let flags = word & (DDS_OP_FLAGS_MASK | DDS_OP_FLAG_EXT);
+        let unknown = || DisasmError::UnknownType { index, word };
+        let typecode = (word & DDS_OP_TYPE_MASK) >> 16;
+        let (value, len) = if let Some(value) = primitive(typecode, word) {
+            (value, 2)
+        } else if typecode == dds_stream_typecode::DDS_OP_VAL_BST as u32 {
+            (
+                Value::String {
+                    bound: Some(self.word(index + 2)?),
+                },
+                3,
+            )
+        } else if typecode == dds_stream_typecode::DDS_OP_VAL_ENU as u32 {
+            (
+                Value::Enum {
+                    size: size(word),
+                    max: self.word(index + 2)?,
+                },
+                3,
+            )
+        } else if typecode == dds_stream_typecode::DDS_OP_VAL_BMK as u32 {
+            (self.bitmask(word, index + 2)?, 4)
+        } else if typecode == dds_stream_typecode::DDS_OP_VAL_EXT as u32 {
+            let jump = self.word(index + 2)?;
+            let value = Value::Program {
+                kind: "external",
+                start: self.call(index, jump)?,
+            };
+            (value, length(jump, 3))
+        } else if typecode == dds_stream_typecode::DDS_OP_VAL_UNI as u32 {
+            self.union(index, word)?
+        } else if typecode == dds_stream_typecode::DDS_OP_VAL_SEQ as u32
+            || typecode == dds_stream_typecode::DDS_OP_VAL_BSQ as u32
+            || typecode == dds_stream_typecode::DDS_OP_VAL_ARR as u32
+        {
+            self.collection(index, word, typecode)?
+        } else {
+            return Err(unknown());
+        };
+        Ok((
+            Op::Adr {
+                value,
+                offset,
+                flags,
+            },
+            len,
+        ))
+    }
+
+    fn bitmask(&self, word: u32, index: usize) -> Result<Value, DisasmError> {
+        let high = self.word(index)? as u64;
+        let low = self.word(index + 1)? as u64;
+        Ok(Value::Bitmask {
+            size: size(word),
+            bits: high << 32 | low,
+        })
+    }
+
+    fn collection(
+        &mut self,
+        index: usize,
+        word: u32,
+        typecode: u32,
+    ) -> Result<(Value, usize), DisasmError> {
+        let array = typecode == dds_stream_typecode::DDS_OP_VAL_ARR as u32;
+        let mut next = index + 2;
+        let count = if typecode == dds_stream_typecode::DDS_OP_VAL_SEQ as u32 {
+            None
+        } else {
+            next += 1;
+            Some(self.word(index + 2)?)
+        };
+
+        let subtype = (word & DDS_OP_SUBTYPE_MASK) >> 8;
+        let mut element_size = None;
+        let element = if let Some(value) = primitive(subtype, word) {
+            value
+        } else if subtype == dds_stream_typecode::DDS_OP_VAL_BST as u32 {
+            if array {
+                next += 1;
+            }
+            next += 1;
+            Value::String {
+                bound: Some(self.word(next - 1)?),
+            }
+        } else if subtype == dds_stream_typecode::DDS_OP_VAL_ENU as u32 {
+            next += 1;
+            Value::Enum {
+                size: size(word),
+                max: self.word(next - 1)?,
+            }
+        } else if subtype == dds_stream_typecode::DDS_OP_VAL_BMK as u32 {
+            next += 2;
+            self.bitmask(word, next - 2)?
+        } else if let Some(kind) = complex(subtype) {
+            let (jump, size) = if array {
+                (self.word(next)?, self.word(next + 1)?)
+            } else {
+                (self.word(next + 1)?, self.word(next)?)
+            };
+            element_size = Some(size);
+            next = index + length(jump, next + 2 - index);
+            Value::Program {
+                kind,
+                start: self.call(index, jump)?,
+            }
+        } else {
+            return Err(DisasmError::UnknownType { index, word });
+        };
+
+        let element = Box::new(element);
+        let value = match count {
+            Some(len) if array => Value::Array {
+                len,
+                element,
+                element_size,
+            },
+            bound => Value::Sequence {
+                bound,
+                element,
+                element_size,
+            },
+        };
+        Ok((value, next - index))
+    }
+
+    fn union(&mut self, index: usize, word: u32) -> Result<(Value, usize), DisasmError> {
+        let subtype = (word & DDS_OP_SUBTYPE_MASK) >> 8;
+        let discriminator = if subtype == dds_stream_typecode::DDS_OP_VAL_ENU as u32 {
+            Value::Enum {
+                size: size(word),
+                max: self.word(index + 4)?,
+            }
+        } else {
+            // DDS_OP_FLAG_DEF shares its bit with DDS_OP_FLAG_FP.
+            primitive(subtype, word & !DDS_OP_FLAG_DEF)
+                .ok_or(DisasmError::UnknownType { index, word })?
+        };
+        let ncases = self.word(index + 2)? as usize;
+        let jump = self.word(index + 3)?;
+        let first = self.jump(index, jump)?;
+        let cases = (0..ncases)
+            .map(|case| self.case(first + 4 * case))
+            .collect::<Result<_, _>>()?;
+        let value = Value::Union {
+            discriminator: Box::new(discriminator),
+            default: word & DDS_OP_FLAG_DEF != 0,
+            cases,
+        };
+        Ok((value, length(jump, 4)))
+    }
+
+    fn case(&mut self, index: usize) -> Result<Case, DisasmError> {
+        let word = self.word(index)?;
+        if opcode(word) != Some(dds_stream_opcode::DDS_OP_JEQ4) {
+            return Err(DisasmError::Unexpected { index, word });
+        }
+        let typecode = (word & DDS_OP_TYPE_MASK) >> 16;
+        let extra = self.word(index + 3)?;
+        let value = if let Some(value) = primitive(typecode, word) {
+            value
+        } else if typecode == dds_stream_typecode::DDS_OP_VAL_BST as u32 {
+            Value::String { bound: Some(extra) }
+        } else if typecode == dds_stream_typecode::DDS_OP_VAL_ENU as u32 {
+            Value::Enum {
+                size: size(word),
+                max: extra,
+            }
+        } else if let Some(kind) = complex(typecode)
+            .or((typecode == dds_stream_typecode::DDS_OP_VAL_EXT as u32).then_some("external"))
+            .or((typecode == dds_stream_typecode::DDS_OP_VAL_BMK as u32).then_some("bitmask"))
+        {
+            Value::Program {
+                kind,
+                start: self.call(index, word)?,
+            }
+        } else {
+            return Err(DisasmError::UnknownType { index, word });
+        };
+        Ok(Case {
+            index,
+            label: self.word(index + 1)?,
+            offset: self.word(index + 2)?,
+            value,
+            external: word & DDS_OP_FLAG_EXT != 0,
+        })
+    }
+
+    /// Resolves the `KOF` instruction at `kof` to the `ADR` instruction of
+    /// the key field, each index of the path is relative to the program of
+    /// the struct the previous one refers to.
+    fn key(
+        &self,
+        programs: &BTreeMap<usize, Vec<Insn>>,
+        name: &str,
+        index: u32,
+        kof: usize,
+    ) -> Result<Key, DisasmError> {
+        let bad_key = |reason: String| DisasmError::BadKey {
+            name: name.to_string(),
+            reason,
+        };
+        let word = self.word(kof).map_err(|err| bad_key(err.to_string()))?;
+        if opcode(word) != Some(dds_stream_opcode::DDS_OP_KOF) {
+            return Err(bad_key(format!("instruction {kof} is not KOF")));
+        }
+        let len = (word & DDS_OP_JMP_MASK) as usize;
+        if len == 0 {
+            return Err(bad_key("the path is empty".to_string()));
+        }
+        let path = (1..=len)
+            .map(|step| self.word(kof + step))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| bad_key(err.to_string()))?;
+
+        let mut program = 0;
+        let mut adr = 0;
+        for (step, member) in path.iter().enumerate() {
+            adr = program + *member as usize;
+            let op = programs
+                .values()
+                .flatten()
+                .find(|insn| insn.index == adr)
+                .map(|insn| &insn.op);
+            match op {
+                Some(Op::Adr {
+                    value: Value::Program { start, .. },
+                    ..
+                }) if step + 1 < path.len() => program = *start,
+                Some(Op::Adr { .. }) if step + 1 == path.len() => {}
+                _ => {
+                    return Err(bad_key(format!(
+                        "step {step} of the path is not a member at instruction {adr}"
+                    )))
+                }
+            }
+        }
+        Ok(Key {
+            name: name.to_string(),
+            index,
+            kof,
+            path,
+            adr,
+        })
+    }
+}
+
+fn opcode(word: u32) -> Option<dds_stream_opcode> {
+    use dds_stream_opcode::*;
+    [
+        DDS_OP_RTS,
+        DDS_OP_ADR,
+        DDS_OP_JSR,
+        DDS_OP_JEQ,
+        DDS_OP_DLC,
+        DDS_OP_PLC,
+        DDS_OP_PLM,
+        DDS_OP_KOF,
+        DDS_OP_JEQ4,
+    ]
+    .into_iter()
+    .find(|opcode| *opcode as u32 == word & DDS_OP_MASK)
+}
+
+/// Decodes the primitive `typecode`, with the flags of `word`.
+fn primitive(typecode: u32, word: u32) -> Option<Value> {
+    use dds_stream_typecode::*;
+    let int = |size| {
+        if word & DDS_OP_FLAG_FP != 0 {
+            Value::Float { size }
+        } else {
+            Value::Int {
+                size,
+                signed: word & DDS_OP_FLAG_SGN != 0,
+            }
+        }
+    };
+    Some(match typecode {
+        t if t == DDS_OP_VAL_BLN as u32 => Value::Bool,
+        t if t == DDS_OP_VAL_1BY as u32 => int(1),
+        t if t == DDS_OP_VAL_2BY as u32 => int(2),
+        t if t == DDS_OP_VAL_4BY as u32 => int(4),
+        t if t == DDS_OP_VAL_8BY as u32 => int(8),
+        t if t == DDS_OP_VAL_STR as u32 => Value::String { bound: None },
+        _ => return None,
+    })
+}
+
+/// The name of a type that is described by a program of its own.
+fn complex(typecode: u32) -> Option<&'static str> {
+    use dds_stream_typecode::*;
+    Some(match typecode {
+        t if t == DDS_OP_VAL_STU as u32 => "struct",
+        t if t == DDS_OP_VAL_UNI as u32 => "union",
+        t if t == DDS_OP_VAL_SEQ as u32 => "sequence",
+        t if t == DDS_OP_VAL_BSQ as u32 => "bounded sequence",
+        t if t == DDS_OP_VAL_ARR as u32 => "array",
+        _ => return None,
+    })
+}
+
+/// The size of an enum or bitmask in bytes.
+fn size(word: u32) -> u32 {
+    1 << ((word & DDS_OP_FLAG_SZ_MASK) >> DDS_OP_FLAG_SZ_SHIFT)
+}
+
+/// The length of an instruction from the high 16 bits of its jump word,
+/// `default` if they are not set.
+fn length(jump: u32, default: usize) -> usize {
+    match (jump >> 16) as usize {
+        0 => default,
+        len => len,
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool => write!(f, "bool"),
+            Value::Int { size, signed } => {
+                write!(f, "{}int{}", if *signed { "" } else { "u" }, size * 8)
+            }
+            Value::Float { size } => write!(f, "float{}", size * 8),
+            Value::String { bound: None } => write!(f, "string"),
+            Value::String { bound: Some(bound) } => {
+                write!(f, "string<{}>", bound.saturating_sub(1))
+            }
+            Value::Enum { size, max } => write!(f, "enum{}(max={max})", size * 8),
+            Value::Bitmask { size, bits } => write!(f, "bitmask{}({bits:#x})", size * 8),
+            Value::Program { kind, start } => write!(f, "{kind} @{start}"),
+            Value::Sequence {
+                bound,
+                element,
+                element_size,
+            } => {
+                write!(f, "sequence<{element}")?;
+                if let Some(bound) = bound {
+                    write!(f, ", {bound}")?;
+                }
+                write!(f, ">")?;
+                if let Some(size) = element_size {
+                    write!(f, " size={size}")?;
+                }
+                Ok(())
+            }
+            Value::Array {
+                len,
+                element,
+                element_size,
+            } => {
+                write!(f, "{element}[{len}]")?;
+                if let Some(size) = element_size {
+                    write!(f, " size={size}")?;
+                }
+                Ok(())
+            }
+            Value::Union {
+                discriminator,
+                default,
+                cases,
+            } => {
+                write!(f, "union({discriminator}")?;
+                if *default {
+                    write!(f, ", default")?;
+                }
+                write!(f, ")")?;
+                for case in cases {
+                    write!(
+                        f,
+                        "\n    {}: JEQ4 {} => {} offset={}",
+                        case.index, case.label, case.value, case.offset
+                    )?;
+                    if case.external {
+                        write!(f, " ext")?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Adr {
+                value,
+                offset,
+                flags,
+            } => {
+                // The value comes last, the cases of a union follow it on
+                // lines of their own.
+                write!(f, "ADR")?;
+                for (flag, name) in [
+                    (DDS_OP_FLAG_KEY, "key"),
+                    (DDS_OP_FLAG_MU, "mu"),
+                    (DDS_OP_FLAG_OPT, "opt"),
+                    (DDS_OP_FLAG_EXT, "ext"),
+                    (DDS_OP_FLAG_BASE, "base"),
+                ] {
+                    if flags & flag != 0 {
+                        write!(f, " {name}")?;
+                    }
+                }
+                write!(f, " offset={offset} {value}")
+            }
+            Op::Jsr { start } => write!(f, "JSR @{start}"),
+            Op::Dlc => write!(f, "DLC"),
+            Op::Plc => write!(f, "PLC"),
+            Op::Plm { id, base, start } => {
+                write!(
+                    f,
+                    "PLM id={id}{} @{start}",
+                    if *base { " base" } else { "" }
+                )
+            }
+            Op::Rts => write!(f, "RTS"),
+        }
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (start, insns) in &self.programs {
+            writeln!(f, "@{start}:")?;
+            for insn in insns {
+                writeln!(f, "  {}: {}", insn.index, insn.op)?;
+            }
+        }
+        if !self.keys.is_empty() {
+            writeln!(f, "keys:")?;
+            for key in &self.keys {
+                writeln!(
+                    f,
+                    "  {} ({}): KOF@{} {:?} -> {}",
+                    key.name, key.index, key.kof, key.path, key.adr
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A way in which a descriptor does not match its Rust type.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum Mismatch {
+    #[error(transparent)]
+    Disassembly(#[from] DisasmError),
+    #[error("the descriptor has size {descriptor}, the type {rust}")]
+    Size { descriptor: u32, rust: usize },
+    #[error("the descriptor has alignment {descriptor}, the type {rust}")]
+    Align { descriptor: u32, rust: usize },
+    #[error("the descriptor counts {descriptor} instructions, the ops have {decoded}")]
+    Nops { descriptor: u32, decoded: usize },
+    #[error("instruction {index} at offset {offset} does not fit a value of size {size}")]
+    Offset {
+        index: usize,
+        offset: u32,
+        size: u32,
+    },
+    #[error("the descriptor has {descriptor} keys, but {decoded} key descriptors")]
+    KeyCount { descriptor: u32, decoded: usize },
+    #[error("key {name} refers to instruction {index}, which is not a key member")]
+    NotKey { name: String, index: usize },
+    #[error("the key indices are not a permutation of 0..{0}")]
+    KeyIndices(usize),
+}
+
+/// All the ways in which a descriptor does not match its Rust type.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+pub struct ValidationError(pub Vec<Mismatch>);
+
+/// Decodes `descriptor` and checks it against `T`: the size and alignment,
+/// that the members fit the sample (and the elements of collections of
+/// structs fit the element size), the number of instructions and that every
+/// key descriptor refers to a key member.
+///
+/// Offsets of members of nested structs are not checked, their size is not
+/// part of the program.
+pub fn validate<T: TopicType + Pod>(
+    descriptor: &TopicDescriptor,
+) -> Result<Disassembly, ValidationError> {
+    let mut mismatches = Vec::new();
+    if descriptor.m_size as usize != size_of::<T>() {
+        mismatches.push(Mismatch::Size {
+            descriptor: descriptor.m_size,
+            rust: size_of::<T>(),
+        });
+    }
+    if descriptor.m_align as usize != align_of::<T>() {
+        mismatches.push(Mismatch::Align {
+            descriptor: descriptor.m_align,
+            rust: align_of::<T>(),
+        });
+    }
+
+    let disassembly = match descriptor.disassemble() {
+        Ok(disassembly) => disassembly,
+        Err(err) => {
+            mismatches.push(err.into());
+            return Err(ValidationError(mismatches));
+        }
+    };
+
+    if descriptor.m_nops as usize != disassembly.nops() {
+        mismatches.push(Mismatch::Nops {
+            descriptor: descriptor.m_nops,
+            decoded: disassembly.nops(),
+        });
+    }
+
+    let mut structs = vec![(0, descriptor.m_size)];
+    for insn in disassembly.programs.values().flatten() {
+        if let Op::Adr {
+            value:
+                Value::Sequence {
+                    element,
+                    element_size: Some(size),
+                    ..
+                }
+                | Value::Array {
+                    element,
+                    element_size: Some(size),
+                    ..
+                },
+            ..
+        } = &insn.op
+        {
+            if let Value::Program {
+                kind: "struct",
+                start,
+            } = element.as_ref()
+            {
+                structs.push((*start, *size));
+            }
+        }
+    }
+    for (start, size) in structs {
+        for insn in disassembly.members(start) {
+            let Op::Adr { value, offset, .. } = &insn.op else {
+                continue;
+            };
+            let len = value.primitive_size().unwrap_or(1);
+            if *offset as u64 + len as u64 > size as u64 || offset % len != 0 {
+                mismatches.push(Mismatch::Offset {
+                    index: insn.index,
+                    offset: *offset,
+                    size: len,
+                });
+            }
+        }
+    }
+
+    if descriptor.m_nkeys as usize != disassembly.keys.len() {
+        mismatches.push(Mismatch::KeyCount {
+            descriptor: descriptor.m_nkeys,
+            decoded: disassembly.keys.len(),
+        });
+    }
+    for key in &disassembly.keys {
+        let flags = disassembly
+            .programs
+            .values()
+            .flatten()
+            .find_map(|insn| match insn.op {
+                Op::Adr { flags, .. } if insn.index == key.adr => Some(flags),
+                _ => None,
+            });
+        if flags.unwrap_or(0) & DDS_OP_FLAG_KEY == 0 {
+            mismatches.push(Mismatch::NotKey {
+                name: key.name.clone(),
+                index: key.adr,
+            });
+        }
+    }
+    let indices = disassembly
+        .keys
+        .iter()
+        .map(|key| key.index as usize)
+        .collect::<BTreeSet<_>>();
+    if !indices.iter().copied().eq(0..disassembly.keys.len()) {
+        mismatches.push(Mismatch::KeyIndices(disassembly.keys.len()));
+    }
+
+    if mismatches.is_empty() {
+        Ok(disassembly)
+    } else {
+        Err(ValidationError(mismatches))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{validate, DisasmError, Mismatch, Op, Value};
+    use crate::{
+        bounded::{BoundedMap, BoundedString, BoundedVec},
+        topic::TopicType,
+    };
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[dds(type_name = "Ops::Msg")]
+    struct Msg {
+        #[dds(key)]
+        id: i32,
+        text: String,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    struct Point {
+        #[dds(key)]
+        x: i32,
+        y: i32,
+    }
+
+    #[allow(dead_code)]
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[repr(u8)]
+    enum Shape {
+        Dot(Point) = 1,
+        Radius(f32) = 2,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[dds(mutable)]
+    struct Drawing {
+        #[dds(key)]
+        origin: Point,
+        points: Vec<Point>,
+        shape: Shape,
+        names: [String; 2],
+    }
+
+    #[derive(TopicType, Clone, Copy, Debug, PartialEq)]
+    #[dds(pod)]
+    #[repr(C)]
+    struct Counter {
+        #[dds(key)]
+        id: i32,
+        count: u32,
+    }
+
+    #[test]
+    fn test_display() {
+        let descriptor = Msg::topic_descriptor();
+        let disassembly = descriptor.disassemble().unwrap();
+        assert_eq!(
+            disassembly.to_string(),
+            "@0:\n  0: ADR key mu offset=0 int32\n  2: ADR offset=8 string\n  4: RTS\n\
+             keys:\n  id (0): KOF@5 [0] -> 0\n"
+        );
+        assert_eq!(disassembly.nops() as u32, descriptor.m_nops);
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let descriptor = Drawing::topic_descriptor();
+        let disassembly = descriptor.disassemble().unwrap();
+        let members = disassembly.members(0);
+        assert_eq!(members.len(), 4);
+
+        // The key is x of the nested origin
+        let [key] = &disassembly.keys[..] else {
+            panic!("{disassembly}");
+        };
+        assert_eq!(key.name, "origin.x");
+        assert_eq!(key.path.len(), 2);
+        let Op::Adr { value, offset, .. } = &members[0].op else {
+            unreachable!()
+        };
+        assert_eq!(*offset, 0);
+        let Value::Program { start, .. } = value else {
+            panic!("{value}");
+        };
+        assert_eq!(disassembly.programs[start][0].index, key.adr);
+
+        let values = members
+            .iter()
+            .map(|insn| match &insn.op {
+                Op::Adr { value, .. } => value,
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            values[1],
+            Value::Sequence { element, element_size: Some(size), .. }
+                if matches!(element.as_ref(), Value::Program { kind: "struct", .. })
+                    && *size == 8
+        ));
+        assert!(matches!(values[3], Value::Array { len: 2, element, .. }
+                if **element == Value::String { bound: None }));
+        let Value::Program { start, .. } = values[2] else {
+            panic!("{}", values[2]);
+        };
+        let union = disassembly.programs[start]
+            .iter()
+            .find_map(|insn| match &insn.op {
+                Op::Adr {
+                    value: Value::Union { cases, .. },
+                    ..
+                } => Some(cases),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(union.len(), 2);
+        assert_eq!(union[0].label, 1);
+        assert!(matches!(union[0].value, Value::Program { .. }));
+        assert_eq!(union[1].value, Value::Float { size: 4 });
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut descriptor = Counter::topic_descriptor();
+        descriptor.m_size += 8;
+        descriptor.m_nops += 1;
+        assert_eq!(
+            validate::<Counter>(&descriptor).unwrap_err().0,
+            vec![
+                Mismatch::Size {
+                    descriptor: descriptor.m_size,
+                    rust: std::mem::size_of::<Counter>()
+                },
+                Mismatch::Nops {
+                    descriptor: descriptor.m_nops,
+                    decoded: 3
+                }
+            ]
+        );
+
+        // The offset of the key is past the end of the sample
+        let mut descriptor = Counter::topic_descriptor();
+        descriptor.m_ops[1] = descriptor.m_size;
+        assert_eq!(
+            validate::<Counter>(&descriptor).unwrap_err().0,
+            vec![Mismatch::Offset {
+                index: 0,
+                offset: descriptor.m_size,
+                size: 4
+            }]
+        );
+
+        // The key refers to the count, which is not a key member
+        let mut descriptor = Counter::topic_descriptor();
+        descriptor.m_ops[6] = 2;
+        assert_eq!(
+            validate::<Counter>(&descriptor).unwrap_err().0,
+            vec![Mismatch::NotKey {
+                name: "id".to_string(),
+                index: 2
+            }]
+        );
+
+        let mut descriptor = Counter::topic_descriptor();
+        descriptor.m_keys[0].m_offset = 0;
+        assert!(matches!(
+            &validate::<Counter>(&descriptor).unwrap_err().0[..],
+            [Mismatch::Disassembly(DisasmError::BadKey { .. })]
+        ));
+
+        let mut descriptor = Counter::topic_descriptor();
+        descriptor.m_ops[4] = 0xff00_0000;
+        assert_eq!(
+            descriptor.disassemble().unwrap_err(),
+            DisasmError::UnknownOpcode {
+                index: 4,
+                word: 0xff00_0000
+            }
+        );
+        descriptor.m_ops.truncate(4);
+        assert_eq!(
+            descriptor.disassemble().unwrap_err(),
+            DisasmError::Truncated { index: 4 }
+        );
+    }
+
+    #[allow(dead_code)]
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[repr(u32)]
+    enum Color {
+        Red,
+        Green,
+    }
+
+    #[derive(TopicType, Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    #[dds(bitmask(A, B))]
+    #[serde(try_from = "u8", into = "u8")]
+    #[repr(transparent)]
+    struct Permissions(u8);
+
+    #[allow(dead_code)]
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[dds(appendable)]
+    #[repr(u32)]
+    enum Choice {
+        Ids(Vec<u16>) = 1,
+        Name(BoundedString<5>) = 2,
+        #[dds(default)]
+        Color(Color) = 3,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[dds(appendable)]
+    struct Inner {
+        #[dds(key)]
+        name: BoundedString<8>,
+        values: Vec<Vec<i64>>,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[dds(appendable)]
+    struct Everything {
+        #[dds(key)]
+        color: Color,
+        #[dds(key)]
+        inner: Inner,
+        permissions: Permissions,
+        choice: Choice,
+        optional: Option<i32>,
+        boxed: Box<Inner>,
+        map: BTreeMap<String, Inner>,
+        bounded_map: BoundedMap<u8, Color, 3>,
+        bounded_seq: BoundedVec<Permissions, 4>,
+        matrix: [[u8; 2]; 3],
+        inners: [Inner; 2],
+        bools: Vec<bool>,
+        strings: [BoundedString<3>; 2],
+    }
+
+    #[test]
+    fn test_disassemble_every_kind() {
+        let descriptor = Everything::topic_descriptor();
+        let disassembly = descriptor.disassemble().unwrap();
+        assert_eq!(disassembly.members(0).len(), 13);
+        assert_eq!(disassembly.keys.len(), 2);
+    }
+}
@@ -1,16 +1,32 @@
 use std::{
     any::Any,
     marker::PhantomData,
+    mem,
+    ops::{Deref, DerefMut},
     os::raw::c_void,
-    ptr::{self, null_mut},
+    ptr::{self, null_mut, NonNull},
     time::{Duration, Instant},
 };
 
-use cyclonedds_sys::dds_delete;
+use cyclonedds_sys::{
+    dds_delete,
+    internal::{ddsi_serdata, ddsi_sertype},
+};
 use serde::Serialize;
+use tracing::error;
 
 use crate::{
-    core::ReturnCodes, domain::DomainParticipant, internal::InstanceHandle, topic::TopicType,
+    bytes::{BytesTopic, KeyedBytesTopic},
+    core::ReturnCodes,
+    domain::DomainParticipant,
+    internal::InstanceHandle,
+    qos::{DataRepresentation, Qos},
+    serdata::SerializedSample,
+    sertype::{
+        entity_sertype, is_default_sertype_of, is_same_sertype, is_sertype_of, key_cdr,
+        serdata_from_cdr, serdata_from_parts,
+    },
+    topic::{Loanable, SerdeTopic, Topic},
 };
 
 pub struct Publisher {
@@ -44,8 +60,35 @@ impl Publisher {
         }
     }
 
-    pub fn create_datawriter<T: TopicType>(&mut self) -> Result<DataWriter<T>, ReturnCodes> {
-        todo!("not implemented")
+    /// Creates a writer of `topic`, which serializes samples in the first
+    /// data representation of [Topic::data_representations].
+    pub fn create_datawriter<T>(&mut self, topic: &Topic<T>) -> Result<DataWriter<T>, ReturnCodes> {
+        self.create_datawriter_with_qos(topic, &Qos::new())
+    }
+
+    /// Creates a writer of `topic` with `qos`. The writer serializes
+    /// samples in the first data representation of `qos` or, if it has
+    /// none, the first one of [Topic::data_representations]. Fails with
+    /// [ReturnCodes::BadParameter] if `qos` lists a data representation the
+    /// topic does not allow.
+    pub fn create_datawriter_with_qos<T>(
+        &mut self,
+        topic: &Topic<T>,
+        qos: &Qos,
+    ) -> Result<DataWriter<T>, ReturnCodes> {
+        let qos = qos.for_endpoint(topic.data_representations(), true)?;
+        let writer = unsafe {
+            cyclonedds_sys::dds_create_writer(self.publisher, topic.topic, qos.qos, ptr::null())
+        };
+
+        if writer < 0 {
+            Err(ReturnCodes::from(writer))
+        } else {
+            Ok(DataWriter {
+                writer,
+                _marker: PhantomData,
+            })
+        }
     }
 
     pub fn create_anydatawriter(&mut self) -> Result<AnyDataWriter, ReturnCodes> {
@@ -89,12 +132,12 @@ impl TryFrom<DomainParticipant> for Publisher {
     }
 }
 
-pub struct DataWriter<T: TopicType> {
+pub struct DataWriter<T> {
     writer: cyclonedds_sys::dds_entity_t,
     _marker: PhantomData<T>,
 }
 
-impl<T: TopicType> DataWriter<T> {
+impl<T> DataWriter<T> {
     /// Get PUBLICATION_MATCHED status.
     ///
     /// This operation gets the status value corresponding to
@@ -121,6 +164,21 @@ impl<T: TopicType> DataWriter<T> {
             Ok(PublicationMatchedStatus { status })
         }
     }
+
+    /// The data representation the writer serializes samples in, the first
+    /// one of its QoS.
+    pub fn data_representation(&self) -> Result<DataRepresentation, ReturnCodes> {
+        let qos = Qos::new();
+        match unsafe { cyclonedds_sys::dds_get_qos(self.writer, qos.qos) } {
+            0 => {}
+            result => return Err(ReturnCodes::from(result)),
+        }
+        qos.data_representation()
+            .ok()
+            .and_then(|values| values.first().copied())
+            .ok_or(ReturnCodes::Error)
+    }
+
     /// This operation disposes an instance with a specific timestamp,
     /// identified by the instance handle.
     ///
@@ -148,15 +206,28 @@ impl<T: TopicType> DataWriter<T> {
     /// Write the value of a data instance
     ///
     /// With this API, the value of the source timestamp is automatically made
-    /// available to the data reader by the service.
-    pub fn write(&mut self, data: &T) -> Result<(), ReturnCodes>
-    where
-        T: serde::Serialize,
-    {
-        let encoded_data = cdr_encoding::to_vec::<T, byteorder::NativeEndian>(data).unwrap();
+    /// available to the data reader by the service. The sertype of the topic
+    /// serializes the sample, which it takes as a `T`, see [Topic].
+    pub fn write(&mut self, data: &T) -> Result<(), ReturnCodes> {
+        let sample = data as *const T as *const c_void;
+        match unsafe { cyclonedds_sys::dds_write(self.writer, sample) } {
+            0 => Ok(()),
+            result => Err(ReturnCodes::from(result)),
+        }
+    }
 
-        self.write_cdr(&encoded_data)
+    /// Looks up the handle of the instance `sample` belongs to, only its
+    /// key fields matter. [None] if the instance is unknown, a writer knows
+    /// it once it has written or registered a sample of it. The sertype of
+    /// the topic takes `sample` as a `T`, see [Topic].
+    pub fn lookup_instance(&self, sample: &T) -> Option<InstanceHandle> {
+        let sample = sample as *const T as *const c_void;
+        match unsafe { cyclonedds_sys::dds_lookup_instance(self.writer, sample) } {
+            0 => None,
+            handle => Some(InstanceHandle { handle }),
+        }
     }
+
     /// Flush a writers batched writes
     ///
     /// When using write batching you can manually batch small writes into
@@ -168,17 +239,14 @@ impl<T: TopicType> DataWriter<T> {
     }
     /// Write a serialized value of a data instance
     ///
-    /// This call causes the writer to write the serialized value that is
-    /// provided in the serdata argument. Timestamp and statusinfo fields are
-    /// set to the current time and 0 (indicating a regular write),
-    /// respectively.
-    pub fn write_cdr(&mut self, data: &[u8]) -> Result<(), ReturnCodes> {
-        match unsafe {
-            cyclonedds_sys::dds_writecdr(
-                self.writer,
-                data.as_ptr() as *mut cyclonedds_sys::ddsi_serdata,
-            )
-        } {
+    /// This call causes the writer to write the serialized sample, e.g. one
+    /// taken with [crate::subscriber::DataReader::takecdr]. Timestamp and
+    /// statusinfo fields are set to the current time and 0 (indicating a
+    /// regular write), respectively. Fails with [ReturnCodes::BadParameter]
+    /// if the sample is not of the sertype of the topic of the writer.
+    pub fn write_cdr(&mut self, sample: &SerializedSample) -> Result<(), ReturnCodes> {
+        let serdata = self.serdata_of(sample)?;
+        match unsafe { cyclonedds_sys::dds_writecdr(self.writer, serdata.cast()) } {
             0 => Ok(()),
             result => Err(ReturnCodes::from(result)),
         }
@@ -186,14 +254,25 @@ impl<T: TopicType> DataWriter<T> {
 
     /// Write a serialized value of a data instance
     ///
-    /// This call causes the writer to write the serialized value that is
-    /// provided in the serdata argument. Timestamp and statusinfo are used as
-    /// is.
-    pub fn forwardcdr(
-        &mut self,
-        data: *mut cyclonedds_sys::ddsi_serdata,
-    ) -> Result<(), ReturnCodes> {
-        todo!("not implemented")
+    /// This call causes the writer to write the serialized sample. Timestamp
+    /// and statusinfo are used as is, so samples read from one domain can
+    /// be forwarded to another unchanged. Fails like [DataWriter::write_cdr].
+    pub fn forward_cdr(&mut self, sample: &SerializedSample) -> Result<(), ReturnCodes> {
+        let serdata = self.serdata_of(sample)?;
+        match unsafe { cyclonedds_sys::dds_forwardcdr(self.writer, serdata.cast()) } {
+            0 => Ok(()),
+            result => Err(ReturnCodes::from(result)),
+        }
+    }
+
+    /// A reference to the serdata of `sample` for the writer to take over,
+    /// fails with [ReturnCodes::BadParameter] if it is not of the type of
+    /// the sertype of the writer.
+    fn serdata_of(&self, sample: &SerializedSample) -> Result<*mut ddsi_serdata, ReturnCodes> {
+        if !is_same_sertype(sample.sertype(), entity_sertype(self.writer)?) {
+            return Err(ReturnCodes::BadParameter);
+        }
+        Ok(sample.clone().into_raw())
     }
 
     /// Write the value of a data instance along with the source timestamp
@@ -229,7 +308,90 @@ impl<T: TopicType> DataWriter<T> {
     }
 }
 
-impl<T: TopicType> Drop for DataWriter<T> {
+impl<T: SerdeTopic> DataWriter<T> {
+    /// Write a serialized value of a data instance
+    ///
+    /// This call causes the writer to write the serialized sample, with its
+    /// encapsulation header, as in [crate::sertype::to_cdr]. Timestamp and
+    /// statusinfo fields are set to the current time and 0 (indicating a
+    /// regular write), respectively. Fails with [ReturnCodes::BadParameter]
+    /// if the topic was not created with [Topic::with_sertype] or `data`
+    /// is not a sample of `T`.
+    pub fn write_serialized_bytes(&mut self, data: &[u8]) -> Result<(), ReturnCodes> {
+        self.write_serdata(|sertype| unsafe { serdata_from_cdr::<T>(sertype, data.to_vec()) })
+    }
+
+    /// Writes the serdata `create` makes for the sertype of the topic,
+    /// fails with [ReturnCodes::BadParameter] if the topic was not created
+    /// with [Topic::with_sertype] or there is no serdata.
+    fn write_serdata(
+        &mut self,
+        create: impl FnOnce(*const ddsi_sertype) -> Option<*mut ddsi_serdata>,
+    ) -> Result<(), ReturnCodes> {
+        let sertype = entity_sertype(self.writer)?;
+        if !is_sertype_of::<T>(sertype) {
+            return Err(ReturnCodes::BadParameter);
+        }
+        let serdata = create(sertype).ok_or(ReturnCodes::BadParameter)?;
+        // The writer takes over the reference to the serdata.
+        match unsafe { cyclonedds_sys::dds_writecdr(self.writer, serdata.cast()) } {
+            0 => Ok(()),
+            result => Err(ReturnCodes::from(result)),
+        }
+    }
+}
+
+impl DataWriter<BytesTopic> {
+    /// Writes `data` as a sample of the topic without creating a
+    /// [BytesTopic], the bytes are copied once, into the serialized sample.
+    pub fn write_bytes(&mut self, data: &[u8]) -> Result<(), ReturnCodes> {
+        self.write_serdata(|sertype| {
+            let data = BytesTopic::cdr(data);
+            Some(unsafe { serdata_from_parts::<BytesTopic>(sertype, data, Vec::new()) })
+        })
+    }
+}
+
+impl DataWriter<KeyedBytesTopic> {
+    /// Writes `data` as a sample of the instance `key` without creating a
+    /// [KeyedBytesTopic], the bytes are copied once like those of a
+    /// [BytesTopic].
+    pub fn write_bytes(&mut self, key: &str, data: &[u8]) -> Result<(), ReturnCodes> {
+        self.write_serdata(|sertype| {
+            let data = KeyedBytesTopic::cdr(key, data);
+            let key = key_cdr(key);
+            Some(unsafe { serdata_from_parts::<KeyedBytesTopic>(sertype, data, key) })
+        })
+    }
+}
+
+impl<T: Loanable> DataWriter<T> {
+    /// Loans a sample from the writer, which is written in place and
+    /// published with [LoanedSample::commit] without copying it. With a
+    /// PSMX transport such as shared memory, the loan is in the memory it
+    /// publishes from. The sample starts out zeroed. Fails with
+    /// [ReturnCodes::BadParameter] if the topic of the writer was not
+    /// created with [Topic::new], only its default sertype loans a `T`.
+    pub fn loan(&mut self) -> Result<LoanedSample<'_, T>, ReturnCodes> {
+        if !is_default_sertype_of::<T>(entity_sertype(self.writer)?) {
+            return Err(ReturnCodes::BadParameter);
+        }
+        let mut sample = null_mut();
+        let result = unsafe { cyclonedds_sys::dds_request_loan(self.writer, &mut sample) };
+        if result < 0 {
+            return Err(ReturnCodes::from(result));
+        }
+        let sample = NonNull::new(sample.cast::<T>()).ok_or(ReturnCodes::Error)?;
+        debug_assert!(sample.as_ptr().is_aligned());
+        unsafe { sample.as_ptr().write_bytes(0, 1) };
+        Ok(LoanedSample {
+            writer: self,
+            sample,
+        })
+    }
+}
+
+impl<T> Drop for DataWriter<T> {
     fn drop(&mut self) {
         match unsafe { dds_delete(self.writer) } {
             0 => (),
@@ -238,6 +400,51 @@ impl<T: TopicType> Drop for DataWriter<T> {
     }
 }
 
+/// A sample loaned from a [DataWriter] with [DataWriter::loan]. Dropping it
+/// without [LoanedSample::commit] returns the loan unpublished.
+pub struct LoanedSample<'a, T: Loanable> {
+    writer: &'a mut DataWriter<T>,
+    sample: NonNull<T>,
+}
+
+impl<T: Loanable> LoanedSample<'_, T> {
+    /// Publishes the sample. The loan goes to Cyclone DDS, also if writing
+    /// fails.
+    pub fn commit(self) -> Result<(), ReturnCodes> {
+        let sample = self.sample.as_ptr() as *const c_void;
+        let writer = self.writer.writer;
+        mem::forget(self);
+        match unsafe { cyclonedds_sys::dds_write(writer, sample) } {
+            0 => Ok(()),
+            result => Err(ReturnCodes::from(result)),
+        }
+    }
+}
+
+impl<T: Loanable> Deref for LoanedSample<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.sample.as_ref() }
+    }
+}
+
+impl<T: Loanable> DerefMut for LoanedSample<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.sample.as_mut() }
+    }
+}
+
+impl<T: Loanable> Drop for LoanedSample<'_, T> {
+    fn drop(&mut self) {
+        let mut sample = self.sample.as_ptr().cast::<c_void>();
+        match unsafe { cyclonedds_sys::dds_return_loan(self.writer.writer, &mut sample, 1) } {
+            0 => (),
+            result => error!("failed to return a loan: {}", ReturnCodes::from(result)),
+        }
+    }
+}
+
 pub struct AnyDataWriter {
     writer: cyclonedds_sys::dds_entity_t,
 }
@@ -272,7 +479,7 @@ impl AnyDataWriter {
     }
 }
 
-impl<T: TopicType> From<DataWriter<T>> for AnyDataWriter {
+impl<T> From<DataWriter<T>> for AnyDataWriter {
     fn from(value: DataWriter<T>) -> Self {
         todo!()
     }
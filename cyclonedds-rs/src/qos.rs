@@ -5,7 +5,14 @@ use std::{
     time::Duration,
 };
 
-use crate::{core::ReturnCodes, IgnorelocalKind};
+use cyclonedds_sys::{
+    DDS_DATA_REPRESENTATION_FLAG_XCDR1, DDS_DATA_REPRESENTATION_FLAG_XCDR2,
+    DDS_DATA_REPRESENTATION_XCDR1, DDS_DATA_REPRESENTATION_XCDR2,
+};
+
+use tracing::error;
+
+use crate::{core::ReturnCodes, xcdr::XcdrVersion, IgnorelocalKind};
 
 /// Durability QoS: Applies to Topic, DataReader, DataWriter.
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -56,6 +63,57 @@ pub enum DestinationOrderKind {
     ByReceptionTimestamp,
     BySourceTimestamp,
 }
+/// DataRepresentation QoS: Applies to Topic, DataReader, DataWriter.
+///
+/// A writer serializes samples in the first representation of its QoS, a
+/// reader accepts samples in any representation of its QoS. Types that
+/// XCDR1 can not encode (appendable or mutable types, optional members)
+/// are restricted to XCDR2, see
+/// [TopicDescriptor::data_representations](crate::descriptor::TopicDescriptor::data_representations).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataRepresentation {
+    /// Extended CDR version 1, the representation of DDS before XTypes
+    Xcdr1,
+    /// Extended CDR version 2
+    Xcdr2,
+}
+
+impl DataRepresentation {
+    /// Identifier of the representation in a [Qos].
+    pub fn id(self) -> i16 {
+        match self {
+            DataRepresentation::Xcdr1 => DDS_DATA_REPRESENTATION_XCDR1 as i16,
+            DataRepresentation::Xcdr2 => DDS_DATA_REPRESENTATION_XCDR2 as i16,
+        }
+    }
+
+    /// The representation with identifier `id`, [None] for representations
+    /// other than XCDR1 and XCDR2.
+    pub fn from_id(id: i16) -> Option<DataRepresentation> {
+        match id as u32 {
+            DDS_DATA_REPRESENTATION_XCDR1 => Some(DataRepresentation::Xcdr1),
+            DDS_DATA_REPRESENTATION_XCDR2 => Some(DataRepresentation::Xcdr2),
+            _ => None,
+        }
+    }
+
+    /// Flag of the representation in the data representations a type
+    /// allows, as in `restrict_data_representation` of a topic descriptor.
+    pub fn flag(self) -> u32 {
+        match self {
+            DataRepresentation::Xcdr1 => DDS_DATA_REPRESENTATION_FLAG_XCDR1,
+            DataRepresentation::Xcdr2 => DDS_DATA_REPRESENTATION_FLAG_XCDR2,
+        }
+    }
+
+    /// The version of the extended CDR encoding of the representation.
+    pub fn xcdr_version(self) -> XcdrVersion {
+        match self {
+            DataRepresentation::Xcdr1 => XcdrVersion::Xcdr1,
+            DataRepresentation::Xcdr2 => XcdrVersion::Xcdr2,
+        }
+    }
+}
 
 pub struct Qos {
     pub(crate) qos: *mut cyclonedds_sys::dds_qos_t,
@@ -445,6 +503,15 @@ impl Qos {
         }
     }
 
+    /// Set the data representation policy of a [Qos] structure.
+    /// * `values` - Data representations, a writer uses the first one
+    pub fn set_data_representation(&mut self, values: &[DataRepresentation]) {
+        let ids: Vec<i16> = values.iter().map(|value| value.id()).collect();
+        unsafe {
+            cyclonedds_sys::dds_qset_data_representation(self.qos, ids.len() as u32, ids.as_ptr());
+        }
+    }
+
     pub fn set_psmx_instances(&mut self, instances: usize, values: &[&str]) {
         unsafe {
             let c_strings: Vec<CString> =
@@ -655,8 +722,69 @@ impl Qos {
         todo!()
     }
 
-    pub fn data_representation(&self) -> Vec<i8> {
-        todo!()
+    /// Get the data representation policy from a qos structure, fails if
+    /// the policy is not set or lists a representation other than XCDR1
+    /// and XCDR2.
+    pub fn data_representation(&self) -> Result<Vec<DataRepresentation>, &'static str> {
+        self.data_representation_ids()
+            .ok_or("Failed to get data representation")?
+            .into_iter()
+            .map(DataRepresentation::from_id)
+            .collect::<Option<_>>()
+            .ok_or("Unsupported data representation")
+    }
+
+    /// The identifiers of the data representation policy, [None] if it is
+    /// not set.
+    fn data_representation_ids(&self) -> Option<Vec<i16>> {
+        let mut n = 0;
+        let mut ids_ptr: *mut i16 = null_mut();
+        let status =
+            unsafe { cyclonedds_sys::dds_qget_data_representation(self.qos, &mut n, &mut ids_ptr) };
+        if !status {
+            return None;
+        }
+        let ids = match ids_ptr.is_null() {
+            true => Vec::new(),
+            false => unsafe { std::slice::from_raw_parts(ids_ptr, n as usize) }.to_vec(),
+        };
+        unsafe { cyclonedds_sys::dds_free(ids_ptr as *mut c_void) };
+        Some(ids)
+    }
+
+    /// Returns a copy of the [Qos] of a reader or writer of a topic whose
+    /// type allows the data representations `allowed`, with the data
+    /// representations set if they are not: the first allowed one for a
+    /// writer, all of them for a reader. Fails with
+    /// [ReturnCodes::BadParameter] if the [Qos] lists a representation that
+    /// is not allowed or none is allowed.
+    pub(crate) fn for_endpoint(
+        &self,
+        allowed: &[DataRepresentation],
+        writer: bool,
+    ) -> Result<Qos, ReturnCodes> {
+        if allowed.is_empty() {
+            error!("the type allows no data representation");
+            return Err(ReturnCodes::BadParameter);
+        }
+        let mut qos = self.clone();
+        match self.data_representation_ids() {
+            Some(ids) => {
+                let all_allowed = ids.iter().all(|id| {
+                    DataRepresentation::from_id(*id).is_some_and(|value| allowed.contains(&value))
+                });
+                if ids.is_empty() || !all_allowed {
+                    error!(
+                        "data representations {:?} are not among the allowed {:?}",
+                        ids, allowed
+                    );
+                    return Err(ReturnCodes::BadParameter);
+                }
+            }
+            None if writer => qos.set_data_representation(&allowed[..1]),
+            None => qos.set_data_representation(allowed),
+        }
+        Ok(qos)
     }
 
     pub fn entity_name(&self) -> String {
@@ -717,12 +845,11 @@ impl Clone for Qos {
 
     /******  f86bd946-8fac-4c62-8526-50f603c8ca89  *******/
     fn clone(&self) -> Qos {
+        let qos = Qos::new();
         unsafe {
-            let qos_output = std::ptr::null_mut();
-            cyclonedds_sys::dds_copy_qos(self.qos, qos_output);
-
-            Qos { qos: qos_output }
+            cyclonedds_sys::dds_copy_qos(qos.qos, self.qos);
         }
+        qos
     }
 }
 
@@ -807,7 +934,11 @@ pub enum TypeConsistencyKind {
 
 #[cfg(test)]
 mod tests {
-    use crate::{domain::DomainParticipant, qos::Qos};
+    use crate::{
+        core::ReturnCodes,
+        domain::DomainParticipant,
+        qos::{DataRepresentation, Qos},
+    };
 
     #[test]
     fn test_new() {
@@ -820,6 +951,55 @@ mod tests {
         qos.reset();
     }
 
+    #[test]
+    fn test_data_representation() {
+        let mut qos = Qos::new();
+        assert!(qos.data_representation().is_err());
+        qos.set_data_representation(&[DataRepresentation::Xcdr2, DataRepresentation::Xcdr1]);
+        assert_eq!(
+            qos.data_representation(),
+            Ok(vec![DataRepresentation::Xcdr2, DataRepresentation::Xcdr1])
+        );
+        assert_eq!(
+            qos.clone().data_representation(),
+            Ok(vec![DataRepresentation::Xcdr2, DataRepresentation::Xcdr1])
+        );
+    }
+
+    #[test]
+    fn test_endpoint_data_representation() {
+        let both = [DataRepresentation::Xcdr1, DataRepresentation::Xcdr2];
+        let qos = Qos::new();
+        let writer = qos.for_endpoint(&both, true).unwrap();
+        assert_eq!(
+            writer.data_representation(),
+            Ok(vec![DataRepresentation::Xcdr1])
+        );
+        let reader = qos.for_endpoint(&both, false).unwrap();
+        assert_eq!(reader.data_representation(), Ok(both.to_vec()));
+        let writer = qos.for_endpoint(&both[1..], true).unwrap();
+        assert_eq!(
+            writer.data_representation(),
+            Ok(vec![DataRepresentation::Xcdr2])
+        );
+
+        let mut qos = Qos::new();
+        qos.set_data_representation(&[DataRepresentation::Xcdr1]);
+        assert!(matches!(
+            qos.for_endpoint(&both[1..], false),
+            Err(ReturnCodes::BadParameter)
+        ));
+        assert!(qos.data_representation().is_ok());
+
+        // A descriptor may restrict its type to no representation at all
+        for writer in [true, false] {
+            assert!(matches!(
+                Qos::new().for_endpoint(&[], writer),
+                Err(ReturnCodes::BadParameter)
+            ));
+        }
+    }
+
     #[test]
     fn test_debug() {
         let particpant = DomainParticipant::default();
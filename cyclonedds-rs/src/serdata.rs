@@ -0,0 +1,293 @@
+//! Serialized samples as Cyclone DDS keeps them.
+//!
+//! Cyclone DDS holds every sample as a reference counted `ddsi_serdata`,
+//! which the sertype of the topic creates and which knows how to produce
+//! the serialized form of the sample, the CDR bytes that go on the wire.
+//! [SerializedSample] is a reference to one, as returned by
+//! [DataReader::readcdr] and friends and accepted by
+//! [DataWriter::write_cdr] and [DataWriter::forward_cdr]. Nothing about the
+//! type of the samples is needed, which is what bridges and recorders want.
+//!
+//! ```ignore
+//! for (sample, info) in reader.takecdr(32)? {
+//!     if info.valid_data() {
+//!         log.push((sample.timestamp(), sample.to_vec()));
+//!     }
+//!     writer.forward_cdr(&sample)?;
+//! }
+//! ```
+//!
+//! [DataReader::readcdr]: crate::subscriber::DataReader::readcdr
+//! [DataWriter::write_cdr]: crate::publisher::DataWriter::write_cdr
+//! [DataWriter::forward_cdr]: crate::publisher::DataWriter::forward_cdr
+
+use std::{
+    marker::PhantomData,
+    mem::{self, MaybeUninit},
+    ops::Deref,
+    ptr::{self, NonNull},
+    slice,
+    sync::atomic::{AtomicU32, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use cyclonedds_sys::internal::{ddsi_serdata, ddsi_serdata_kind, ddsi_sertype, ddsrt_iovec_t};
+
+use crate::xcdr::HEADER_SIZE;
+
+/// The sample was disposed.
+pub const STATUSINFO_DISPOSE: u32 = 1;
+/// The sample was unregistered.
+pub const STATUSINFO_UNREGISTER: u32 = 2;
+
+/// What a [SerializedSample] holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleKind {
+    /// Nothing, e.g. an unregister without a key
+    Empty,
+    /// Only the key fields, e.g. a dispose
+    Key,
+    /// The whole sample
+    Data,
+}
+
+/// A reference to a sample serialized by the sertype of its topic. Clones
+/// are references to the same sample, which is freed once the last one is
+/// dropped.
+pub struct SerializedSample {
+    serdata: NonNull<ddsi_serdata>,
+}
+
+// The serdata is immutable once created, only its reference count changes
+// and it does so atomically.
+unsafe impl Send for SerializedSample {}
+unsafe impl Sync for SerializedSample {}
+
+impl SerializedSample {
+    /// Takes over a reference to `serdata`, [None] if it is null.
+    ///
+    /// # Safety
+    ///
+    /// `serdata` must be null or a valid serdata, of which the caller owns a
+    /// reference.
+    pub(crate) unsafe fn from_raw(serdata: *mut ddsi_serdata) -> Option<SerializedSample> {
+        NonNull::new(serdata).map(|serdata| SerializedSample { serdata })
+    }
+
+    /// Gives up the reference, which the caller must release.
+    pub(crate) fn into_raw(self) -> *mut ddsi_serdata {
+        let serdata = self.serdata.as_ptr();
+        mem::forget(self);
+        serdata
+    }
+
+    fn serdata(&self) -> &ddsi_serdata {
+        unsafe { self.serdata.as_ref() }
+    }
+
+    /// The sertype of the sample, null for an untyped sample.
+    pub(crate) fn sertype(&self) -> *const ddsi_sertype {
+        self.serdata().type_
+    }
+
+    fn refc(&self) -> &AtomicU32 {
+        unsafe { AtomicU32::from_ptr(ptr::addr_of_mut!((*self.serdata.as_ptr()).refc.v)) }
+    }
+
+    pub fn kind(&self) -> SampleKind {
+        match self.serdata().kind {
+            ddsi_serdata_kind::SDK_EMPTY => SampleKind::Empty,
+            ddsi_serdata_kind::SDK_KEY => SampleKind::Key,
+            ddsi_serdata_kind::SDK_DATA => SampleKind::Data,
+        }
+    }
+
+    /// The source timestamp, [None] if the sample was not written yet.
+    pub fn timestamp(&self) -> Option<SystemTime> {
+        match self.serdata().timestamp.v {
+            i64::MIN => None,
+            nanos => Some(SystemTime::UNIX_EPOCH + Duration::from_nanos(nanos.max(0) as u64)),
+        }
+    }
+
+    /// The [STATUSINFO_DISPOSE] and [STATUSINFO_UNREGISTER] flags, 0 for a
+    /// regular write.
+    pub fn statusinfo(&self) -> u32 {
+        self.serdata().statusinfo
+    }
+
+    /// Size of the serialized sample, including the encapsulation header.
+    pub fn len(&self) -> usize {
+        let ops = unsafe { &*self.serdata().ops };
+        unsafe { ops.get_size.unwrap()(self.serdata.as_ptr()) as usize }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The encapsulation identifier of the serialized sample, one of the
+    /// constants of [crate::xcdr], or [None] if it has no header.
+    pub fn encoding(&self) -> Option<u16> {
+        if self.len() < HEADER_SIZE {
+            return None;
+        }
+        let mut header = [0u8; HEADER_SIZE];
+        let ops = unsafe { &*self.serdata().ops };
+        unsafe {
+            ops.to_ser.unwrap()(
+                self.serdata.as_ptr(),
+                0,
+                HEADER_SIZE,
+                header.as_mut_ptr().cast(),
+            )
+        };
+        Some(u16::from_be_bytes([header[0], header[1]]))
+    }
+
+    /// Copies the serialized sample, including the encapsulation header.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let len = self.len();
+        let mut data = Vec::<u8>::with_capacity(len);
+        let ops = unsafe { &*self.serdata().ops };
+        unsafe {
+            ops.to_ser.unwrap()(self.serdata.as_ptr(), 0, len, data.as_mut_ptr().cast());
+            data.set_len(len);
+        }
+        data
+    }
+
+    /// The serialized sample without copying it, if the sertype keeps it
+    /// in one piece, which the default sertype of Cyclone DDS and the one
+    /// of [crate::sertype] do.
+    pub fn bytes(&self) -> SerializedBytes<'_> {
+        let len = self.len();
+        let ops = unsafe { &*self.serdata().ops };
+        let mut iov = MaybeUninit::<ddsrt_iovec_t>::uninit();
+        let serdata =
+            unsafe { ops.to_ser_ref.unwrap()(self.serdata.as_ptr(), 0, len, iov.as_mut_ptr()) };
+        SerializedBytes {
+            serdata,
+            iov: unsafe { iov.assume_init() },
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Clone for SerializedSample {
+    fn clone(&self) -> Self {
+        self.refc().fetch_add(1, Ordering::Relaxed);
+        SerializedSample {
+            serdata: self.serdata,
+        }
+    }
+}
+
+impl Drop for SerializedSample {
+    fn drop(&mut self) {
+        if self.refc().fetch_sub(1, Ordering::AcqRel) == 1 {
+            let ops = unsafe { &*self.serdata().ops };
+            unsafe { ops.free.unwrap()(self.serdata.as_ptr()) };
+        }
+    }
+}
+
+impl std::fmt::Debug for SerializedSample {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SerializedSample")
+            .field("kind", &self.kind())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// The serialized form of a [SerializedSample] borrowed from its serdata,
+/// see [SerializedSample::bytes].
+pub struct SerializedBytes<'a> {
+    serdata: *mut ddsi_serdata,
+    iov: ddsrt_iovec_t,
+    _marker: PhantomData<&'a SerializedSample>,
+}
+
+impl Deref for SerializedBytes<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        if self.iov.iov_len == 0 {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.iov.iov_base.cast(), self.iov.iov_len) }
+    }
+}
+
+impl Drop for SerializedBytes<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            let ops = &*(*self.serdata).ops;
+            ops.to_ser_unref.unwrap()(self.serdata, &self.iov);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{SampleKind, SerializedSample};
+    use crate::{
+        sertype::{key_cdr, serdata_from_cdr, sertype, to_cdr},
+        topic::SerdeTopic,
+        xcdr::{XcdrError, CDR_LE},
+    };
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Reading {
+        sensor: String,
+        value: f64,
+    }
+
+    impl SerdeTopic for Reading {
+        fn has_key() -> bool {
+            true
+        }
+
+        fn key(&self) -> Result<Vec<u8>, XcdrError> {
+            Ok(key_cdr(&self.sensor))
+        }
+    }
+
+    #[test]
+    fn test_serialized_sample() {
+        let sertype = sertype::<Reading>();
+        let data = to_cdr(&Reading {
+            sensor: "a".to_string(),
+            value: 0.5,
+        })
+        .unwrap();
+
+        unsafe {
+            let serdata = serdata_from_cdr::<Reading>(sertype, data.clone()).unwrap();
+            let sample = SerializedSample::from_raw(serdata).unwrap();
+            assert_eq!(sample.kind(), SampleKind::Data);
+            assert_eq!(sample.timestamp(), None);
+            assert_eq!(sample.statusinfo(), 0);
+            assert_eq!(sample.len(), data.len());
+            assert_eq!(sample.encoding(), Some(CDR_LE));
+            assert_eq!(sample.to_vec(), data);
+
+            // Clones and borrowed bytes hold references of their own
+            let clone = sample.clone();
+            assert_eq!((*serdata).refc.v, 2);
+            let bytes = sample.bytes();
+            assert_eq!(&bytes[..], &data[..]);
+            assert_eq!((*serdata).refc.v, 3);
+            drop(bytes);
+            drop(sample);
+            assert_eq!((*serdata).refc.v, 1);
+            assert_eq!(clone.to_vec(), data);
+            drop(clone);
+
+            ((*(*sertype).ops).free.unwrap())(sertype);
+        }
+    }
+}
@@ -0,0 +1,1118 @@
+//! A sertype that serializes samples with serde instead of an op program.
+//!
+//! Cyclone DDS converts between samples and their serialized form through a
+//! `ddsi_sertype` and the `ddsi_serdata` it creates. The default sertype
+//! interprets the `DDS_OP_*` program of a [crate::internal::TopicDescriptor]
+//! and therefore needs a C compatible sample layout. The sertype here is
+//! implemented in Rust for any [SerdeTopic]: samples are serialized with
+//! serde on write, deserialized on read, and the key, its hash and the
+//! printed form of a sample come from the Rust type.
+//!
+//! The samples Cyclone DDS hands to the sertype on write are `T`, the
+//! samples it reads into are `Option<T>`, which the sertype can create and
+//! drop without knowing anything about `T`.
+//!
+//! Samples are encoded by [crate::xcdr] with a little endian encapsulation
+//! header, as [SerdeTopic::description] describes them and in
+//! [SerdeTopic::data_representation]. Types without a description are
+//! encoded as final XCDR1 types. A key-only sample (a dispose or
+//! unregister) is the key of [SerdeTopic::key] after the big endian
+//! encapsulation header of [SerdeTopic::key_version], padded like a sample.
+//!
+//! This is how topic types that are not plain old data are published:
+//! their strings, sequences, optional members, maps and unions are
+//! serialized from and deserialized into Rust values, while the default
+//! sertype would treat their memory as the C types of the op program.
+
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    ffi::{c_char, c_void, CStr, CString},
+    marker::PhantomData,
+    mem::{size_of, MaybeUninit},
+    ptr, slice,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex, OnceLock,
+    },
+};
+
+use cyclonedds_sys::dds_entity_t;
+use cyclonedds_sys::internal::{
+    dds_free_op_t, ddsi_keyhash, ddsi_rdata, ddsi_rmsg, ddsi_serdata, ddsi_serdata_init,
+    ddsi_serdata_kind, ddsi_serdata_ops, ddsi_sertype, ddsi_sertype_fini, ddsi_sertype_init,
+    ddsi_sertype_ops, ddsi_sertype_v0, ddsrt_iovec_t, ddsrt_msg_iovlen_t, DDS_FREE_ALL_BIT,
+    DDS_FREE_CONTENTS_BIT, DDS_FREE_KEY_BIT,
+};
+use serde::Serialize;
+
+use crate::{
+    core::ReturnCodes,
+    descriptor::{registered_descriptor, TypeDesc, TypeExtensibility},
+    topic::{SerdeTopic, TopicType},
+    xcdr::{self, Endianness, XcdrVersion, HEADER_SIZE},
+};
+
+/// Serializes a key for [SerdeTopic::key] as big endian CDR, which is how
+/// a key-only sample carries it.
+pub fn key_cdr<K: Serialize + ?Sized>(key: &K) -> Vec<u8> {
+    xcdr::to_payload(key, None, XcdrVersion::Xcdr1, Endianness::Big)
+        .expect("keys can be serialized")
+}
+
+/// Serializes `sample` with its encapsulation header.
+pub fn to_cdr<T: SerdeTopic>(sample: &T) -> Option<Vec<u8>> {
+    ops::<T>().encoding.serialize(sample)
+}
+
+/// Deserializes a sample serialized with its encapsulation header, fails
+/// for anything but plain CDR if `T` has no description.
+pub fn from_cdr<T: SerdeTopic>(data: &[u8]) -> Option<T> {
+    ops::<T>().encoding.deserialize(data)
+}
+
+/// How the samples of a type are serialized, see [SerdeTopic::description]
+/// and [SerdeTopic::data_representation].
+struct Encoding {
+    desc: Option<TypeDesc>,
+    version: XcdrVersion,
+    /// See [SerdeTopic::key_version]
+    key_version: XcdrVersion,
+    /// See [SerdeTopic::key_is_fixed]
+    key_is_fixed: bool,
+}
+
+impl Encoding {
+    fn serialize<T: SerdeTopic>(&self, sample: &T) -> Option<Vec<u8>> {
+        xcdr::to_vec_with(sample, self.desc.as_ref(), self.version, Endianness::Little).ok()
+    }
+
+    fn deserialize<T: SerdeTopic>(&self, data: &[u8]) -> Option<T> {
+        xcdr::from_slice_with(data, self.desc.as_ref()).ok()
+    }
+
+    /// The key-only sample of `key`, padded to a multiple of 4 bytes like
+    /// a serialized sample.
+    fn key_payload(&self, key: &[u8]) -> Vec<u8> {
+        let id =
+            xcdr::encapsulation_id(self.key_version, Endianness::Big, TypeExtensibility::Final);
+        let size = HEADER_SIZE + key.len();
+        let padding = size.next_multiple_of(HEADER_SIZE) - size;
+        let mut payload = Vec::with_capacity(size + padding);
+        payload.extend_from_slice(&id.to_be_bytes());
+        payload.extend_from_slice(&[0, padding as u8]);
+        payload.extend_from_slice(key);
+        payload.resize(size + padding, 0);
+        payload
+    }
+
+    /// The key hash of `key`: the key padded with zeros if keys of the type
+    /// always fit, otherwise its MD5 hash, as [xcdr::key_hash] computes it.
+    fn key_hash(&self, key: &[u8], force_md5: bool) -> [u8; 16] {
+        if force_md5 || !self.key_is_fixed || key.len() > 16 {
+            md5::compute(key).0
+        } else {
+            let mut hash = [0; 16];
+            hash[..key.len()].copy_from_slice(key);
+            hash
+        }
+    }
+}
+
+/// The sertype and serdata operations of a type, one pair per type so that
+/// Cyclone DDS recognizes sertypes of the same type by their operations,
+/// and the encoding of its samples.
+struct Ops {
+    sertype: ddsi_sertype_ops,
+    serdata: ddsi_serdata_ops,
+    encoding: Encoding,
+}
+
+// Function pointers and a null argument only.
+unsafe impl Send for Ops {}
+unsafe impl Sync for Ops {}
+
+static OPS: OnceLock<Mutex<HashMap<TypeId, &'static Ops>>> = OnceLock::new();
+
+fn ops<T: SerdeTopic>() -> &'static Ops {
+    let mut ops = OPS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    ops.entry(TypeId::of::<T>()).or_insert_with(|| {
+        Box::leak(Box::new(Ops {
+            sertype: ddsi_sertype_ops {
+                version: Some(ddsi_sertype_v0),
+                arg: ptr::null_mut(),
+                free: Some(SerType::<T>::free),
+                zero_samples: Some(SerType::<T>::zero_samples),
+                realloc_samples: Some(SerType::<T>::realloc_samples),
+                free_samples: Some(SerType::<T>::free_samples),
+                equal: Some(SerType::<T>::equal),
+                hash: Some(SerType::<T>::hash),
+                type_id: None,
+                type_map: None,
+                type_info: None,
+                derive_sertype: None,
+                get_serialized_size: None,
+                serialize_into: None,
+            },
+            serdata: ddsi_serdata_ops {
+                eqkey: Some(SerData::<T>::eqkey),
+                get_size: Some(SerData::<T>::get_size),
+                from_ser: Some(SerData::<T>::from_ser),
+                from_ser_iov: Some(SerData::<T>::from_ser_iov),
+                from_keyhash: Some(SerData::<T>::from_keyhash),
+                from_sample: Some(SerData::<T>::from_sample),
+                to_ser: Some(SerData::<T>::to_ser),
+                to_ser_ref: Some(SerData::<T>::to_ser_ref),
+                to_ser_unref: Some(SerData::<T>::to_ser_unref),
+                to_sample: Some(SerData::<T>::to_sample),
+                to_untyped: Some(SerData::<T>::to_untyped),
+                untyped_to_sample: Some(SerData::<T>::untyped_to_sample),
+                free: Some(SerData::<T>::free),
+                print: Some(SerData::<T>::print),
+                get_keyhash: Some(SerData::<T>::get_keyhash),
+                from_loaned_sample: None,
+                from_psmx: None,
+            },
+            encoding: Encoding {
+                desc: T::description(),
+                version: T::data_representation().xcdr_version(),
+                key_version: T::key_version(),
+                key_is_fixed: T::key_is_fixed(),
+            },
+        }))
+    })
+}
+
+/// Returns a new sertype for `T`, owned by the caller until it is handed to
+/// `dds_create_topic_sertype`.
+pub(crate) fn sertype<T: SerdeTopic>() -> *mut ddsi_sertype {
+    let ops = ops::<T>();
+    let name = CString::new(T::type_name()).expect("type names can not contain nul bytes");
+    let sertype = Box::into_raw(Box::new(SerType::<T> {
+        // Initialized by ddsi_sertype_init.
+        sertype: unsafe { MaybeUninit::zeroed().assume_init() },
+        encoding: &ops.encoding,
+        _marker: PhantomData,
+    }));
+    unsafe {
+        ddsi_sertype_init(
+            ptr::addr_of_mut!((*sertype).sertype),
+            name.as_ptr(),
+            &ops.sertype,
+            &ops.serdata,
+            !T::has_key(),
+        );
+        // Samples are serialized in one representation only, so readers
+        // and writers can not ask for the other one.
+        (*sertype).sertype.allowed_data_representation = T::data_representation().flag();
+    }
+    sertype.cast()
+}
+
+/// Returns whether `sertype` was created by [sertype] for `T`.
+pub(crate) fn is_sertype_of<T: SerdeTopic>(sertype: *const ddsi_sertype) -> bool {
+    !sertype.is_null() && ptr::eq(unsafe { (*sertype).ops }, &ops::<T>().sertype)
+}
+
+/// Returns whether `sertype` is the default sertype Cyclone DDS creates
+/// from the descriptor of `T`, the one that interprets its op program on
+/// the memory of the samples, rather than one created by [sertype].
+pub(crate) fn is_default_sertype_of<T: TopicType + 'static>(sertype: *const ddsi_sertype) -> bool {
+    if sertype.is_null() {
+        return false;
+    }
+    let (ops, name) = unsafe { ((*sertype).ops, CStr::from_ptr((*sertype).type_name)) };
+    let serde = OPS.get().is_some_and(|registry| {
+        registry
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .values()
+            .any(|other| ptr::eq(ops, &other.sertype))
+    });
+    !serde && name.to_str() == Ok(registered_descriptor::<T>().topic_descriptor().type_name())
+}
+
+/// Returns whether samples of `a` are samples of `b`: both are the same
+/// sertype or one of the same operations and type name, e.g. the sertypes
+/// of a type in two domains.
+pub(crate) fn is_same_sertype(a: *const ddsi_sertype, b: *const ddsi_sertype) -> bool {
+    if a.is_null() || b.is_null() {
+        return false;
+    }
+    ptr::eq(a, b)
+        || unsafe {
+            ptr::eq((*a).ops, (*b).ops)
+                && CStr::from_ptr((*a).type_name) == CStr::from_ptr((*b).type_name)
+        }
+}
+
+/// Returns the sertype of a reader, writer or topic.
+pub(crate) fn entity_sertype(entity: dds_entity_t) -> Result<*const ddsi_sertype, ReturnCodes> {
+    let mut sertype = ptr::null();
+    let result = unsafe { cyclonedds_sys::dds_get_entity_sertype(entity, &mut sertype) };
+    if result < 0 {
+        Err(ReturnCodes::from(result))
+    } else {
+        Ok(sertype.cast())
+    }
+}
+
+/// Returns a serdata of `sertype` for a sample serialized with its
+/// encapsulation header, absent if it can not be deserialized.
+///
+/// # Safety
+///
+/// `sertype` must have been created by [sertype] for `T`.
+pub(crate) unsafe fn serdata_from_cdr<T: SerdeTopic>(
+    sertype: *const ddsi_sertype,
+    data: Vec<u8>,
+) -> Option<*mut ddsi_serdata> {
+    let serdata = SerData::<T>::from_wire(sertype, ddsi_serdata_kind::SDK_DATA, data);
+    (!serdata.is_null()).then_some(serdata)
+}
+
+/// Returns a serdata of `sertype` for a sample serialized with its
+/// encapsulation header, with the key the caller already knows, so the
+/// sample is not deserialized.
+///
+/// # Safety
+///
+/// `sertype` must have been created by [sertype] for `T`, `data` must be a
+/// sample of `T` and `key` its [SerdeTopic::key].
+pub(crate) unsafe fn serdata_from_parts<T: SerdeTopic>(
+    sertype: *const ddsi_sertype,
+    data: Vec<u8>,
+    key: Vec<u8>,
+) -> *mut ddsi_serdata {
+    SerData::<T>::alloc(sertype, ddsi_serdata_kind::SDK_DATA, data, key, None)
+}
+
+/// FNV-1a, for the hashes Cyclone DDS uses within the process.
+fn fnv1a(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+#[repr(C)]
+struct SerType<T> {
+    sertype: ddsi_sertype,
+    /// The encoding of [Ops], kept here so that samples are serialized
+    /// without looking up the operations of `T`
+    encoding: &'static Encoding,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: SerdeTopic> SerType<T> {
+    /// The encoding of the samples of `sertype`.
+    unsafe fn encoding<'a>(sertype: *const ddsi_sertype) -> &'a Encoding {
+        (*sertype.cast::<SerType<T>>()).encoding
+    }
+
+    unsafe extern "C" fn free(sertype: *mut ddsi_sertype) {
+        ddsi_sertype_fini(sertype);
+        drop(Box::from_raw(sertype.cast::<SerType<T>>()));
+    }
+
+    unsafe extern "C" fn zero_samples(
+        _sertype: *const ddsi_sertype,
+        samples: *mut c_void,
+        count: usize,
+    ) {
+        let samples = samples.cast::<Option<T>>();
+        for index in 0..count {
+            samples.add(index).write(None);
+        }
+    }
+
+    unsafe extern "C" fn realloc_samples(
+        ptrs: *mut *mut c_void,
+        _sertype: *const ddsi_sertype,
+        old: *mut c_void,
+        oldcount: usize,
+        count: usize,
+    ) {
+        let old = old.cast::<Option<T>>();
+        for index in count..oldcount {
+            ptr::drop_in_place(old.add(index));
+        }
+        let samples =
+            cyclonedds_sys::dds_realloc(old.cast(), (count * size_of::<Option<T>>()).max(1))
+                .cast::<Option<T>>();
+        for index in oldcount..count {
+            samples.add(index).write(None);
+        }
+        if !ptrs.is_null() {
+            for index in 0..count {
+                *ptrs.add(index) = samples.add(index).cast();
+            }
+        }
+    }
+
+    unsafe extern "C" fn free_samples(
+        _sertype: *const ddsi_sertype,
+        ptrs: *mut *mut c_void,
+        count: usize,
+        op: dds_free_op_t,
+    ) {
+        if ptrs.is_null() || count == 0 {
+            return;
+        }
+        let op = op as u32;
+        if op & (DDS_FREE_KEY_BIT | DDS_FREE_CONTENTS_BIT) != 0 {
+            for index in 0..count {
+                let sample = (*ptrs.add(index)).cast::<Option<T>>();
+                if !sample.is_null() {
+                    *sample = None;
+                }
+            }
+        }
+        if op & DDS_FREE_ALL_BIT != 0 {
+            cyclonedds_sys::dds_free(*ptrs);
+        }
+    }
+
+    unsafe extern "C" fn equal(_a: *const ddsi_sertype, _b: *const ddsi_sertype) -> bool {
+        // Cyclone DDS compares the names and operations, which are per type.
+        true
+    }
+
+    unsafe extern "C" fn hash(_sertype: *const ddsi_sertype) -> u32 {
+        fnv1a(T::type_name().as_bytes())
+    }
+}
+
+#[repr(C)]
+struct SerData<T> {
+    serdata: ddsi_serdata,
+    /// What goes on the wire: the serialized sample, or the key of a
+    /// key-only sample, after the encapsulation header.
+    data: Vec<u8>,
+    /// The key of the sample, see [SerdeTopic::key].
+    key: Vec<u8>,
+    /// The serialized sample of a key-only serdata made from a sample.
+    sample: Option<Vec<u8>>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: SerdeTopic> SerData<T> {
+    /// Creates a serdata with a reference count of one.
+    unsafe fn alloc(
+        sertype: *const ddsi_sertype,
+        kind: ddsi_serdata_kind,
+        data: Vec<u8>,
+        key: Vec<u8>,
+        sample: Option<Vec<u8>>,
+    ) -> *mut ddsi_serdata {
+        let serdata = Box::into_raw(Box::new(SerData::<T> {
+            // Initialized by ddsi_serdata_init.
+            serdata: MaybeUninit::zeroed().assume_init(),
+            data,
+            key,
+            sample,
+            _marker: PhantomData,
+        }));
+        ddsi_serdata_init(ptr::addr_of_mut!((*serdata).serdata), sertype, kind);
+        (*serdata).serdata.hash = fnv1a(&(*serdata).key) ^ (*sertype).serdata_basehash;
+        serdata.cast()
+    }
+
+    unsafe fn get<'a>(serdata: *const ddsi_serdata) -> &'a SerData<T> {
+        &*serdata.cast::<SerData<T>>()
+    }
+
+    /// The serialized sample, absent for a key-only sample from the network.
+    fn sample(&self) -> Option<&[u8]> {
+        match self.serdata.kind {
+            ddsi_serdata_kind::SDK_DATA => Some(&self.data),
+            _ => self.sample.as_deref(),
+        }
+    }
+
+    unsafe fn from_wire(
+        sertype: *const ddsi_sertype,
+        kind: ddsi_serdata_kind,
+        data: Vec<u8>,
+    ) -> *mut ddsi_serdata {
+        match kind {
+            ddsi_serdata_kind::SDK_DATA => {
+                let sample = SerType::<T>::encoding(sertype).deserialize::<T>(&data);
+                match sample.map(|sample| sample.key()) {
+                    Some(Ok(key)) => Self::alloc(sertype, kind, data, key, None),
+                    _ => ptr::null_mut(),
+                }
+            }
+            ddsi_serdata_kind::SDK_KEY if data.len() >= HEADER_SIZE => {
+                // The options of the header end with the number of padding
+                // bytes after the key
+                let padding = (data[3] & 3) as usize;
+                let end = (data.len() - padding).max(HEADER_SIZE);
+                let key = data[HEADER_SIZE..end].to_vec();
+                Self::alloc(sertype, kind, data, key, None)
+            }
+            _ => ptr::null_mut(),
+        }
+    }
+
+    unsafe extern "C" fn eqkey(a: *const ddsi_serdata, b: *const ddsi_serdata) -> bool {
+        Self::get(a).key == Self::get(b).key
+    }
+
+    unsafe extern "C" fn get_size(serdata: *const ddsi_serdata) -> u32 {
+        Self::get(serdata).data.len() as u32
+    }
+
+    unsafe extern "C" fn from_ser(
+        sertype: *const ddsi_sertype,
+        kind: ddsi_serdata_kind,
+        mut fragment: *const ddsi_rdata,
+        size: usize,
+    ) -> *mut ddsi_serdata {
+        // The fragments may overlap, each one continues where the previous
+        // one ended.
+        let mut data = Vec::with_capacity(size);
+        while !fragment.is_null() {
+            let rdata = &*fragment;
+            if rdata.maxp1 as usize > data.len() {
+                let payload = rdata
+                    .rmsg
+                    .cast::<u8>()
+                    .add(size_of::<ddsi_rmsg>() + rdata.payload_zoff as usize);
+                let start = data.len() - rdata.min as usize;
+                let len = rdata.maxp1 as usize - data.len();
+                data.extend_from_slice(slice::from_raw_parts(payload.add(start), len));
+            }
+            fragment = rdata.nextfrag;
+        }
+        Self::from_wire(sertype, kind, data)
+    }
+
+    unsafe extern "C" fn from_ser_iov(
+        sertype: *const ddsi_sertype,
+        kind: ddsi_serdata_kind,
+        niov: ddsrt_msg_iovlen_t,
+        iov: *const ddsrt_iovec_t,
+        size: usize,
+    ) -> *mut ddsi_serdata {
+        let mut data = Vec::with_capacity(size);
+        for iov in slice::from_raw_parts(iov, niov) {
+            data.extend_from_slice(slice::from_raw_parts(
+                iov.iov_base.cast::<u8>(),
+                iov.iov_len,
+            ));
+        }
+        Self::from_wire(sertype, kind, data)
+    }
+
+    unsafe extern "C" fn from_keyhash(
+        _sertype: *const ddsi_sertype,
+        _keyhash: *const ddsi_keyhash,
+    ) -> *mut ddsi_serdata {
+        // The key can not be recovered from its hash.
+        ptr::null_mut()
+    }
+
+    unsafe extern "C" fn from_sample(
+        sertype: *const ddsi_sertype,
+        kind: ddsi_serdata_kind,
+        sample: *const c_void,
+    ) -> *mut ddsi_serdata {
+        let sample = &*sample.cast::<T>();
+        let encoding = SerType::<T>::encoding(sertype);
+        // A sample that can not be serialized fails the write
+        let (Some(data), Ok(key)) = (encoding.serialize(sample), sample.key()) else {
+            return ptr::null_mut();
+        };
+        match kind {
+            ddsi_serdata_kind::SDK_DATA => Self::alloc(sertype, kind, data, key, None),
+            ddsi_serdata_kind::SDK_KEY => {
+                let payload = encoding.key_payload(&key);
+                Self::alloc(sertype, kind, payload, key, Some(data))
+            }
+            ddsi_serdata_kind::SDK_EMPTY => ptr::null_mut(),
+        }
+    }
+
+    unsafe extern "C" fn to_ser(
+        serdata: *const ddsi_serdata,
+        offset: usize,
+        size: usize,
+        buf: *mut c_void,
+    ) {
+        let data = &Self::get(serdata).data[offset..offset + size];
+        ptr::copy_nonoverlapping(data.as_ptr(), buf.cast(), size);
+    }
+
+    unsafe extern "C" fn to_ser_ref(
+        serdata: *const ddsi_serdata,
+        offset: usize,
+        size: usize,
+        iov: *mut ddsrt_iovec_t,
+    ) -> *mut ddsi_serdata {
+        let data = &Self::get(serdata).data[offset..offset + size];
+        (*iov).iov_base = data.as_ptr() as *mut c_void;
+        (*iov).iov_len = size as _;
+        let refc = AtomicU32::from_ptr(ptr::addr_of!((*serdata).refc.v) as *mut u32);
+        refc.fetch_add(1, Ordering::Relaxed);
+        serdata as *mut ddsi_serdata
+    }
+
+    unsafe extern "C" fn to_ser_unref(serdata: *mut ddsi_serdata, _iov: *const ddsrt_iovec_t) {
+        let refc = AtomicU32::from_ptr(ptr::addr_of_mut!((*serdata).refc.v));
+        if refc.fetch_sub(1, Ordering::AcqRel) == 1 {
+            Self::free(serdata);
+        }
+    }
+
+    /// Deserializes the sample of `serdata` as `sertype` does, absent for a
+    /// key-only sample from the network.
+    unsafe fn decode(sertype: *const ddsi_sertype, serdata: *const ddsi_serdata) -> Option<T> {
+        if sertype.is_null() {
+            return None;
+        }
+        let encoding = SerType::<T>::encoding(sertype);
+        Self::get(serdata)
+            .sample()
+            .and_then(|data| encoding.deserialize(data))
+    }
+
+    unsafe fn fill(sample: *mut c_void, value: Option<T>) -> bool {
+        match value {
+            Some(value) => {
+                *sample.cast::<Option<T>>() = Some(value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    unsafe extern "C" fn to_sample(
+        serdata: *const ddsi_serdata,
+        sample: *mut c_void,
+        _bufptr: *mut *mut c_void,
+        _buflim: *mut c_void,
+    ) -> bool {
+        Self::fill(sample, Self::decode((*serdata).type_, serdata))
+    }
+
+    unsafe extern "C" fn to_untyped(serdata: *const ddsi_serdata) -> *mut ddsi_serdata {
+        let this = Self::get(serdata);
+        let (data, sample) = match this.serdata.kind {
+            ddsi_serdata_kind::SDK_DATA => {
+                let payload = SerType::<T>::encoding(this.serdata.type_).key_payload(&this.key);
+                (payload, Some(this.data.clone()))
+            }
+            _ => (this.data.clone(), this.sample.clone()),
+        };
+        let untyped = Self::alloc(
+            this.serdata.type_,
+            ddsi_serdata_kind::SDK_KEY,
+            data,
+            this.key.clone(),
+            sample,
+        );
+        (*untyped).type_ = ptr::null();
+        untyped
+    }
+
+    unsafe extern "C" fn untyped_to_sample(
+        sertype: *const ddsi_sertype,
+        serdata: *const ddsi_serdata,
+        sample: *mut c_void,
+        _bufptr: *mut *mut c_void,
+        _buflim: *mut c_void,
+    ) -> bool {
+        // The untyped serdata has no sertype of its own.
+        Self::fill(sample, Self::decode(sertype, serdata))
+    }
+
+    unsafe extern "C" fn free(serdata: *mut ddsi_serdata) {
+        drop(Box::from_raw(serdata.cast::<SerData<T>>()));
+    }
+
+    unsafe extern "C" fn print(
+        sertype: *const ddsi_sertype,
+        serdata: *const ddsi_serdata,
+        buf: *mut c_char,
+        size: usize,
+    ) -> usize {
+        if size == 0 {
+            return 0;
+        }
+        let this = Self::get(serdata);
+        let text = match Self::decode(sertype, serdata) {
+            Some(sample) => format!("{sample:?}"),
+            None => format!("key {:02x?}", this.key),
+        };
+        let len = text.len().min(size - 1);
+        ptr::copy_nonoverlapping(text.as_ptr(), buf.cast(), len);
+        *buf.add(len) = 0;
+        len
+    }
+
+    unsafe extern "C" fn get_keyhash(
+        serdata: *const ddsi_serdata,
+        keyhash: *mut ddsi_keyhash,
+        force_md5: bool,
+    ) {
+        // An untyped serdata has no sertype of its own
+        let sertype = (*serdata).type_;
+        let encoding = match sertype.is_null() {
+            true => &ops::<T>().encoding,
+            false => SerType::<T>::encoding(sertype),
+        };
+        (*keyhash).value = encoding.key_hash(&Self::get(serdata).key, force_md5);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        ffi::{CStr, CString},
+        mem::MaybeUninit,
+        ptr,
+    };
+
+    use cyclonedds_sys::{
+        internal::{ddsi_keyhash, ddsi_serdata_kind, ddsi_sertype, ddsrt_iovec_t},
+        DDS_DATA_REPRESENTATION_FLAG_XCDR1, DDS_DATA_REPRESENTATION_FLAG_XCDR2,
+    };
+    use serde::{Deserialize, Serialize};
+
+    use super::{
+        from_cdr, is_default_sertype_of, is_same_sertype, is_sertype_of, key_cdr, ops, sertype,
+        to_cdr,
+    };
+    use crate::{
+        qos::DataRepresentation,
+        topic::{SerdeTopic, TopicType},
+        xcdr::{self, Endianness, XcdrError, XcdrVersion},
+    };
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Reading {
+        sensor: String,
+        values: Vec<f64>,
+    }
+
+    impl SerdeTopic for Reading {
+        fn has_key() -> bool {
+            true
+        }
+
+        fn key(&self) -> Result<Vec<u8>, XcdrError> {
+            Ok(key_cdr(&self.sensor))
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Counter(u64);
+
+    impl SerdeTopic for Counter {}
+
+    #[derive(TopicType, Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Sensor {
+        #[dds(key)]
+        name: String,
+        values: Vec<f64>,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Calibration {
+        #[dds(key)]
+        id: u32,
+        offset: Option<f64>,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Inventory {
+        #[dds(key)]
+        store: u32,
+        counts: HashMap<String, u32>,
+        prices: BTreeMap<u16, f64>,
+    }
+
+    fn reading(sensor: &str) -> Reading {
+        Reading {
+            sensor: sensor.to_string(),
+            values: vec![1.5, -2.0],
+        }
+    }
+
+    #[test]
+    fn test_cdr() {
+        let sample = reading("a");
+        let data = to_cdr(&sample).unwrap();
+        assert_eq!(data[..4], [0, 1, 0, 0]);
+        assert_eq!(from_cdr::<Reading>(&data), Some(sample));
+        // XCDR2
+        assert_eq!(from_cdr::<Reading>(&[0, 7, 0, 0]), None);
+        assert_eq!(from_cdr::<Reading>(&[0]), None);
+    }
+
+    #[test]
+    fn test_topic_type_cdr() {
+        let sample = Sensor {
+            name: "a".to_string(),
+            values: vec![1.5],
+        };
+        assert!(<Sensor as SerdeTopic>::has_key());
+        assert_eq!(Sensor::data_representation(), DataRepresentation::Xcdr1);
+        let data = to_cdr(&sample).unwrap();
+        assert_eq!(
+            data,
+            xcdr::to_vec(&sample, XcdrVersion::Xcdr1, Endianness::Little).unwrap()
+        );
+        assert_eq!(from_cdr::<Sensor>(&data), Some(sample.clone()));
+        assert_eq!(sample.key(), xcdr::to_key_payload(&sample, Endianness::Big));
+
+        // Optional members need XCDR2
+        let sample = Calibration {
+            id: 1,
+            offset: None,
+        };
+        assert_eq!(
+            Calibration::data_representation(),
+            DataRepresentation::Xcdr2
+        );
+        let data = to_cdr(&sample).unwrap();
+        assert_eq!(data[..2], [0, 7]);
+        assert_eq!(from_cdr::<Calibration>(&data), Some(sample));
+
+        for (sertype, flag) in [
+            (sertype::<Sensor>(), DDS_DATA_REPRESENTATION_FLAG_XCDR1),
+            (sertype::<Calibration>(), DDS_DATA_REPRESENTATION_FLAG_XCDR2),
+        ] {
+            unsafe {
+                assert_eq!((*sertype).allowed_data_representation, flag);
+                (*(*sertype).ops).free.unwrap()(sertype);
+            }
+        }
+    }
+
+    #[test]
+    fn test_map_cdr() {
+        let sample = Inventory {
+            store: 7,
+            counts: HashMap::from([("apple".to_string(), 3), ("pear".to_string(), 0)]),
+            prices: BTreeMap::from([(1, 0.5), (20, 2.25)]),
+        };
+        let data = to_cdr(&sample).unwrap();
+        assert_eq!(from_cdr::<Inventory>(&data), Some(sample));
+
+        let empty = Inventory {
+            store: 8,
+            counts: HashMap::new(),
+            prices: BTreeMap::new(),
+        };
+        let data = to_cdr(&empty).unwrap();
+        // The key and two empty maps
+        assert_eq!(data[4..], [8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(from_cdr::<Inventory>(&data), Some(empty));
+    }
+
+    #[test]
+    fn test_default_sertype() {
+        let serde = sertype::<Sensor>();
+        assert!(!is_default_sertype_of::<Sensor>(serde));
+
+        // Any other sertype of the name of the descriptor
+        let name = CString::new(Sensor::topic_descriptor().type_name()).unwrap();
+        let mut default = unsafe { MaybeUninit::<ddsi_sertype>::zeroed().assume_init() };
+        default.type_name = name.as_ptr().cast_mut();
+        assert!(is_default_sertype_of::<Sensor>(&default));
+        assert!(!is_default_sertype_of::<Calibration>(&default));
+        assert!(!is_default_sertype_of::<Sensor>(ptr::null()));
+        unsafe { ops::<Sensor>().sertype.free.unwrap()(serde) };
+    }
+
+    #[test]
+    fn test_same_sertype() {
+        let (a, b, other) = (
+            sertype::<Reading>(),
+            sertype::<Reading>(),
+            sertype::<Counter>(),
+        );
+        assert!(is_same_sertype(a, a));
+        assert!(is_same_sertype(a, b));
+        assert!(!is_same_sertype(a, other));
+        assert!(!is_same_sertype(a, ptr::null()));
+        unsafe {
+            for sertype in [a, b, other] {
+                (*(*sertype).ops).free.unwrap()(sertype);
+            }
+        }
+    }
+
+    #[test]
+    fn test_serdata() {
+        let sertype = sertype::<Reading>();
+        assert!(is_sertype_of::<Reading>(sertype));
+        assert!(!is_sertype_of::<Counter>(sertype));
+        let serdata_ops = &ops::<Reading>().serdata;
+        let sample = reading("a");
+
+        unsafe {
+            let serdata = serdata_ops.from_sample.unwrap()(
+                sertype,
+                ddsi_serdata_kind::SDK_DATA,
+                ptr::addr_of!(sample).cast(),
+            );
+            let size = serdata_ops.get_size.unwrap()(serdata) as usize;
+            let mut data = vec![0u8; size];
+            serdata_ops.to_ser.unwrap()(serdata, 0, size, data.as_mut_ptr().cast());
+            assert_eq!(data, to_cdr(&sample).unwrap());
+
+            // Serialized data from the network
+            let iov = ddsrt_iovec_t {
+                iov_base: data.as_mut_ptr().cast(),
+                iov_len: data.len() as _,
+            };
+            let received = serdata_ops.from_ser_iov.unwrap()(
+                sertype,
+                ddsi_serdata_kind::SDK_DATA,
+                1,
+                &iov,
+                data.len(),
+            );
+            assert!(serdata_ops.eqkey.unwrap()(serdata, received));
+            assert_eq!((*serdata).hash, (*received).hash);
+            let mut read = None::<Reading>;
+            assert!(serdata_ops.to_sample.unwrap()(
+                received,
+                ptr::addr_of_mut!(read).cast(),
+                ptr::null_mut(),
+                ptr::null_mut()
+            ));
+            assert_eq!(read.as_ref(), Some(&sample));
+
+            // The untyped key keeps the sample, e.g. for invalid samples
+            let untyped = serdata_ops.to_untyped.unwrap()(received);
+            assert!((*untyped).type_.is_null());
+            assert_eq!((*untyped).kind, ddsi_serdata_kind::SDK_KEY);
+            assert!(serdata_ops.eqkey.unwrap()(serdata, untyped));
+            read = None;
+            assert!(serdata_ops.untyped_to_sample.unwrap()(
+                sertype,
+                untyped,
+                ptr::addr_of_mut!(read).cast(),
+                ptr::null_mut(),
+                ptr::null_mut()
+            ));
+            assert_eq!(read, Some(sample.clone()));
+
+            let other = reading("b");
+            let other = serdata_ops.from_sample.unwrap()(
+                sertype,
+                ddsi_serdata_kind::SDK_DATA,
+                ptr::addr_of!(other).cast(),
+            );
+            assert!(!serdata_ops.eqkey.unwrap()(serdata, other));
+
+            // Strings may exceed 16 bytes, so their key hash is MD5
+            assert!(!Reading::key_is_fixed());
+            for force_md5 in [false, true] {
+                let mut keyhash = ddsi_keyhash { value: [0; 16] };
+                serdata_ops.get_keyhash.unwrap()(serdata, &mut keyhash, force_md5);
+                assert_eq!(keyhash.value, md5::compute(key_cdr("a")).0);
+            }
+
+            let mut buf = [0 as std::ffi::c_char; 64];
+            let len = serdata_ops.print.unwrap()(sertype, serdata, buf.as_mut_ptr(), buf.len());
+            let text = CStr::from_ptr(buf.as_ptr()).to_str().unwrap();
+            assert_eq!(len, text.len());
+            assert_eq!(text, r#"Reading { sensor: "a", values: [1.5, -2.0] }"#);
+            serdata_ops.print.unwrap()(sertype, serdata, buf.as_mut_ptr(), 8);
+            assert_eq!(CStr::from_ptr(buf.as_ptr()).to_str().unwrap(), "Reading");
+
+            // A reference keeps the serdata alive after its owner is done
+            let mut iov = MaybeUninit::<ddsrt_iovec_t>::uninit();
+            let referenced =
+                serdata_ops.to_ser_ref.unwrap()(serdata, 4, size - 4, iov.as_mut_ptr());
+            assert_eq!((*serdata).refc.v, 2);
+            serdata_ops.to_ser_unref.unwrap()(referenced, iov.as_ptr());
+            assert_eq!((*serdata).refc.v, 1);
+
+            for serdata in [serdata, received, untyped, other] {
+                serdata_ops.to_ser_unref.unwrap()(serdata, iov.as_ptr());
+            }
+            ops::<Reading>().sertype.free.unwrap()(sertype);
+        }
+    }
+
+    #[test]
+    fn test_key_serdata() {
+        let sertype = sertype::<Reading>();
+        let serdata_ops = &ops::<Reading>().serdata;
+        let sample = reading("a");
+
+        unsafe {
+            // A dispose, the key is what goes on the wire
+            let key = serdata_ops.from_sample.unwrap()(
+                sertype,
+                ddsi_serdata_kind::SDK_KEY,
+                ptr::addr_of!(sample).cast(),
+            );
+            let size = serdata_ops.get_size.unwrap()(key) as usize;
+            let mut data = vec![0u8; size];
+            serdata_ops.to_ser.unwrap()(key, 0, size, data.as_mut_ptr().cast());
+            // Plain CDR, big endian, with two bytes of padding
+            assert_eq!(data[..4], [0, 0, 0, 2]);
+            assert_eq!(data[4..10], key_cdr("a"));
+            assert_eq!(data[10..], [0, 0]);
+
+            let iov = ddsrt_iovec_t {
+                iov_base: data.as_mut_ptr().cast(),
+                iov_len: data.len() as _,
+            };
+            let received = serdata_ops.from_ser_iov.unwrap()(
+                sertype,
+                ddsi_serdata_kind::SDK_KEY,
+                1,
+                &iov,
+                data.len(),
+            );
+            assert!(serdata_ops.eqkey.unwrap()(key, received));
+            // The sample itself is not known
+            let mut read = None::<Reading>;
+            assert!(!serdata_ops.to_sample.unwrap()(
+                received,
+                ptr::addr_of_mut!(read).cast(),
+                ptr::null_mut(),
+                ptr::null_mut()
+            ));
+
+            serdata_ops.free.unwrap()(key);
+            serdata_ops.free.unwrap()(received);
+            ops::<Reading>().sertype.free.unwrap()(sertype);
+        }
+    }
+
+    #[test]
+    fn test_topic_type_key_serdata() {
+        let sertype = sertype::<Sensor>();
+        let serdata_ops = &ops::<Sensor>().serdata;
+        let sample = Sensor {
+            name: "a".to_string(),
+            values: vec![1.5],
+        };
+
+        unsafe {
+            let written = serdata_ops.from_sample.unwrap()(
+                sertype,
+                ddsi_serdata_kind::SDK_DATA,
+                ptr::addr_of!(sample).cast(),
+            );
+            let key = serdata_ops.from_sample.unwrap()(
+                sertype,
+                ddsi_serdata_kind::SDK_KEY,
+                ptr::addr_of!(sample).cast(),
+            );
+            let size = serdata_ops.get_size.unwrap()(key) as usize;
+            let mut data = vec![0u8; size];
+            serdata_ops.to_ser.unwrap()(key, 0, size, data.as_mut_ptr().cast());
+            // XCDR2, big endian, with two bytes of padding
+            assert_eq!(data[..4], [0, 6, 0, 2]);
+            assert_eq!(
+                data[4..10],
+                xcdr::to_key_payload(&sample, Endianness::Big).unwrap()
+            );
+
+            // A dispose from the network is of the instance of the sample
+            let iov = ddsrt_iovec_t {
+                iov_base: data.as_mut_ptr().cast(),
+                iov_len: data.len() as _,
+            };
+            let received = serdata_ops.from_ser_iov.unwrap()(
+                sertype,
+                ddsi_serdata_kind::SDK_KEY,
+                1,
+                &iov,
+                data.len(),
+            );
+            assert!(serdata_ops.eqkey.unwrap()(written, received));
+            assert_eq!((*written).hash, (*received).hash);
+
+            // The untyped key of the sample goes on the wire the same way
+            let untyped = serdata_ops.to_untyped.unwrap()(written);
+            assert_eq!(serdata_ops.get_size.unwrap()(untyped) as usize, size);
+            assert!(serdata_ops.eqkey.unwrap()(received, untyped));
+
+            for serdata in [written, key, received, untyped] {
+                serdata_ops.free.unwrap()(serdata);
+            }
+            ops::<Sensor>().sertype.free.unwrap()(sertype);
+        }
+    }
+
+    #[test]
+    fn test_key_hash() {
+        // The key hash Cyclone DDS gets from the sertype
+        fn keyhash<T: SerdeTopic>(sample: &T) -> [u8; 16] {
+            let sertype = sertype::<T>();
+            let serdata_ops = &ops::<T>().serdata;
+            let mut keyhash = ddsi_keyhash { value: [0; 16] };
+            unsafe {
+                let serdata = serdata_ops.from_sample.unwrap()(
+                    sertype,
+                    ddsi_serdata_kind::SDK_DATA,
+                    ptr::from_ref(sample).cast(),
+                );
+                serdata_ops.get_keyhash.unwrap()(serdata, &mut keyhash, false);
+                serdata_ops.free.unwrap()(serdata);
+                ops::<T>().sertype.free.unwrap()(sertype);
+            }
+            keyhash.value
+        }
+
+        // A string key may exceed 16 bytes
+        let sensor = Sensor {
+            name: "a".to_string(),
+            values: vec![1.5],
+        };
+        assert!(!Sensor::key_is_fixed());
+        assert_eq!(sensor.key_hash(), Ok(keyhash(&sensor)));
+
+        let calibration = Calibration {
+            id: 7,
+            offset: Some(0.5),
+        };
+        assert!(Calibration::key_is_fixed());
+        let hash = keyhash(&calibration);
+        assert_eq!(calibration.key_hash(), Ok(hash));
+        assert_eq!(hash[..4], 7u32.to_be_bytes());
+        assert_eq!(hash[4..], [0; 12]);
+    }
+
+    #[test]
+    fn test_samples() {
+        let sertype = sertype::<Counter>();
+        let sertype_ops = &ops::<Counter>().sertype;
+        unsafe {
+            let mut ptrs = [ptr::null_mut(); 3];
+            sertype_ops.realloc_samples.unwrap()(ptrs.as_mut_ptr(), sertype, ptr::null_mut(), 0, 2);
+            *ptrs[0].cast::<Option<Counter>>() = Some(Counter(1));
+            sertype_ops.realloc_samples.unwrap()(ptrs.as_mut_ptr(), sertype, ptrs[0], 2, 3);
+            assert_eq!(*ptrs[0].cast::<Option<Counter>>(), Some(Counter(1)));
+            assert_eq!(*ptrs[2].cast::<Option<Counter>>(), None);
+            sertype_ops.free_samples.unwrap()(
+                sertype,
+                ptrs.as_mut_ptr(),
+                3,
+                cyclonedds_sys::internal::dds_free_op_t::DDS_FREE_CONTENTS,
+            );
+            assert_eq!(*ptrs[0].cast::<Option<Counter>>(), None);
+            sertype_ops.free_samples.unwrap()(
+                sertype,
+                ptrs.as_mut_ptr(),
+                3,
+                cyclonedds_sys::internal::dds_free_op_t::DDS_FREE_ALL,
+            );
+            sertype_ops.free.unwrap()(sertype);
+        }
+    }
+}
@@ -1,14 +1,22 @@
 use std::{
     marker::PhantomData,
+    mem::MaybeUninit,
+    os::raw::c_void,
     ptr::{null, null_mut},
+    slice,
     time::{Duration, SystemTime},
 };
 
+use tracing::error;
+
 use crate::{
     core::{FetchableEntity, ReturnCodes},
     domain::DomainParticipant,
     internal::InstanceHandle,
-    topic::{Topic, TopicType},
+    qos::{DataRepresentation, Qos},
+    serdata::SerializedSample,
+    sertype::{entity_sertype, is_default_sertype_of, is_sertype_of},
+    topic::{Loanable, SerdeTopic, Topic},
 };
 
 pub struct Subscriber {
@@ -42,15 +50,28 @@ impl Subscriber {
         }
     }
 
-    pub fn create_reader<T: TopicType>(
+    /// Creates a reader of `topic`, which accepts samples in all data
+    /// representations of [Topic::data_representations].
+    pub fn create_reader<T>(&self, topic: &Topic<T>) -> Result<DataReader<T>, ReturnCodes> {
+        self.create_reader_with_qos(topic, &Qos::new())
+    }
+
+    /// Creates a reader of `topic` with `qos`. The reader accepts samples
+    /// in the data representations of `qos` or, if it has none, all of
+    /// [Topic::data_representations], so that it matches writers that use
+    /// either. Fails with [ReturnCodes::BadParameter] if `qos` lists a data
+    /// representation the topic does not allow.
+    pub fn create_reader_with_qos<T>(
         &self,
         topic: &Topic<T>,
+        qos: &Qos,
     ) -> Result<DataReader<T>, ReturnCodes> {
+        let qos = qos.for_endpoint(topic.data_representations(), false)?;
         unsafe {
             let reader = cyclonedds_sys::dds_create_reader(
                 self.subscriber,
                 topic.topic,
-                null(),
+                qos.qos,
                 std::ptr::null_mut(),
             );
             if reader < 0 {
@@ -108,33 +129,17 @@ impl FetchableEntity for Subscriber {
 /// DataReader<type> classes. For each application data type that is used as
 /// Topic data type, a typed class DataReader<type> is derived from the
 /// [AnyDataReader] class.
-pub struct DataReader<T: TopicType> {
+pub struct DataReader<T> {
     reader: cyclonedds_sys::dds_entity_t,
     _marker: PhantomData<T>,
 }
 
-impl<T: TopicType> DataReader<T> {
+impl<T> DataReader<T> {
     pub(crate) fn new(
         subscriber: &mut Subscriber,
         topic: &Topic<T>,
     ) -> Result<DataReader<T>, ReturnCodes> {
-        let data_reader = unsafe {
-            cyclonedds_sys::dds_create_reader(
-                subscriber.subscriber,
-                topic.topic,
-                null(),
-                std::ptr::null_mut(),
-            )
-        };
-
-        if data_reader < 0 {
-            Err(ReturnCodes::from(data_reader))
-        } else {
-            Ok(DataReader {
-                reader: data_reader,
-                _marker: PhantomData::default(),
-            })
-        }
+        subscriber.create_reader(topic)
     }
 
     /// Wait until reader receives all historic data
@@ -159,6 +164,28 @@ impl<T: TopicType> DataReader<T> {
         }
     }
 
+    /// The data representations the reader accepts samples in.
+    pub fn data_representations(&self) -> Result<Vec<DataRepresentation>, ReturnCodes> {
+        let qos = Qos::new();
+        match unsafe { cyclonedds_sys::dds_get_qos(self.reader, qos.qos) } {
+            0 => {}
+            result => return Err(ReturnCodes::from(result)),
+        }
+        qos.data_representation().map_err(|_| ReturnCodes::Error)
+    }
+
+    /// Looks up the handle of the instance `sample` belongs to, only its
+    /// key fields matter. [None] if the reader has not received a sample
+    /// of the instance. The sertype of the topic takes `sample` as a `T`,
+    /// see [Topic].
+    pub fn lookup_instance(&self, sample: &T) -> Option<InstanceHandle> {
+        let sample = sample as *const T as *const c_void;
+        match unsafe { cyclonedds_sys::dds_lookup_instance(self.reader, sample) } {
+            0 => None,
+            handle => Some(InstanceHandle { handle }),
+        }
+    }
+
     /// Read data from the data reader, read or query condition without
     /// updating state
     ///
@@ -219,44 +246,6 @@ impl<T: TopicType> DataReader<T> {
 
 
     }
-    /// Read data from the data reader, read or query condition
-    ///
-    /// Reads samples from the reader history cache, marking these samples as
-    /// “read”. It starts with an arbitrary (matching) instance, reading
-    /// (matching) samples from the oldest to the most recent, then continues
-    /// with another arbitrarily selected (matching) instance, etc. This
-    /// continues until it has traversed the entire history cache or has
-    /// gathered maxs samples.
-    ///
-    /// The dds_peek operation can be read samples without marking them as
-    /// “read”; the dds_take operation can be used to also remove the returned
-    /// samples from the history cache.
-    ///
-    /// For the plain dds_read operation, all instances and samples match.
-    /// This is different for the more selective variants, where the
-    /// documentation refers to this function and only gives detailed
-    /// information where it differs.
-    pub fn read(&self) -> Result<T, ReturnCodes>{
-        let mut buffer = Vec::new();
-        let mut sample_infos = Vec::new();
-        let return_code = unsafe {
-            cyclonedds_sys::dds_read(
-                self.reader,
-                buffer.as_mut_ptr(),
-                sample_infos.as_mut_ptr(),
-                2 << 16,
-                1,
-            )
-        };
-
-        if return_code < 0 {
-            Err(ReturnCodes::from(return_code))
-        } else {
-            todo!("not implemented")
-        }
-        }
-    
-
     /// Read data from the data reader, read or query condition.
     pub fn read_wl(&self) {
         todo!("not implemented")
@@ -330,27 +319,6 @@ impl<T: TopicType> DataReader<T> {
 
     /// Read the first unread sample.
     pub fn read_next_wl(&self) {}
-    /// Take data from the data reader, read or query condition
-    ///
-    /// Reads and removes samples from the reader history cache. It starts with
-    /// an arbitrary (matching) instance, reading (matching) samples from the
-    /// oldest to the most recent, then continues with another arbitrarily
-    /// selected (matching) instance, etc. This continues until it has
-    /// traversed the entire history cache or has gathered maxs samples.
-    ///
-    /// The dds_read operation can be used to read samples without removing them
-    /// from the history cache but marking them as “read”; the dds_peek
-    /// operation can be used to read samples from the cache without changing
-    /// any internal state.
-    ///
-    /// For the plain dds_take operation, all instances and samples match. This
-    /// is different for the more selective variants, where the documentation
-    /// refers to this function and only gives detailed information where it
-    /// differs.
-    pub fn take(&self) {
-        todo!("not implemented")
-    }
-
     /// Take data from the data reader, read or query condition.
     pub fn take_wl(&self) {
         todo!("not implemented")
@@ -443,9 +411,9 @@ impl<T: TopicType> DataReader<T> {
     /// updated; [DataReader::readcdr] updates these states; dds_takecdr
     /// removes the data from the history cache.
     ///
-    /// The returned references must eventually be released by calling
-    /// ddsi_serdata_unref. There is no guarantee the type pointer survives
-    /// beyond the existence of the reader from which the references were read.
+    /// The references are released when the returned [SerializedSample]s
+    /// are dropped. There is no guarantee the type pointer survives beyond
+    /// the existence of the reader from which the references were read.
     ///
     /// When using a readcondition or querycondition, their masks are or’d with
     /// the given mask.
@@ -455,8 +423,13 @@ impl<T: TopicType> DataReader<T> {
     /// equivalent to any sample/view/instance state.
     ///
     /// Note that this is a simple wrapper around dds_peek_with_collector.
-    pub fn peekcdr(&self) {
-        todo!("not implemented")
+    pub fn peekcdr(
+        &self,
+        max_samples: usize,
+    ) -> Result<Vec<(SerializedSample, SampleInfo)>, ReturnCodes> {
+        self.cdr_samples(max_samples, |buf, maxs, si| unsafe {
+            cyclonedds_sys::dds_peekcdr(self.reader, buf, maxs, si, 0)
+        })
     }
 
     /// Get references to a representation of the samples of a specific
@@ -475,9 +448,9 @@ impl<T: TopicType> DataReader<T> {
     /// dds_readcdr updates these states; dds_takecdr removes the data from the
     /// history cache.
     ///
-    /// The returned references must eventually be released by calling
-    /// ddsi_serdata_unref. There is no guarantee the type pointer survives
-    /// beyond the existence of the reader from which the references were read.
+    /// The references are released when the returned [SerializedSample]s
+    /// are dropped. There is no guarantee the type pointer survives beyond
+    /// the existence of the reader from which the references were read.
     ///
     /// When using a readcondition or querycondition, their masks are or’d with
     /// the given mask.
@@ -487,8 +460,21 @@ impl<T: TopicType> DataReader<T> {
     /// equivalent to any sample/view/instance state.
     ///
     /// Note that this is a simple wrapper around dds_peek_with_collector.
-    pub fn peekcdr_instance(&self) {
-        todo!("not implemented")
+    pub fn peekcdr_instance(
+        &self,
+        max_samples: usize,
+        instance_handle: InstanceHandle,
+    ) -> Result<Vec<(SerializedSample, SampleInfo)>, ReturnCodes> {
+        self.cdr_samples(max_samples, |buf, maxs, si| unsafe {
+            cyclonedds_sys::dds_peekcdr_instance(
+                self.reader,
+                buf,
+                maxs,
+                si,
+                instance_handle.handle,
+                0,
+            )
+        })
     }
 
     /// Get references to a representation of the samples in a reader history
@@ -507,9 +493,9 @@ impl<T: TopicType> DataReader<T> {
     /// dds_peekcdr returns the data without updating these states; dds_takecdr
     /// removes the data from the history cache.
     ///
-    ///The returned references must eventually be released by calling
-    /// ddsi_serdata_unref. There is no guarantee the type pointer survives
-    /// beyond the existence of the reader from which the references were read.
+    /// The references are released when the returned [SerializedSample]s
+    /// are dropped. There is no guarantee the type pointer survives beyond
+    /// the existence of the reader from which the references were read.
     ///
     ///When using a readcondition or querycondition, their masks are or’d with
     /// the given mask.
@@ -519,8 +505,13 @@ impl<T: TopicType> DataReader<T> {
     /// equivalent to any sample/view/instance state.
     ///
     ///Note that this is a simple wrapper around dds_read_with_collector.
-    pub fn readcdr(&self) {
-        todo!("not implemented")
+    pub fn readcdr(
+        &self,
+        max_samples: usize,
+    ) -> Result<Vec<(SerializedSample, SampleInfo)>, ReturnCodes> {
+        self.cdr_samples(max_samples, |buf, maxs, si| unsafe {
+            cyclonedds_sys::dds_readcdr(self.reader, buf, maxs, si, 0)
+        })
     }
 
     /// Get references to a representation of the samples of a specific
@@ -539,9 +530,9 @@ impl<T: TopicType> DataReader<T> {
     /// dds_peekcdr returns the data without updating these states; dds_takecdr
     /// removes the data from the history cache.
     ///
-    ///The returned references must eventually be released by calling
-    /// ddsi_serdata_unref. There is no guarantee the type pointer survives
-    /// beyond the existence of the reader from which the references were read.
+    /// The references are released when the returned [SerializedSample]s
+    /// are dropped. There is no guarantee the type pointer survives beyond
+    /// the existence of the reader from which the references were read.
     ///
     /// When using a readcondition or querycondition, their masks are or’d with
     /// the given mask.
@@ -551,8 +542,21 @@ impl<T: TopicType> DataReader<T> {
     /// equivalent to any sample/view/instance state.
     ///
     /// * Note that this is a simple wrapper around dds_read_with_collector.
-    pub fn readcdr_instance(&self) {
-        todo!("not implemented")
+    pub fn readcdr_instance(
+        &self,
+        max_samples: usize,
+        instance_handle: InstanceHandle,
+    ) -> Result<Vec<(SerializedSample, SampleInfo)>, ReturnCodes> {
+        self.cdr_samples(max_samples, |buf, maxs, si| unsafe {
+            cyclonedds_sys::dds_readcdr_instance(
+                self.reader,
+                buf,
+                maxs,
+                si,
+                instance_handle.handle,
+                0,
+            )
+        })
     }
 
     /// Get references to a representation of the samples in a reader history
@@ -570,9 +574,9 @@ impl<T: TopicType> DataReader<T> {
     /// them in and leaves the sample and view states unchanged; dds_readcdr
     /// leaves the data in the cache but does update the sample and view states.
     ///
-    ///The returned references must eventually be released by calling
-    /// ddsi_serdata_unref. There is no guarantee the type pointer survives
-    /// beyond the existence of the reader from which the references were read.
+    /// The references are released when the returned [SerializedSample]s
+    /// are dropped. There is no guarantee the type pointer survives beyond
+    /// the existence of the reader from which the references were read.
     ///
     /// When using a readcondition or querycondition, their masks are or’d with
     /// the given mask.
@@ -582,8 +586,13 @@ impl<T: TopicType> DataReader<T> {
     /// equivalent to any sample/view/instance state.
     ///
     /// Note that this is a simple wrapper around dds_take_with_collector.
-    pub fn takecdr(&self) {
-        todo!("not implemented")
+    pub fn takecdr(
+        &self,
+        max_samples: usize,
+    ) -> Result<Vec<(SerializedSample, SampleInfo)>, ReturnCodes> {
+        self.cdr_samples(max_samples, |buf, maxs, si| unsafe {
+            cyclonedds_sys::dds_takecdr(self.reader, buf, maxs, si, 0)
+        })
     }
 
     /// Get references to a representation of the samples of a specific
@@ -599,9 +608,9 @@ impl<T: TopicType> DataReader<T> {
     ///
     /// The data is removed from the reader history cache; dds_peekcdr leaves them in and leaves the sample and view states unchanged; dds_readcdr leaves the data in the cache but does update the sample and view states.
     ///
-    /// The returned references must eventually be released by calling
-    /// ddsi_serdata_unref. There is no guarantee the type pointer survives
-    /// beyond the existence of the reader from which the references were read.
+    /// The references are released when the returned [SerializedSample]s
+    /// are dropped. There is no guarantee the type pointer survives beyond
+    /// the existence of the reader from which the references were read.
     ///
     /// When using a readcondition or querycondition, their masks are or’d with
     /// the given mask.
@@ -611,12 +620,190 @@ impl<T: TopicType> DataReader<T> {
     /// equivalent to any sample/view/instance state.
     ///
     /// Note that this is a simple wrapper around dds_take_with_collector.
-    pub fn takecdr_instance(&self) {
-        todo!("not implemented")
+    pub fn takecdr_instance(
+        &self,
+        max_samples: usize,
+        instance_handle: InstanceHandle,
+    ) -> Result<Vec<(SerializedSample, SampleInfo)>, ReturnCodes> {
+        self.cdr_samples(max_samples, |buf, maxs, si| unsafe {
+            cyclonedds_sys::dds_takecdr_instance(
+                self.reader,
+                buf,
+                maxs,
+                si,
+                instance_handle.handle,
+                0,
+            )
+        })
+    }
+
+    /// Runs one of the cdr reads with room for `max_samples` serdata
+    /// references and sample infos.
+    fn cdr_samples(
+        &self,
+        max_samples: usize,
+        read: impl FnOnce(
+            *mut *mut cyclonedds_sys::ddsi_serdata,
+            u32,
+            *mut cyclonedds_sys::dds_sample_info_t,
+        ) -> cyclonedds_sys::dds_return_t,
+    ) -> Result<Vec<(SerializedSample, SampleInfo)>, ReturnCodes> {
+        let max_samples = max_samples.clamp(1, i32::MAX as usize);
+        let mut samples = vec![null_mut(); max_samples];
+        let mut infos = Vec::<SampleInfo>::with_capacity(max_samples);
+        let count = read(
+            samples.as_mut_ptr(),
+            max_samples as u32,
+            infos.as_mut_ptr().cast(),
+        );
+
+        if count < 0 {
+            return Err(ReturnCodes::from(count));
+        }
+        unsafe { infos.set_len(count as usize) };
+        Ok(samples
+            .into_iter()
+            .zip(infos)
+            .filter_map(|(serdata, info)| {
+                unsafe { SerializedSample::from_raw(serdata.cast()) }.map(|sample| (sample, info))
+            })
+            .collect())
+    }
+}
+
+impl<T: SerdeTopic> DataReader<T> {
+    /// Read data from the data reader, read or query condition
+    ///
+    /// Reads samples from the reader history cache, marking these samples as
+    /// “read”. It starts with an arbitrary (matching) instance, reading
+    /// (matching) samples from the oldest to the most recent, then continues
+    /// with another arbitrarily selected (matching) instance, etc. This
+    /// continues until it has traversed the entire history cache or has
+    /// gathered maxs samples.
+    ///
+    /// The dds_peek operation can be read samples without marking them as
+    /// “read”; the dds_take operation can be used to also remove the returned
+    /// samples from the history cache.
+    ///
+    /// For the plain dds_read operation, all instances and samples match.
+    /// This is different for the more selective variants, where the
+    /// documentation refers to this function and only gives detailed
+    /// information where it differs.
+    ///
+    /// Fails with [ReturnCodes::BadParameter] if the topic of the reader
+    /// was not created with [Topic::with_sertype].
+    pub fn read(&self) -> Result<T, ReturnCodes> {
+        self.read_or_take(false)
+    }
+
+    /// Take data from the data reader, read or query condition
+    ///
+    /// Reads and removes samples from the reader history cache. It starts with
+    /// an arbitrary (matching) instance, reading (matching) samples from the
+    /// oldest to the most recent, then continues with another arbitrarily
+    /// selected (matching) instance, etc. This continues until it has
+    /// traversed the entire history cache or has gathered maxs samples.
+    ///
+    /// The dds_read operation can be used to read samples without removing them
+    /// from the history cache but marking them as “read”; the dds_peek
+    /// operation can be used to read samples from the cache without changing
+    /// any internal state.
+    ///
+    /// For the plain dds_take operation, all instances and samples match. This
+    /// is different for the more selective variants, where the documentation
+    /// refers to this function and only gives detailed information where it
+    /// differs.
+    ///
+    /// Fails with [ReturnCodes::BadParameter] if the topic of the reader
+    /// was not created with [Topic::with_sertype].
+    pub fn take(&self) -> Result<T, ReturnCodes> {
+        self.read_or_take(true)
+    }
+
+    /// Reads or takes a single sample into an `Option<T>` that the sertype
+    /// of the topic fills in, fails with [ReturnCodes::BadParameter] if the
+    /// topic was not created with [Topic::with_sertype]: the default
+    /// sertype would read into it as if it were a `T`.
+    fn read_or_take(&self, take: bool) -> Result<T, ReturnCodes> {
+        if !is_sertype_of::<T>(entity_sertype(self.reader)?) {
+            return Err(ReturnCodes::BadParameter);
+        }
+        let mut sample: Option<T> = None;
+        let mut buffer = [&mut sample as *mut Option<T> as *mut c_void];
+        let mut info = MaybeUninit::<cyclonedds_sys::dds_sample_info_t>::uninit();
+        let operation = if take {
+            cyclonedds_sys::dds_take
+        } else {
+            cyclonedds_sys::dds_read
+        };
+        let count = unsafe { operation(self.reader, buffer.as_mut_ptr(), info.as_mut_ptr(), 1, 1) };
+
+        if count < 0 {
+            return Err(ReturnCodes::from(count));
+        }
+        if count == 0 || !unsafe { info.assume_init() }.valid_data {
+            return Err(ReturnCodes::NoData);
+        }
+        sample.ok_or(ReturnCodes::NoData)
+    }
+}
+
+impl<T: Loanable> DataReader<T> {
+    /// Reads up to `max_samples` samples without copying them, see
+    /// [DataReader::read]. The samples stay in memory loaned from the
+    /// reader until the [LoanedSamples] are dropped. Fails with
+    /// [ReturnCodes::BadParameter] if the topic of the reader was not
+    /// created with [Topic::new].
+    pub fn read_loan(&self, max_samples: usize) -> Result<LoanedSamples<'_, T>, ReturnCodes> {
+        self.loan(max_samples, false)
+    }
+
+    /// Takes up to `max_samples` samples without copying them, see
+    /// [DataReader::take] and [DataReader::read_loan].
+    pub fn take_loan(&self, max_samples: usize) -> Result<LoanedSamples<'_, T>, ReturnCodes> {
+        self.loan(max_samples, true)
+    }
+
+    /// Reads or takes into a buffer of null pointers, which Cyclone DDS
+    /// points to loaned samples. Only the default sertype of the
+    /// descriptor of `T` lays them out as a `T`, the serde sertype loans an
+    /// `Option<T>`.
+    fn loan(&self, max_samples: usize, take: bool) -> Result<LoanedSamples<'_, T>, ReturnCodes> {
+        if !is_default_sertype_of::<T>(entity_sertype(self.reader)?) {
+            return Err(ReturnCodes::BadParameter);
+        }
+        let max_samples = max_samples.clamp(1, i32::MAX as usize);
+        let mut samples = vec![null_mut(); max_samples];
+        let mut infos = Vec::<SampleInfo>::with_capacity(max_samples);
+        let operation = if take {
+            cyclonedds_sys::dds_take
+        } else {
+            cyclonedds_sys::dds_read
+        };
+        let count = unsafe {
+            operation(
+                self.reader,
+                samples.as_mut_ptr(),
+                infos.as_mut_ptr().cast(),
+                max_samples,
+                max_samples as u32,
+            )
+        };
+
+        if count < 0 {
+            return Err(ReturnCodes::from(count));
+        }
+        unsafe { infos.set_len(count as usize) };
+        samples.truncate(count as usize);
+        Ok(LoanedSamples {
+            reader: self,
+            samples,
+            infos,
+        })
     }
 }
 
-impl<T: TopicType> FetchableEntity for DataReader<T> {
+impl<T> FetchableEntity for DataReader<T> {
     fn fetch(&self) -> cyclonedds_sys::dds_entity_t {
         self.reader
     }
@@ -639,6 +826,7 @@ pub enum ReaderMask {
 }
 
 /// Read state for a data value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SampleState {
     /// [DataReader] has already accessed the sample by read
     Read,
@@ -646,6 +834,7 @@ pub enum SampleState {
     NotRead,
 }
 /// View state of an instance relative to the samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ViewState {
     /// [DataReader] is accessing the sample for the first time when the instance
     /// is alive
@@ -655,6 +844,7 @@ pub enum ViewState {
 }
 
 /// Defines the state of the instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InstanceState {
     /// Samples received for the instance from the live data writers
     Alive,
@@ -666,31 +856,178 @@ pub enum InstanceState {
 }
 
 /// Contains information about the associated data value
+#[repr(transparent)]
 pub struct SampleInfo {
-    sample_state: SampleState,
-    view_state: ViewState,
-    instance_state: InstanceState,
+    info: cyclonedds_sys::dds_sample_info_t,
+}
+
+impl SampleInfo {
+    pub fn sample_state(&self) -> SampleState {
+        match self.info.sample_state {
+            cyclonedds_sys::dds_sample_state::DDS_SST_READ => SampleState::Read,
+            cyclonedds_sys::dds_sample_state::DDS_SST_NOT_READ => SampleState::NotRead,
+        }
+    }
+
+    pub fn view_state(&self) -> ViewState {
+        match self.info.view_state {
+            cyclonedds_sys::dds_view_state::DDS_VST_NEW => ViewState::New,
+            cyclonedds_sys::dds_view_state::DDS_VST_OLD => ViewState::Old,
+        }
+    }
+
+    pub fn instance_state(&self) -> InstanceState {
+        match self.info.instance_state {
+            cyclonedds_sys::dds_instance_state::DDS_IST_ALIVE => InstanceState::Alive,
+            cyclonedds_sys::dds_instance_state::DDS_IST_NOT_ALIVE_DISPOSED => {
+                InstanceState::NotAliveDisposed
+            }
+            cyclonedds_sys::dds_instance_state::DDS_IST_NOT_ALIVE_NO_WRITERS => {
+                InstanceState::NotAliveNoWriters
+            }
+        }
+    }
+
     /// Indicates whether there is a data associated with a sample
     /// * `true`, indicates the data is valid
-    /// * `false`, indicates the data is invalid, no data to read
-    valid_data: bool,
+    /// * `false`, indicates the data is invalid, only the key fields of the
+    ///   sample are set
+    pub fn valid_data(&self) -> bool {
+        self.info.valid_data
+    }
+
     /// timestamp of a data instance when it is written
-    source_timestamp: SystemTime,
+    pub fn source_timestamp(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_nanos(self.info.source_timestamp.max(0) as u64)
+    }
+
     /// handle to the data instance
-    instance_handle: InstanceHandle,
+    pub fn instance_handle(&self) -> InstanceHandle {
+        InstanceHandle {
+            handle: self.info.instance_handle,
+        }
+    }
+
     /// handle to the publisher
-    publication_handle: cyclonedds_sys::dds_instance_handle_t,
+    pub fn publication_handle(&self) -> InstanceHandle {
+        InstanceHandle {
+            handle: self.info.publication_handle,
+        }
+    }
+
     /// count of instance state change from NOT_ALIVE_DISPOSED to ALIVE
-    disposed_generation_count: u32,
+    pub fn disposed_generation_count(&self) -> u32 {
+        self.info.disposed_generation_count
+    }
+
     /// count of instance state change from NOT_ALIVE_NO_WRITERS to ALIVE
-    no_writers_generation_count: u32,
+    pub fn no_writers_generation_count(&self) -> u32 {
+        self.info.no_writers_generation_count
+    }
+
     /// indicates the number of samples of the same instance that follow the
     /// current one in the collection
-    sample_rank: u32,
+    pub fn sample_rank(&self) -> u32 {
+        self.info.sample_rank
+    }
+
     /// difference in generations between the sample and most recent sample of
     /// the same instance that appears in the returned collection
-    generation_rank: u32,
+    pub fn generation_rank(&self) -> u32 {
+        self.info.generation_rank
+    }
+
     /// difference in generations between the sample and most recent sample of
     /// the same instance when read/take was called
-    absolute_generation_rank: u32,
+    pub fn absolute_generation_rank(&self) -> u32 {
+        self.info.absolute_generation_rank
+    }
+}
+
+/// Samples loaned from a [DataReader] by [DataReader::read_loan] or
+/// [DataReader::take_loan], each with its [SampleInfo]. The loan is
+/// returned to the reader when they are dropped. They are only handed out
+/// for readers of the default sertype, whose samples are laid out as `T`.
+///
+/// ```ignore
+/// for (frame, info) in &reader.take_loan(16)? {
+///     if info.valid_data() {
+///         show(frame);
+///     }
+/// }
+/// ```
+pub struct LoanedSamples<'a, T: Loanable> {
+    reader: &'a DataReader<T>,
+    samples: Vec<*mut c_void>,
+    infos: Vec<SampleInfo>,
+}
+
+impl<T: Loanable> LoanedSamples<'_, T> {
+    /// Number of samples
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The samples and their [SampleInfo]. Check [SampleInfo::valid_data]
+    /// before using more than the key fields of a sample.
+    pub fn iter(&self) -> LoanedSamplesIter<'_, T> {
+        LoanedSamplesIter {
+            samples: self.samples.iter(),
+            infos: self.infos.iter(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'s, T: Loanable> IntoIterator for &'s LoanedSamples<'_, T> {
+    type Item = (&'s T, &'s SampleInfo);
+    type IntoIter = LoanedSamplesIter<'s, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the samples of [LoanedSamples].
+pub struct LoanedSamplesIter<'s, T> {
+    samples: slice::Iter<'s, *mut c_void>,
+    infos: slice::Iter<'s, SampleInfo>,
+    _marker: PhantomData<&'s T>,
+}
+
+impl<'s, T> Iterator for LoanedSamplesIter<'s, T> {
+    type Item = (&'s T, &'s SampleInfo);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = unsafe { &*self.samples.next()?.cast::<T>() };
+        Some((sample, self.infos.next()?))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.samples.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for LoanedSamplesIter<'_, T> {}
+
+impl<T: Loanable> Drop for LoanedSamples<'_, T> {
+    fn drop(&mut self) {
+        if self.samples.is_empty() {
+            return;
+        }
+        let result = unsafe {
+            cyclonedds_sys::dds_return_loan(
+                self.reader.reader,
+                self.samples.as_mut_ptr(),
+                self.samples.len() as i32,
+            )
+        };
+        if result != 0 {
+            error!("failed to return a loan: {}", ReturnCodes::from(result));
+        }
+    }
 }
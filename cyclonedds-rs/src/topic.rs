@@ -1,14 +1,24 @@
 use std::{ffi::CString, fmt::Debug, ptr::null};
 
-use serde::Serialize;
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::error;
 
 use crate::{
     core::{Entity, EntityParticipantError, Guid, ReturnCodes},
+    descriptor::{key_members, registered_descriptor, type_name_of, DdsType, Pod, TypeDesc},
     domain::DomainParticipant,
     internal::{InstanceHandle, TopicDescriptor},
-    InconsistentTopicStatus, qos::Qos,
+    ops::validate,
+    qos::{DataRepresentation, Qos},
+    sertype::sertype,
+    xcdr::{self, Endianness, XcdrError, XcdrVersion},
+    InconsistentTopicStatus,
 };
 
+/// Derive macro generating [TopicType] and
+/// [DdsType](crate::descriptor::DdsType) implementations for a struct.
+pub use cyclonedds_derive::TopicType;
+
 pub enum FindScope {
     Global,
     LocalDomain,
@@ -23,18 +33,102 @@ pub enum FilterMode {
     SampleSampleinfoArg,
 }
 
-pub struct Topic<T: TopicType> {
+/// A topic of samples of type `T`. Its sertype takes the samples readers
+/// and writers hand to Cyclone DDS as a `T`: it is either the default
+/// sertype of the descriptor of plain old data ([Topic::new]) or the serde
+/// based sertype of `T` ([Topic::with_sertype]).
+pub struct Topic<T> {
     pub(super) topic: cyclonedds_sys::dds_entity_t,
+    data_representations: Vec<DataRepresentation>,
     _marker: std::marker::PhantomData<T>,
 }
 
+impl<T> Topic<T> {
+    /// The data representations samples of the topic can be serialized in,
+    /// the first one is the one writers use by default.
+    pub fn data_representations(&self) -> &[DataRepresentation] {
+        &self.data_representations
+    }
+}
+
 impl<T: TopicType> Topic<T> {
-    pub fn new(participant: &DomainParticipant) -> Result<Topic<T>, ReturnCodes> {
+    /// Creates the topic [TopicType::name] of type `T`, fails with
+    /// [ReturnCodes::BadParameter] if the name is not a legal topic name or
+    /// the descriptor of `T` does not match `T` ([crate::ops::validate]).
+    /// The descriptor of `T` is registered on first use and outlives the
+    /// topic.
+    ///
+    /// Cyclone DDS serializes samples of the topic straight from and into
+    /// their memory, as the op program of the descriptor describes it, so
+    /// `T` must be plain old data ([Pod]). Topics of types with strings,
+    /// sequences, optional members, maps or unions are created with
+    /// [Topic::with_sertype].
+    pub fn new(participant: &DomainParticipant) -> Result<Topic<T>, ReturnCodes>
+    where
+        T: Pod,
+    {
+        if !is_valid_topic_name(T::name()) {
+            return Err(ReturnCodes::BadParameter);
+        }
+        let descriptor = registered_descriptor::<T>();
+        if let Err(err) = validate::<T>(descriptor.topic_descriptor()) {
+            error!("the descriptor of {} is invalid: {}", T::type_name(), err);
+            return Err(ReturnCodes::BadParameter);
+        }
+        let name = CString::new(T::name()).unwrap();
         let topic: cyclonedds_sys::dds_entity_t = unsafe {
             cyclonedds_sys::dds_create_topic(
                 participant.participant,
-                &cyclonedds_sys::dds_topic_descriptor::from(T::topic_descriptor().clone()),
-                CString::new(T::name()).unwrap().as_ptr(),
+                descriptor.as_c(),
+                name.as_ptr(),
+                null(),
+                null(),
+            )
+        };
+
+        if topic < 0 {
+            Err(ReturnCodes::from(topic))
+        } else {
+            Ok(Topic {
+                topic,
+                data_representations: descriptor.topic_descriptor().data_representations(),
+                _marker: std::marker::PhantomData,
+            })
+        }
+    }
+}
+
+impl<T: SerdeTopic> Topic<T> {
+    /// Creates the topic [SerdeTopic::name] of type `T` with a sertype
+    /// that serializes samples with serde ([crate::sertype]), fails with
+    /// [ReturnCodes::BadParameter] if the name is not a legal topic name.
+    /// The samples are serialized in [SerdeTopic::data_representation]
+    /// only.
+    pub fn with_sertype(participant: &DomainParticipant) -> Result<Topic<T>, ReturnCodes> {
+        Self::with_sertype_named(participant, T::name())
+    }
+
+    /// Creates the topic `name` of type `T` like [Topic::with_sertype], for
+    /// types such as [BytesTopic](crate::bytes::BytesTopic) that many
+    /// topics share.
+    pub fn with_sertype_named(
+        participant: &DomainParticipant,
+        name: &str,
+    ) -> Result<Topic<T>, ReturnCodes> {
+        if !is_valid_topic_name(name) {
+            return Err(ReturnCodes::BadParameter);
+        }
+        let name = CString::new(name).unwrap();
+        let mut sertype = sertype::<T>().cast::<cyclonedds_sys::ddsi_sertype>();
+        // On success the topic owns the sertype, which may be replaced by
+        // an equal one that already exists. Cyclone DDS does not say who
+        // owns it on failure, so it is leaked then.
+        let topic = unsafe {
+            cyclonedds_sys::dds_create_topic_sertype(
+                participant.participant,
+                name.as_ptr(),
+                &mut sertype,
+                null(),
                 null(),
                 null(),
             )
@@ -45,13 +139,14 @@ impl<T: TopicType> Topic<T> {
         } else {
             Ok(Topic {
                 topic,
+                data_representations: vec![T::data_representation()],
                 _marker: std::marker::PhantomData,
             })
         }
     }
 }
 
-impl<T: TopicType> Drop for Topic<T> {
+impl<T> Drop for Topic<T> {
     fn drop(&mut self) {
         unsafe {
             cyclonedds_sys::dds_delete(self.topic);
@@ -191,7 +286,7 @@ impl Drop for AnyTopic {
     }
 }
 
-impl<T: TopicType> From<Topic<T>> for AnyTopic {
+impl<T> From<Topic<T>> for AnyTopic {
     fn from(topic: Topic<T>) -> Self {
         todo!("Need to implement the cyclone dds copy function")
     }
@@ -230,11 +325,23 @@ pub trait MessageType {
     fn type_name() -> String;
 }
 
+/// A topic type that inherits from another topic type, like an IDL
+/// `struct Derived : Base`. `#[derive(TopicType)]` implements it for
+/// structs with `#[dds(extends = Base)]`.
+///
+/// Whether a reader of the base type receives samples of the derived type
+/// follows the XTypes assignability rules: the base must be `@appendable`
+/// or `@mutable`, `@final` types are only assignable to themselves.
 pub trait XType: TopicType {
-    type Extends;
+    /// The base type
+    type Extends: TopicType;
+
+    /// Returns the base part of the sample.
+    fn extends(&self) -> &Self::Extends;
 
-    fn extends() -> Self::Extends;
-    fn extends_name() -> String;
+    fn extends_name() -> String {
+        Self::Extends::type_name().to_string()
+    }
 }
 
 pub struct ParticipantBuiltinTopicData {}
@@ -245,26 +352,225 @@ pub struct PublicationBuiltinTopicData {}
 
 pub struct SubscriptionBuiltinTopicData {}
 
+/// Returns whether `name` is a legal topic name: letters, digits, `_` and
+/// `/` (for ROS 2), not starting with a digit.
+pub fn is_valid_topic_name(name: &str) -> bool {
+    !name.starts_with(|c: char| c.is_ascii_digit())
+        && !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '/')
+}
+
 /// Trait for Topic types
 /// All messages must be of [TopicType] to be used by a data reader or writer.
+///
+/// The derive takes the names from `#[dds(topic = "...")]` and
+/// `#[dds(type_name = "...")]` when given.
 pub trait TopicType: Clone + Debug + PartialEq {
+    /// Name of the topic. By default this is the name of the type `T`
+    /// without its module path and generic arguments.
     fn name() -> &'static str {
-        std::any::type_name::<Self>().rsplit("::").next().unwrap()
+        default_name::<Self>()
     }
 
+    /// IDL name of the type, which must match the type name of peers.
     /// By default this is the module path of the type `T` excluding
-    /// the crate name.
+    /// the crate name, see [type_name_of].
     fn type_name() -> &'static str {
-        // Get the fully qualified name and remove the crate name.
-        let full = std::any::type_name::<Self>();
-        if let Some((_, rest)) = full.split_once("::") {
-            rest
-        } else {
-            full
-        }
+        type_name_of::<Self>()
     }
 
     fn topic_descriptor() -> TopicDescriptor;
+
+    /// The key hash of the instance the sample belongs to, as
+    /// [xcdr::key_hash] computes it without Cyclone DDS. Peers compute the
+    /// same 16 bytes for samples of the instance, so it can be used to
+    /// shard or deduplicate instances. The instance handle of a reader or
+    /// writer is only known locally, see
+    /// [DataWriter::lookup_instance](crate::publisher::DataWriter::lookup_instance).
+    fn key_hash(&self) -> Result<[u8; 16], XcdrError>
+    where
+        Self: Serialize + DdsType,
+    {
+        xcdr::key_hash(self)
+    }
+}
+
+/// Trait for types published through the serde based sertype of
+/// [crate::sertype], see [Topic::with_sertype]. Any type that serde can
+/// serialize works, no [TopicDescriptor] is needed.
+///
+/// Types that derive [TopicType] as well as serde's `Serialize` and
+/// `Deserialize` are [SerdeTopic]s already, serialized as their
+/// [DdsType] describes. Others implement it by hand and are serialized as
+/// final types in XCDR1:
+///
+/// ```ignore
+/// #[derive(Clone, Debug, Serialize, Deserialize)]
+/// struct Reading {
+///     sensor: String,
+///     value: f64,
+/// }
+///
+/// impl SerdeTopic for Reading {
+///     fn has_key() -> bool {
+///         true
+///     }
+///
+///     fn key(&self) -> Result<Vec<u8>, XcdrError> {
+///         Ok(key_cdr(&self.sensor))
+///     }
+/// }
+/// ```
+pub trait SerdeTopic: Serialize + DeserializeOwned + Debug + Send + Sync + 'static {
+    /// Name of the topic, like [TopicType::name].
+    fn name() -> &'static str {
+        default_name::<Self>()
+    }
+
+    /// IDL name of the type, like [TopicType::type_name].
+    fn type_name() -> &'static str {
+        type_name_of::<Self>()
+    }
+
+    /// Whether the type has a key. Without a key all samples belong to a
+    /// single instance.
+    fn has_key() -> bool {
+        false
+    }
+
+    /// The serialized key fields, equal for samples of the same instance
+    /// only. A key-only sample carries it as is, so peers must agree on
+    /// it: big endian CDR of the key fields as [crate::sertype::key_cdr]
+    /// makes is what the DDS specification asks for. A sample whose key
+    /// can not be serialized is not written.
+    fn key(&self) -> Result<Vec<u8>, XcdrError> {
+        Ok(Vec::new())
+    }
+
+    /// The version of the CDR [SerdeTopic::key] is serialized in, which the
+    /// encapsulation header of a key-only sample tells peers.
+    fn key_version() -> XcdrVersion {
+        XcdrVersion::Xcdr1
+    }
+
+    /// Whether [SerdeTopic::key] always fits in the 16 bytes of a key hash,
+    /// which is then the key padded with zeros rather than its MD5 hash.
+    /// True for types without a key, types whose key is of fixed size,
+    /// e.g. integers, override it.
+    fn key_is_fixed() -> bool {
+        !Self::has_key()
+    }
+
+    /// The description the samples are serialized as, see [crate::xcdr].
+    /// Without one they are serialized as final types.
+    fn description() -> Option<TypeDesc> {
+        None
+    }
+
+    /// The data representation the samples are serialized in, XCDR2 needs
+    /// a [SerdeTopic::description].
+    fn data_representation() -> DataRepresentation {
+        DataRepresentation::Xcdr1
+    }
+}
+
+/// Topic types that serde can serialize are published through the serde
+/// based sertype, so their members need not be plain old data. The key is
+/// the serialized key of [xcdr::to_key_payload], whose hash is the key
+/// hash of the DDS specification, and samples are serialized in XCDR1
+/// unless their descriptor restricts them to XCDR2.
+impl<T> SerdeTopic for T
+where
+    T: TopicType + DdsType + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    fn name() -> &'static str {
+        <T as TopicType>::name()
+    }
+
+    fn type_name() -> &'static str {
+        <T as TopicType>::type_name()
+    }
+
+    fn has_key() -> bool {
+        T::type_desc()
+            .as_struct()
+            .is_some_and(|desc| !key_members(desc).0.is_empty())
+    }
+
+    fn key(&self) -> Result<Vec<u8>, XcdrError> {
+        xcdr::to_key_payload(self, Endianness::Big)
+    }
+
+    fn key_version() -> XcdrVersion {
+        XcdrVersion::Xcdr2
+    }
+
+    fn key_is_fixed() -> bool {
+        T::type_desc()
+            .as_struct()
+            .is_none_or(|desc| key_members(desc).1)
+    }
+
+    fn description() -> Option<TypeDesc> {
+        Some(T::type_desc())
+    }
+
+    fn data_representation() -> DataRepresentation {
+        registered_descriptor::<T>()
+            .topic_descriptor()
+            .data_representations()[0]
+    }
+}
+
+/// Marker for [TopicType]s whose samples can be written in place, in memory
+/// loaned from Cyclone DDS, see
+/// [DataWriter::loan](crate::publisher::DataWriter::loan), and read without
+/// copying them, see
+/// [DataReader::read_loan](crate::subscriber::DataReader::read_loan). Loans
+/// are of topics created with [Topic::new].
+///
+/// `#[dds(pod)]` implements it with the derive, see [PodTopic].
+///
+/// # Safety
+///
+/// The same as for [Pod]: Cyclone DDS publishes the bytes of the loaned
+/// sample, so its layout must be the one of its [TopicDescriptor], and a
+/// loan starts out zeroed.
+pub unsafe trait Loanable: TopicType + Pod {}
+
+/// Marker for [TopicType]s that are plain old data, derived with
+/// `#[dds(pod)]`:
+///
+/// ```ignore
+/// #[derive(TopicType, Clone, Copy, Debug, PartialEq)]
+/// #[dds(pod)]
+/// #[repr(C)]
+/// struct Pose {
+///     #[dds(key)]
+///     id: u32,
+///     position: [f64; 3],
+///     orientation: [f64; 4],
+/// }
+/// ```
+///
+/// The derive checks that the struct is `#[repr(C)]` and its members are
+/// [Pod], and flags the [TopicDescriptor] as of fixed size, see
+/// [TopicDescriptor::from_pod_type]. Samples can then be loaned
+/// ([Loanable]) and, with a PSMX interface configured, are exchanged
+/// through shared memory without being serialized.
+///
+/// # Safety
+///
+/// The same as for [Pod] and [Loanable].
+pub unsafe trait PodTopic: Loanable + Pod {}
+
+/// The name of the type `T` without its module path and generic arguments.
+fn default_name<T: ?Sized>() -> &'static str {
+    let path = std::any::type_name::<T>();
+    let path = path.split_once('<').map_or(path, |(path, _)| path);
+    path.rsplit("::").next().unwrap()
 }
 
 pub struct TopicGuid {
@@ -0,0 +1,886 @@
+//! XTypes type information of topic types.
+//!
+//! For type discovery and the assignability checks between readers and
+//! writers, every topic advertises a TypeInformation: the TypeIdentifiers of
+//! the minimal and complete TypeObjects of its type and of all the types it
+//! depends on. The TypeMapping contains the TypeObjects themselves, so that
+//! Cyclone DDS can answer type lookup requests from remote participants.
+//!
+//! [TypeInfo::new] builds both from a [StructDesc] and serializes them as
+//! XCDR2, the same as idlc does for the descriptors it generates. The
+//! TypeIdentifier of a struct, enum or union is the first 14 bytes of the
+//! MD5 hash of its serialized TypeObject, all other types are described by
+//! the TypeIdentifier itself.
+use std::collections::HashMap;
+
+use crate::{
+    descriptor::{
+        ArrayDesc, BitmaskDesc, CaseDesc, EnumDesc, MapDesc, MemberDesc, PrimitiveKind,
+        SequenceDesc, StructDesc, TypeAutoid, TypeDesc, TypeExtensibility, UnionDesc,
+    },
+    internal::TypeMetaSer,
+};
+
+// Type kinds
+const TK_NONE: u8 = 0x00;
+const TK_BOOLEAN: u8 = 0x01;
+const TK_BYTE: u8 = 0x02;
+const TK_INT16: u8 = 0x03;
+const TK_INT32: u8 = 0x04;
+const TK_INT64: u8 = 0x05;
+const TK_UINT16: u8 = 0x06;
+const TK_UINT32: u8 = 0x07;
+const TK_UINT64: u8 = 0x08;
+const TK_FLOAT32: u8 = 0x09;
+const TK_FLOAT64: u8 = 0x0a;
+const TK_INT8: u8 = 0x0c;
+const TK_UINT8: u8 = 0x0d;
+const TK_ENUM: u8 = 0x40;
+const TK_BITMASK: u8 = 0x41;
+const TK_STRUCTURE: u8 = 0x51;
+const TK_UNION: u8 = 0x52;
+
+// TypeIdentifier kinds
+const TI_STRING8_SMALL: u8 = 0x70;
+const TI_STRING8_LARGE: u8 = 0x71;
+const TI_PLAIN_SEQUENCE_SMALL: u8 = 0x80;
+const TI_PLAIN_SEQUENCE_LARGE: u8 = 0x81;
+const TI_PLAIN_ARRAY_SMALL: u8 = 0x90;
+const TI_PLAIN_ARRAY_LARGE: u8 = 0x91;
+const TI_PLAIN_MAP_SMALL: u8 = 0xa0;
+const TI_PLAIN_MAP_LARGE: u8 = 0xa1;
+
+// Equivalence kinds
+const EK_MINIMAL: u8 = 0xf1;
+const EK_COMPLETE: u8 = 0xf2;
+const EK_BOTH: u8 = 0xf3;
+
+// Member flags
+const TRY_CONSTRUCT1: u16 = 1 << 0;
+const IS_EXTERNAL: u16 = 1 << 2;
+const IS_OPTIONAL: u16 = 1 << 3;
+const IS_MUST_UNDERSTAND: u16 = 1 << 4;
+const IS_KEY: u16 = 1 << 5;
+const IS_DEFAULT: u16 = 1 << 6;
+
+// Type flags
+const IS_FINAL: u16 = 1 << 0;
+const IS_APPENDABLE: u16 = 1 << 1;
+const IS_MUTABLE: u16 = 1 << 2;
+const IS_AUTOID_HASH: u16 = 1 << 4;
+
+// Member ids of the mutable TypeInformation
+const TYPE_INFORMATION_MINIMAL: u32 = 0x1001;
+const TYPE_INFORMATION_COMPLETE: u32 = 0x1002;
+
+/// Length code of a member header that is followed by the length of the
+/// member.
+const LC_NEXTINT: u32 = 4 << 28;
+
+/// Equivalence hash of a TypeObject.
+pub type EquivalenceHash = [u8; 14];
+
+/// The XTypes type information of a topic type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeInfo {
+    minimal: EquivalenceHash,
+    complete: EquivalenceHash,
+    type_information: Vec<u8>,
+    type_mapping: Vec<u8>,
+}
+
+impl TypeInfo {
+    /// Builds the TypeObjects of a struct and the types it depends on.
+    pub fn new(desc: &StructDesc) -> TypeInfo {
+        let mut minimal = TypeObjects::new(EK_MINIMAL);
+        let mut complete = TypeObjects::new(EK_COMPLETE);
+        let minimal_hash = minimal.hash_struct(desc);
+        let complete_hash = complete.hash_struct(desc);
+
+        let mut w = Writer::default();
+        w.dheader(|w| {
+            w.emheader(TYPE_INFORMATION_MINIMAL, |w| minimal.write_dependencies(w));
+            w.emheader(TYPE_INFORMATION_COMPLETE, |w| {
+                complete.write_dependencies(w)
+            });
+        });
+        let type_information = w.finish();
+
+        let mut w = Writer::default();
+        minimal.write_pairs(&mut w);
+        complete.write_pairs(&mut w);
+        w.dheader(|w| {
+            w.u32(complete.objects.len() as u32);
+            for (complete, minimal) in complete.ordered().zip(minimal.ordered()) {
+                TypeIdentifier::Hash(EK_COMPLETE, complete.hash).write(w);
+                TypeIdentifier::Hash(EK_MINIMAL, minimal.hash).write(w);
+            }
+        });
+        let type_mapping = w.finish();
+
+        TypeInfo {
+            minimal: minimal_hash,
+            complete: complete_hash,
+            type_information,
+            type_mapping,
+        }
+    }
+
+    /// Hash of the minimal TypeObject
+    pub fn minimal_hash(&self) -> &EquivalenceHash {
+        &self.minimal
+    }
+
+    /// Hash of the complete TypeObject
+    pub fn complete_hash(&self) -> &EquivalenceHash {
+        &self.complete
+    }
+
+    /// XCDR2 serialized TypeInformation
+    pub fn type_information(&self) -> &[u8] {
+        &self.type_information
+    }
+
+    /// XCDR2 serialized TypeMapping
+    pub fn type_mapping(&self) -> &[u8] {
+        &self.type_mapping
+    }
+
+    pub(crate) fn into_meta(self) -> (TypeMetaSer, TypeMetaSer) {
+        (
+            TypeMetaSer {
+                data: self.type_information,
+            },
+            TypeMetaSer {
+                data: self.type_mapping,
+            },
+        )
+    }
+}
+
+/// XCDR2 little endian writer for the XTypes types, which need at most an
+/// alignment of 4.
+#[derive(Default)]
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+
+    fn align(&mut self, align: usize) {
+        self.buf.resize(self.buf.len().next_multiple_of(align), 0);
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.align(2);
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.align(4);
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.u32(value as u32);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32 + 1);
+        self.bytes(value.as_bytes());
+        self.u8(0);
+    }
+
+    /// An optional member of a final struct that is absent.
+    fn absent(&mut self) {
+        self.u8(0);
+    }
+
+    /// Writes a length that is patched once `f` has written what follows.
+    fn length(&mut self, f: impl FnOnce(&mut Self)) {
+        self.u32(0);
+        let start = self.buf.len();
+        f(self);
+        let length = (self.buf.len() - start) as u32;
+        self.buf[start - 4..start].copy_from_slice(&length.to_le_bytes());
+    }
+
+    /// Writes an appendable type, or a sequence of non-primitive elements,
+    /// which start with their length (DHEADER).
+    fn dheader(&mut self, f: impl FnOnce(&mut Self)) {
+        self.length(f);
+    }
+
+    /// Writes a member of a mutable type with its member header (EMHEADER).
+    fn emheader(&mut self, id: u32, f: impl FnOnce(&mut Self)) {
+        self.u32(LC_NEXTINT | id);
+        self.length(f);
+    }
+}
+
+/// A TypeIdentifier.
+#[derive(Debug, Clone, PartialEq)]
+enum TypeIdentifier {
+    /// A primitive type, or no type at all
+    Primitive(u8),
+    /// A string, unbounded if the bound is 0
+    String(u32),
+    /// A sequence, unbounded if the bound is 0
+    Sequence {
+        equiv_kind: u8,
+        bound: u32,
+        element: Box<TypeIdentifier>,
+    },
+    Array {
+        equiv_kind: u8,
+        dims: Vec<u32>,
+        element: Box<TypeIdentifier>,
+    },
+    /// A map, unbounded if the bound is 0
+    Map {
+        equiv_kind: u8,
+        bound: u32,
+        key: Box<TypeIdentifier>,
+        element: Box<TypeIdentifier>,
+    },
+    /// A struct, enum or union by the hash of its TypeObject
+    Hash(u8, EquivalenceHash),
+}
+
+impl TypeIdentifier {
+    fn primitive(kind: &PrimitiveKind) -> TypeIdentifier {
+        TypeIdentifier::Primitive(match kind {
+            PrimitiveKind::Boolean => TK_BOOLEAN,
+            PrimitiveKind::Octet => TK_BYTE,
+            PrimitiveKind::Int8 => TK_INT8,
+            PrimitiveKind::UInt8 => TK_UINT8,
+            PrimitiveKind::Int16 => TK_INT16,
+            PrimitiveKind::UInt16 => TK_UINT16,
+            PrimitiveKind::Int32 => TK_INT32,
+            PrimitiveKind::UInt32 => TK_UINT32,
+            PrimitiveKind::Int64 => TK_INT64,
+            PrimitiveKind::UInt64 => TK_UINT64,
+            PrimitiveKind::Float32 => TK_FLOAT32,
+            PrimitiveKind::Float64 => TK_FLOAT64,
+        })
+    }
+
+    /// The equivalence kind of a collection of `element`: a fully
+    /// descriptive element is the same in minimal and complete TypeObjects.
+    fn equiv_kind(element: &TypeIdentifier) -> u8 {
+        match element {
+            TypeIdentifier::Primitive(_) | TypeIdentifier::String(_) => EK_BOTH,
+            TypeIdentifier::Sequence { equiv_kind, .. }
+            | TypeIdentifier::Array { equiv_kind, .. }
+            | TypeIdentifier::Map { equiv_kind, .. } => *equiv_kind,
+            TypeIdentifier::Hash(equiv_kind, _) => *equiv_kind,
+        }
+    }
+
+    fn write(&self, w: &mut Writer) {
+        match self {
+            TypeIdentifier::Primitive(kind) => w.u8(*kind),
+            TypeIdentifier::String(bound) if *bound < 256 => {
+                w.u8(TI_STRING8_SMALL);
+                w.u8(*bound as u8);
+            }
+            TypeIdentifier::String(bound) => {
+                w.u8(TI_STRING8_LARGE);
+                w.u32(*bound);
+            }
+            TypeIdentifier::Sequence {
+                equiv_kind,
+                bound,
+                element,
+            } => {
+                let small = *bound < 256;
+                w.u8(if small {
+                    TI_PLAIN_SEQUENCE_SMALL
+                } else {
+                    TI_PLAIN_SEQUENCE_LARGE
+                });
+                collection_header(w, *equiv_kind);
+                if small {
+                    w.u8(*bound as u8);
+                } else {
+                    w.u32(*bound);
+                }
+                element.write(w);
+            }
+            TypeIdentifier::Array {
+                equiv_kind,
+                dims,
+                element,
+            } => {
+                let small = dims.iter().all(|dim| *dim < 256);
+                w.u8(if small {
+                    TI_PLAIN_ARRAY_SMALL
+                } else {
+                    TI_PLAIN_ARRAY_LARGE
+                });
+                collection_header(w, *equiv_kind);
+                w.u32(dims.len() as u32);
+                for dim in dims {
+                    if small {
+                        w.u8(*dim as u8);
+                    } else {
+                        w.u32(*dim);
+                    }
+                }
+                element.write(w);
+            }
+            TypeIdentifier::Map {
+                equiv_kind,
+                bound,
+                key,
+                element,
+            } => {
+                let small = *bound < 256;
+                w.u8(if small {
+                    TI_PLAIN_MAP_SMALL
+                } else {
+                    TI_PLAIN_MAP_LARGE
+                });
+                collection_header(w, *equiv_kind);
+                if small {
+                    w.u8(*bound as u8);
+                } else {
+                    w.u32(*bound);
+                }
+                element.write(w);
+                // Flags of the key
+                w.u16(TRY_CONSTRUCT1);
+                key.write(w);
+            }
+            TypeIdentifier::Hash(equiv_kind, hash) => {
+                w.u8(*equiv_kind);
+                w.bytes(hash);
+            }
+        }
+    }
+}
+
+/// Writes the PlainCollectionHeader: the equivalence kind followed by the
+/// flags of the elements.
+fn collection_header(w: &mut Writer, equiv_kind: u8) {
+    w.u8(equiv_kind);
+    w.u16(TRY_CONSTRUCT1);
+}
+
+/// A serialized TypeObject and its hash.
+struct TypeObject {
+    hash: EquivalenceHash,
+    data: Vec<u8>,
+}
+
+/// Builds the minimal or complete TypeObjects of a type and the types it
+/// depends on.
+struct TypeObjects {
+    equiv_kind: u8,
+    /// The TypeObjects in the order they were built, every type comes after
+    /// the types it depends on, so the top-level type is last.
+    objects: Vec<TypeObject>,
+    /// Index in `objects` by type name
+    names: HashMap<String, usize>,
+}
+
+impl TypeObjects {
+    fn new(equiv_kind: u8) -> TypeObjects {
+        TypeObjects {
+            equiv_kind,
+            objects: Vec::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    fn complete(&self) -> bool {
+        self.equiv_kind == EK_COMPLETE
+    }
+
+    fn type_id(&mut self, type_desc: &TypeDesc) -> TypeIdentifier {
+        match type_desc {
+            TypeDesc::Primitive(kind) => TypeIdentifier::primitive(kind),
+            TypeDesc::String => TypeIdentifier::String(0),
+            TypeDesc::BoundedString(bound) => TypeIdentifier::String(*bound),
+            TypeDesc::Sequence(desc) => self.sequence_id(desc),
+            TypeDesc::Array(desc) => self.array_id(desc),
+            TypeDesc::Map(desc) => self.map_id(desc),
+            TypeDesc::Struct(desc) => TypeIdentifier::Hash(self.equiv_kind, self.hash_struct(desc)),
+            TypeDesc::Enum(desc) => TypeIdentifier::Hash(self.equiv_kind, self.hash_enum(desc)),
+            TypeDesc::Bitmask(desc) => {
+                TypeIdentifier::Hash(self.equiv_kind, self.hash_bitmask(desc))
+            }
+            TypeDesc::Union(desc) => TypeIdentifier::Hash(self.equiv_kind, self.hash_union(desc)),
+            // Optional and external are flags of the member
+            TypeDesc::Optional(inner) | TypeDesc::External(inner) => self.type_id(inner),
+        }
+    }
+
+    fn sequence_id(&mut self, desc: &SequenceDesc) -> TypeIdentifier {
+        let element = self.type_id(&desc.element);
+        TypeIdentifier::Sequence {
+            equiv_kind: TypeIdentifier::equiv_kind(&element),
+            bound: desc.bound.unwrap_or(0),
+            element: Box::new(element),
+        }
+    }
+
+    fn array_id(&mut self, desc: &ArrayDesc) -> TypeIdentifier {
+        let element = self.type_id(&desc.element);
+        TypeIdentifier::Array {
+            equiv_kind: TypeIdentifier::equiv_kind(&element),
+            dims: desc.dims.clone(),
+            element: Box::new(element),
+        }
+    }
+
+    fn map_id(&mut self, desc: &MapDesc) -> TypeIdentifier {
+        let key = self.type_id(&desc.key);
+        let element = self.type_id(&desc.value);
+        // Keys are fully descriptive, the value decides the equivalence kind
+        TypeIdentifier::Map {
+            equiv_kind: TypeIdentifier::equiv_kind(&element),
+            bound: desc.bound.unwrap_or(0),
+            key: Box::new(key),
+            element: Box::new(element),
+        }
+    }
+
+    /// Adds the TypeObject of a named type unless it has been added already
+    /// and returns its hash.
+    fn hash_named(
+        &mut self,
+        name: &str,
+        build: impl FnOnce(&mut Self, &mut Writer),
+    ) -> EquivalenceHash {
+        if let Some(index) = self.names.get(name) {
+            return self.objects[*index].hash;
+        }
+
+        let mut w = Writer::default();
+        w.dheader(|w| {
+            w.u8(self.equiv_kind);
+            build(self, w);
+        });
+        let data = w.finish();
+        let mut hash = EquivalenceHash::default();
+        hash.copy_from_slice(&md5::compute(&data)[..14]);
+
+        self.names.insert(name.to_string(), self.objects.len());
+        self.objects.push(TypeObject { hash, data });
+        hash
+    }
+
+    fn hash_struct(&mut self, desc: &StructDesc) -> EquivalenceHash {
+        // Resolve the types of the members first, the TypeObjects of the
+        // types this struct depends on come before its own.
+        let types = desc
+            .members
+            .iter()
+            .map(|member| self.type_id(&member.type_desc))
+            .collect::<Vec<_>>();
+        let base = match &desc.base {
+            Some(base) => self.type_id(&base.type_desc),
+            None => TypeIdentifier::Primitive(TK_NONE),
+        };
+        // The ids of the inherited members come first
+        let ids = desc.member_ids();
+        let ids = &ids[ids.len() - desc.members.len()..];
+
+        self.hash_named(&desc.name, |this, w| {
+            w.u8(TK_STRUCTURE);
+            let mut flags = match desc.extensibility {
+                TypeExtensibility::Final => IS_FINAL,
+                TypeExtensibility::Appendable => IS_APPENDABLE,
+                TypeExtensibility::Mutable => IS_MUTABLE,
+            };
+            if desc.autoid == TypeAutoid::Hash {
+                flags |= IS_AUTOID_HASH;
+            }
+            w.u16(flags);
+            w.dheader(|w| {
+                base.write(w);
+                this.write_type_detail(w, &desc.name);
+            });
+            w.dheader(|w| {
+                w.u32(desc.members.len() as u32);
+                for ((member, type_id), id) in desc.members.iter().zip(&types).zip(ids) {
+                    w.dheader(|w| {
+                        w.u32(*id);
+                        w.u16(member_flags(member));
+                        type_id.write(w);
+                        this.write_member_detail(w, &member.name);
+                    });
+                }
+            });
+        })
+    }
+
+    fn hash_enum(&mut self, desc: &EnumDesc) -> EquivalenceHash {
+        self.hash_named(&desc.name, |this, w| {
+            w.u8(TK_ENUM);
+            // The enum flags are unused
+            w.u16(0);
+            w.dheader(|w| {
+                w.u16(desc.bit_bound as u16);
+                this.write_type_detail(w, &desc.name);
+            });
+            w.dheader(|w| {
+                w.u32(desc.enumerators.len() as u32);
+                for (index, enumerator) in desc.enumerators.iter().enumerate() {
+                    w.dheader(|w| {
+                        w.i32(enumerator.value as i32);
+                        // The first enumerator is the default
+                        w.u16(if index == 0 { IS_DEFAULT } else { 0 });
+                        this.write_member_detail(w, &enumerator.name);
+                    });
+                }
+            });
+        })
+    }
+
+    fn hash_bitmask(&mut self, desc: &BitmaskDesc) -> EquivalenceHash {
+        self.hash_named(&desc.name, |this, w| {
+            w.u8(TK_BITMASK);
+            // The bitmask flags are unused
+            w.u16(0);
+            w.dheader(|w| {
+                w.u16(desc.bit_bound as u16);
+                this.write_type_detail(w, &desc.name);
+            });
+            w.dheader(|w| {
+                w.u32(desc.flags.len() as u32);
+                for flag in &desc.flags {
+                    w.dheader(|w| {
+                        w.u16(flag.position as u16);
+                        // The flag flags are unused
+                        w.u16(0);
+                        this.write_member_detail(w, &flag.name);
+                    });
+                }
+            });
+        })
+    }
+
+    fn hash_union(&mut self, desc: &UnionDesc) -> EquivalenceHash {
+        let types = desc
+            .cases
+            .iter()
+            .map(|case| self.type_id(&case.type_desc))
+            .collect::<Vec<_>>();
+
+        self.hash_named(&desc.name, |this, w| {
+            w.u8(TK_UNION);
+            w.u16(match desc.extensibility {
+                TypeExtensibility::Appendable => IS_APPENDABLE,
+                _ => IS_FINAL,
+            });
+            w.dheader(|w| this.write_type_detail(w, &desc.name));
+            w.dheader(|w| {
+                w.u16(TRY_CONSTRUCT1);
+                TypeIdentifier::primitive(&desc.discriminator).write(w);
+                if this.complete() {
+                    // No builtin and custom annotations
+                    w.absent();
+                    w.absent();
+                }
+            });
+            w.dheader(|w| {
+                w.u32(desc.cases.len() as u32);
+                // The discriminator has member id 0, the cases are numbered
+                // from 1
+                for (index, (case, type_id)) in desc.cases.iter().zip(&types).enumerate() {
+                    w.dheader(|w| {
+                        w.u32(index as u32 + 1);
+                        w.u16(case_flags(case));
+                        type_id.write(w);
+                        w.u32(1);
+                        w.i32(case.label as i32);
+                        this.write_member_detail(w, &case.name);
+                    });
+                }
+            });
+        })
+    }
+
+    /// Writes the CompleteTypeDetail, the minimal TypeObjects have no type
+    /// detail.
+    fn write_type_detail(&self, w: &mut Writer, name: &str) {
+        if self.complete() {
+            // No builtin and custom annotations
+            w.absent();
+            w.absent();
+            w.string(name);
+        }
+    }
+
+    /// Writes the CompleteMemberDetail or the MinimalMemberDetail, which
+    /// only holds the hash of the name.
+    fn write_member_detail(&self, w: &mut Writer, name: &str) {
+        if self.complete() {
+            w.string(name);
+            w.absent();
+            w.absent();
+        } else {
+            w.bytes(&md5::compute(name.as_bytes())[..4]);
+        }
+    }
+
+    /// Returns the TypeObjects with the top-level type first.
+    fn ordered(&self) -> impl Iterator<Item = &TypeObject> {
+        let (top, dependencies) = self.objects.split_last().unwrap();
+        std::iter::once(top).chain(dependencies)
+    }
+
+    /// Writes the TypeIdentifierWithDependencies of the top-level type.
+    fn write_dependencies(&self, w: &mut Writer) {
+        let (top, dependencies) = self.objects.split_last().unwrap();
+        w.dheader(|w| {
+            self.write_with_size(w, top);
+            w.i32(dependencies.len() as i32);
+            w.dheader(|w| {
+                w.u32(dependencies.len() as u32);
+                for object in dependencies {
+                    self.write_with_size(w, object);
+                }
+            });
+        });
+    }
+
+    /// Writes a TypeIdentifierWithSize.
+    fn write_with_size(&self, w: &mut Writer, object: &TypeObject) {
+        w.dheader(|w| {
+            TypeIdentifier::Hash(self.equiv_kind, object.hash).write(w);
+            w.u32(object.data.len() as u32);
+        });
+    }
+
+    /// Writes the pairs of TypeIdentifier and TypeObject of the TypeMapping.
+    fn write_pairs(&self, w: &mut Writer) {
+        w.dheader(|w| {
+            w.u32(self.objects.len() as u32);
+            for object in self.ordered() {
+                TypeIdentifier::Hash(self.equiv_kind, object.hash).write(w);
+                w.align(4);
+                w.bytes(&object.data);
+            }
+        });
+    }
+}
+
+fn member_flags(member: &MemberDesc) -> u16 {
+    let mut flags = TRY_CONSTRUCT1;
+    if member.key {
+        flags |= IS_KEY | IS_MUST_UNDERSTAND;
+    }
+    match &member.type_desc {
+        TypeDesc::Optional(inner) => {
+            flags |= IS_OPTIONAL;
+            if matches!(inner.as_ref(), TypeDesc::External(_)) {
+                flags |= IS_EXTERNAL;
+            }
+        }
+        TypeDesc::External(_) => flags |= IS_EXTERNAL,
+        _ => {}
+    }
+    flags
+}
+
+fn case_flags(case: &CaseDesc) -> u16 {
+    let mut flags = TRY_CONSTRUCT1;
+    if case.default {
+        flags |= IS_DEFAULT;
+    }
+    if matches!(case.type_desc, TypeDesc::External(_)) {
+        flags |= IS_EXTERNAL;
+    }
+    flags
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{TypeInfo, TypeObjects, Writer, EK_MINIMAL};
+    use crate::{
+        bounded::{BoundedMap, BoundedString},
+        descriptor::{DdsType, MemberDesc, StructDesc},
+        topic::TopicType,
+    };
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    struct Point {
+        x: f64,
+        y: f64,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    struct Path {
+        start: Point,
+        points: Vec<Point>,
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[dds(extends = Point)]
+    struct Point3 {
+        point: Point,
+        z: f64,
+    }
+
+    /// TypeMapping generated by idlc for `HelloWorldData::Msg`
+    const HELLO_WORLD_TYPE_MAPPING: &[u8] = &[
+        0x4c, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0xf1, 0x6e, 0x42, 0x14, 0x9e, 0xb1, 0x41,
+        0xa0, 0xd7, 0x25, 0x94, 0xc6, 0x50, 0xd7, 0x3c, 0x00, 0x34, 0x00, 0x00, 0x00, 0xf1, 0x51,
+        0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x24, 0x00, 0x00, 0x00, 0x02,
+        0x00, 0x00, 0x00, 0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x31, 0x00, 0x04, 0x58,
+        0x5c, 0x95, 0x70, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x70,
+        0x00, 0x78, 0xe7, 0x31, 0x02, 0x7a, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0xf2, 0xb1,
+        0xa3, 0xb1, 0xfb, 0x8b, 0x1a, 0x60, 0x51, 0x60, 0x14, 0x29, 0x7c, 0x3b, 0x8a, 0x00, 0x62,
+        0x00, 0x00, 0x00, 0xf2, 0x51, 0x01, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x14, 0x00, 0x00, 0x00, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x57, 0x6f, 0x72, 0x6c, 0x64, 0x44,
+        0x61, 0x74, 0x61, 0x3a, 0x3a, 0x4d, 0x73, 0x67, 0x00, 0x3a, 0x00, 0x00, 0x00, 0x02, 0x00,
+        0x00, 0x00, 0x15, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x31, 0x00, 0x04, 0x00, 0x07,
+        0x00, 0x00, 0x00, 0x75, 0x73, 0x65, 0x72, 0x49, 0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x16, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x70, 0x00, 0x08, 0x00, 0x00,
+        0x00, 0x6d, 0x65, 0x73, 0x73, 0x61, 0x67, 0x65, 0x00, 0x00, 0x00, 0x00, 0x00, 0x22, 0x00,
+        0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0xf2, 0xb1, 0xa3, 0xb1, 0xfb, 0x8b, 0x1a, 0x60, 0x51,
+        0x60, 0x14, 0x29, 0x7c, 0x3b, 0x8a, 0xf1, 0x6e, 0x42, 0x14, 0x9e, 0xb1, 0x41, 0xa0, 0xd7,
+        0x25, 0x94, 0xc6, 0x50, 0xd7, 0x3c,
+    ];
+
+    /// TypeInformation generated by idlc for `HelloWorldData::Msg`
+    const HELLO_WORLD_TYPE_INFORMATION: &[u8] = &[
+        0x60, 0x00, 0x00, 0x00, 0x01, 0x10, 0x00, 0x40, 0x28, 0x00, 0x00, 0x00, 0x24, 0x00, 0x00,
+        0x00, 0x14, 0x00, 0x00, 0x00, 0xf1, 0x6e, 0x42, 0x14, 0x9e, 0xb1, 0x41, 0xa0, 0xd7, 0x25,
+        0x94, 0xc6, 0x50, 0xd7, 0x3c, 0x00, 0x38, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x10, 0x00, 0x40, 0x28, 0x00, 0x00, 0x00,
+        0x24, 0x00, 0x00, 0x00, 0x14, 0x00, 0x00, 0x00, 0xf2, 0xb1, 0xa3, 0xb1, 0xfb, 0x8b, 0x1a,
+        0x60, 0x51, 0x60, 0x14, 0x29, 0x7c, 0x3b, 0x8a, 0x00, 0x66, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    fn hello_world() -> StructDesc {
+        StructDesc::new("HelloWorldData::Msg", 16, 8)
+            .member(MemberDesc::new("userID", 0, i32::type_desc()).key())
+            .member(MemberDesc::new("message", 8, String::type_desc()))
+    }
+
+    #[test]
+    fn test_hello_world() {
+        let type_info = TypeInfo::new(&hello_world());
+
+        assert_eq!(
+            type_info.minimal_hash(),
+            &[0x6e, 0x42, 0x14, 0x9e, 0xb1, 0x41, 0xa0, 0xd7, 0x25, 0x94, 0xc6, 0x50, 0xd7, 0x3c]
+        );
+        assert_eq!(
+            type_info.complete_hash(),
+            &[0xb1, 0xa3, 0xb1, 0xfb, 0x8b, 0x1a, 0x60, 0x51, 0x60, 0x14, 0x29, 0x7c, 0x3b, 0x8a]
+        );
+        assert_eq!(type_info.type_mapping(), HELLO_WORLD_TYPE_MAPPING);
+        assert_eq!(type_info.type_information(), HELLO_WORLD_TYPE_INFORMATION);
+    }
+
+    #[derive(TopicType, Clone, Debug, PartialEq)]
+    #[dds(type_name = "HelloWorldData::Msg")]
+    #[allow(non_snake_case)]
+    struct Greeting {
+        #[dds(key)]
+        userID: i32,
+        message: String,
+    }
+
+    #[test]
+    fn test_type_name() {
+        // A derived type with the IDL name of the C type is the same type
+        let type_desc = Greeting::type_desc();
+        let type_info = TypeInfo::new(type_desc.as_struct().unwrap());
+        assert_eq!(type_info.type_information(), HELLO_WORLD_TYPE_INFORMATION);
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let type_desc = Path::type_desc();
+        let type_info = TypeInfo::new(type_desc.as_struct().unwrap());
+        let point = Point::type_desc();
+        let point = TypeInfo::new(point.as_struct().unwrap());
+
+        // The TypeIdentifierWithDependencies of the minimal type lists the
+        // struct Point once
+        let type_information = type_info.type_information();
+        assert_eq!(type_information[40..44], 1i32.to_le_bytes());
+        assert!(type_information
+            .windows(14)
+            .any(|window| window == point.minimal_hash()));
+
+        // The TypeMapping holds the TypeObjects of Path and Point
+        let type_mapping = type_info.type_mapping();
+        assert_eq!(type_mapping[4..8], 2u32.to_le_bytes());
+        assert_eq!(&type_mapping[9..23], type_info.minimal_hash());
+        assert!(type_mapping
+            .windows(14)
+            .any(|window| window == point.complete_hash()));
+    }
+
+    #[test]
+    fn test_inheritance() {
+        let type_desc = Point3::type_desc();
+        let type_info = TypeInfo::new(type_desc.as_struct().unwrap());
+        let point = Point::type_desc();
+        let point = TypeInfo::new(point.as_struct().unwrap());
+
+        // The struct header refers to the base, which is a dependency
+        let type_mapping = type_info.type_mapping();
+        assert_eq!(type_mapping[4..8], 2u32.to_le_bytes());
+        let mut base = vec![EK_MINIMAL];
+        base.extend_from_slice(point.minimal_hash());
+        assert!(type_mapping.windows(15).any(|window| window == base));
+
+        // Only the own members are listed, z gets the id after y
+        let flat = StructDesc::new("typeinfo::tests::Point3", 24, 8)
+            .member(MemberDesc::new("z", 16, f64::type_desc()).id(2));
+        assert_ne!(
+            type_info.minimal_hash(),
+            TypeInfo::new(&flat).minimal_hash()
+        );
+    }
+
+    fn identifier(type_desc: &crate::descriptor::TypeDesc) -> Vec<u8> {
+        let mut w = Writer::default();
+        TypeObjects::new(EK_MINIMAL)
+            .type_id(type_desc)
+            .write(&mut w);
+        w.finish()
+    }
+
+    #[test]
+    fn test_map() {
+        // PlainMapSTypeDefn: header, bound, element identifier, key flags and
+        // key identifier
+        assert_eq!(
+            identifier(&BTreeMap::<i32, String>::type_desc()),
+            [0xa0, 0xf3, 0x01, 0x00, 0x00, 0x70, 0x00, 0x00, 0x01, 0x00, 0x04]
+        );
+        assert_eq!(
+            identifier(&BoundedMap::<BoundedString<8>, u8, 300>::type_desc()),
+            [0xa1, 0xf3, 0x01, 0x00, 0x2c, 0x01, 0x00, 0x00, 0x0d, 0x00, 0x01, 0x00, 0x70, 0x08]
+        );
+
+        // A map of structs takes the equivalence kind of the struct
+        assert_eq!(
+            identifier(&BTreeMap::<u32, Point>::type_desc())[1],
+            EK_MINIMAL
+        );
+    }
+}
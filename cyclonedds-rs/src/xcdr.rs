@@ -0,0 +1,2774 @@
+//! Serde serializer and deserializer for XCDR1 and XCDR2.
+//!
+//! Serialized samples start with an encapsulation header, whose identifier
+//! tells the version of the extended CDR encoding, the endianness and
+//! whether the top-level type is final, appendable (`D_CDR2`, a DHEADER
+//! with the length precedes the members) or mutable (`PL_CDR2`, every
+//! member is preceded by an EMHEADER with its id). [to_vec] and
+//! [from_slice] take the extensibility, the member ids, the bounds and the
+//! sizes of enums and bitmasks from the [TypeDesc] of the type, so the
+//! result is what the serializer of Cyclone DDS makes of the same sample
+//! with the descriptor of [TopicDescriptor::from_type].
+//!
+//! [to_vec_with] and [from_slice_with] without a description encode the
+//! serde data model as final types: structs and tuples are their fields,
+//! sequences, maps and strings are prefixed with their length, options
+//! with a boolean and enum variants with their index. Without the element
+//! types the DHEADERs of XCDR2 are unknown, that needs a description.
+//!
+//! Mutable types and optional members exist in XCDR2 only. XCDR1 encodes
+//! appendable types like final ones.
+//!
+//! ```ignore
+//! use cyclonedds_rs::xcdr::{from_slice, to_vec, Endianness, XcdrVersion};
+//!
+//! let data = to_vec(&msg, XcdrVersion::Xcdr2, Endianness::Little)?;
+//! assert_eq!(from_slice::<Msg>(&data)?, msg);
+//! ```
+//!
+//! [TopicDescriptor::from_type]: crate::descriptor::TopicDescriptor::from_type
+use std::{mem, ptr};
+
+use serde::{
+    de::{
+        self, value::U32Deserializer, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor,
+    },
+    ser::{self, Serialize},
+    Deserialize,
+};
+use thiserror::Error;
+
+use crate::descriptor::{
+    key_members, ArrayDesc, DdsType, MemberDesc, PrimitiveKind, StructDesc, TypeDesc,
+    TypeExtensibility, UnionDesc, MEMBER_ID_MASK,
+};
+
+/// Plain CDR, big endian
+pub const CDR_BE: u16 = 0x0000;
+/// Plain CDR, little endian
+pub const CDR_LE: u16 = 0x0001;
+/// Parameter list CDR, big endian
+pub const PL_CDR_BE: u16 = 0x0002;
+/// Parameter list CDR, little endian
+pub const PL_CDR_LE: u16 = 0x0003;
+/// XCDR2 of a final type, big endian
+pub const CDR2_BE: u16 = 0x0006;
+/// XCDR2 of a final type, little endian
+pub const CDR2_LE: u16 = 0x0007;
+/// XCDR2 of an appendable type, big endian
+pub const D_CDR2_BE: u16 = 0x0008;
+/// XCDR2 of an appendable type, little endian
+pub const D_CDR2_LE: u16 = 0x0009;
+/// XCDR2 of a mutable type, big endian
+pub const PL_CDR2_BE: u16 = 0x000a;
+/// XCDR2 of a mutable type, little endian
+pub const PL_CDR2_LE: u16 = 0x000b;
+
+/// Size of the encapsulation header: the identifier and the options, whose
+/// lowest two bits are the number of padding bytes at the end.
+pub const HEADER_SIZE: usize = 4;
+
+/// The member must be understood by the reader, set for key members.
+const EMHEADER_MUST_UNDERSTAND: u32 = 1 << 31;
+/// Length code of a member header that is followed by the length.
+const LC_NEXTINT: u32 = 4;
+
+/// Version of the extended CDR encoding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum XcdrVersion {
+    #[default]
+    Xcdr1,
+    Xcdr2,
+}
+
+impl XcdrVersion {
+    /// Largest alignment of a primitive, 8 byte primitives are aligned to
+    /// 4 bytes in XCDR2.
+    fn max_align(self) -> usize {
+        match self {
+            XcdrVersion::Xcdr1 => 8,
+            XcdrVersion::Xcdr2 => 4,
+        }
+    }
+}
+
+/// Byte order of serialized data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endianness {
+    Big,
+    Little,
+}
+
+impl Endianness {
+    /// The byte order of the host.
+    pub const NATIVE: Endianness = if cfg!(target_endian = "big") {
+        Endianness::Big
+    } else {
+        Endianness::Little
+    };
+}
+
+/// Errors of the serializer and the deserializer.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum XcdrError {
+    #[error("{0}")]
+    Message(String),
+    #[error("unexpected end of data")]
+    Eof,
+    #[error("unknown encapsulation {0:#06x}")]
+    Encapsulation(u16),
+    #[error("XCDR1 can not encode {0}")]
+    Xcdr1(&'static str),
+    #[error("XCDR2 needs the description of the type")]
+    Untyped,
+    #[error("expected {expected}, found {found}")]
+    Mismatch {
+        expected: String,
+        found: &'static str,
+    },
+    #[error("{len} elements exceed the bound of {bound}")]
+    Bound { bound: u32, len: usize },
+    #[error("expected {expected} elements, found {len}")]
+    Length { expected: u32, len: usize },
+    #[error("invalid boolean {0}")]
+    Bool(u8),
+    #[error("string is not nul terminated UTF-8")]
+    String,
+    #[error("{0} is not an enumerator of the enum")]
+    Enumerator(u64),
+    #[error("bits {0:#x} are not flags of the bitmask")]
+    Bits(u64),
+    #[error("no case of the union for discriminator {0}")]
+    Discriminator(u32),
+    #[error("unknown member {0} must be understood")]
+    MustUnderstand(u32),
+    #[error("{0} is not supported")]
+    Unsupported(&'static str),
+}
+
+impl ser::Error for XcdrError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        XcdrError::Message(msg.to_string())
+    }
+}
+
+impl de::Error for XcdrError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        XcdrError::Message(msg.to_string())
+    }
+}
+
+type Result<T> = std::result::Result<T, XcdrError>;
+
+/// Returns the encapsulation identifier of a top-level type with
+/// `extensibility`. XCDR1 encodes appendable types as final ones.
+pub fn encapsulation_id(
+    version: XcdrVersion,
+    endianness: Endianness,
+    extensibility: TypeExtensibility,
+) -> u16 {
+    let id = match (version, extensibility) {
+        (XcdrVersion::Xcdr1, TypeExtensibility::Mutable) => PL_CDR_BE,
+        (XcdrVersion::Xcdr1, _) => CDR_BE,
+        (XcdrVersion::Xcdr2, TypeExtensibility::Final) => CDR2_BE,
+        (XcdrVersion::Xcdr2, TypeExtensibility::Appendable) => D_CDR2_BE,
+        (XcdrVersion::Xcdr2, TypeExtensibility::Mutable) => PL_CDR2_BE,
+    };
+    match endianness {
+        Endianness::Big => id,
+        Endianness::Little => id | 1,
+    }
+}
+
+/// Returns the version and the endianness of an encapsulation identifier.
+pub fn parse_encapsulation_id(id: u16) -> Result<(XcdrVersion, Endianness)> {
+    let version = match id & !1 {
+        CDR_BE | PL_CDR_BE => XcdrVersion::Xcdr1,
+        CDR2_BE | D_CDR2_BE | PL_CDR2_BE => XcdrVersion::Xcdr2,
+        _ => return Err(XcdrError::Encapsulation(id)),
+    };
+    let endianness = match id & 1 {
+        0 => Endianness::Big,
+        _ => Endianness::Little,
+    };
+    Ok((version, endianness))
+}
+
+/// Serializes `value` with an encapsulation header.
+pub fn to_vec<T: Serialize + DdsType>(
+    value: &T,
+    version: XcdrVersion,
+    endianness: Endianness,
+) -> Result<Vec<u8>> {
+    to_vec_with(value, Some(&T::type_desc()), version, endianness)
+}
+
+/// Serializes `value` with an encapsulation header, as the type `desc`
+/// describes or, without it, as final types.
+pub fn to_vec_with<T: Serialize + ?Sized>(
+    value: &T,
+    desc: Option<&TypeDesc>,
+    version: XcdrVersion,
+    endianness: Endianness,
+) -> Result<Vec<u8>> {
+    let id = encapsulation_id(version, endianness, top_level_extensibility(desc));
+    let mut buf = Vec::with_capacity(64);
+    buf.extend_from_slice(&id.to_be_bytes());
+    buf.extend_from_slice(&[0, 0]);
+    let mut buf = serialize_into(buf, value, desc, version, endianness)?;
+    let padding = (HEADER_SIZE - buf.len() % HEADER_SIZE) % HEADER_SIZE;
+    buf.resize(buf.len() + padding, 0);
+    buf[3] = padding as u8;
+    Ok(buf)
+}
+
+/// Serializes `value` without an encapsulation header.
+pub fn to_payload<T: Serialize + ?Sized>(
+    value: &T,
+    desc: Option<&TypeDesc>,
+    version: XcdrVersion,
+    endianness: Endianness,
+) -> Result<Vec<u8>> {
+    serialize_into(Vec::new(), value, desc, version, endianness)
+}
+
+fn serialize_into<T: Serialize + ?Sized>(
+    buf: Vec<u8>,
+    value: &T,
+    desc: Option<&TypeDesc>,
+    version: XcdrVersion,
+    endianness: Endianness,
+) -> Result<Vec<u8>> {
+    if desc.is_none() && version == XcdrVersion::Xcdr2 {
+        return Err(XcdrError::Untyped);
+    }
+    let mut w = Writer {
+        origin: buf.len(),
+        buf,
+        version,
+        endianness,
+        absent: false,
+        key: None,
+        depth: 0,
+    };
+    value.serialize(Serializer {
+        w: &mut w,
+        ty: desc.map_or(Ty::Untyped, Ty::of),
+    })?;
+    Ok(w.buf)
+}
+
+/// Serializes the key fields of `value` as XCDR2 in the order of their
+/// member ids, without DHEADERs and EMHEADERs. This is the serialized key
+/// the key hash is made of, a type without keys has an empty key.
+pub fn to_key_payload<T: Serialize + DdsType>(
+    value: &T,
+    endianness: Endianness,
+) -> Result<Vec<u8>> {
+    let desc = T::type_desc();
+    let (keys, _) = desc.as_struct().map_or((Vec::new(), true), key_members);
+    serialize_key(value, &desc, &keys, endianness)
+}
+
+/// Returns the key hash of `value` as the DDS specification defines it:
+/// the big endian serialized key of [to_key_payload], padded with zeros,
+/// if it always fits in 16 bytes, otherwise its MD5 hash. Samples of the
+/// same instance have the same key hash, no matter who computes it.
+pub fn key_hash<T: Serialize + DdsType>(value: &T) -> Result<[u8; 16]> {
+    let desc = T::type_desc();
+    let (keys, fixed) = desc.as_struct().map_or((Vec::new(), true), key_members);
+    let key = serialize_key(value, &desc, &keys, Endianness::Big)?;
+    if !fixed {
+        return Ok(md5::compute(&key).0);
+    }
+    let mut hash = [0; 16];
+    hash[..key.len()].copy_from_slice(&key);
+    Ok(hash)
+}
+
+/// Serializes the key fields, each one leading from `desc` through the
+/// members of `keys`, one after the other.
+fn serialize_key<T: Serialize + ?Sized>(
+    value: &T,
+    desc: &TypeDesc,
+    keys: &[Vec<&MemberDesc>],
+    endianness: Endianness,
+) -> Result<Vec<u8>> {
+    let mut w = Writer {
+        buf: Vec::new(),
+        origin: 0,
+        version: XcdrVersion::Xcdr2,
+        endianness,
+        absent: false,
+        key: None,
+        depth: 0,
+    };
+    for key in keys {
+        w.key = Some(key.as_slice());
+        value.serialize(Serializer {
+            w: &mut w,
+            ty: Ty::of(desc),
+        })?;
+    }
+    Ok(w.buf)
+}
+
+/// Deserializes a sample serialized with its encapsulation header.
+pub fn from_slice<T: DeserializeOwned + DdsType>(data: &[u8]) -> Result<T> {
+    from_slice_with(data, Some(&T::type_desc()))
+}
+
+/// Deserializes a sample serialized with its encapsulation header, as the
+/// type `desc` describes or, without it, as final types.
+pub fn from_slice_with<'de, T: Deserialize<'de>>(
+    data: &'de [u8],
+    desc: Option<&TypeDesc>,
+) -> Result<T> {
+    let header = data.get(..HEADER_SIZE).ok_or(XcdrError::Eof)?;
+    let id = u16::from_be_bytes([header[0], header[1]]);
+    let (version, endianness) = parse_encapsulation_id(id)?;
+    if id != encapsulation_id(version, endianness, top_level_extensibility(desc)) {
+        return Err(XcdrError::Encapsulation(id));
+    }
+    from_payload(&data[HEADER_SIZE..], desc, version, endianness)
+}
+
+/// Deserializes a sample serialized without an encapsulation header.
+pub fn from_payload<'de, T: Deserialize<'de>>(
+    data: &'de [u8],
+    desc: Option<&TypeDesc>,
+    version: XcdrVersion,
+    endianness: Endianness,
+) -> Result<T> {
+    if desc.is_none() && version == XcdrVersion::Xcdr2 {
+        return Err(XcdrError::Untyped);
+    }
+    let mut r = Reader {
+        data,
+        pos: 0,
+        version,
+        endianness,
+        params: Vec::new(),
+    };
+    T::deserialize(Deserializer {
+        r: &mut r,
+        ty: desc.map_or(Ty::Untyped, Ty::of),
+    })
+}
+
+fn top_level_extensibility(desc: Option<&TypeDesc>) -> TypeExtensibility {
+    match desc {
+        Some(TypeDesc::Struct(desc)) => desc.extensibility,
+        Some(TypeDesc::Union(desc)) => desc.extensibility,
+        Some(TypeDesc::External(inner)) => top_level_extensibility(Some(inner)),
+        _ => TypeExtensibility::Final,
+    }
+}
+
+/// The type the serializer expects next.
+#[derive(Clone, Copy)]
+enum Ty<'a> {
+    /// No description, the serde data model is encoded as final types
+    Untyped,
+    Type(&'a TypeDesc),
+    /// An optional member, absent members of a mutable type are left out
+    Optional(&'a TypeDesc, bool),
+    /// The base of a struct, whose members are part of the derived struct
+    Base(&'a StructDesc),
+    /// A dimension after the first of a multi-dimensional array
+    Dim(&'a ArrayDesc, usize),
+}
+
+impl<'a> Ty<'a> {
+    /// External types are stored behind a pointer, serialized they are
+    /// the type itself.
+    fn of(desc: &'a TypeDesc) -> Ty<'a> {
+        match desc {
+            TypeDesc::External(inner) => Ty::of(inner),
+            desc => Ty::Type(desc),
+        }
+    }
+
+    fn member(desc: &'a TypeDesc, mutable: bool) -> Ty<'a> {
+        match desc {
+            TypeDesc::Optional(inner) => Ty::Optional(inner, mutable),
+            desc => Ty::of(desc),
+        }
+    }
+
+    fn mismatch(self, found: &'static str) -> XcdrError {
+        let expected = match self {
+            Ty::Untyped => "a value".to_string(),
+            Ty::Type(desc) => describe(desc),
+            Ty::Optional(desc, _) => format!("optional {}", describe(desc)),
+            Ty::Base(desc) => format!("base struct {}", desc.name),
+            Ty::Dim(_, _) => "array".to_string(),
+        };
+        XcdrError::Mismatch { expected, found }
+    }
+}
+
+fn describe(desc: &TypeDesc) -> String {
+    match desc {
+        TypeDesc::Primitive(kind) => kind.idl_name().to_string(),
+        TypeDesc::String | TypeDesc::BoundedString(_) => "string".to_string(),
+        TypeDesc::Sequence(_) => "sequence".to_string(),
+        TypeDesc::Array(_) => "array".to_string(),
+        TypeDesc::Map(_) => "map".to_string(),
+        TypeDesc::Struct(desc) => format!("struct {}", desc.name),
+        TypeDesc::Enum(desc) => format!("enum {}", desc.name),
+        TypeDesc::Bitmask(desc) => format!("bitmask {}", desc.name),
+        TypeDesc::Union(desc) => format!("union {}", desc.name),
+        TypeDesc::Optional(inner) => format!("optional {}", describe(inner)),
+        TypeDesc::External(inner) => describe(inner),
+    }
+}
+
+/// Whether a sequence or array of `element` needs a DHEADER in XCDR2.
+fn needs_dheader(version: XcdrVersion, element: &TypeDesc) -> bool {
+    version == XcdrVersion::Xcdr2 && !matches!(element, TypeDesc::Primitive(_))
+}
+
+/// The ids of the members of a struct without those of its base.
+fn own_member_ids(desc: &StructDesc) -> Vec<u32> {
+    let mut ids = desc.member_ids();
+    ids.drain(..ids.len() - desc.members.len());
+    ids
+}
+
+fn check_enumerator(desc: &crate::descriptor::EnumDesc, value: u64) -> Result<()> {
+    match desc.enumerators.iter().any(|e| e.value as u64 == value) {
+        true => Ok(()),
+        false => Err(XcdrError::Enumerator(value)),
+    }
+}
+
+fn check_bits(desc: &crate::descriptor::BitmaskDesc, value: u64) -> Result<()> {
+    match value & !desc.bits() {
+        0 => Ok(()),
+        bits => Err(XcdrError::Bits(bits)),
+    }
+}
+
+fn check_bound(bound: Option<u32>, len: usize) -> Result<()> {
+    match bound {
+        Some(bound) if len > bound as usize => Err(XcdrError::Bound { bound, len }),
+        _ => Ok(()),
+    }
+}
+
+fn is_float(kind: PrimitiveKind) -> bool {
+    matches!(kind, PrimitiveKind::Float32 | PrimitiveKind::Float64)
+}
+
+struct Writer<'a> {
+    buf: Vec<u8>,
+    /// Where the payload starts, alignment is relative to it
+    origin: usize,
+    version: XcdrVersion,
+    endianness: Endianness,
+    /// Set when an optional member of a mutable type turned out absent
+    absent: bool,
+    /// The members leading to the key field to serialize, all other members
+    /// are skipped
+    key: Option<&'a [&'a MemberDesc]>,
+    /// Number of members of `key` entered
+    depth: usize,
+}
+
+impl Writer<'_> {
+    fn align(&mut self, align: usize) {
+        let align = align.min(self.version.max_align());
+        let len = (self.buf.len() - self.origin).next_multiple_of(align) + self.origin;
+        self.buf.resize(len, 0);
+    }
+
+    /// Writes the lowest `size` bytes of `value`.
+    fn uint(&mut self, value: u64, size: usize) {
+        self.align(size);
+        match self.endianness {
+            Endianness::Big => self.buf.extend_from_slice(&value.to_be_bytes()[8 - size..]),
+            Endianness::Little => self.buf.extend_from_slice(&value.to_le_bytes()[..size]),
+        }
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.uint(value as u64, 4);
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32 + 1);
+        self.buf.extend_from_slice(value.as_bytes());
+        self.buf.push(0);
+    }
+
+    /// Writes a length or count to be patched, returns where what it
+    /// covers starts.
+    fn reserve(&mut self) -> usize {
+        self.u32(0);
+        self.buf.len()
+    }
+
+    fn patch(&mut self, start: usize, value: u32) {
+        let bytes = match self.endianness {
+            Endianness::Big => value.to_be_bytes(),
+            Endianness::Little => value.to_le_bytes(),
+        };
+        self.buf[start - 4..start].copy_from_slice(&bytes);
+    }
+
+    /// Patches a DHEADER or the length of a member with the number of
+    /// bytes written since `start`.
+    fn patch_length(&mut self, start: usize) {
+        self.patch(start, (self.buf.len() - start) as u32);
+    }
+
+    fn dheader(&mut self, needed: bool) -> Option<usize> {
+        needed.then(|| self.reserve())
+    }
+
+    fn end_dheader(&mut self, start: Option<usize>) {
+        if let Some(start) = start {
+            self.patch_length(start);
+        }
+    }
+}
+
+struct Serializer<'a, 'w> {
+    w: &'w mut Writer<'a>,
+    ty: Ty<'a>,
+}
+
+impl<'a, 'w> Serializer<'a, 'w> {
+    fn primitive(self, kind: PrimitiveKind, bits: u64) -> Result<()> {
+        match self.ty {
+            Ty::Untyped => self.w.uint(bits, kind.size() as usize),
+            Ty::Type(TypeDesc::Primitive(expected))
+                if *expected == kind
+                    || !is_float(kind)
+                        && !is_float(*expected)
+                        && kind != PrimitiveKind::Boolean
+                        && *expected != PrimitiveKind::Boolean =>
+            {
+                self.w.uint(bits, expected.size() as usize)
+            }
+            Ty::Type(TypeDesc::Enum(desc)) if !is_float(kind) => {
+                check_enumerator(desc, bits)?;
+                self.w.uint(bits, desc.serialized_size() as usize)
+            }
+            Ty::Type(TypeDesc::Bitmask(desc)) if !is_float(kind) => {
+                check_bits(desc, bits)?;
+                self.w.uint(bits, desc.serialized_size() as usize)
+            }
+            ty => return Err(ty.mismatch(kind.idl_name())),
+        }
+        Ok(())
+    }
+
+    fn sequence(self, len: Option<usize>) -> Result<Compound<'a, 'w>> {
+        let (element, bound) = match self.ty {
+            Ty::Untyped => (Ty::Untyped, None),
+            Ty::Type(TypeDesc::Sequence(desc)) => (Ty::of(&desc.element), desc.bound),
+            Ty::Type(TypeDesc::Array(desc)) => return self.array(desc, 0, len),
+            Ty::Dim(desc, dim) => return self.array(desc, dim, len),
+            ty => return Err(ty.mismatch("sequence")),
+        };
+        if let Some(len) = len {
+            check_bound(bound, len)?;
+        }
+        let dheader = match element {
+            Ty::Type(element) => self.w.dheader(needs_dheader(self.w.version, element)),
+            _ => None,
+        };
+        let count = Some(self.w.reserve());
+        Ok(Compound {
+            w: self.w,
+            element,
+            dheader,
+            count,
+            len: 0,
+            expected: None,
+            bound,
+        })
+    }
+
+    fn array(
+        self,
+        desc: &'a ArrayDesc,
+        dim: usize,
+        len: Option<usize>,
+    ) -> Result<Compound<'a, 'w>> {
+        let expected = desc.dims[dim];
+        if let Some(len) = len.filter(|len| *len != expected as usize) {
+            return Err(XcdrError::Length { expected, len });
+        }
+        let dheader = match dim {
+            0 => self.w.dheader(needs_dheader(self.w.version, &desc.element)),
+            _ => None,
+        };
+        let element = match dim + 1 < desc.dims.len() {
+            true => Ty::Dim(desc, dim + 1),
+            false => Ty::of(&desc.element),
+        };
+        Ok(Compound {
+            w: self.w,
+            element,
+            dheader,
+            count: None,
+            len: 0,
+            expected: Some(expected),
+            bound: None,
+        })
+    }
+
+    fn structure(self, desc: &'a StructDesc, inline: bool) -> Result<StructSerializer<'a, 'w>> {
+        // A key is serialized without the headers of the structs leading
+        // to the key fields.
+        let keyed = self.w.key.is_some();
+        let mutable = desc.extensibility == TypeExtensibility::Mutable && !keyed;
+        if mutable && self.w.version == XcdrVersion::Xcdr1 {
+            return Err(XcdrError::Xcdr1("mutable types"));
+        }
+        let dheader = self.w.dheader(
+            !inline
+                && !keyed
+                && self.w.version == XcdrVersion::Xcdr2
+                && desc.extensibility != TypeExtensibility::Final,
+        );
+        Ok(StructSerializer {
+            w: self.w,
+            desc: Some(desc),
+            ids: if mutable {
+                own_member_ids(desc)
+            } else {
+                Vec::new()
+            },
+            index: 0,
+            dheader,
+            mutable,
+        })
+    }
+
+    fn union(self, desc: &'a UnionDesc, index: u32) -> Result<(Option<usize>, Ty<'a>)> {
+        let case = desc
+            .cases
+            .get(index as usize)
+            .ok_or(XcdrError::Discriminator(index))?;
+        let dheader = self.w.dheader(
+            self.w.version == XcdrVersion::Xcdr2 && desc.extensibility != TypeExtensibility::Final,
+        );
+        self.w
+            .uint(case.label as u64, desc.discriminator.size() as usize);
+        Ok((dheader, Ty::of(&case.type_desc)))
+    }
+
+    fn untyped_variant(self, index: u32, found: &'static str) -> Result<Self> {
+        match self.ty {
+            Ty::Untyped => {
+                self.w.u32(index);
+                Ok(self)
+            }
+            ty => Err(ty.mismatch(found)),
+        }
+    }
+}
+
+macro_rules! serialize_primitive {
+    ($($method:ident($type:ty) => $kind:ident,)*) => {
+        $(
+            fn $method(self, value: $type) -> Result<()> {
+                self.primitive(PrimitiveKind::$kind, value as u64)
+            }
+        )*
+    };
+}
+
+impl<'a, 'w> ser::Serializer for Serializer<'a, 'w> {
+    type Ok = ();
+    type Error = XcdrError;
+    type SerializeSeq = Compound<'a, 'w>;
+    type SerializeTuple = Compound<'a, 'w>;
+    type SerializeTupleStruct = Compound<'a, 'w>;
+    type SerializeTupleVariant = Compound<'a, 'w>;
+    type SerializeMap = MapSerializer<'a, 'w>;
+    type SerializeStruct = StructSerializer<'a, 'w>;
+    type SerializeStructVariant = StructSerializer<'a, 'w>;
+
+    serialize_primitive! {
+        serialize_bool(bool) => Boolean,
+        serialize_i8(i8) => Int8,
+        serialize_i16(i16) => Int16,
+        serialize_i32(i32) => Int32,
+        serialize_i64(i64) => Int64,
+        serialize_u8(u8) => UInt8,
+        serialize_u16(u16) => UInt16,
+        serialize_u32(u32) => UInt32,
+        serialize_u64(u64) => UInt64,
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<()> {
+        self.primitive(PrimitiveKind::Float32, value.to_bits() as u64)
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<()> {
+        self.primitive(PrimitiveKind::Float64, value.to_bits())
+    }
+
+    /// An IDL `char`, which is a single byte.
+    fn serialize_char(self, value: char) -> Result<()> {
+        let byte = u8::try_from(value).map_err(|_| XcdrError::Unsupported("char beyond U+00FF"))?;
+        self.serialize_u8(byte)
+    }
+
+    fn serialize_str(self, value: &str) -> Result<()> {
+        match self.ty {
+            Ty::Untyped | Ty::Type(TypeDesc::String) => {}
+            Ty::Type(TypeDesc::BoundedString(bound)) => check_bound(Some(*bound), value.len())?,
+            ty => return Err(ty.mismatch("string")),
+        }
+        self.w.string(value);
+        Ok(())
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<()> {
+        match self.ty {
+            Ty::Untyped => {}
+            Ty::Type(TypeDesc::Sequence(desc)) if matches!(desc.element, TypeDesc::Primitive(kind) if kind.size() == 1) => {
+                check_bound(desc.bound, value.len())?
+            }
+            ty => return Err(ty.mismatch("bytes")),
+        }
+        self.w.u32(value.len() as u32);
+        self.w.buf.extend_from_slice(value);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        match self.ty {
+            Ty::Untyped => self.w.uint(0, 1),
+            Ty::Optional(_, _) if self.w.version == XcdrVersion::Xcdr1 => {
+                return Err(XcdrError::Xcdr1("optional members"))
+            }
+            Ty::Optional(_, true) => self.w.absent = true,
+            Ty::Optional(_, false) => self.w.uint(0, 1),
+            ty => return Err(ty.mismatch("none")),
+        }
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<()> {
+        let ty = match self.ty {
+            Ty::Untyped => Ty::Untyped,
+            Ty::Optional(_, _) if self.w.version == XcdrVersion::Xcdr1 => {
+                return Err(XcdrError::Xcdr1("optional members"))
+            }
+            Ty::Optional(inner, mutable) => {
+                if !mutable {
+                    self.w.uint(1, 1);
+                }
+                return value.serialize(Serializer {
+                    w: self.w,
+                    ty: Ty::of(inner),
+                });
+            }
+            ty => return Err(ty.mismatch("some")),
+        };
+        self.w.uint(1, 1);
+        value.serialize(Serializer { w: self.w, ty })
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        match self.ty {
+            Ty::Untyped => self.w.u32(variant_index),
+            Ty::Type(TypeDesc::Enum(desc)) => {
+                let enumerator = desc
+                    .enumerators
+                    .get(variant_index as usize)
+                    .ok_or(XcdrError::Enumerator(variant_index as u64))?;
+                self.w
+                    .uint(enumerator.value as u64, desc.serialized_size() as usize);
+            }
+            ty => return Err(ty.mismatch("unit variant")),
+        }
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        match self.ty {
+            Ty::Type(TypeDesc::Union(desc)) => {
+                let w = &mut *self.w;
+                let (dheader, ty) = Serializer { w, ty: self.ty }.union(desc, variant_index)?;
+                value.serialize(Serializer { w, ty })?;
+                w.end_dheader(dheader);
+                Ok(())
+            }
+            _ => value.serialize(self.untyped_variant(variant_index, "newtype variant")?),
+        }
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Compound<'a, 'w>> {
+        self.sequence(len)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'a, 'w>> {
+        match self.ty {
+            Ty::Untyped => Ok(Compound {
+                w: self.w,
+                element: Ty::Untyped,
+                dheader: None,
+                count: None,
+                len: 0,
+                expected: None,
+                bound: None,
+            }),
+            Ty::Type(TypeDesc::Array(desc)) => self.array(desc, 0, Some(len)),
+            Ty::Dim(desc, dim) => self.array(desc, dim, Some(len)),
+            ty => Err(ty.mismatch("tuple")),
+        }
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Compound<'a, 'w>> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a, 'w>> {
+        self.untyped_variant(variant_index, "tuple variant")?
+            .serialize_tuple(len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer<'a, 'w>> {
+        let (key, value, bound) = match self.ty {
+            Ty::Untyped => (Ty::Untyped, Ty::Untyped, None),
+            Ty::Type(TypeDesc::Map(desc)) => (Ty::of(&desc.key), Ty::of(&desc.value), desc.bound),
+            ty => return Err(ty.mismatch("map")),
+        };
+        if let Some(len) = len {
+            check_bound(bound, len)?;
+        }
+        // The entries are structs, which need a DHEADER.
+        let dheader = self.w.dheader(self.w.version == XcdrVersion::Xcdr2);
+        let count = self.w.reserve();
+        Ok(MapSerializer {
+            w: self.w,
+            key,
+            value,
+            dheader,
+            count,
+            len: 0,
+            bound,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<StructSerializer<'a, 'w>> {
+        match self.ty {
+            Ty::Untyped => Ok(StructSerializer {
+                w: self.w,
+                desc: None,
+                ids: Vec::new(),
+                index: 0,
+                dheader: None,
+                mutable: false,
+            }),
+            Ty::Type(TypeDesc::Struct(desc)) => self.structure(desc, false),
+            Ty::Base(desc) => self.structure(desc, true),
+            ty => Err(ty.mismatch("struct")),
+        }
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        len: usize,
+    ) -> Result<StructSerializer<'a, 'w>> {
+        self.untyped_variant(variant_index, "struct variant")?
+            .serialize_struct("", len)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Sequences, arrays and tuples.
+struct Compound<'a, 'w> {
+    w: &'w mut Writer<'a>,
+    element: Ty<'a>,
+    dheader: Option<usize>,
+    /// Where the number of elements is to be patched in
+    count: Option<usize>,
+    len: usize,
+    /// Number of elements of an array
+    expected: Option<u32>,
+    bound: Option<u32>,
+}
+
+impl<'a, 'w> Compound<'a, 'w> {
+    fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.len += 1;
+        value.serialize(Serializer {
+            w: self.w,
+            ty: self.element,
+        })
+    }
+
+    fn finish(self) -> Result<()> {
+        if let Some(expected) = self
+            .expected
+            .filter(|expected| *expected as usize != self.len)
+        {
+            return Err(XcdrError::Length {
+                expected,
+                len: self.len,
+            });
+        }
+        check_bound(self.bound, self.len)?;
+        if let Some(count) = self.count {
+            self.w.patch(count, self.len as u32);
+        }
+        self.w.end_dheader(self.dheader);
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for Compound<'_, '_> {
+    type Ok = ();
+    type Error = XcdrError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for Compound<'_, '_> {
+    type Ok = ();
+    type Error = XcdrError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound<'_, '_> {
+    type Ok = ();
+    type Error = XcdrError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for Compound<'_, '_> {
+    type Ok = ();
+    type Error = XcdrError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+struct MapSerializer<'a, 'w> {
+    w: &'w mut Writer<'a>,
+    key: Ty<'a>,
+    value: Ty<'a>,
+    dheader: Option<usize>,
+    count: usize,
+    len: usize,
+    bound: Option<u32>,
+}
+
+impl ser::SerializeMap for MapSerializer<'_, '_> {
+    type Ok = ();
+    type Error = XcdrError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<()> {
+        self.len += 1;
+        key.serialize(Serializer {
+            w: self.w,
+            ty: self.key,
+        })
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        value.serialize(Serializer {
+            w: self.w,
+            ty: self.value,
+        })
+    }
+
+    fn end(self) -> Result<()> {
+        check_bound(self.bound, self.len)?;
+        self.w.patch(self.count, self.len as u32);
+        self.w.end_dheader(self.dheader);
+        Ok(())
+    }
+}
+
+struct StructSerializer<'a, 'w> {
+    w: &'w mut Writer<'a>,
+    desc: Option<&'a StructDesc>,
+    /// Member ids of a mutable struct
+    ids: Vec<u32>,
+    index: usize,
+    dheader: Option<usize>,
+    mutable: bool,
+}
+
+impl<'a> StructSerializer<'a, '_> {
+    fn field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        let Some(desc) = self.desc else {
+            return value.serialize(Serializer {
+                w: self.w,
+                ty: Ty::Untyped,
+            });
+        };
+        let index = self.index;
+        self.index += 1;
+        // The first field of a derived struct holds the base.
+        let index = match &desc.base {
+            Some(base) if index == 0 => {
+                return value.serialize(Serializer {
+                    w: self.w,
+                    ty: Ty::Base(base.desc()),
+                })
+            }
+            Some(_) => index - 1,
+            None => index,
+        };
+        let member = desc.members.get(index).ok_or_else(|| XcdrError::Mismatch {
+            expected: format!("struct {}", desc.name),
+            found: "more fields",
+        })?;
+        if let Some(key) = self.w.key {
+            return self.key_field(member, key, value);
+        }
+        let ty = Ty::member(&member.type_desc, self.mutable);
+        if !self.mutable {
+            return value.serialize(Serializer { w: self.w, ty });
+        }
+
+        self.w.align(4);
+        let header = self.w.buf.len();
+        let mut emheader = LC_NEXTINT << 28 | self.ids[index] & MEMBER_ID_MASK;
+        if member.key {
+            emheader |= EMHEADER_MUST_UNDERSTAND;
+        }
+        self.w.u32(emheader);
+        let start = self.w.reserve();
+        value.serialize(Serializer { w: self.w, ty })?;
+        if mem::take(&mut self.w.absent) {
+            self.w.buf.truncate(header);
+        } else {
+            self.w.patch_length(start);
+        }
+        Ok(())
+    }
+
+    /// Serializes `value` if `member` leads to the key field of `key`, the
+    /// key field itself is serialized as a whole.
+    fn key_field<T: ?Sized + Serialize>(
+        &mut self,
+        member: &'a MemberDesc,
+        key: &'a [&'a MemberDesc],
+        value: &T,
+    ) -> Result<()> {
+        if !ptr::eq(member, key[self.w.depth]) {
+            return Ok(());
+        }
+        let ty = Ty::of(&member.type_desc);
+        if self.w.depth + 1 < key.len() {
+            self.w.depth += 1;
+            value.serialize(Serializer { w: self.w, ty })?;
+            self.w.depth -= 1;
+        } else {
+            self.w.key = None;
+            value.serialize(Serializer { w: self.w, ty })?;
+            self.w.key = Some(key);
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for StructSerializer<'_, '_> {
+    type Ok = ();
+    type Error = XcdrError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.field(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.w.end_dheader(self.dheader);
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for StructSerializer<'_, '_> {
+    type Ok = ();
+    type Error = XcdrError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.field(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.w.end_dheader(self.dheader);
+        Ok(())
+    }
+}
+
+/// A member of a mutable struct found in the serialized data.
+#[derive(Debug, Clone, Copy)]
+struct Param {
+    id: u32,
+    start: usize,
+}
+
+struct Reader<'de> {
+    data: &'de [u8],
+    pos: usize,
+    version: XcdrVersion,
+    endianness: Endianness,
+    /// The members of the innermost mutable struct
+    params: Vec<Param>,
+}
+
+impl<'de> Reader<'de> {
+    fn align(&mut self, align: usize) {
+        self.pos = self
+            .pos
+            .next_multiple_of(align.min(self.version.max_align()));
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'de [u8]> {
+        let end = self.pos.checked_add(len).ok_or(XcdrError::Eof)?;
+        let bytes = self.data.get(self.pos..end).ok_or(XcdrError::Eof)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn uint(&mut self, size: usize) -> Result<u64> {
+        self.align(size);
+        let bytes = self.take(size)?;
+        Ok(match self.endianness {
+            Endianness::Big => bytes
+                .iter()
+                .fold(0, |value, byte| value << 8 | *byte as u64),
+            Endianness::Little => bytes
+                .iter()
+                .rev()
+                .fold(0, |value, byte| value << 8 | *byte as u64),
+        })
+    }
+
+    fn int(&mut self, size: usize) -> Result<i64> {
+        let shift = 64 - 8 * size as u32;
+        Ok((self.uint(size)? << shift) as i64 >> shift)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(self.uint(4)? as u32)
+    }
+
+    fn str(&mut self, bound: Option<u32>) -> Result<&'de str> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+        let Some((0, bytes)) = bytes.split_last() else {
+            return Err(XcdrError::String);
+        };
+        check_bound(bound, bytes.len())?;
+        std::str::from_utf8(bytes).map_err(|_| XcdrError::String)
+    }
+
+    /// Reads a DHEADER, returns where the data it covers ends.
+    fn dheader(&mut self, needed: bool) -> Result<Option<usize>> {
+        if !needed {
+            return Ok(None);
+        }
+        let len = self.u32()? as usize;
+        let end = self.pos.checked_add(len).ok_or(XcdrError::Eof)?;
+        if end > self.data.len() {
+            return Err(XcdrError::Eof);
+        }
+        Ok(Some(end))
+    }
+
+    fn end_dheader(&mut self, end: Option<usize>) {
+        if let Some(end) = end {
+            self.pos = end;
+        }
+    }
+
+    fn primitive<V: Visitor<'de>>(&mut self, kind: PrimitiveKind, visitor: V) -> Result<V::Value> {
+        let size = kind.size() as usize;
+        match kind {
+            PrimitiveKind::Boolean => match self.uint(1)? as u8 {
+                0 => visitor.visit_bool(false),
+                1 => visitor.visit_bool(true),
+                byte => Err(XcdrError::Bool(byte)),
+            },
+            PrimitiveKind::Int8 => visitor.visit_i8(self.int(size)? as i8),
+            PrimitiveKind::Int16 => visitor.visit_i16(self.int(size)? as i16),
+            PrimitiveKind::Int32 => visitor.visit_i32(self.int(size)? as i32),
+            PrimitiveKind::Int64 => visitor.visit_i64(self.int(size)?),
+            PrimitiveKind::Octet | PrimitiveKind::UInt8 => visitor.visit_u8(self.uint(size)? as u8),
+            PrimitiveKind::UInt16 => visitor.visit_u16(self.uint(size)? as u16),
+            PrimitiveKind::UInt32 => visitor.visit_u32(self.uint(size)? as u32),
+            PrimitiveKind::UInt64 => visitor.visit_u64(self.uint(size)?),
+            PrimitiveKind::Float32 => visitor.visit_f32(f32::from_bits(self.uint(size)? as u32)),
+            PrimitiveKind::Float64 => visitor.visit_f64(f64::from_bits(self.uint(size)?)),
+        }
+    }
+
+    /// Reads the member headers of a mutable struct up to `end`, the
+    /// members are what follows their header or, with the length codes
+    /// 5 to 7, the length itself.
+    fn params(&mut self, end: usize, ids: &[u32]) -> Result<Vec<Param>> {
+        let mut params = Vec::with_capacity(ids.len());
+        loop {
+            self.align(4);
+            if self.pos >= end {
+                break;
+            }
+            let emheader = self.u32()?;
+            let id = emheader & MEMBER_ID_MASK;
+            let (start, len) = match emheader >> 28 & 0x7 {
+                lc @ 0..=3 => (self.pos, 1 << lc),
+                LC_NEXTINT => {
+                    let len = self.u32()? as usize;
+                    (self.pos, len)
+                }
+                lc => {
+                    let start = self.pos;
+                    let len = self.u32()? as usize;
+                    let len = len
+                        .checked_mul([1, 4, 8][lc as usize - 5])
+                        .ok_or(XcdrError::Eof)?;
+                    (start, 4 + len)
+                }
+            };
+            let member_end = start
+                .checked_add(len)
+                .filter(|member_end| *member_end <= end);
+            self.pos = member_end.ok_or(XcdrError::Eof)?;
+            if ids.contains(&id) {
+                params.push(Param { id, start });
+            } else if emheader & EMHEADER_MUST_UNDERSTAND != 0 {
+                return Err(XcdrError::MustUnderstand(id));
+            }
+        }
+        Ok(params)
+    }
+
+    fn discriminator(&mut self, kind: PrimitiveKind) -> Result<u32> {
+        let size = kind.size() as usize;
+        Ok(match kind {
+            PrimitiveKind::Int8
+            | PrimitiveKind::Int16
+            | PrimitiveKind::Int32
+            | PrimitiveKind::Int64 => self.int(size)? as u32,
+            _ => self.uint(size)? as u32,
+        })
+    }
+}
+
+struct Deserializer<'a, 'r, 'de> {
+    r: &'r mut Reader<'de>,
+    ty: Ty<'a>,
+}
+
+impl<'a, 'r, 'de> Deserializer<'a, 'r, 'de> {
+    fn number<V: Visitor<'de>>(self, requested: PrimitiveKind, visitor: V) -> Result<V::Value> {
+        let kind = match self.ty {
+            Ty::Untyped => requested,
+            Ty::Type(TypeDesc::Primitive(kind)) => *kind,
+            Ty::Type(TypeDesc::Enum(desc)) => {
+                let value = self.r.uint(desc.serialized_size() as usize)?;
+                check_enumerator(desc, value)?;
+                return visitor.visit_u64(value);
+            }
+            Ty::Type(TypeDesc::Bitmask(desc)) => {
+                let value = self.r.uint(desc.serialized_size() as usize)?;
+                check_bits(desc, value)?;
+                return visitor.visit_u64(value);
+            }
+            ty => return Err(ty.mismatch(requested.idl_name())),
+        };
+        self.r.primitive(kind, visitor)
+    }
+
+    fn sequence<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let (element, bound) = match self.ty {
+            Ty::Untyped => (Ty::Untyped, None),
+            Ty::Type(TypeDesc::Sequence(desc)) => (Ty::of(&desc.element), desc.bound),
+            Ty::Type(TypeDesc::Array(desc)) => return self.array(desc, 0, None, visitor),
+            Ty::Dim(desc, dim) => return self.array(desc, dim, None, visitor),
+            ty => return Err(ty.mismatch("sequence")),
+        };
+        let needed = matches!(element, Ty::Type(element) if needs_dheader(self.r.version, element));
+        let end = self.r.dheader(needed)?;
+        let len = self.r.u32()? as usize;
+        check_bound(bound, len)?;
+        let value = visitor.visit_seq(Elements {
+            r: self.r,
+            ty: element,
+            remaining: len,
+        })?;
+        self.r.end_dheader(end);
+        Ok(value)
+    }
+
+    fn array<V: Visitor<'de>>(
+        self,
+        desc: &'a ArrayDesc,
+        dim: usize,
+        len: Option<usize>,
+        visitor: V,
+    ) -> Result<V::Value> {
+        let expected = desc.dims[dim];
+        if let Some(len) = len.filter(|len| *len != expected as usize) {
+            return Err(XcdrError::Length { expected, len });
+        }
+        let end = self
+            .r
+            .dheader(dim == 0 && needs_dheader(self.r.version, &desc.element))?;
+        let element = match dim + 1 < desc.dims.len() {
+            true => Ty::Dim(desc, dim + 1),
+            false => Ty::of(&desc.element),
+        };
+        let value = visitor.visit_seq(Elements {
+            r: self.r,
+            ty: element,
+            remaining: expected as usize,
+        })?;
+        self.r.end_dheader(end);
+        Ok(value)
+    }
+
+    fn structure<V: Visitor<'de>>(
+        self,
+        desc: &'a StructDesc,
+        inline: bool,
+        fields: Option<&'static [&'static str]>,
+        visitor: V,
+    ) -> Result<V::Value> {
+        let r = self.r;
+        let mutable = desc.extensibility == TypeExtensibility::Mutable;
+        if mutable && r.version == XcdrVersion::Xcdr1 {
+            return Err(XcdrError::Xcdr1("mutable types"));
+        }
+        let end = r.dheader(
+            !inline
+                && r.version == XcdrVersion::Xcdr2
+                && desc.extensibility != TypeExtensibility::Final,
+        )?;
+        let fields = Fields {
+            desc,
+            names: fields,
+        };
+        if !mutable {
+            let value = visitor.visit_map(Members {
+                r,
+                fields,
+                index: 0,
+                end,
+            })?;
+            // An appendable struct may have more members than known.
+            r.end_dheader(end);
+            return Ok(value);
+        }
+
+        // The members of a base are in the parameter list of the derived
+        // struct, which is read once by the outermost struct.
+        let saved = match end {
+            Some(end) => {
+                let params = r.params(end, &desc.member_ids())?;
+                Some(mem::replace(&mut r.params, params))
+            }
+            None => None,
+        };
+        let value = visitor.visit_map(Params {
+            r,
+            fields,
+            ids: own_member_ids(desc),
+            index: 0,
+            next: None,
+        });
+        if let Some(saved) = saved {
+            r.params = saved;
+        }
+        r.end_dheader(end);
+        value
+    }
+
+    fn union<V: Visitor<'de>>(self, desc: &'a UnionDesc, visitor: V) -> Result<V::Value> {
+        let r = self.r;
+        let end = r.dheader(
+            r.version == XcdrVersion::Xcdr2 && desc.extensibility != TypeExtensibility::Final,
+        )?;
+        let label = r.discriminator(desc.discriminator)?;
+        let index = desc
+            .cases
+            .iter()
+            .position(|case| case.label == label)
+            .or_else(|| desc.cases.iter().position(|case| case.default))
+            .ok_or(XcdrError::Discriminator(label))?;
+        let value = visitor.visit_enum(Variant {
+            r,
+            index: index as u32,
+            ty: Ty::of(&desc.cases[index].type_desc),
+        })?;
+        r.end_dheader(end);
+        Ok(value)
+    }
+}
+
+macro_rules! deserialize_number {
+    ($($method:ident => $kind:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                self.number(PrimitiveKind::$kind, visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'_, '_, 'de> {
+    type Error = XcdrError;
+
+    /// Only with a description, the serialized data does not tell its
+    /// types.
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.ty {
+            Ty::Untyped => Err(XcdrError::Unsupported(
+                "deserializing without a description",
+            )),
+            Ty::Type(TypeDesc::Primitive(kind)) => self.r.primitive(*kind, visitor),
+            Ty::Type(TypeDesc::String | TypeDesc::BoundedString(_)) => {
+                self.deserialize_str(visitor)
+            }
+            Ty::Type(TypeDesc::Sequence(_) | TypeDesc::Array(_)) | Ty::Dim(_, _) => {
+                self.sequence(visitor)
+            }
+            Ty::Type(TypeDesc::Map(_)) => self.deserialize_map(visitor),
+            Ty::Type(TypeDesc::Struct(desc)) => self.structure(desc, false, None, visitor),
+            Ty::Base(desc) => self.structure(desc, true, None, visitor),
+            Ty::Type(TypeDesc::Enum(_) | TypeDesc::Bitmask(_)) => {
+                self.number(PrimitiveKind::UInt64, visitor)
+            }
+            Ty::Type(TypeDesc::Union(desc)) => self.union(desc, visitor),
+            Ty::Optional(_, _) => self.deserialize_option(visitor),
+            Ty::Type(TypeDesc::Optional(_) | TypeDesc::External(_)) => {
+                unreachable!("resolved by Ty::of and Ty::member")
+            }
+        }
+    }
+
+    deserialize_number! {
+        deserialize_bool => Boolean,
+        deserialize_i8 => Int8,
+        deserialize_i16 => Int16,
+        deserialize_i32 => Int32,
+        deserialize_i64 => Int64,
+        deserialize_u8 => UInt8,
+        deserialize_u16 => UInt16,
+        deserialize_u32 => UInt32,
+        deserialize_u64 => UInt64,
+        deserialize_f32 => Float32,
+        deserialize_f64 => Float64,
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.ty {
+            Ty::Untyped
+            | Ty::Type(TypeDesc::Primitive(PrimitiveKind::Int8 | PrimitiveKind::UInt8)) => {
+                visitor.visit_char(self.r.uint(1)? as u8 as char)
+            }
+            ty => Err(ty.mismatch("char")),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let bound = match self.ty {
+            Ty::Untyped | Ty::Type(TypeDesc::String) => None,
+            Ty::Type(TypeDesc::BoundedString(bound)) => Some(*bound),
+            ty => return Err(ty.mismatch("string")),
+        };
+        visitor.visit_borrowed_str(self.r.str(bound)?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let bound = match self.ty {
+            Ty::Untyped => None,
+            Ty::Type(TypeDesc::Sequence(desc)) if matches!(desc.element, TypeDesc::Primitive(kind) if kind.size() == 1) => {
+                desc.bound
+            }
+            ty => return Err(ty.mismatch("bytes")),
+        };
+        let len = self.r.u32()? as usize;
+        check_bound(bound, len)?;
+        visitor.visit_borrowed_bytes(self.r.take(len)?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let ty = match self.ty {
+            Ty::Untyped => Ty::Untyped,
+            Ty::Optional(_, _) if self.r.version == XcdrVersion::Xcdr1 => {
+                return Err(XcdrError::Xcdr1("optional members"))
+            }
+            // Only members that are present are deserialized.
+            Ty::Optional(inner, true) => {
+                return visitor.visit_some(Deserializer {
+                    r: self.r,
+                    ty: Ty::of(inner),
+                })
+            }
+            Ty::Optional(inner, false) => Ty::of(inner),
+            ty => return Err(ty.mismatch("option")),
+        };
+        match self.r.uint(1)? as u8 {
+            0 => visitor.visit_none(),
+            1 => visitor.visit_some(Deserializer { r: self.r, ty }),
+            byte => Err(XcdrError::Bool(byte)),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.sequence(visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        match self.ty {
+            Ty::Untyped => visitor.visit_seq(Elements {
+                r: self.r,
+                ty: Ty::Untyped,
+                remaining: len,
+            }),
+            Ty::Type(TypeDesc::Array(desc)) => self.array(desc, 0, Some(len), visitor),
+            Ty::Dim(desc, dim) => self.array(desc, dim, Some(len), visitor),
+            ty => Err(ty.mismatch("tuple")),
+        }
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let (key, value, bound) = match self.ty {
+            Ty::Untyped => (Ty::Untyped, Ty::Untyped, None),
+            Ty::Type(TypeDesc::Map(desc)) => (Ty::of(&desc.key), Ty::of(&desc.value), desc.bound),
+            ty => return Err(ty.mismatch("map")),
+        };
+        let r = self.r;
+        let end = r.dheader(r.version == XcdrVersion::Xcdr2)?;
+        let len = r.u32()? as usize;
+        check_bound(bound, len)?;
+        let map = visitor.visit_map(Entries {
+            r,
+            key,
+            value,
+            remaining: len,
+        })?;
+        r.end_dheader(end);
+        Ok(map)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.ty {
+            Ty::Untyped => visitor.visit_seq(Elements {
+                r: self.r,
+                ty: Ty::Untyped,
+                remaining: fields.len(),
+            }),
+            Ty::Type(TypeDesc::Struct(desc)) => self.structure(desc, false, Some(fields), visitor),
+            Ty::Base(desc) => self.structure(desc, true, Some(fields), visitor),
+            ty => Err(ty.mismatch("struct")),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.ty {
+            Ty::Untyped => {
+                let index = self.r.u32()?;
+                visitor.visit_enum(Variant {
+                    r: self.r,
+                    index,
+                    ty: Ty::Untyped,
+                })
+            }
+            Ty::Type(TypeDesc::Enum(desc)) => {
+                let value = self.r.uint(desc.serialized_size() as usize)?;
+                let index = desc
+                    .enumerators
+                    .iter()
+                    .position(|enumerator| enumerator.value as u64 == value)
+                    .ok_or(XcdrError::Enumerator(value))?;
+                visitor.visit_enum(Variant {
+                    r: self.r,
+                    index: index as u32,
+                    ty: self.ty,
+                })
+            }
+            Ty::Type(TypeDesc::Union(desc)) => self.union(desc, visitor),
+            ty => Err(ty.mismatch("enum")),
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_u32(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_any(visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Elements of sequences, arrays and tuples, and the fields of structs
+/// without a description.
+struct Elements<'a, 'r, 'de> {
+    r: &'r mut Reader<'de>,
+    ty: Ty<'a>,
+    remaining: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for Elements<'_, '_, 'de> {
+    type Error = XcdrError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(Deserializer {
+            r: self.r,
+            ty: self.ty,
+        })
+        .map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+struct Entries<'a, 'r, 'de> {
+    r: &'r mut Reader<'de>,
+    key: Ty<'a>,
+    value: Ty<'a>,
+    remaining: usize,
+}
+
+impl<'de> de::MapAccess<'de> for Entries<'_, '_, 'de> {
+    type Error = XcdrError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(Deserializer {
+            r: self.r,
+            ty: self.key,
+        })
+        .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        seed.deserialize(Deserializer {
+            r: self.r,
+            ty: self.value,
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// The fields of a described struct: the base, if any, followed by the
+/// members, named as serde knows them or else as the description does.
+#[derive(Clone, Copy)]
+struct Fields<'a> {
+    desc: &'a StructDesc,
+    names: Option<&'static [&'static str]>,
+}
+
+impl<'a> Fields<'a> {
+    fn len(&self) -> usize {
+        self.desc.members.len() + self.desc.base.is_some() as usize
+    }
+
+    fn name(&self, index: usize) -> Result<&'a str> {
+        if let Some(names) = self.names {
+            return names
+                .get(index)
+                .copied()
+                .ok_or_else(|| XcdrError::Mismatch {
+                    expected: format!("{} fields", self.len()),
+                    found: "fewer fields",
+                });
+        }
+        Ok(match (&self.desc.base, index) {
+            (Some(base), 0) => &base.desc().name,
+            (Some(_), index) => &self.desc.members[index - 1].name,
+            (None, index) => &self.desc.members[index].name,
+        })
+    }
+
+    /// The type of a field and, for members, its index in the members.
+    fn ty(&self, index: usize, mutable: bool) -> (Ty<'a>, Option<usize>) {
+        let index = match (&self.desc.base, index) {
+            (Some(base), 0) => return (Ty::Base(base.desc()), None),
+            (Some(_), index) => index - 1,
+            (None, index) => index,
+        };
+        (
+            Ty::member(&self.desc.members[index].type_desc, mutable),
+            Some(index),
+        )
+    }
+}
+
+/// The members of a final or appendable struct, in order. An appendable
+/// struct ends after fewer members if the writer knew fewer.
+struct Members<'a, 'r, 'de> {
+    r: &'r mut Reader<'de>,
+    fields: Fields<'a>,
+    index: usize,
+    end: Option<usize>,
+}
+
+impl<'de> de::MapAccess<'de> for Members<'_, '_, 'de> {
+    type Error = XcdrError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.index == self.fields.len() || self.end.is_some_and(|end| self.r.pos >= end) {
+            return Ok(None);
+        }
+        let name = self.fields.name(self.index)?;
+        seed.deserialize(name.into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let (ty, _) = self.fields.ty(self.index, false);
+        self.index += 1;
+        seed.deserialize(Deserializer { r: self.r, ty })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.fields.len() - self.index)
+    }
+}
+
+/// The members of a mutable struct that are present, in the order of the
+/// description.
+struct Params<'a, 'r, 'de> {
+    r: &'r mut Reader<'de>,
+    fields: Fields<'a>,
+    ids: Vec<u32>,
+    index: usize,
+    /// The type of the field whose key was returned and where the member
+    /// starts, the base has no start of its own
+    next: Option<(Ty<'a>, Option<usize>)>,
+}
+
+impl<'de> de::MapAccess<'de> for Params<'_, '_, 'de> {
+    type Error = XcdrError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        while self.index < self.fields.len() {
+            let index = self.index;
+            self.index += 1;
+            self.next = match self.fields.ty(index, true) {
+                (ty, None) => Some((ty, None)),
+                (ty, Some(member)) => {
+                    let id = self.ids[member];
+                    match self.r.params.iter().find(|param| param.id == id) {
+                        Some(param) => Some((ty, Some(param.start))),
+                        None => continue,
+                    }
+                }
+            };
+            let name = self.fields.name(index)?;
+            return seed.deserialize(name.into_deserializer()).map(Some);
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let (ty, start) = self.next.take().expect("next_key_seed returned a key");
+        if let Some(start) = start {
+            self.r.pos = start;
+        }
+        seed.deserialize(Deserializer { r: self.r, ty })
+    }
+}
+
+/// A variant of an enum or a union, or of an enum of the serde data model
+/// without a description.
+struct Variant<'a, 'r, 'de> {
+    r: &'r mut Reader<'de>,
+    index: u32,
+    ty: Ty<'a>,
+}
+
+impl<'a, 'r, 'de> de::EnumAccess<'de> for Variant<'a, 'r, 'de> {
+    type Error = XcdrError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let deserializer: U32Deserializer<XcdrError> = self.index.into_deserializer();
+        Ok((seed.deserialize(deserializer)?, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Variant<'_, '_, 'de> {
+    type Error = XcdrError;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(Deserializer {
+            r: self.r,
+            ty: self.ty,
+        })
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        match self.ty {
+            Ty::Untyped => visitor.visit_seq(Elements {
+                r: self.r,
+                ty: Ty::Untyped,
+                remaining: len,
+            }),
+            ty => Err(ty.mismatch("tuple variant")),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.tuple_variant(fields.len(), visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        ffi::c_void,
+        mem::{size_of, MaybeUninit},
+        slice,
+    };
+
+    use cyclonedds_sys::{DDS_CDR_ENC_VERSION_1, DDS_CDR_ENC_VERSION_2};
+    use serde::{Deserialize, Serialize};
+
+    use super::{
+        from_payload, from_slice, key_hash, to_key_payload, to_payload, to_vec, Endianness,
+        XcdrError, XcdrVersion, HEADER_SIZE,
+    };
+    use crate::{
+        bounded::{BoundedString, BoundedVec},
+        descriptor::{DdsType, OwnedTopicDescriptor},
+        topic::TopicType,
+    };
+
+    const LE: Endianness = Endianness::Little;
+    const BE: Endianness = Endianness::Big;
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Msg {
+        #[dds(key)]
+        user_id: i32,
+        message: String,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Position {
+        x: f64,
+        y: f64,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Sample {
+        tag: u8,
+        value: f64,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+    #[repr(u32)]
+    enum Color {
+        Red,
+        Green = 2,
+        Blue = 1,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+    #[repr(i32)]
+    #[dds(bit_bound = 8)]
+    enum Level {
+        Low,
+        High,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Light {
+        #[dds(key)]
+        level: Level,
+        color: Color,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+    #[repr(C, u32)]
+    enum Value {
+        Int(i32) = 1,
+        Real(f64),
+        #[dds(default)]
+        Level(Level) = 7,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[repr(i8)]
+    enum Small {
+        Byte(u8),
+        Word(u32),
+        Point(Position),
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+    #[dds(bitmask(READ, WRITE, EXECUTE = 4), bit_bound = 5)]
+    #[repr(transparent)]
+    struct Permissions(u8);
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[dds(appendable)]
+    struct Status {
+        #[dds(key)]
+        id: u32,
+        #[serde(default)]
+        level: u8,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[dds(appendable, type_name = "Status")]
+    struct StatusV0 {
+        #[dds(key)]
+        id: u32,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[dds(appendable, type_name = "Status")]
+    struct StatusV2 {
+        #[dds(key)]
+        id: u32,
+        level: u8,
+        detail: String,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[dds(appendable, extends = Status)]
+    struct Alarm {
+        status: Status,
+        message: String,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[dds(mutable)]
+    struct Settings {
+        #[dds(key)]
+        id: u32,
+        note: Option<String>,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[dds(mutable)]
+    struct Tuning {
+        #[dds(key, id = 10)]
+        id: u32,
+        gain: f64,
+        #[dds(hashid)]
+        level: Level,
+        #[dds(id = 2)]
+        mode: Permissions,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[dds(mutable, extends = Settings)]
+    struct Profile {
+        settings: Settings,
+        #[dds(id = 5)]
+        limit: u16,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[dds(appendable)]
+    struct Device {
+        #[dds(key, id = 2)]
+        serial: String,
+        #[dds(key, id = 1)]
+        zone: u8,
+        #[dds(key, id = 3)]
+        position: Position,
+        reading: f64,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Range {
+        value: Option<u16>,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Inventory {
+        counts: BTreeMap<String, u32>,
+        names: Vec<String>,
+        ids: Vec<u32>,
+        colors: [Color; 2],
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[dds(appendable)]
+    struct Frame {
+        #[dds(key)]
+        id: u32,
+        light: Light,
+        value: Value,
+        small: Small,
+        mode: Permissions,
+        matrix: [[i16; 3]; 2],
+        status: Status,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Plain {
+        flag: bool,
+        small: i8,
+        real: f64,
+        name: String,
+        frame: Frame,
+        inventory: Inventory,
+        tags: BoundedVec<BoundedString<8>, 4>,
+    }
+
+    #[derive(TopicType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[dds(appendable)]
+    struct Everything {
+        plain: Plain,
+        range: Option<u16>,
+        origin: Option<Position>,
+        settings: Settings,
+        tuning: Tuning,
+        profile: Profile,
+        alarms: Vec<Alarm>,
+    }
+
+    fn payload(data: &[u8]) -> &[u8] {
+        let padding = data[3] as usize & 0x3;
+        &data[HEADER_SIZE..data.len() - padding]
+    }
+
+    #[test]
+    fn test_header() {
+        let msg = Msg {
+            user_id: 7,
+            message: "hi".to_string(),
+        };
+        let data = to_vec(&msg, XcdrVersion::Xcdr1, LE).unwrap();
+        assert_eq!(data, [0, 1, 0, 1, 7, 0, 0, 0, 3, 0, 0, 0, b'h', b'i', 0, 0]);
+        assert_eq!(from_slice::<Msg>(&data), Ok(msg.clone()));
+
+        let data = to_vec(&msg, XcdrVersion::Xcdr2, BE).unwrap();
+        assert_eq!(data, [0, 6, 0, 1, 0, 0, 0, 7, 0, 0, 0, 3, b'h', b'i', 0, 0]);
+        assert_eq!(from_slice::<Msg>(&data), Ok(msg));
+
+        assert_eq!(from_slice::<Msg>(&[0, 6]), Err(XcdrError::Eof));
+        assert_eq!(
+            from_slice::<Msg>(&[0, 0x0c, 0, 0]),
+            Err(XcdrError::Encapsulation(0x0c))
+        );
+        // The header of an appendable type
+        assert_eq!(
+            from_slice::<Msg>(&[0, 9, 0, 0]),
+            Err(XcdrError::Encapsulation(9))
+        );
+    }
+
+    #[test]
+    fn test_alignment() {
+        let sample = Sample { tag: 1, value: 0.5 };
+        let xcdr1 = to_vec(&sample, XcdrVersion::Xcdr1, LE).unwrap();
+        let mut expected = vec![1, 0, 0, 0, 0, 0, 0, 0];
+        expected.extend(0.5f64.to_le_bytes());
+        assert_eq!(payload(&xcdr1), expected);
+
+        let xcdr2 = to_vec(&sample, XcdrVersion::Xcdr2, LE).unwrap();
+        expected.drain(4..8);
+        assert_eq!(payload(&xcdr2), expected);
+        assert_eq!(from_slice::<Sample>(&xcdr1), Ok(sample.clone()));
+        assert_eq!(from_slice::<Sample>(&xcdr2), Ok(sample));
+    }
+
+    #[test]
+    fn test_appendable() {
+        let status = Status { id: 1, level: 2 };
+        assert_eq!(
+            to_vec(&status, XcdrVersion::Xcdr2, BE).unwrap(),
+            [0, 8, 0, 3, 0, 0, 0, 5, 0, 0, 0, 1, 2, 0, 0, 0]
+        );
+        // XCDR1 has no DHEADER
+        assert_eq!(
+            to_vec(&status, XcdrVersion::Xcdr1, LE).unwrap(),
+            [0, 1, 0, 3, 1, 0, 0, 0, 2, 0, 0, 0]
+        );
+
+        // A newer writer with more members, an older one with fewer
+        let newer = StatusV2 {
+            id: 1,
+            level: 2,
+            detail: "more".to_string(),
+        };
+        let data = to_vec(&newer, XcdrVersion::Xcdr2, LE).unwrap();
+        assert_eq!(from_slice::<Status>(&data), Ok(status));
+        let data = to_vec(&StatusV0 { id: 3 }, XcdrVersion::Xcdr2, LE).unwrap();
+        assert_eq!(from_slice::<Status>(&data), Ok(Status { id: 3, level: 0 }));
+    }
+
+    #[test]
+    fn test_mutable() {
+        let settings = Settings { id: 1, note: None };
+        let data = to_vec(&settings, XcdrVersion::Xcdr2, LE).unwrap();
+        assert_eq!(
+            data,
+            [0, 0x0b, 0, 0, 12, 0, 0, 0, 0, 0, 0, 0xc0, 4, 0, 0, 0, 1, 0, 0, 0]
+        );
+        assert_eq!(from_slice::<Settings>(&data), Ok(settings.clone()));
+        assert_eq!(
+            to_vec(&settings, XcdrVersion::Xcdr1, LE),
+            Err(XcdrError::Xcdr1("mutable types"))
+        );
+
+        let settings = Settings {
+            id: 1,
+            note: Some("x".to_string()),
+        };
+        let data = to_vec(&settings, XcdrVersion::Xcdr2, BE).unwrap();
+        assert_eq!(
+            payload(&data),
+            [
+                0, 0, 0, 26, 0xc0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 1, 0x40, 0, 0, 1, 0, 0, 0, 6, 0,
+                0, 0, 2, b'x', 0
+            ]
+        );
+        assert_eq!(from_slice::<Settings>(&data), Ok(settings));
+
+        // Out of order, with length codes other than NEXTINT and an
+        // unknown member that need not be understood
+        let data = [
+            0, 0x0b, 0, 0, 28, 0, 0, 0, 1, 0, 0, 0x50, 2, 0, 0, 0, b'x', 0, 0, 0, 9, 0, 0, 0x10, 0,
+            0, 0, 0, 0, 0, 0, 0xa0, 3, 0, 0, 0,
+        ];
+        assert_eq!(
+            from_slice::<Settings>(&data),
+            Ok(Settings {
+                id: 3,
+                note: Some("x".to_string()),
+            })
+        );
+        let mut data = data;
+        data[23] = 0x90;
+        assert_eq!(
+            from_slice::<Settings>(&data),
+            Err(XcdrError::MustUnderstand(9))
+        );
+    }
+
+    #[test]
+    fn test_mutable_inheritance() {
+        let profile = Profile {
+            settings: Settings {
+                id: 4,
+                note: Some("n".to_string()),
+            },
+            limit: 9,
+        };
+        let data = to_vec(&profile, XcdrVersion::Xcdr2, LE).unwrap();
+        // One parameter list with the members of the base first
+        let ids: Vec<u8> = [4, 16, 32].iter().map(|at| payload(&data)[*at]).collect();
+        assert_eq!(ids, [0, 1, 5]);
+        assert_eq!(from_slice::<Profile>(&data), Ok(profile));
+    }
+
+    #[test]
+    fn test_optional() {
+        let range = Range { value: Some(5) };
+        let data = to_vec(&range, XcdrVersion::Xcdr2, LE).unwrap();
+        assert_eq!(payload(&data), [1, 0, 5, 0]);
+        assert_eq!(from_slice::<Range>(&data), Ok(range));
+
+        let range = Range { value: None };
+        let data = to_vec(&range, XcdrVersion::Xcdr2, LE).unwrap();
+        assert_eq!(payload(&data), [0]);
+        assert_eq!(from_slice::<Range>(&data), Ok(range.clone()));
+
+        assert_eq!(
+            to_vec(&range, XcdrVersion::Xcdr1, LE),
+            Err(XcdrError::Xcdr1("optional members"))
+        );
+        assert_eq!(
+            from_slice::<Range>(&[0, 1, 0, 0, 0]),
+            Err(XcdrError::Xcdr1("optional members"))
+        );
+        assert_eq!(
+            from_slice::<Range>(&[0, 7, 0, 0, 2]),
+            Err(XcdrError::Bool(2))
+        );
+    }
+
+    #[test]
+    fn test_enums_and_unions() {
+        let light = Light {
+            level: Level::High,
+            color: Color::Green,
+        };
+        let data = to_vec(&light, XcdrVersion::Xcdr1, LE).unwrap();
+        assert_eq!(payload(&data), [1, 0, 0, 0, 2, 0, 0, 0]);
+        assert_eq!(from_slice::<Light>(&data), Ok(light));
+        assert_eq!(
+            from_slice::<Light>(&[0, 1, 0, 0, 1, 0, 0, 0, 3, 0, 0, 0]),
+            Err(XcdrError::Enumerator(3))
+        );
+
+        let value = Value::Level(Level::High);
+        let data = to_vec(&value, XcdrVersion::Xcdr2, LE).unwrap();
+        assert_eq!(payload(&data), [7, 0, 0, 0, 1]);
+        assert_eq!(from_slice::<Value>(&data), Ok(value));
+        // The default case takes any other discriminator
+        assert_eq!(
+            from_slice::<Value>(&[0, 7, 0, 0, 9, 0, 0, 0, 0]),
+            Ok(Value::Level(Level::Low))
+        );
+
+        let small = Small::Word(9);
+        let data = to_vec(&small, XcdrVersion::Xcdr2, BE).unwrap();
+        assert_eq!(payload(&data), [1, 0, 0, 0, 0, 0, 0, 9]);
+        assert_eq!(from_slice::<Small>(&data), Ok(small));
+        assert_eq!(
+            from_slice::<Small>(&[0, 6, 0, 0, 5]),
+            Err(XcdrError::Discriminator(5))
+        );
+    }
+
+    #[test]
+    fn test_bitmask() {
+        let data = to_vec(&Permissions(0b10011), XcdrVersion::Xcdr2, LE).unwrap();
+        assert_eq!(payload(&data), [0b10011]);
+        assert_eq!(from_slice(&data), Ok(Permissions(0b10011)));
+        assert_eq!(
+            to_vec(&Permissions(0b1000), XcdrVersion::Xcdr2, LE),
+            Err(XcdrError::Bits(0b1000))
+        );
+        assert_eq!(
+            from_slice::<Permissions>(&[0, 7, 0, 0, 0b1100]),
+            Err(XcdrError::Bits(0b1100))
+        );
+    }
+
+    #[test]
+    fn test_collections() {
+        let inventory = Inventory {
+            counts: BTreeMap::from([("a".to_string(), 1)]),
+            names: vec!["b".to_string()],
+            ids: vec![2],
+            colors: [Color::Blue, Color::Red],
+        };
+        let data = to_vec(&inventory, XcdrVersion::Xcdr2, LE).unwrap();
+        assert_eq!(
+            payload(&data),
+            [
+                // map: DHEADER, count, key and value
+                16, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, b'a', 0, 0, 0, 1, 0, 0, 0,
+                // sequence of strings: DHEADER, count, string
+                10, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, b'b', 0, 0, 0,
+                // sequence of primitives: count, element
+                1, 0, 0, 0, 2, 0, 0, 0, // array of enums: DHEADER, elements
+                8, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0
+            ]
+        );
+        assert_eq!(from_slice::<Inventory>(&data), Ok(inventory.clone()));
+
+        let data = to_vec(&inventory, XcdrVersion::Xcdr1, LE).unwrap();
+        assert_eq!(payload(&data).len(), 44);
+        assert_eq!(from_slice::<Inventory>(&data), Ok(inventory));
+    }
+
+    #[test]
+    fn test_bounds() {
+        let tags: BoundedVec<BoundedString<8>, 4> = BoundedVec::new();
+        let desc = <BoundedVec<BoundedString<8>, 4>>::type_desc();
+        let mut data = to_payload(&tags, Some(&desc), XcdrVersion::Xcdr1, LE).unwrap();
+        data[0] = 5;
+        assert_eq!(
+            from_payload::<BoundedVec<BoundedString<8>, 4>>(
+                &data,
+                Some(&desc),
+                XcdrVersion::Xcdr1,
+                LE
+            ),
+            Err(XcdrError::Bound { bound: 4, len: 5 })
+        );
+        let names = ["a"; 5];
+        assert_eq!(
+            to_payload(&names[..], Some(&desc), XcdrVersion::Xcdr1, LE),
+            Err(XcdrError::Bound { bound: 4, len: 5 })
+        );
+        assert_eq!(
+            to_payload(&["too long"; 1][..], Some(&desc), XcdrVersion::Xcdr1, LE),
+            Ok(vec![
+                1, 0, 0, 0, 9, 0, 0, 0, b't', b'o', b'o', b' ', b'l', b'o', b'n', b'g', 0
+            ])
+        );
+        assert_eq!(
+            to_payload(&["longer..."; 1][..], Some(&desc), XcdrVersion::Xcdr1, LE),
+            Err(XcdrError::Bound { bound: 8, len: 9 })
+        );
+    }
+
+    #[test]
+    fn test_key() {
+        // A key that fits in 16 bytes is the key hash
+        let msg = Msg {
+            user_id: 7,
+            message: "hi".to_string(),
+        };
+        assert_eq!(to_key_payload(&msg, BE), Ok(vec![0, 0, 0, 7]));
+        assert_eq!(to_key_payload(&msg, LE), Ok(vec![7, 0, 0, 0]));
+        let mut hash = [0; 16];
+        hash[3] = 7;
+        assert_eq!(key_hash(&msg), Ok(hash));
+        assert_eq!(msg.key_hash(), Ok(hash));
+
+        // Without the member headers of appendable and mutable types,
+        // inherited keys included
+        let status = Status { id: 3, level: 1 };
+        assert_eq!(to_key_payload(&status, BE), Ok(vec![0, 0, 0, 3]));
+        let alarm = Alarm {
+            status,
+            message: "fire".to_string(),
+        };
+        assert_eq!(to_key_payload(&alarm, BE), Ok(vec![0, 0, 0, 3]));
+        let tuning = Tuning {
+            id: 5,
+            gain: 0.5,
+            level: Level::High,
+            mode: Permissions::READ,
+        };
+        assert_eq!(to_key_payload(&tuning, BE), Ok(vec![0, 0, 0, 5]));
+        assert_eq!(to_key_payload(&Position { x: 1.0, y: 2.0 }, BE), Ok(vec![]));
+
+        // In the order of the member ids, nested structs with all their
+        // members, aligned to at most 4 bytes
+        let device = Device {
+            serial: "abc".to_string(),
+            zone: 9,
+            position: Position { x: 1.0, y: -1.0 },
+            reading: 20.5,
+        };
+        let key = to_key_payload(&device, BE).unwrap();
+        let mut expected = vec![9, 0, 0, 0, 0, 0, 0, 4, b'a', b'b', b'c', 0];
+        expected.extend_from_slice(&1.0f64.to_be_bytes());
+        expected.extend_from_slice(&(-1.0f64).to_be_bytes());
+        assert_eq!(key, expected);
+        // A string key may not fit, it is hashed even if it does
+        assert_eq!(key_hash(&device), Ok(md5::compute(&key).0));
+        let other = Device {
+            reading: 0.0,
+            ..device.clone()
+        };
+        assert_eq!(key_hash(&other), key_hash(&device));
+    }
+
+    #[test]
+    fn test_untyped() {
+        let sample = (1u8, Some(2.5f64), vec!["x".to_string()], Value::Int(3));
+        let data = super::to_vec_with(&sample, None, XcdrVersion::Xcdr1, BE).unwrap();
+        assert_eq!(super::from_slice_with(&data, None), Ok(sample.clone()));
+        assert_eq!(
+            super::to_vec_with(&sample, None, XcdrVersion::Xcdr2, BE),
+            Err(XcdrError::Untyped)
+        );
+    }
+
+    /// A xorshift generator, the samples of a seed are always the same.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+
+        fn string(&mut self, max: u64) -> String {
+            (0..self.below(max + 1))
+                .map(|_| (b'a' + self.below(26) as u8) as char)
+                .collect()
+        }
+
+        fn level(&mut self) -> Level {
+            [Level::Low, Level::High][self.below(2) as usize]
+        }
+
+        fn status(&mut self) -> Status {
+            Status {
+                id: self.next() as u32,
+                level: self.next() as u8,
+            }
+        }
+
+        fn settings(&mut self) -> Settings {
+            Settings {
+                id: self.next() as u32,
+                note: self.below(2).eq(&0).then(|| self.string(5)),
+            }
+        }
+
+        fn frame(&mut self) -> Frame {
+            Frame {
+                id: self.next() as u32,
+                light: Light {
+                    level: self.level(),
+                    color: [Color::Red, Color::Green, Color::Blue][self.below(3) as usize],
+                },
+                value: match self.below(3) {
+                    0 => Value::Int(self.next() as i32),
+                    1 => Value::Real(self.next() as f64 / 3.0),
+                    _ => Value::Level(self.level()),
+                },
+                small: match self.below(3) {
+                    0 => Small::Byte(self.next() as u8),
+                    1 => Small::Word(self.next() as u32),
+                    _ => Small::Point(Position {
+                        x: self.next() as f64,
+                        y: -(self.next() as f64),
+                    }),
+                },
+                mode: Permissions(self.below(32) as u8 & 0b10011),
+                matrix: [[self.next() as i16; 3], [self.next() as i16; 3]],
+                status: self.status(),
+            }
+        }
+
+        fn plain(&mut self) -> Plain {
+            Plain {
+                flag: self.below(2) == 0,
+                small: self.next() as i8,
+                real: self.next() as f64 * 0.25,
+                name: self.string(12),
+                frame: self.frame(),
+                inventory: Inventory {
+                    counts: (0..self.below(4))
+                        .map(|_| (self.string(3), self.next() as u32))
+                        .collect(),
+                    names: (0..self.below(4)).map(|_| self.string(6)).collect(),
+                    ids: (0..self.below(6)).map(|_| self.next() as u32).collect(),
+                    colors: [Color::Blue, Color::Green],
+                },
+                tags: (0..self.below(5))
+                    .map(|_| BoundedString::try_from(self.string(8)).unwrap())
+                    .collect::<Vec<_>>()
+                    .try_into()
+                    .unwrap(),
+            }
+        }
+
+        fn everything(&mut self) -> Everything {
+            Everything {
+                plain: self.plain(),
+                range: self.below(2).eq(&0).then(|| self.next() as u16),
+                origin: self.below(2).eq(&0).then(|| Position {
+                    x: self.next() as f64,
+                    y: 0.5,
+                }),
+                settings: self.settings(),
+                tuning: Tuning {
+                    id: self.next() as u32,
+                    gain: self.next() as f64 / 7.0,
+                    level: self.level(),
+                    mode: Permissions(0b11),
+                },
+                profile: Profile {
+                    settings: self.settings(),
+                    limit: self.next() as u16,
+                },
+                alarms: (0..self.below(3))
+                    .map(|_| Alarm {
+                        status: self.status(),
+                        message: self.string(4),
+                    })
+                    .collect(),
+            }
+        }
+    }
+
+    /// Serializes and deserializes `sample`, and deserializes truncated
+    /// and corrupted copies, which must fail or succeed but never panic.
+    fn check<T>(rng: &mut Rng, sample: &T, version: XcdrVersion, endianness: Endianness)
+    where
+        T: Serialize + serde::de::DeserializeOwned + DdsType + PartialEq + std::fmt::Debug,
+    {
+        let data = to_vec(sample, version, endianness).unwrap();
+        assert_eq!(data.len() % 4, 0);
+        assert_eq!(from_slice::<T>(&data).as_ref(), Ok(sample));
+        for len in 0..data.len() - data[3] as usize {
+            assert!(from_slice::<T>(&data[..len]).is_err());
+        }
+        for _ in 0..32 {
+            let mut corrupted = data.clone();
+            let at = HEADER_SIZE + rng.below((data.len() - HEADER_SIZE) as u64) as usize;
+            corrupted[at] ^= 1 << rng.below(8);
+            let _ = from_slice::<T>(&corrupted);
+        }
+    }
+
+    #[test]
+    fn test_fuzz() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..100 {
+            for endianness in [LE, BE] {
+                let plain = rng.plain();
+                check(&mut rng, &plain, XcdrVersion::Xcdr1, endianness);
+                check(&mut rng, &plain, XcdrVersion::Xcdr2, endianness);
+                let everything = rng.everything();
+                check(&mut rng, &everything, XcdrVersion::Xcdr2, endianness);
+            }
+        }
+    }
+
+    /// Serializes `sample` with [to_payload], reads the data with Cyclone DDS
+    /// into a C sample laid out as the descriptor describes and serializes
+    /// that with Cyclone DDS again, which must give the same data.
+    fn cyclone_roundtrip<T>(sample: &T, version: XcdrVersion, endianness: Endianness)
+    where
+        T: TopicType
+            + DdsType
+            + Serialize
+            + serde::de::DeserializeOwned
+            + PartialEq
+            + std::fmt::Debug,
+    {
+        let descriptor = OwnedTopicDescriptor::new(&T::topic_descriptor());
+        let xcdr_version = match version {
+            XcdrVersion::Xcdr1 => DDS_CDR_ENC_VERSION_1,
+            XcdrVersion::Xcdr2 => DDS_CDR_ENC_VERSION_2,
+        };
+        let ours = to_payload(sample, Some(&T::type_desc()), version, endianness).unwrap();
+
+        unsafe {
+            let allocator = &cyclonedds_sys::dds_cdrstream_default_allocator;
+            let mut desc = MaybeUninit::zeroed().assume_init();
+            cyclonedds_sys::dds_cdrstream_desc_from_topic_desc(&mut desc, descriptor.as_c());
+
+            // Cyclone DDS reads native endian data only
+            let mut data = ours.clone();
+            let mut actual_size = 0;
+            assert!(cyclonedds_sys::dds_stream_normalize(
+                data.as_mut_ptr() as *mut c_void,
+                data.len() as u32,
+                endianness != Endianness::NATIVE,
+                xcdr_version,
+                &desc,
+                false,
+                &mut actual_size
+            ));
+            assert_eq!(actual_size as usize, data.len());
+
+            // The strings, sequences and pointers of a zeroed C sample are
+            // empty, Cyclone DDS allocates them while reading
+            let size = descriptor.topic_descriptor().size() as usize;
+            let mut buf = vec![0u64; size.div_ceil(size_of::<u64>())];
+            let c_sample = buf.as_mut_ptr() as *mut c_void;
+            let mut is = MaybeUninit::zeroed().assume_init();
+            cyclonedds_sys::dds_istream_init(
+                &mut is,
+                data.len() as u32,
+                data.as_ptr() as *const c_void,
+                xcdr_version,
+            );
+            cyclonedds_sys::dds_stream_read_sample(&mut is, c_sample, allocator, &desc);
+
+            let theirs = match endianness {
+                Endianness::Little => {
+                    let mut os = MaybeUninit::zeroed().assume_init();
+                    cyclonedds_sys::dds_ostreamLE_init(&mut os, allocator, 0, xcdr_version);
+                    assert!(cyclonedds_sys::dds_stream_write_sampleLE(
+                        &mut os, allocator, c_sample, &desc
+                    ));
+                    let cdr = slice::from_raw_parts(os.x.m_buffer, os.x.m_index as usize).to_vec();
+                    cyclonedds_sys::dds_ostreamLE_fini(&mut os, allocator);
+                    cdr
+                }
+                Endianness::Big => {
+                    let mut os = MaybeUninit::zeroed().assume_init();
+                    cyclonedds_sys::dds_ostreamBE_init(&mut os, allocator, 0, xcdr_version);
+                    assert!(cyclonedds_sys::dds_stream_write_sampleBE(
+                        &mut os, allocator, c_sample, &desc
+                    ));
+                    let cdr = slice::from_raw_parts(os.x.m_buffer, os.x.m_index as usize).to_vec();
+                    cyclonedds_sys::dds_ostreamBE_fini(&mut os, allocator);
+                    cdr
+                }
+            };
+            cyclonedds_sys::dds_stream_free_sample(c_sample, allocator, descriptor.as_c().m_ops);
+            cyclonedds_sys::dds_cdrstream_desc_fini(&mut desc, allocator);
+
+            assert_eq!(ours, theirs);
+            assert_eq!(
+                from_payload::<T>(&theirs, Some(&T::type_desc()), version, endianness).as_ref(),
+                Ok(sample)
+            );
+        }
+    }
+
+    #[test]
+    fn test_cyclone_roundtrip() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..50 {
+            for endianness in [LE, BE] {
+                let frame = rng.frame();
+                for version in [XcdrVersion::Xcdr1, XcdrVersion::Xcdr2] {
+                    cyclone_roundtrip(&frame, version, endianness);
+                }
+                let sample = Tuning {
+                    id: rng.next() as u32,
+                    gain: rng.next() as f64,
+                    level: rng.level(),
+                    mode: Permissions(rng.below(32) as u8 & 0b10011),
+                };
+                cyclone_roundtrip(&sample, XcdrVersion::Xcdr2, endianness);
+                // Strings, sequences, maps and optional members are read
+                // into the C sample as well
+                let everything = rng.everything();
+                cyclone_roundtrip(&everything, XcdrVersion::Xcdr2, endianness);
+            }
+        }
+    }
+}